@@ -0,0 +1,46 @@
+import { pluginModuleFederation } from '@module-federation/rsbuild-plugin';
+import { defineConfig } from '@rsbuild/core';
+
+// Set by `framectl build --target(s)`: each target builds into dist/<target>
+// with its own container format.
+const target = process.env.FRAMECTL_TARGET;
+const library = { esm: { type: 'module' }, system: { type: 'system' } }[target];
+
+export default defineConfig({
+  output: {
+    assetPrefix: '{{assetPrefix}}',
+    ...(target ? { distPath: { root: `dist/${target}` } } : {}),
+  },
+  server: {
+    port: {{port}},
+  },
+  source: {
+    entry: {
+      index: './src/frame.js',
+    },
+  },
+  plugins: [
+    pluginModuleFederation({
+      name: '{{scope}}',
+      // Stable entry filename so the host can skip mf-manifest.json.
+      filename: 'static/js/remoteEntry.js',
+      exposes: {
+        './Frame': './src/frame.js',
+      },
+      shared: {},
+      ...(library ? { library } : {}),
+      experiments: {
+        // Use the host's MF runtime (see host: provideExternalRuntime).
+        externalRuntime: true,
+      },
+    }),
+  ],
+  tools: {
+    rspack: {
+      output: {
+        uniqueName: '{{scope}}',
+      },
+      ...(target === 'esm' ? { experiments: { outputModule: true } } : {}),
+    },
+  },
+});
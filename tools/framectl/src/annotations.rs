@@ -0,0 +1,222 @@
+//! `--annotations=github`: the run's failures and warnings as GitHub Actions
+//! workflow commands on stdout (`::error file=...,line=...::message`), so they
+//! show inline on the PR. An error points at the file and line TypeScript or
+//! the bundler blamed when the frame's log names one, else at the frame's
+//! package.json; the same diagnostic in many frames is one annotation listing
+//! them. Warnings come out one per category, like the `warnings:` lines.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use crate::drift::normalize;
+use crate::exec::{self, Script};
+use crate::exitcode;
+use crate::frames::{self, fmt_ranges};
+use crate::summary::{FrameRecord, Status};
+use crate::typereport::{parse_line, strip_ansi};
+use crate::{parse_kv, warnings};
+
+/// Diagnostics taken from one frame's log.
+const MAX_PER_FRAME: usize = 10;
+/// Log lines in an annotation that has no diagnostic to show.
+const TAIL_LINES: usize = 8;
+
+#[derive(Clone, Copy)]
+pub enum Format {
+  Github,
+}
+
+/// `--annotations=github|none`.
+pub fn parse(args: &[String]) -> Option<Format> {
+  match parse_kv(args, "--annotations").as_deref() {
+    None | Some("none" | "0") => None,
+    Some("github") => Some(Format::Github),
+    Some(v) => {
+      exitcode::usage(format!("invalid --annotations={v} (expected github or none)"));
+    }
+  }
+}
+
+/// Where an error was reported, as the log has it.
+struct Loc {
+  file: String,
+  line: String,
+  col: String,
+  message: String,
+}
+
+/// `12:5`, `12:5-10` or `12`.
+fn position(s: &str) -> Option<(String, String)> {
+  let s = s.split('-').next()?;
+  let (line, col) = s.split_once(':').unwrap_or((s, ""));
+  if line.is_empty() || !line.bytes().all(|b| b.is_ascii_digit()) || !col.bytes().all(|b| b.is_ascii_digit()) {
+    return None;
+  }
+  Some((line.to_string(), col.to_string()))
+}
+
+/// `path:12:5` into the path and position.
+fn split_path(s: &str) -> (String, String, String) {
+  let mut parts = s.rsplitn(3, ':');
+  match (parts.next(), parts.next(), parts.next()) {
+    (Some(col), Some(line), Some(file)) if position(&format!("{line}:{col}")).is_some() => (file.to_string(), line.to_string(), col.to_string()),
+    _ => match s.rsplit_once(':') {
+      Some((file, line)) if position(line).is_some() => (file.to_string(), line.to_string(), String::new()),
+      _ => (s.to_string(), String::new(), String::new()),
+    },
+  }
+}
+
+/// Locations in a build log: tsc diagnostics, webpack/rspack
+/// `ERROR in ./src/x.ts 12:5` followed by the message, and the
+/// `╭─[src/x.ts:12:5]` / `File: src/x.ts:12:5` frames rspack and rsbuild put
+/// under an error line.
+fn locations(log: &str) -> Vec<Loc> {
+  let lines: Vec<String> = log.lines().map(|l| strip_ansi(l).trim().to_string()).collect();
+  let mut out: Vec<Loc> = Vec::new();
+  let mut last_error = String::new();
+  for (i, line) in lines.iter().enumerate() {
+    if let Some(d) = parse_line(line) {
+      let (l, c) = d.at.split_once(':').unwrap_or((&d.at, ""));
+      out.push(Loc {
+        file: d.file,
+        line: l.to_string(),
+        col: c.to_string(),
+        message: format!("{}: {}", d.code, d.message),
+      });
+    } else if let Some(rest) = line.strip_prefix("ERROR in ") {
+      let mut words = rest.split_whitespace();
+      let file = words.next().unwrap_or("").to_string();
+      let (l, c) = words.next().and_then(position).unwrap_or_default();
+      let message = lines[i + 1..]
+        .iter()
+        .find(|m| !m.is_empty() && !m.starts_with('@'))
+        .cloned()
+        .unwrap_or_else(|| "build error".to_string());
+      out.push(Loc { file, line: l, col: c, message });
+    } else if let Some(at) = line
+      .split_once("╭─[")
+      .and_then(|(_, r)| r.strip_suffix(']'))
+      .or_else(|| line.strip_prefix("File: "))
+    {
+      let (file, l, c) = split_path(at.trim());
+      let message = if last_error.is_empty() { "build error".to_string() } else { last_error.clone() };
+      out.push(Loc { file, line: l, col: c, message });
+    } else if line.starts_with('×') || line.starts_with("error") || line.starts_with("Error") {
+      last_error = line.trim_start_matches('×').trim().to_string();
+    }
+    if out.len() == MAX_PER_FRAME {
+      break;
+    }
+  }
+  out
+}
+
+/// `path` from frame `n`'s log as a path from the workspace root, which is
+/// what GitHub resolves `file=` against.
+fn workspace_path(n: usize, path: &str) -> String {
+  let dir = frames::frame_dir(n).to_string_lossy().replace('\\', "/");
+  let path = path.replace('\\', "/");
+  if let Some(at) = path.find(&format!("{dir}/")) {
+    return path[at..].to_string();
+  }
+  if path.starts_with('/') {
+    return path;
+  }
+  format!("{dir}/{}", path.trim_start_matches("./"))
+}
+
+/// Workflow command data: `%`, CR and LF escaped.
+fn escape_data(s: &str) -> String {
+  s.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Workflow command property values: also `:` and `,`.
+fn escape_prop(s: &str) -> String {
+  escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+fn command(kind: &str, file: &str, line: &str, col: &str, title: &str, message: &str) -> String {
+  let mut props = format!("file={}", escape_prop(file));
+  if !line.is_empty() {
+    let _ = write!(props, ",line={line}");
+  }
+  if !col.is_empty() {
+    let _ = write!(props, ",col={col}");
+  }
+  let _ = write!(props, ",title={}", escape_prop(title));
+  format!("::{kind} {props}::{}", escape_data(message))
+}
+
+/// One error annotation, for every frame it happened in.
+struct Group {
+  frames: Vec<usize>,
+  file: String,
+  line: String,
+  col: String,
+  title: String,
+  message: String,
+}
+
+/// Print the annotations for a finished run.
+pub fn print(format: Format, records: &BTreeMap<usize, FrameRecord>, script: Script) {
+  let Format::Github = format;
+  let mut groups: BTreeMap<(String, String, String, String), Group> = BTreeMap::new();
+  let mut add = |n: usize, loc: Loc, title: String| {
+    let file = workspace_path(n, &loc.file);
+    let key = (normalize(&file, n), loc.line.clone(), loc.col.clone(), normalize(&loc.message, n));
+    let g = groups.entry(key).or_insert_with(|| Group {
+      frames: Vec::new(),
+      file,
+      line: loc.line,
+      col: loc.col,
+      title,
+      message: loc.message,
+    });
+    if g.frames.last() != Some(&n) {
+      g.frames.push(n);
+    }
+  };
+  for (&n, r) in records.iter().filter(|(_, r)| r.status == Status::Failed) {
+    let stage = r.failed_stage().unwrap_or(script);
+    let class = r.error.as_deref().unwrap_or("other");
+    let log = std::fs::read_to_string(exec::log_path(n, stage)).unwrap_or_default();
+    let title = format!("{} failed ({class})", stage.name());
+    let locs = locations(&log);
+    if locs.is_empty() {
+      let mut message = String::new();
+      if !r.failed_tests.is_empty() {
+        let _ = writeln!(message, "failing tests: {}", r.failed_tests.join(", "));
+      }
+      let lines: Vec<&str> = log.lines().filter(|l| !l.trim().is_empty()).collect();
+      let tail = lines[lines.len().saturating_sub(TAIL_LINES)..].join("\n");
+      message.push_str(&strip_ansi(&tail));
+      if message.is_empty() {
+        message = format!("{} {} failed", frames::label(n), stage.name());
+      }
+      let file = frames::frame_dir(n).join("package.json").to_string_lossy().into_owned();
+      add(n, Loc { file, line: String::new(), col: String::new(), message }, title);
+    } else {
+      for loc in locs {
+        add(n, loc, title.clone());
+      }
+    }
+  }
+
+  let mut groups: Vec<Group> = groups.into_values().collect();
+  groups.sort_by_key(|g| (std::cmp::Reverse(g.frames.len()), g.frames[0]));
+  for g in &groups {
+    let (title, message) = match g.frames.len() {
+      1 => (format!("{} {}", frames::label(g.frames[0]), g.title), g.message.clone()),
+      k => (format!("{k} frames {}", g.title), format!("{}\n\nframes: {}", g.message, fmt_ranges(&g.frames))),
+    };
+    println!("{}", command("error", &g.file, &g.line, &g.col, &title, &message));
+  }
+
+  for (name, c) in warnings::totals() {
+    let file = frames::frame_dir(c.frames[0]).join("package.json").to_string_lossy().into_owned();
+    let title = format!("{name} warnings");
+    let message = format!("{} in {} frame(s) ({}): {}", c.count, c.frames.len(), fmt_ranges(&c.frames), c.sample);
+    println!("{}", command("warning", &file, "", "", &title, &message));
+  }
+}
@@ -0,0 +1,126 @@
+//! `validate-assets`: source frame images and the packages generated from
+//! them, checked before a build trips over them.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::frames;
+use crate::png;
+use crate::thumbs::parse_dims;
+use crate::parse_kv;
+
+pub fn run(args: &[String]) {
+  let src_dir = parse_kv(args, "--frames-dir")
+    .map(PathBuf::from)
+    .unwrap_or_else(frames::sources_dir);
+  let want_size = parse_kv(args, "--size").and_then(|v| parse_dims(&v));
+  let want_depth: Option<u8> = parse_kv(args, "--bit-depth").and_then(|v| v.parse().ok());
+
+  let sources = frames::discover_sources(&src_dir);
+  let packages = frames::discover(&frames::frames_dir());
+  let start: usize = parse_kv(args, "--start")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(1);
+  let end: usize = parse_kv(args, "--end")
+    .and_then(|v| v.parse().ok())
+    .or_else(|| sources.last().copied().max(packages.last().copied()))
+    .unwrap_or(0);
+  if end < start || end == 0 {
+    eprintln!("validate-assets: nothing to check (no frames found, start={start} end={end})");
+    std::process::exit(2);
+  }
+
+  let mut headers = BTreeMap::new();
+  let mut problems: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+  let mut add = |n: usize, msg: String| problems.entry(n).or_default().push(msg);
+
+  for n in start..=end {
+    if !frames::frame_dir(n).join("src").join("frame.js").is_file() {
+      add(n, "package has no src/frame.js".to_string());
+    }
+    let Some(path) = frames::source_image(&src_dir, n) else {
+      add(n, format!("no source image in {}", src_dir.display()));
+      continue;
+    };
+    match png::read_header(&path) {
+      Ok(h) => {
+        headers.insert(n, h);
+      }
+      Err(e) => add(n, format!("{}: {e}", path.display())),
+    }
+  }
+
+  // Without explicit expectations, the most common size/depth is the norm.
+  let majority = |key: &dyn Fn(&png::Header) -> (u32, u32, u8)| {
+    let mut counts: BTreeMap<(u32, u32, u8), usize> = BTreeMap::new();
+    for h in headers.values() {
+      *counts.entry(key(h)).or_default() += 1;
+    }
+    counts.into_iter().max_by_key(|(_, c)| *c).map(|(k, _)| k)
+  };
+  let size = want_size
+    .map(|(w, h)| (w as u32, h as u32))
+    .or_else(|| majority(&|h| (h.width, h.height, 0)).map(|(w, h, _)| (w, h)));
+  let depth = want_depth.or_else(|| majority(&|h| (0, 0, h.bit_depth)).map(|(_, _, d)| d));
+  let color = majority(&|h| (0, 0, h.color_type)).map(|(_, _, c)| c);
+
+  for (n, h) in &headers {
+    if let Some((w, ht)) = size {
+      if (h.width, h.height) != (w, ht) {
+        add(*n, format!("{}x{} (expected {w}x{ht})", h.width, h.height));
+      }
+    }
+    if let Some(d) = depth {
+      if h.bit_depth != d {
+        add(*n, format!("bit depth {} (expected {d})", h.bit_depth));
+      }
+    }
+    if let Some(c) = color {
+      if h.color_type != c {
+        add(
+          *n,
+          format!("color {} (expected {})", png::color_name(h.color_type), png::color_name(c)),
+        );
+      }
+    }
+    if h.interlace != 0 {
+      add(*n, "interlaced".to_string());
+    }
+  }
+
+  let gaps: Vec<usize> = (start..=end)
+    .filter(|n| frames::source_image(&src_dir, *n).is_none())
+    .collect();
+  let stray: Vec<usize> = sources
+    .iter()
+    .chain(packages.iter())
+    .copied()
+    .filter(|n| *n < start || *n > end)
+    .collect();
+
+  let (w, h) = size.unwrap_or((0, 0));
+  eprintln!(
+    "validate-assets: frames {start}..{end} expected={w}x{h} depth={} color={}",
+    depth.unwrap_or(0),
+    color.map(png::color_name).unwrap_or("?")
+  );
+  for (n, msgs) in &problems {
+    eprintln!("frame-{n:04}: {}", msgs.join("; "));
+  }
+  if !gaps.is_empty() {
+    eprintln!("gaps in source images: {}", frames::fmt_ranges(&gaps));
+  }
+  if !stray.is_empty() && parse_kv(args, "--end").is_some() {
+    let mut stray = stray;
+    stray.sort_unstable();
+    stray.dedup();
+    eprintln!("note: frames outside the range were not checked: {}", frames::fmt_ranges(&stray));
+  }
+
+  if problems.is_empty() {
+    eprintln!("ok: {} frame(s) valid", end - start + 1);
+    return;
+  }
+  eprintln!("exit: {} frame(s) with problems", problems.len());
+  std::process::exit(1);
+}
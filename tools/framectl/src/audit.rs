@@ -0,0 +1,172 @@
+//! `audit`: `pnpm audit` for the whole workspace, with each advisory's
+//! thousands of per-frame dependency paths folded into one line and a frame
+//! range.
+
+use std::collections::BTreeMap;
+use std::process::{Command, Stdio};
+
+use crate::frames::fmt_ranges;
+use crate::json::{self, Value};
+use crate::{exec, parse_bool, parse_kv};
+
+const SEVERITIES: [&str; 4] = ["low", "moderate", "high", "critical"];
+
+fn rank(severity: &str) -> usize {
+  SEVERITIES.iter().position(|s| *s == severity).map(|i| i + 1).unwrap_or(0)
+}
+
+#[derive(Default)]
+struct Advisory {
+  severity: String,
+  module: String,
+  title: String,
+  vulnerable: String,
+  patched: String,
+  url: String,
+  frames: Vec<usize>,
+  /// Importers that aren't frames (the host, the workspace root).
+  others: Vec<String>,
+}
+
+fn field(v: &Value, key: &str) -> String {
+  v.get(key).and_then(|s| s.as_str()).unwrap_or("").to_string()
+}
+
+/// The importer a dependency path starts from: `apps__frames__frame-0042>react>...`.
+fn importer(path: &str) -> (Option<usize>, String) {
+  let first = path.split('>').next().unwrap_or("").trim();
+  let n = first
+    .rsplit("frame-")
+    .next()
+    .filter(|_| first.contains("frame-"))
+    .and_then(|id| id.parse().ok());
+  let name = match first {
+    "" | "." => "(root)".to_string(),
+    other => other.replace("__", "/"),
+  };
+  (n, name)
+}
+
+fn collect(doc: &Value) -> Vec<Advisory> {
+  let Some(Value::Obj(entries)) = doc.get("advisories") else {
+    return Vec::new();
+  };
+  // Keyed by GHSA id where there is one, so the same advisory reported under
+  // two ids is still one line.
+  let mut by_id: BTreeMap<String, Advisory> = BTreeMap::new();
+  for (id, a) in entries {
+    let ghsa = field(a, "github_advisory_id");
+    let key = if ghsa.is_empty() { id.clone() } else { ghsa };
+    let adv = by_id.entry(key).or_insert_with(|| Advisory {
+      severity: field(a, "severity"),
+      module: field(a, "module_name"),
+      title: field(a, "title"),
+      vulnerable: field(a, "vulnerable_versions"),
+      patched: field(a, "patched_versions"),
+      url: field(a, "url"),
+      ..Advisory::default()
+    });
+    let Some(Value::Arr(findings)) = a.get("findings") else {
+      continue;
+    };
+    for f in findings {
+      let Some(Value::Arr(paths)) = f.get("paths") else {
+        continue;
+      };
+      for p in paths.iter().filter_map(|p| p.as_str()) {
+        match importer(p) {
+          (Some(n), _) => adv.frames.push(n),
+          (None, name) => adv.others.push(name),
+        }
+      }
+    }
+  }
+  let mut out: Vec<Advisory> = by_id.into_values().collect();
+  for a in &mut out {
+    a.frames.sort_unstable();
+    a.frames.dedup();
+    a.others.sort();
+    a.others.dedup();
+  }
+  out.sort_by(|a, b| {
+    rank(&b.severity)
+      .cmp(&rank(&a.severity))
+      .then(b.frames.len().cmp(&a.frames.len()))
+      .then(a.module.cmp(&b.module))
+  });
+  out
+}
+
+pub fn run(args: &[String]) {
+  let fail_on = parse_kv(args, "--fail-on").map(|v| {
+    if rank(&v) == 0 {
+      eprintln!("invalid --fail-on={v} (expected {})", SEVERITIES.join(", "));
+      std::process::exit(2);
+    }
+    v
+  });
+  let prod = parse_kv(args, "--prod").and_then(|v| parse_bool(&v)).unwrap_or(false);
+
+  let mut cmd = Command::new("pnpm");
+  cmd.args(["audit", "--json"]);
+  if prod {
+    cmd.arg("--prod");
+  }
+  // pnpm audit exits non-zero whenever it finds anything; the JSON decides.
+  let out = match cmd.stdin(Stdio::null()).stderr(Stdio::piped()).output() {
+    Ok(o) => o,
+    Err(e) => {
+      eprintln!("audit: pnpm: {e}");
+      std::process::exit(1);
+    }
+  };
+  let doc = match json::parse(&String::from_utf8_lossy(&out.stdout)) {
+    Ok(d) => d,
+    Err(e) => {
+      let err = exec::tail(&String::from_utf8_lossy(&out.stderr), 1500);
+      eprintln!("audit: pnpm audit gave no report ({e}):\n{}", err.trim_end());
+      std::process::exit(1);
+    }
+  };
+  if let Some(err) = doc.get("error") {
+    let msg = err.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error");
+    eprintln!("audit: pnpm audit failed: {msg}");
+    std::process::exit(1);
+  }
+
+  let advisories = collect(&doc);
+  if advisories.is_empty() {
+    eprintln!("audit: no known vulnerabilities");
+    return;
+  }
+  let mut counts: Vec<String> = Vec::new();
+  for sev in SEVERITIES.iter().rev() {
+    let c = advisories.iter().filter(|a| a.severity == *sev).count();
+    if c > 0 {
+      counts.push(format!("{c} {sev}"));
+    }
+  }
+  eprintln!("audit: {} advisory(ies) ({})", advisories.len(), counts.join(", "));
+  for a in &advisories {
+    eprintln!("  {:<9} {} {}  {}", a.severity, a.module, a.vulnerable, a.title);
+    let mut affected = Vec::new();
+    if !a.frames.is_empty() {
+      affected.push(format!("{} frame(s) {}", a.frames.len(), fmt_ranges(&a.frames)));
+    }
+    affected.extend(a.others.iter().cloned());
+    let fix = if a.patched.is_empty() || a.patched == "<0.0.0" {
+      "no fix yet".to_string()
+    } else {
+      format!("fixed in {}", a.patched)
+    };
+    eprintln!("            {}; {fix}  {}", affected.join(", "), a.url);
+  }
+
+  if let Some(level) = fail_on {
+    let over = advisories.iter().filter(|a| rank(&a.severity) >= rank(&level)).count();
+    if over > 0 {
+      eprintln!("audit: {over} advisory(ies) at or above {level}");
+      std::process::exit(1);
+    }
+  }
+}
@@ -0,0 +1,182 @@
+//! `--batch-size=N --cooldown=60s`: the run builds N frames, lets them all
+//! finish, then starts nothing for the cooldown before the next N, so a
+//! laptop can cool down or a shared runner gets some air during a long run.
+//! The pauses still to come are part of the ETA.
+
+use std::time::{Duration, Instant};
+
+use crate::eta::Eta;
+use crate::exitcode;
+use crate::parse_kv;
+use crate::queue::TaskQueue;
+
+pub struct Batches {
+  size: usize,
+  cooldown: Duration,
+  /// Batches started so far.
+  started: usize,
+  cooling_until: Option<Instant>,
+}
+
+/// `90`, `90s`, `2m` or `1h`.
+pub fn parse_secs(s: &str) -> Option<Duration> {
+  let (num, unit) = s.find(|c: char| c.is_ascii_alphabetic()).map_or((s, ""), |i| s.split_at(i));
+  let mult = match unit {
+    "" | "s" => 1.0,
+    "m" => 60.0,
+    "h" => 3600.0,
+    _ => return None,
+  };
+  let secs = num.parse::<f64>().ok()? * mult;
+  (secs.is_finite() && secs >= 0.0).then(|| Duration::from_secs_f64(secs))
+}
+
+impl Batches {
+  pub fn from_args(args: &[String]) -> Option<Batches> {
+    let size = parse_kv(args, "--batch-size")?;
+    let size = size.parse::<usize>().ok().filter(|s| *s > 0).unwrap_or_else(|| {
+      exitcode::usage(format!("invalid --batch-size={size} (expected frames per batch)"));
+    });
+    let cooldown = match parse_kv(args, "--cooldown") {
+      None => Duration::from_secs(60),
+      Some(v) => parse_secs(&v).unwrap_or_else(|| {
+        exitcode::usage(format!("invalid --cooldown={v} (expected e.g. 60s or 2m)"));
+      }),
+    };
+    Some(Batches {
+      size,
+      cooldown,
+      started: 0,
+      cooling_until: None,
+    })
+  }
+
+  pub fn describe(&self) -> String {
+    format!("batch={} cooldown={}s", self.size, self.cooldown.as_secs())
+  }
+
+  /// Let the first batch out.
+  pub fn start(&mut self, queue: &TaskQueue) {
+    self.started = 1;
+    queue.set_budget(Some(self.size));
+  }
+
+  pub fn cooling(&self) -> bool {
+    self.cooling_until.is_some()
+  }
+
+  /// Called every tick: begin the cooldown once a batch has been handed out
+  /// and has finished, and end it when it's over.
+  pub fn tick(&mut self, queue: &TaskQueue) {
+    match self.cooling_until {
+      Some(until) if Instant::now() >= until => {
+        self.cooling_until = None;
+        self.started += 1;
+        eprintln!("batch: cooldown over; starting batch {}", self.started);
+        queue.set_budget(Some(self.size));
+      }
+      Some(_) => {}
+      None if queue.budget() == Some(0) && queue.in_flight().is_empty() => {
+        let left = queue.pending().len();
+        if left > 0 {
+          eprintln!(
+            "batch: batch {} finished; cooling down for {}s ({left} frame(s) left)",
+            self.started,
+            self.cooldown.as_secs()
+          );
+          self.cooling_until = Some(Instant::now() + self.cooldown);
+        }
+      }
+      None => {}
+    }
+  }
+
+  /// `eta` pushed back by the cooldowns still ahead.
+  pub fn delay(&self, eta: Eta, queue: &TaskQueue) -> Eta {
+    eta.delayed(self.pauses_left(queue, queue.pending().len()))
+  }
+
+  /// Cooldown time still ahead with `unstarted` frames not handed out yet.
+  fn pauses_left(&self, queue: &TaskQueue, unstarted: usize) -> Duration {
+    let now = Instant::now();
+    let (current, beyond) = match self.cooling_until {
+      Some(until) => (until.saturating_duration_since(now), unstarted.saturating_sub(self.size)),
+      None => (Duration::ZERO, unstarted.saturating_sub(queue.budget().unwrap_or(0))),
+    };
+    current + self.cooldown * beyond.div_ceil(self.size) as u32
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn batches(args: &[&str]) -> Batches {
+    let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+    Batches::from_args(&args).unwrap()
+  }
+
+  #[test]
+  fn parses_durations() {
+    assert_eq!(parse_secs("90"), Some(Duration::from_secs(90)));
+    assert_eq!(parse_secs("90s"), Some(Duration::from_secs(90)));
+    assert_eq!(parse_secs("2m"), Some(Duration::from_secs(120)));
+    assert_eq!(parse_secs("1.5h"), Some(Duration::from_secs(5400)));
+    assert_eq!(parse_secs("0"), Some(Duration::ZERO));
+    for bad in ["", "s", "5d", "-1s", "inf", "1ms"] {
+      assert_eq!(parse_secs(bad), None, "{bad:?}");
+    }
+  }
+
+  #[test]
+  fn reads_flags() {
+    assert!(Batches::from_args(&[]).is_none());
+    assert_eq!(batches(&["--batch-size=4"]).describe(), "batch=4 cooldown=60s");
+    assert_eq!(batches(&["--batch-size=4", "--cooldown=2m"]).describe(), "batch=4 cooldown=120s");
+  }
+
+  #[test]
+  fn cools_down_between_batches() {
+    let queue = TaskQueue::new(1..=5, false);
+    let mut b = batches(&["--batch-size=2", "--cooldown=0s"]);
+    b.start(&queue);
+    assert_eq!((queue.pop(), queue.pop()), (Some(1), Some(2)));
+    assert_eq!(queue.budget(), Some(0));
+    // Not while the batch is still building.
+    b.tick(&queue);
+    assert!(!b.cooling());
+    queue.finish(1);
+    queue.finish(2);
+    b.tick(&queue);
+    assert!(b.cooling());
+    b.tick(&queue);
+    assert!(!b.cooling());
+    assert_eq!(queue.budget(), Some(2));
+    assert_eq!((queue.pop(), queue.pop()), (Some(3), Some(4)));
+    queue.finish(3);
+    queue.finish(4);
+    b.tick(&queue);
+    b.tick(&queue);
+    assert_eq!(queue.pop(), Some(5));
+    queue.finish(5);
+    // Nothing left to cool down for.
+    b.tick(&queue);
+    assert!(!b.cooling());
+  }
+
+  #[test]
+  fn counts_cooldowns_ahead() {
+    let queue = TaskQueue::new(1..=7, false);
+    let mut b = batches(&["--batch-size=2", "--cooldown=60s"]);
+    b.start(&queue);
+    // 2 of 7 go in this batch; the other 5 need 3 more, each after a pause.
+    assert_eq!(b.pauses_left(&queue, 7), Duration::from_secs(180));
+    assert_eq!(b.pauses_left(&queue, 2), Duration::ZERO);
+    queue.set_budget(Some(0));
+    assert_eq!(b.pauses_left(&queue, 4), Duration::from_secs(120));
+    // Cooling: what's left of this pause, then one per batch after the next.
+    b.cooling_until = Some(Instant::now() + Duration::from_secs(30));
+    let left = b.pauses_left(&queue, 5);
+    assert!(left > Duration::from_secs(145) && left <= Duration::from_secs(150), "{left:?}");
+  }
+}
@@ -0,0 +1,184 @@
+//! `bisect-failure N`: frames are generated from one template, so when one
+//! fails and its neighbours build, whatever differs between them is the
+//! first suspect. Compares the failing frame with the nearest passing one,
+//! frame numbers normalized away.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::classify::classify;
+use crate::drift::{self, normalize};
+use crate::exec::{self, Script};
+use crate::json::Value;
+use crate::{frames, parse_kv, summary};
+
+/// Compared field by field by `drift::frame_fields` instead of line by line
+/// (the port differs in every frame).
+const FIELD_FILES: [&str; 2] = ["package.json", "rsbuild.config.mjs"];
+const SKIP_DIRS: [&str; 2] = ["node_modules", "dist"];
+/// Differing lines shown per file and side.
+const SHOW_LINES: usize = 5;
+
+/// Last run's status per frame, from `summary.json`.
+fn last_status() -> BTreeMap<usize, String> {
+  let Ok(doc) = crate::json::read(&summary::last_path(Script::Build)) else {
+    return BTreeMap::new();
+  };
+  let Some(Value::Arr(records)) = doc.get("frames") else {
+    return BTreeMap::new();
+  };
+  records
+    .iter()
+    .filter_map(|f| match (f.get("frame"), f.get("status").and_then(Value::as_str)) {
+      (Some(Value::Num(n)), Some(s)) => Some((*n as usize, s.to_string())),
+      _ => None,
+    })
+    .collect()
+}
+
+/// Passed last run, or (never built in a recorded run) has a current dist.
+fn passing(n: usize, status: &BTreeMap<usize, String>) -> bool {
+  match status.get(&n) {
+    Some(s) => s == "ok",
+    None => frames::dist_current(n),
+  }
+}
+
+/// Files under a frame dir, relative to it, without installs and output.
+fn files(dir: &Path) -> Vec<PathBuf> {
+  fn walk(root: &Path, dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(rd) = std::fs::read_dir(dir) else {
+      return;
+    };
+    for ent in rd.flatten() {
+      let p = ent.path();
+      let name = ent.file_name().to_string_lossy().into_owned();
+      match ent.file_type() {
+        Ok(t) if t.is_dir() && !SKIP_DIRS.contains(&name.as_str()) && !name.starts_with('.') => {
+          walk(root, &p, out);
+        }
+        Ok(t) if t.is_file() => out.push(p.strip_prefix(root).unwrap_or(&p).to_path_buf()),
+        _ => {}
+      }
+    }
+  }
+  let mut out = Vec::new();
+  walk(dir, dir, &mut out);
+  out.sort();
+  out
+}
+
+/// Lines of `a` missing from `b`, counted as multisets.
+fn only_in<'a>(a: &'a str, b: &str) -> Vec<&'a str> {
+  let mut left: BTreeMap<&str, usize> = BTreeMap::new();
+  for l in b.lines() {
+    *left.entry(l).or_default() += 1;
+  }
+  a.lines()
+    .filter(|l| match left.get_mut(l) {
+      Some(c) if *c > 0 => {
+        *c -= 1;
+        false
+      }
+      _ => true,
+    })
+    .collect()
+}
+
+/// Differences in one file both frames have, or `None` if it matches.
+fn diff_file(bad: (&Path, usize), good: (&Path, usize)) -> Option<Vec<String>> {
+  let (a, b) = (std::fs::read(bad.0).ok()?, std::fs::read(good.0).ok()?);
+  match (std::str::from_utf8(&a), std::str::from_utf8(&b)) {
+    (Ok(a), Ok(b)) => {
+      let (a, b) = (normalize(a, bad.1), normalize(b, good.1));
+      if a == b {
+        return None;
+      }
+      let (minus, plus) = (only_in(&b, &a), only_in(&a, &b));
+      let mut out = vec![format!("{} line(s) differ", minus.len().max(plus.len()))];
+      out.extend(minus.iter().take(SHOW_LINES).map(|l| format!("- {}", l.trim())));
+      out.extend(plus.iter().take(SHOW_LINES).map(|l| format!("+ {}", l.trim())));
+      Some(out)
+    }
+    _ if a == b => None,
+    _ => Some(vec![format!("binary, {} bytes (passing frame: {} bytes)", a.len(), b.len())]),
+  }
+}
+
+pub fn run(args: &[String]) {
+  let Some(n) = args.first().and_then(|v| v.parse::<usize>().ok()) else {
+    eprintln!("usage: framectl bisect-failure N [--against=M]");
+    std::process::exit(2);
+  };
+  let found = frames::discover(&frames::frames_dir());
+  if !found.contains(&n) {
+    eprintln!("bisect-failure: frame-{n:04} is not in this workspace");
+    std::process::exit(2);
+  }
+  let status = last_status();
+  let against = match parse_kv(args, "--against").and_then(|v| v.parse::<usize>().ok()) {
+    Some(m) => Some(m).filter(|m| found.contains(m)),
+    // Nearest first, the lower one on ties.
+    None => {
+      let mut by_distance: Vec<usize> = found.iter().copied().filter(|&m| m != n).collect();
+      by_distance.sort_by_key(|&m| (m.abs_diff(n), m));
+      by_distance.into_iter().find(|&m| passing(m, &status))
+    }
+  };
+  let Some(m) = against else {
+    eprintln!("bisect-failure: no passing frame to compare with (build some first, or pass --against=M)");
+    std::process::exit(2);
+  };
+
+  eprintln!("bisect-failure: frame-{n:04} against passing frame-{m:04}");
+  if let Ok(log) = std::fs::read_to_string(exec::log_path(n, Script::Build)) {
+    eprintln!(
+      "  last failure: {} ({})",
+      classify(&log),
+      exec::log_path(n, Script::Build).display()
+    );
+  }
+
+  let mut count = 0usize;
+  let (bad_fields, good_fields) = (drift::frame_fields(n), drift::frame_fields(m));
+  let keys: BTreeSet<&String> = bad_fields.keys().chain(good_fields.keys()).collect();
+  for key in keys {
+    let (a, b) = (bad_fields.get(key), good_fields.get(key));
+    if a != b {
+      count += 1;
+      eprintln!("  {key}");
+      eprintln!("    - {}", b.map(String::as_str).unwrap_or("(missing)"));
+      eprintln!("    + {}", a.map(String::as_str).unwrap_or("(missing)"));
+    }
+  }
+
+  let (bad_dir, good_dir) = (frames::frame_dir(n), frames::frame_dir(m));
+  let (bad_files, good_files) = (files(&bad_dir), files(&good_dir));
+  let all: BTreeSet<&PathBuf> = bad_files.iter().chain(&good_files).collect();
+  for rel in all {
+    if FIELD_FILES.iter().any(|f| rel == Path::new(f)) {
+      continue;
+    }
+    let lines = match (bad_files.contains(rel), good_files.contains(rel)) {
+      (true, false) => vec!["only in the failing frame".to_string()],
+      (false, true) => vec!["missing from the failing frame".to_string()],
+      _ => match diff_file((&bad_dir.join(rel), n), (&good_dir.join(rel), m)) {
+        Some(lines) => lines,
+        None => continue,
+      },
+    };
+    count += 1;
+    eprintln!("  {}: {}", rel.display(), lines[0]);
+    for l in &lines[1..] {
+      eprintln!("    {l}");
+    }
+  }
+
+  if count == 0 {
+    eprintln!(
+      "bisect-failure: no differences besides the frame number; look outside the frame (deps, cache, machine)"
+    );
+  } else {
+    eprintln!("bisect-failure: {count} difference(s)");
+  }
+}
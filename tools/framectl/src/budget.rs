@@ -0,0 +1,59 @@
+//! `--max-dist-size` / `--max-entry-size`: a build that succeeds but leaves
+//! its dist (or its remote entry) over budget fails, so a bundle size
+//! regression fails on the frame that caused it rather than at deploy.
+
+use std::sync::OnceLock;
+
+use crate::disk::parse_size;
+use crate::gc::{fmt_bytes, size};
+use crate::{exitcode, filterexpr, frames, parse_kv};
+
+/// Prefix of the line a budget failure appends to the output.
+pub const MARK: &str = "budget:";
+
+struct Budget {
+  dist: Option<u64>,
+  entry: Option<u64>,
+}
+
+static BUDGET: OnceLock<Budget> = OnceLock::new();
+
+fn limit(args: &[String], key: &str) -> Option<u64> {
+  let v = parse_kv(args, key)?;
+  let bytes = parse_size(&v).unwrap_or_else(|| {
+    exitcode::usage(format!("invalid {key}={v} (expected a size like 2M or 500K)"));
+  });
+  Some(bytes)
+}
+
+/// Check builds against the budgets in the flags; `Some((dist, entry))` when
+/// either is set.
+pub fn install(args: &[String]) -> Option<(Option<u64>, Option<u64>)> {
+  let dist = limit(args, "--max-dist-size");
+  let entry = limit(args, "--max-entry-size");
+  if dist.is_none() && entry.is_none() {
+    return None;
+  }
+  let _ = BUDGET.set(Budget { dist, entry });
+  Some((dist, entry))
+}
+
+fn over(what: &str, bytes: u64, key: &str, max: u64) -> Option<String> {
+  (bytes > max).then(|| format!("{what} is {} (over {key}={})", fmt_bytes(bytes), fmt_bytes(max)))
+}
+
+/// What frame `n`'s fresh build exceeds; `None` when within budget or no
+/// budget is set. `--filter-expr` tasks have no frame dist to measure.
+pub fn violation(n: usize) -> Option<String> {
+  let budget = BUDGET.get()?;
+  if filterexpr::is_task(n) {
+    return None;
+  }
+  let dist = budget.dist.and_then(|max| over("dist", size(&frames::dist_dir(n)), "--max-dist-size", max));
+  let entry = budget.entry.and_then(|max| {
+    let bytes = frames::entry_path(n).metadata().map(|m| m.len()).unwrap_or(0);
+    over("remote entry", bytes, "--max-entry-size", max)
+  });
+  let hits: Vec<String> = dist.into_iter().chain(entry).collect();
+  (!hits.is_empty()).then(|| format!("{MARK} {}", hits.join(", ")))
+}
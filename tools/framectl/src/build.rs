@@ -0,0 +1,1289 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::annotations;
+use crate::batch::{parse_secs, Batches};
+use crate::budget;
+use crate::classify::{classify, failed_tests, is_oom};
+use crate::container;
+use crate::control::{self, Control};
+use crate::disk::{self, Level};
+use crate::dump::{self, Failure};
+use crate::eta::Estimator;
+use crate::exec::{self, ExecOpts, Network, Script, StageTime};
+use crate::exitcode::{self, Kind};
+use crate::failbundle;
+use crate::filterexpr;
+use crate::frames::{self, frame_pkg};
+use crate::gc::fmt_bytes;
+use crate::heartbeat::{Heartbeat, Snapshot};
+use crate::hooks::Hooks;
+use crate::json::Value;
+use crate::junit;
+use crate::lock;
+use crate::nodematrix;
+use crate::order::{self, Order};
+use crate::preflight;
+use crate::priority;
+use crate::prompt::{self, Choice};
+use crate::queue::TaskQueue;
+use crate::rusage::{self, Usage};
+use crate::ssh::Ssh;
+use crate::strict;
+use crate::summary::{self, FrameRecord, Status, Totals};
+use crate::targets;
+use crate::throttle::Throttle;
+use crate::trace::Trace;
+use crate::warnings;
+use crate::web::Web;
+use crate::weights::Weights;
+use crate::{default_concurrency, fmt_dur, history, parse_bool, parse_kv, remote};
+
+/// How often the main loop wakes up without results, for heartbeats and
+/// state dumps.
+const TICK: Duration = Duration::from_millis(500);
+
+/// After an OOM or spawn backoff, one slot comes back this often.
+const RAMP_EVERY: Duration = Duration::from_secs(30);
+/// After halving for failed spawns, time for the retries already in flight
+/// to land before halving again.
+const SPAWN_SETTLE: Duration = Duration::from_secs(2);
+
+pub struct TaskResult {
+  pub n: usize,
+  /// Build slot the frame ran on, and when it started there.
+  pub slot: usize,
+  pub started: Instant,
+  pub ok: bool,
+  pub err_tail: String,
+  pub dur: Duration,
+  pub usage: Option<Usage>,
+  pub stages: Vec<StageTime>,
+}
+
+/// `build` and `test`: run `script` (or the `--stages` pipeline) in every
+/// frame package of the range.
+pub fn run(args: &[String], script: Script) {
+  if parse_kv(args, "--node-versions").is_some() {
+    return nodematrix::run(args, script);
+  }
+  if parse_kv(args, "--targets").is_some() {
+    return targets::run(args, script);
+  }
+
+  let start: usize = parse_kv(args, "--start")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(1);
+
+  let silent: bool = parse_kv(args, "--silent")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(true);
+
+  let dry_run: bool = parse_kv(args, "--dry-run")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(false);
+
+  let concurrency: usize = parse_kv(args, "--concurrency")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or_else(default_concurrency);
+
+  let stagger_ms: u64 = parse_kv(args, "--stagger-ms")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(0);
+
+  let spawn_rate: Option<f64> = parse_kv(args, "--spawn-rate").and_then(|v| v.parse().ok());
+
+  let max_load: Option<f64> = parse_kv(args, "--max-load").and_then(|v| v.parse().ok());
+
+  let mut disk = disk::Monitor::from_args(args, &frames::frames_dir());
+
+  let plan_out = parse_kv(args, "--plan-out");
+
+  let summary_out = parse_kv(args, "--summary-out");
+
+  let junit_out = parse_kv(args, "--junit-out");
+
+  let annotations = annotations::parse(args);
+
+  let warnings_out = parse_kv(args, "--warnings-out");
+
+  let heartbeat_file = parse_kv(args, "--heartbeat-file");
+
+  let progress_interval = parse_progress_interval(args);
+
+  let quiet = parse_quiet(args);
+  // The summary file has them; a quiet CI log keeps to one line per failure.
+  let show_tails = !(quiet && summary_out.is_some());
+
+  let profile = parse_kv(args, "--profile");
+
+  let web_addr = parse_kv(args, "--web");
+
+  let mut batches = Batches::from_args(args).filter(|_| !dry_run);
+
+  let oom_retries: usize = parse_kv(args, "--oom-retries")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(2);
+
+  let stop_file = parse_kv(args, "--stop-file");
+
+  let timeout = parse_kv(args, "--timeout").map(|v| {
+    parse_secs(&v).filter(|d| !d.is_zero()).unwrap_or_else(|| exitcode::usage(format!("invalid --timeout={v} (expected a duration, e.g. 90m or 2h)")))
+  });
+  if let Some(path) = stop_file.as_deref().filter(|p| Path::new(p).exists()) {
+    exitcode::fail(Kind::Environment, format!("stop file {path} already exists; remove it to start a run"));
+  }
+
+  let interactive: bool = parse_kv(args, "--interactive")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or_else(prompt::is_interactive);
+
+  let keep_going: bool = parse_kv(args, "--keep-going")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(false);
+  let interactive = interactive && !keep_going;
+
+  let network = parse_network(args);
+
+  let node = parse_node(args);
+
+  let stages = parse_stages(args, script);
+  // Messages and prompts name the run after its final stage.
+  let script = stages[stages.len() - 1];
+
+  let weights = Weights::from_args(args);
+
+  // Weights are only worth having if heavy frames go first.
+  let order = match parse_kv(args, "--order") {
+    None if weights.is_some() => Order::SlowestFirst,
+    None => Order::Sequential,
+    Some(v) => Order::parse(&v).unwrap_or_else(|| {
+      exitcode::usage(format!("invalid --order={v} (expected sequential, shuffle, failed-first or slowest-first)"));
+    }),
+  };
+
+  let canary_count: usize = parse_kv(args, "--canary")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(0);
+
+  let preflight: bool = parse_kv(args, "--preflight")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(true);
+
+  let workers: Vec<String> = parse_kv(args, "--workers")
+    .map(|v| {
+      v.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+    })
+    .unwrap_or_default();
+
+  if !workers.is_empty() && node.is_some() {
+    exitcode::usage("--node only applies to local builds, not with --workers");
+  }
+
+  if let Some(t) = targets::install(args) {
+    if !workers.is_empty() || parse_kv(args, "--ssh-hosts").is_some() {
+      exitcode::usage("--target only applies to local builds, not with --workers or --ssh-hosts");
+    }
+    eprintln!("target: {t} ({}={t})", targets::ENV);
+  }
+  let tasks = filterexpr::install(args);
+  if !tasks.is_empty() && (!workers.is_empty() || parse_kv(args, "--ssh-hosts").is_some()) {
+    exitcode::usage("--filter-expr only applies to local builds, not with --workers or --ssh-hosts");
+  }
+
+  if !workers.is_empty() && parse_kv(args, "--strict-stderr").is_some() {
+    exitcode::usage("--strict-stderr only applies to local builds, not with --workers");
+  }
+  let ssh = Ssh::from_args(args).map(Arc::new);
+  if ssh.is_some() {
+    let clash = [("--workers", !workers.is_empty()), ("--node", node.is_some()), ("--container", parse_kv(args, "--container").is_some())];
+    if let Some((flag, _)) = clash.iter().find(|(_, set)| *set) {
+      exitcode::usage(format!("--ssh-hosts and {flag} don't mix"));
+    }
+  }
+  if parse_kv(args, "--container").is_some() {
+    if !workers.is_empty() {
+      exitcode::usage("--container only applies to local builds, not with --workers");
+    }
+    if node.is_some() {
+      exitcode::usage("--node and --container don't mix; pick the Node version by image");
+    }
+  }
+  if let Some(c) = container::install(args, !dry_run) {
+    eprintln!("container: {} via {}", c.image, c.runtime);
+  }
+  if let Some((patterns, allowed)) = strict::install(args) {
+    eprintln!("strict-stderr: {patterns} warning pattern(s), {allowed} allowed");
+  }
+  if (!workers.is_empty() || parse_kv(args, "--ssh-hosts").is_some())
+    && (parse_kv(args, "--max-dist-size").is_some() || parse_kv(args, "--max-entry-size").is_some())
+  {
+    exitcode::usage("--max-dist-size/--max-entry-size only apply to local builds, not with --workers or --ssh-hosts");
+  }
+  if let Some((dist, entry)) = budget::install(args) {
+    let show = |b: Option<u64>| b.map_or("none".to_string(), fmt_bytes);
+    eprintln!("budget: dist {}, remote entry {}", show(dist), show(entry));
+  }
+
+  let hooks = Arc::new(Hooks::from_args(args, silent));
+  if !workers.is_empty() && !hooks.is_empty() {
+    // They would run here while the dist is on the worker.
+    exitcode::usage("--pre-hook/--post-hook only run with local builds, not with --workers");
+  }
+
+  let frames_dir = frames::frames_dir();
+  let end: usize = match parse_kv(args, "--end").and_then(|v| v.parse().ok()) {
+    Some(v) => v,
+    None => frames::infer_end(&frames_dir).unwrap_or(0),
+  };
+
+  // --retry-failed extends the summary it reads: --summary-out if it's
+  // there, else the last run's.
+  let retry_from = parse_retry_failed(args).then(|| {
+    summary_out
+      .as_deref()
+      .map(PathBuf::from)
+      .filter(|p| p.is_file())
+      .unwrap_or_else(|| summary::last_path(script))
+  });
+  let previous = retry_from.as_ref().map(|p| {
+    summary::read(p).unwrap_or_else(|e| {
+      exitcode::usage(format!("--retry-failed: {e}"));
+    })
+  });
+  let mut retrying: Vec<usize> = previous
+    .iter()
+    .flat_map(|(prev, _)| prev.iter().filter(|(_, r)| r.status == Status::Failed).map(|(n, _)| *n))
+    .collect();
+  // Failed tasks are retried by expression, if this run has them.
+  let unlisted: Vec<usize> = retrying.iter().copied().filter(|&n| filterexpr::is_task(n) && !tasks.contains(&n)).collect();
+  if !unlisted.is_empty() {
+    eprintln!(
+      "retry: {} --filter-expr task(s) failed too: {}; pass them in --filter-expr to rebuild them",
+      unlisted.len(),
+      frames::fmt_ranges(&unlisted)
+    );
+    retrying.retain(|n| !unlisted.contains(n));
+  }
+
+  // --frames replaces the range; start and end just describe it.
+  let listed = match (&retry_from, parse_kv(args, "--frames")) {
+    (Some(_), Some(_)) => {
+      exitcode::usage("--retry-failed and --frames don't mix");
+    }
+    (Some(path), None) => {
+      if retrying.is_empty() {
+        eprintln!("nothing to do: no frames failed in {}", path.display());
+        return;
+      }
+      eprintln!("retry: {} frame(s) that failed in {}: {}", retrying.len(), path.display(), frames::fmt_ranges(&retrying));
+      Some(retrying.iter().copied().filter(|&n| !filterexpr::is_task(n)).collect())
+    }
+    (None, v) => v.map(|v| parse_frames(&v)),
+  };
+  if retry_from.is_none() && listed.as_ref().is_some_and(|l| l.is_empty()) {
+    exitcode::fail(Kind::Empty, "nothing to do: --frames listed no frames");
+  }
+  let (start, end) = match &listed {
+    Some(l) if !l.is_empty() => (l[0], l[l.len() - 1]),
+    _ => (start, end),
+  };
+
+  if end < start || end == 0 {
+    exitcode::usage(format!("invalid frame range: start={start} end={end}"));
+  }
+
+  // Remote slots replace local ones: one per slot each worker offers.
+  let mut remote_slots: Vec<String> = Vec::new();
+  for addr in &workers {
+    match remote::probe(addr) {
+      Ok(slots) => {
+        eprintln!("worker {addr}: slots={slots}");
+        remote_slots.extend(std::iter::repeat_n(addr.clone(), slots));
+      }
+      Err(e) => eprintln!("worker {addr}: unreachable ({e}); skipping"),
+    }
+  }
+  if !workers.is_empty() && remote_slots.is_empty() {
+    exitcode::fail(Kind::Environment, "no reachable workers");
+  }
+  // SSH hosts likewise, though their builds are still our children.
+  let mut ssh_slots: Vec<String> = Vec::new();
+  if let Some(ssh) = &ssh {
+    for host in &ssh.hosts {
+      match ssh.probe(host) {
+        Ok(()) => {
+          eprintln!("ssh {host}: slots={}", ssh.slots);
+          ssh_slots.extend(std::iter::repeat_n(host.clone(), ssh.slots));
+        }
+        Err(e) => eprintln!("ssh {host}: unreachable ({e}); skipping"),
+      }
+    }
+    if ssh_slots.is_empty() {
+      exitcode::fail(Kind::Environment, "no reachable ssh hosts");
+    }
+  }
+  let slots = if !workers.is_empty() {
+    remote_slots.len()
+  } else if !ssh_slots.is_empty() {
+    ssh_slots.len()
+  } else {
+    concurrency
+  };
+
+  let exclude: Vec<(usize, usize)> = match parse_kv(args, "--exclude") {
+    None => Vec::new(),
+    Some(v) => frames::parse_ranges(&v).unwrap_or_else(|| {
+      exitcode::usage(format!("invalid --exclude={v} (expected ranges like 100-250,1337)"));
+    }),
+  };
+  let candidates: Vec<usize> = listed.clone().unwrap_or_else(|| (start..=end).collect());
+  let mut plan: Vec<usize> = candidates
+    .iter()
+    .copied()
+    .filter(|n| !exclude.iter().any(|(a, b)| (a..=b).contains(&n)))
+    .collect();
+  // Retried, only the tasks that failed. First, as one usually outlasts
+  // many frames.
+  let tasks: Vec<usize> = tasks.into_iter().filter(|n| retry_from.is_none() || retrying.contains(n)).collect();
+  plan.splice(0..0, tasks.iter().copied());
+  if plan.is_empty() {
+    exitcode::fail(Kind::Empty, format!("nothing to do: --exclude covers start={start} end={end}"));
+  }
+
+  // A dry run writes nothing, so it may look while another run builds.
+  let _lock = (!dry_run).then(|| lock::acquire(args, script.name()));
+
+  let total = plan.len();
+  eprintln!(
+    "{} frames: start={start} end={end} total={total} concurrency={slots} silent={} dry_run={}{}{}{}{}",
+    script.name(),
+    if silent { 1 } else { 0 },
+    if dry_run { 1 } else { 0 },
+    match &listed {
+      Some(l) => format!(" frames={} listed", l.len()),
+      None => String::new(),
+    },
+    if exclude.is_empty() {
+      String::new()
+    } else {
+      let parts: Vec<String> = exclude
+        .iter()
+        .map(|&(a, b)| if a == b { a.to_string() } else { format!("{a}-{b}") })
+        .collect();
+      format!(" exclude={} ({} frames)", parts.join(","), candidates.len() - total)
+    },
+    if !workers.is_empty() {
+      format!(" workers={}", workers.join(","))
+    } else if let Some(ssh) = &ssh {
+      format!(" ssh={}", ssh.hosts.join(","))
+    } else {
+      String::new()
+    },
+    batches.as_ref().map(|b| format!(" {}", b.describe())).unwrap_or_default()
+  );
+
+  if order != Order::Sequential {
+    let note = order::apply(order, &mut plan, &stages, weights.as_ref(), slots);
+    if note.is_empty() {
+      eprintln!("order: {}", order.name());
+    } else {
+      eprintln!("order: {} ({note})", order.name());
+    }
+  }
+
+  // Too small a range to sample from: the whole run is the canary.
+  let sampled: Vec<usize> = plan.iter().copied().filter(|&n| !filterexpr::is_task(n)).collect();
+  let canary: BTreeSet<usize> = if canary_count > 0 && canary_count < sampled.len() {
+    order::canary(&sampled, canary_count)
+  } else {
+    BTreeSet::new()
+  };
+  if !canary.is_empty() {
+    plan.sort_by_key(|n| !canary.contains(n));
+    let picked: Vec<usize> = canary.iter().copied().collect();
+    eprintln!("canary: {} frame(s) first: {}", picked.len(), frames::fmt_ranges(&picked));
+  }
+
+  priority::apply_args(args);
+
+  if !tasks.is_empty() {
+    eprintln!("filter-expr: {} task(s): {}", tasks.len(), frames::fmt_ranges(&tasks));
+  }
+
+  if let (Some(v), Some(manager)) = (node, exec::node_manager()) {
+    eprintln!("node: {v} via {manager}");
+  }
+
+  if stages.len() > 1 {
+    let names: Vec<&str> = stages.iter().map(|s| s.name()).collect();
+    eprintln!("stages: {}", names.join(" -> "));
+  }
+
+  if stagger_ms > 0 || spawn_rate.is_some() || max_load.is_some() {
+    eprintln!(
+      "throttle: stagger={stagger_ms}ms spawn_rate={}{}",
+      spawn_rate.map(|r| format!("{r}/s")).unwrap_or_else(|| "unlimited".to_string()),
+      max_load.map(|l| format!(" max_load={l}")).unwrap_or_default()
+    );
+  }
+
+  let opts = ExecOpts {
+    script,
+    silent,
+    dry_run,
+    network,
+    node,
+  };
+
+  if preflight {
+    // Workers build from their own checkouts; only the local one is checked.
+    let problems = preflight::check(&plan, !dry_run, network);
+    if !problems.is_empty() {
+      eprintln!("preflight failed:");
+      for p in &problems {
+        eprintln!("  - {p}");
+      }
+      eprintln!("(skip with --preflight=0)");
+      let problems = problems.iter().map(|p| Value::from(p.as_str())).collect();
+      exitcode::exit(Kind::Environment, "preflight failed", vec![("problems", Value::Arr(problems))]);
+    }
+  }
+
+  if let Some(path) = &plan_out {
+    let est = Estimator::new(history::load_stages(&stages)).weighted(weights.clone()).estimate(plan.iter().copied(), slots);
+    let doc = plan_json(&plan, opts, &stages, slots, &workers, est.map(|e| e.mid));
+    if let Err(e) = std::fs::write(path, doc.pretty()) {
+      exitcode::fail(Kind::Environment, format!("plan: write {path}: {e}"));
+    }
+    eprintln!("plan: wrote {path} ({} frames)", plan.len());
+  }
+
+  let tracks: Vec<String> = if !ssh_slots.is_empty() {
+    ssh_slots.iter().map(|host| format!("ssh {host}")).collect()
+  } else if remote_slots.is_empty() {
+    (0..concurrency).map(|i| format!("slot {i}")).collect()
+  } else {
+    remote_slots.iter().map(|addr| format!("worker {addr}")).collect()
+  };
+
+  // With --canary only the sample is queued; the rest waits for it to pass.
+  let (first, rest) = plan.split_at(if canary.is_empty() { plan.len() } else { canary.len() });
+  let queue = Arc::new(TaskQueue::new(first.iter().copied(), !interactive && !keep_going));
+  queue.hold(rest.iter().copied());
+  if let Some(b) = batches.as_mut() {
+    b.start(&queue);
+  }
+  let throttle = Arc::new(Throttle::new(Duration::from_millis(stagger_ms), spawn_rate).with_max_load(max_load));
+  // Checked before the first spawn as well, so a full disk or a loaded
+  // machine starts nothing.
+  let mut disk_paused = false;
+  if let Some(d) = disk.as_mut() {
+    match d.check() {
+      Some(Level::Abort) => {
+        exitcode::fail(Kind::Environment, format!("disk: {}; not starting", d.describe()));
+      }
+      Some(Level::Pause) => {
+        eprintln!("disk: {}; waiting for space before the first build", d.describe());
+        disk_paused = true;
+      }
+      _ => {}
+    }
+  }
+  let mut load_paused = throttle.load_high();
+  queue.set_paused(disk_paused || load_paused);
+  let (res_tx, res_rx) = mpsc::channel::<TaskResult>();
+
+  if remote_slots.is_empty() {
+    for i in 0..slots {
+      let queue = Arc::clone(&queue);
+      let throttle = Arc::clone(&throttle);
+      let res_tx = res_tx.clone();
+      let stages = stages.clone();
+      let hooks = Arc::clone(&hooks);
+      let on_host = ssh.clone().zip(ssh_slots.get(i).cloned());
+      thread::spawn(move || {
+        throttle.ramp_up(i);
+        while let Some(n) = queue.pop() {
+          throttle.before_spawn();
+          // Closed while waiting for the rate limit: a halted run starts nothing.
+          if queue.is_closed() {
+            queue.requeue(n);
+            break;
+          }
+          let t = Instant::now();
+          let (out, stages) = hooks.around(n, opts.script, opts.dry_run, || match &on_host {
+            Some((ssh, host)) => ssh.run_pipeline(host, n, opts, &stages),
+            None => exec::run_pipeline(n, opts, &stages),
+          });
+          if out.ok {
+            queue.finish(n);
+          } else if !is_oom(&out.err_tail) {
+            // OOMs may be retried; the main loop decides.
+            queue.fail(n);
+          }
+          let _ = res_tx.send(TaskResult {
+            n,
+            slot: i,
+            started: t,
+            ok: out.ok,
+            err_tail: out.err_tail,
+            dur: t.elapsed(),
+            usage: out.usage,
+            stages,
+          });
+        }
+      });
+    }
+  } else {
+    for (i, addr) in remote_slots.into_iter().enumerate() {
+      let queue = Arc::clone(&queue);
+      let throttle = Arc::clone(&throttle);
+      let res_tx = res_tx.clone();
+      let stages = stages.clone();
+      thread::spawn(move || {
+        throttle.ramp_up(i);
+        remote::run_slot(&addr, i, &queue, &throttle, &res_tx, opts, &stages)
+      });
+    }
+  }
+  drop(res_tx);
+
+  exitcode::catch_signals();
+  let t0 = Instant::now();
+  let mut last_print = Instant::now();
+  let mut trace = profile.as_ref().map(|_| Trace::new(t0, &tracks));
+
+  let mut pending: BTreeSet<usize> = plan.iter().copied().collect();
+  let mut estimator = Estimator::new(if dry_run { BTreeMap::new() } else { history::load_stages(&stages) }).weighted(weights.clone());
+  // Per stage, in `stages` order; each stage keeps its own history.
+  let mut durations: Vec<BTreeMap<usize, Duration>> = vec![BTreeMap::new(); stages.len()];
+  let mut records: BTreeMap<usize, FrameRecord> = BTreeMap::new();
+
+  let mut done = 0usize;
+  let mut ok = 0usize;
+  let mut first_fail: Option<usize> = None;
+  let mut skipped: Vec<usize> = Vec::new();
+  let mut aborted = false;
+  let mut stopped = false;
+  let mut canary_left = canary.len();
+  let mut canary_failed = false;
+  let mut disk_low = false;
+  let mut store_missing = false;
+  // Set by a signal or --timeout: builds in flight are stopped, nothing new starts.
+  let mut halted: Option<Kind> = None;
+  let mut oom_tries: BTreeMap<usize, usize> = BTreeMap::new();
+  let mut limit = slots;
+  let mut limit_changed = Instant::now();
+  let mut recent_failures: VecDeque<Failure> = VecDeque::new();
+  let mut heartbeat = heartbeat_file.as_deref().map(Heartbeat::new);
+  let mut web = web_addr.as_deref().map(|addr| {
+    Web::start(&remote::listen_addr(addr)).unwrap_or_else(|e| {
+      exitcode::fail(Kind::Environment, format!("web: cannot listen on {addr}: {e}"));
+    })
+  });
+  // Per slot: frames done, failed, and the last one finished.
+  let mut slot_stats: Vec<(usize, usize, Option<usize>)> = vec![(0, 0, None); tracks.len()];
+  // `framectl top` may cap concurrency below the slots; OOM backoff ramps
+  // back up to that cap. Only local builds have children it can see.
+  let mut ceiling = slots;
+  // What the last backoff was for, to say so when ramping back up.
+  let mut backoff = "oom";
+  let mut spawn_squeezed: Option<Instant> = None;
+  let control = (!dry_run && workers.is_empty()).then(|| Control::start(Arc::clone(&queue), slots)).flatten();
+  dump::install();
+  loop {
+    // Wake up regularly even when nothing finishes, so a stalled run still
+    // refreshes its heartbeat and answers dump requests.
+    let msg = res_rx.recv_timeout(TICK);
+    let hb_due = heartbeat.as_ref().is_some_and(|hb| hb.due());
+    let web_due = web.as_ref().is_some_and(|w| w.due());
+    if hb_due || web_due {
+      let snap = Snapshot {
+        state: if disk_paused || load_paused {
+          "paused"
+        } else if batches.as_ref().is_some_and(|b| b.cooling()) {
+          "cooldown"
+        } else if canary_left > 0 {
+          "canary"
+        } else {
+          "running"
+        },
+        total,
+        done,
+        ok,
+        elapsed: t0.elapsed(),
+        eta: estimator
+          .estimate(pending.iter().copied(), slots)
+          .map(|e| match &batches {
+            Some(b) => b.delay(e, &queue),
+            None => e,
+          }),
+        in_flight: queue.in_flight().into_iter().map(|(n, _)| n).collect(),
+      };
+      if let Some(hb) = heartbeat.as_mut().filter(|_| hb_due) {
+        hb.write(&snap);
+      }
+      if let Some(w) = web.as_mut().filter(|_| web_due) {
+        let mut fields = snap.fields();
+        fields.push(("workers", workers_json(&tracks, &slot_stats)));
+        w.progress(fields);
+      }
+    }
+    if !stopped && stop_file.as_deref().is_some_and(|p| Path::new(p).exists()) {
+      let in_flight = queue.in_flight().len();
+      eprintln!("stop: {} appeared; waiting for {in_flight} frame(s) in flight", stop_file.as_deref().unwrap_or(""));
+      stopped = true;
+      queue.close();
+    }
+    if halted.is_none() {
+      let why = if exitcode::interrupted() {
+        Some((Kind::Interrupted, "interrupted".to_string()))
+      } else if timeout.is_some_and(|t| t0.elapsed() >= t) {
+        Some((Kind::Timeout, format!("timeout: {} reached", fmt_dur(timeout.unwrap_or_default()))))
+      } else {
+        None
+      };
+      if let Some((kind, why)) = why {
+        queue.close();
+        let killed = control::kill_all();
+        eprintln!("{why}; stopping {killed} build(s) in flight (again to exit now)");
+        halted = Some(kind);
+      }
+    }
+    if let Some(d) = disk.as_mut().filter(|_| !disk_low) {
+      match d.check() {
+        Some(Level::Abort) => {
+          let in_flight = queue.in_flight().len();
+          eprintln!("disk: {}; aborting, waiting for {in_flight} frame(s) in flight", d.describe());
+          disk_low = true;
+          queue.close();
+        }
+        Some(Level::Pause) if !disk_paused => {
+          eprintln!("disk: {}; pausing new builds", d.describe());
+          disk_paused = true;
+          queue.set_paused(true);
+        }
+        Some(Level::Ok) if disk_paused => {
+          eprintln!("disk: {}; resuming", d.describe());
+          disk_paused = false;
+          queue.set_paused(load_paused);
+        }
+        _ => {}
+      }
+    }
+    let high = throttle.load_high();
+    if high != load_paused {
+      load_paused = high;
+      queue.set_paused(disk_paused || load_paused);
+    }
+    if let Some(c) = &control {
+      if let Some(want) = c.requested_limit() {
+        eprintln!("top: concurrency set to {want} (was {limit})");
+        ceiling = want;
+        limit = want;
+        limit_changed = Instant::now();
+        queue.set_limit(limit);
+      }
+      c.update(limit, done, total);
+    }
+    if let Some(b) = batches.as_mut() {
+      b.tick(&queue);
+    }
+    let exhausted = exec::take_exhausted();
+    if exhausted > 0 && limit > 1 && spawn_squeezed.is_none_or(|t| t.elapsed() >= SPAWN_SETTLE) {
+      let was = limit;
+      limit = (limit / 2).max(1);
+      limit_changed = Instant::now();
+      spawn_squeezed = Some(limit_changed);
+      backoff = "spawn";
+      queue.set_limit(limit);
+      eprintln!("spawn: out of processes or file descriptors ({exhausted} failed spawn(s)); concurrency {limit} (was {was})");
+    }
+    if limit < ceiling && limit_changed.elapsed() >= RAMP_EVERY {
+      limit += 1;
+      limit_changed = Instant::now();
+      queue.set_limit(limit);
+      eprintln!("{backoff}: concurrency back up to {limit}");
+    }
+    if dump::requested() {
+      dump::write(&dump::State {
+        total,
+        done,
+        ok,
+        elapsed: t0.elapsed(),
+        pending: queue.pending(),
+        in_flight: queue.in_flight(),
+        pids: exec::running_pids(),
+        recent_failures: &recent_failures,
+      });
+    }
+    let TaskResult {
+      n,
+      slot,
+      started,
+      ok: status_ok,
+      err_tail,
+      dur,
+      usage,
+      stages: stage_times,
+    } = match msg {
+      Ok(r) => r,
+      Err(RecvTimeoutError::Timeout) => continue,
+      Err(RecvTimeoutError::Disconnected) => break,
+    };
+    if let Some(trace) = trace.as_mut() {
+      trace.frame(slot, n, started, dur, status_ok, &stage_times);
+    }
+    if let Some(st) = slot_stats.get_mut(slot) {
+      st.0 += 1;
+      st.1 += usize::from(!status_ok);
+      st.2 = Some(n);
+    }
+    if !status_ok && is_oom(&err_tail) {
+      let tries = oom_tries.entry(n).or_default();
+      if *tries < oom_retries {
+        *tries += 1;
+        let was = limit;
+        limit = (limit / 2).max(1);
+        limit_changed = Instant::now();
+        backoff = "oom";
+        queue.set_limit(limit);
+        queue.requeue(n);
+        eprintln!("oom: {} ran out of memory; retrying with concurrency {limit} (was {was})", frames::label(n));
+        continue;
+      }
+      queue.fail(n);
+    }
+    done += 1;
+    pending.remove(&n);
+    for (i, st) in stage_times.iter().enumerate() {
+      if st.ok {
+        durations[i].insert(n, st.dur);
+      }
+    }
+    let failed_stage = stage_times.last().filter(|st| !st.ok).map(|st| st.script);
+    records.insert(
+      n,
+      FrameRecord {
+        status: if status_ok { Status::Ok } else { Status::Failed },
+        dur,
+        usage,
+        error: (!status_ok).then(|| classify(&err_tail)),
+        failed_tests: if status_ok { Vec::new() } else { failed_tests(&err_tail) },
+        stages: stage_times,
+      },
+    );
+    if status_ok {
+      ok += 1;
+      estimator.observe(n, dur);
+      if canary_left > 0 && canary.contains(&n) {
+        canary_left -= 1;
+        if canary_left == 0 {
+          eprintln!(
+            "canary: {} frame(s) passed in {}; starting the remaining {}",
+            canary.len(),
+            fmt_dur(t0.elapsed()),
+            total - canary.len()
+          );
+          queue.release();
+        }
+      }
+    } else {
+      if recent_failures.len() == dump::RECENT_FAILURES {
+        recent_failures.pop_front();
+      }
+      recent_failures.push_back(Failure {
+        n,
+        class: classify(&err_tail),
+        at: Instant::now(),
+      });
+      if let Some(w) = &web {
+        let mut fields = vec![("frame", Value::from(n)), ("class", Value::from(classify(&err_tail)))];
+        if let Some(st) = failed_stage.filter(|_| stages.len() > 1) {
+          fields.push(("stage", Value::from(st.name())));
+        }
+        w.event("failure", fields);
+      }
+    }
+    if !status_ok && first_fail.is_none() {
+      first_fail = Some(n);
+    }
+
+    if !quiet && (last_print.elapsed() >= progress_interval || done == total) {
+      let elapsed = t0.elapsed().as_secs_f64().max(0.0001);
+      let rate = done as f64 / elapsed;
+      let eta = estimator
+        .estimate(pending.iter().copied(), slots)
+        .map(|e| match &batches {
+          Some(b) => b.delay(e, &queue),
+          None => e,
+        });
+      let eta = match eta {
+        Some(e) if !pending.is_empty() => {
+          format!("{} ({}..{})", fmt_dur(e.mid), fmt_dur(e.low), fmt_dur(e.high))
+        }
+        _ => fmt_dur(Duration::ZERO),
+      };
+      eprintln!(
+        "progress: done={done}/{total} ok={ok} failed={} rate={:.1}/s eta={eta}",
+        done.saturating_sub(ok),
+        rate,
+      );
+      last_print = Instant::now();
+    }
+
+    if !status_ok {
+      match failed_stage.filter(|_| stages.len() > 1) {
+        Some(st) => eprintln!("failed: {} at {}", who(n), st.name()),
+        None => eprintln!("failed: {}", who(n)),
+      }
+      if show_tails && !err_tail.trim().is_empty() && halted.is_none() {
+        eprintln!("stderr tail:\n{err_tail}");
+      }
+      // Stopped on purpose: collect the rest of the frames in flight.
+      if halted.is_some() {
+        queue.finish(n);
+        continue;
+      }
+      // Every other frame would hit the same missing package.
+      if network == Network::Offline && records.get(&n).and_then(|r| r.error.as_deref()) == Some("offline") {
+        eprintln!("offline: the pnpm store is missing packages; run `pnpm install` online first (or drop --offline)");
+        aborted = true;
+        store_missing = true;
+        queue.close();
+        break;
+      }
+      // A failing sample stops the run even with --keep-going or a prompt.
+      if canary_left > 0 && canary.contains(&n) {
+        eprintln!("canary: frame-{n:04} failed; not starting the remaining {} frame(s)", total - canary.len());
+        canary_failed = true;
+        queue.close();
+        break;
+      }
+      if keep_going {
+        queue.finish(n);
+        continue;
+      }
+      if !interactive {
+        break;
+      }
+      match prompt::on_failure(n, failed_stage.unwrap_or(script)) {
+        Choice::Retry => {
+          done -= 1;
+          pending.insert(n);
+          if first_fail == Some(n) {
+            first_fail = None;
+          }
+          queue.requeue(n);
+        }
+        Choice::Skip => {
+          skipped.push(n);
+          if let Some(r) = records.get_mut(&n) {
+            r.status = Status::Skipped;
+          }
+          queue.finish(n);
+        }
+        Choice::Abort => {
+          aborted = true;
+          queue.close();
+          break;
+        }
+      }
+    }
+  }
+
+  dump::uninstall();
+  if let Some(c) = &control {
+    c.stop();
+  }
+
+  if !dry_run {
+    for (stage, durations) in stages.iter().zip(&durations) {
+      if let Err(e) = history::save(*stage, durations) {
+        eprintln!("warning: could not save duration history: {e}");
+      }
+    }
+    summary::print_timing(&records);
+    summary::print_failures(&records);
+    warnings::report(warnings_out.as_deref());
+    if let Some(dir) = failbundle::dir(args) {
+      // A --filter-expr task's directory is the whole workspace.
+      for (n, r) in records.iter().filter(|(n, r)| r.status == Status::Failed && !filterexpr::is_task(**n)) {
+        match failbundle::write(&dir, *n, r, opts, args, script.name()) {
+          Ok(path) => eprintln!("failure bundle: {}", path.display()),
+          Err(e) => eprintln!("warning: {}: no failure bundle: {e}", frames::label(*n)),
+        }
+      }
+    }
+    if !oom_tries.is_empty() {
+      let frames: Vec<usize> = oom_tries.keys().copied().collect();
+      eprintln!(
+        "oom: {} frame(s) retried after running out of memory: {}",
+        frames.len(),
+        frames::fmt_ranges(&frames)
+      );
+    }
+  }
+  if let (Some(path), Some(trace)) = (&profile, trace) {
+    trace.write(path);
+  }
+  let mut totals = Totals {
+    total,
+    elapsed: t0.elapsed(),
+  };
+  if let Some((mut prev, elapsed)) = previous {
+    if !dry_run {
+      let fixed: Vec<usize> = retrying.iter().copied().filter(|n| records.get(n).is_some_and(|r| r.status == Status::Ok)).collect();
+      let still: Vec<usize> = retrying.iter().copied().filter(|n| !fixed.contains(n)).collect();
+      eprintln!(
+        "retry: {}/{} previously failing frame(s) now pass{}",
+        fixed.len(),
+        retrying.len(),
+        if fixed.is_empty() { String::new() } else { format!(": {}", frames::fmt_ranges(&fixed)) }
+      );
+      if !still.is_empty() {
+        eprintln!("retry: still failing: {}", frames::fmt_ranges(&still));
+      }
+    }
+    // A frame skipped this time keeps what it had.
+    for (n, r) in std::mem::take(&mut records) {
+      if r.status != Status::Skipped || !prev.contains_key(&n) {
+        prev.insert(n, r);
+      }
+    }
+    records = prev;
+    totals = Totals {
+      total: records.len(),
+      elapsed: elapsed + totals.elapsed,
+    };
+  }
+  if !dry_run {
+    summary::write(summary::last_path(script), &records, &totals);
+  }
+  if let Some(path) = &summary_out {
+    summary::write(path, &records, &totals);
+  }
+  if let Some(path) = &junit_out {
+    junit::write(path, &records, &totals, script);
+  }
+  if let Some(format) = annotations.filter(|_| !dry_run) {
+    annotations::print(format, &records, script);
+  }
+  let final_state = if done == total && ok == total {
+    "done"
+  } else if stopped || halted.is_some() {
+    "stopped"
+  } else if aborted || disk_low {
+    "aborted"
+  } else {
+    "failed"
+  };
+  if let Some(hb) = heartbeat.as_mut() {
+    hb.write(&Snapshot {
+      state: final_state,
+      total,
+      done,
+      ok,
+      elapsed: t0.elapsed(),
+      eta: None,
+      in_flight: queue.in_flight().into_iter().map(|(n, _)| n).collect(),
+    });
+  }
+  if let Some(w) = web.as_mut() {
+    let mut fields = Snapshot {
+      state: final_state,
+      total,
+      done,
+      ok,
+      elapsed: t0.elapsed(),
+      eta: None,
+      in_flight: Vec::new(),
+    }
+    .fields();
+    fields.push(("workers", workers_json(&tracks, &slot_stats)));
+    w.finish(fields);
+  }
+
+  if done == total && ok == total {
+    eprintln!("success: {} {ok} frames in {}", script.past(), fmt_dur(t0.elapsed()));
+    return;
+  }
+
+  let failed: Vec<usize> = records
+    .iter()
+    .filter(|(_, r)| r.status == Status::Failed)
+    .map(|(n, _)| *n)
+    .collect();
+  let counts = format!("done={done}/{total} ok={ok}");
+  let (kind, message) = if let Some(kind) = halted {
+    match kind {
+      Kind::Timeout => (kind, format!("exit: timed out after {} ({counts})", fmt_dur(t0.elapsed()))),
+      _ => (kind, format!("exit: interrupted ({counts})")),
+    }
+  } else if stopped && ok == done {
+    (Kind::Interrupted, format!("exit: stopped ({counts})"))
+  } else if disk_low {
+    let free = disk.as_ref().map(disk::Monitor::describe).unwrap_or_default();
+    (Kind::Environment, format!("exit: low disk space, {free} ({counts})"))
+  } else if canary_failed {
+    (Kind::Build, format!("exit: canary failed ({counts})"))
+  } else if store_missing {
+    (Kind::Environment, format!("exit: pnpm store incomplete for --offline ({counts})"))
+  } else if aborted {
+    (Kind::Build, format!("exit: aborted ({counts})"))
+  } else if !skipped.is_empty() && done == total {
+    skipped.sort_unstable();
+    (Kind::Build, format!("exit: {} frame(s) skipped after failing: {}", skipped.len(), frames::fmt_ranges(&skipped)))
+  } else if keep_going && done == total {
+    (Kind::Build, format!("exit: {} frame(s) failed: {}", failed.len(), frames::fmt_ranges(&failed)))
+  } else if let Some(n) = first_fail {
+    (Kind::Build, format!("exit: {} failed at {}", script.name(), frames::label(n)))
+  } else if !workers.is_empty() {
+    (Kind::Environment, format!("exit: all workers lost ({counts})"))
+  } else {
+    (Kind::Build, format!("exit: {} stopped ({counts})", script.name()))
+  };
+  eprintln!("{message}");
+  let summary = summary_out.clone().or_else(|| (!dry_run).then(|| summary::last_path(script).display().to_string()));
+  exitcode::exit(
+    kind,
+    &message,
+    vec![
+      ("total", Value::from(total)),
+      ("done", Value::from(done)),
+      ("ok", Value::from(ok)),
+      ("failed", Value::Arr(failed.iter().map(|&n| Value::from(n)).collect())),
+      ("summary", summary.map(Value::from).unwrap_or(Value::Null)),
+    ],
+  );
+}
+
+/// `build-one N`: a single frame with output streamed straight through.
+pub fn run_one(args: &[String]) {
+  let Some(n) = args.iter().find(|a| !a.starts_with("--")).and_then(|v| v.parse::<usize>().ok()) else {
+    exitcode::usage("usage: framectl build-one N [--verbose] [--dry-run=0|1] [--offline=0|1|prefer]");
+  };
+  let verbose = args.iter().any(|a| a == "--verbose" || a == "-v")
+    || parse_kv(args, "--verbose").and_then(|v| parse_bool(&v)).unwrap_or(false);
+  let dry_run: bool = parse_kv(args, "--dry-run")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(false);
+  let opts = ExecOpts {
+    script: Script::Build,
+    silent: false,
+    dry_run,
+    network: parse_network(args),
+    node: parse_node(args),
+  };
+
+  if verbose || dry_run {
+    for (k, v) in exec::env(opts) {
+      eprintln!("env: {k}={v}");
+    }
+    eprintln!("exec: {}", exec::argv(n, opts).join(" "));
+  }
+  if dry_run {
+    return;
+  }
+  let _lock = lock::acquire(args, "build-one");
+
+  let t = Instant::now();
+  let mut child = match exec::command(n, opts).spawn() {
+    Ok(c) => c,
+    Err(e) => {
+      exitcode::fail(Kind::Environment, format!("frame-{n:04}: spawn failed: {e}"));
+    }
+  };
+  let (status, usage) = match rusage::wait(&mut child) {
+    Ok(v) => v,
+    Err(e) => {
+      exitcode::fail(Kind::Environment, format!("frame-{n:04}: wait failed: {e}"));
+    }
+  };
+  if verbose {
+    let usage = usage
+      .map(|u| format!(" cpu={:.1}s max_rss={}KB", u.cpu.as_secs_f64(), u.max_rss_kb))
+      .unwrap_or_default();
+    eprintln!("frame-{n:04}: {status} in {}{usage}", fmt_dur(t.elapsed()));
+  }
+  if !status.success() {
+    exitcode::exit(Kind::Build, &format!("frame-{n:04}: build failed ({status})"), vec![("frame", Value::from(n))]);
+  }
+}
+
+fn workers_json(tracks: &[String], stats: &[(usize, usize, Option<usize>)]) -> Value {
+  Value::Arr(
+    tracks
+      .iter()
+      .zip(stats)
+      .map(|(name, (done, failed, last))| {
+        Value::obj([
+          ("name", Value::from(name.as_str())),
+          ("done", Value::from(*done)),
+          ("failed", Value::from(*failed)),
+          ("last", last.map(Value::from).unwrap_or(Value::Null)),
+        ])
+      })
+      .collect(),
+  )
+}
+
+/// `--stages=install,typecheck,build`, defaulting to just `script`.
+fn parse_stages(args: &[String], script: Script) -> Vec<Script> {
+  let Some(v) = parse_kv(args, "--stages") else {
+    return vec![script];
+  };
+  let mut stages = Vec::new();
+  for name in v.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+    match Script::parse(name) {
+      Some(s) => stages.push(s),
+      None => {
+        exitcode::usage(format!("unknown stage: {name} (expected install, typecheck, build or test)"));
+      }
+    }
+  }
+  if stages.is_empty() {
+    exitcode::usage("--stages needs at least one stage");
+  }
+  stages
+}
+
+/// `frame-0042 (@bad-apple/frame-0042)`, or a `--filter-expr` task's
+/// expression.
+fn who(n: usize) -> String {
+  match filterexpr::get(n) {
+    Some(expr) => expr.to_string(),
+    None => format!("{} ({})", frames::label(n), frame_pkg(n)),
+  }
+}
+
+/// `--frames=1,5,10-20`, or `--frames=-` for numbers (or ranges) separated by
+/// whitespace or commas on stdin; sorted, duplicates dropped. Numbers past
+/// `MAX_FRAME` are typos (or `--filter-expr` task numbers), not frames.
+fn parse_frames(v: &str) -> Vec<usize> {
+  let text = if v == "-" {
+    let mut buf = String::new();
+    if let Err(e) = std::io::stdin().read_to_string(&mut buf) {
+      exitcode::fail(Kind::Environment, format!("--frames=-: cannot read stdin: {e}"));
+    }
+    buf
+  } else {
+    v.to_string()
+  };
+  let mut out = Vec::new();
+  for tok in text.split(|c: char| c.is_whitespace() || c == ',').filter(|t| !t.is_empty()) {
+    match frames::parse_range(tok) {
+      Some((a, b)) if a > 0 && b <= frames::MAX_FRAME => out.extend(a..=b),
+      _ => {
+        exitcode::usage(format!(
+          "invalid frame in --frames: {tok:?} (expected N or A-B, from 1 to {})",
+          frames::MAX_FRAME
+        ));
+      }
+    }
+  }
+  out.sort_unstable();
+  out.dedup();
+  out
+}
+
+/// `--node=20`: a Node major version, which needs fnm or Volta.
+fn parse_node(args: &[String]) -> Option<u32> {
+  let v = parse_kv(args, "--node")?;
+  let Ok(major) = v.parse() else {
+    exitcode::usage(format!("invalid --node={v} (expected a major version like 20)"));
+  };
+  if exec::node_manager().is_none() {
+    exitcode::fail(Kind::Environment, "--node needs fnm or volta on PATH");
+  }
+  Some(major)
+}
+
+/// `--progress-interval=SECS` (`0.2`, `60s`, `1m`), default one second.
+pub fn parse_progress_interval(args: &[String]) -> Duration {
+  match parse_kv(args, "--progress-interval") {
+    None => Duration::from_secs(1),
+    Some(v) => parse_secs(&v).unwrap_or_else(|| {
+      exitcode::usage(format!("invalid --progress-interval={v} (expected seconds, e.g. 0.5 or 60s)"));
+    }),
+  }
+}
+
+/// `--retry-failed` (or `=0|1`).
+pub fn parse_retry_failed(args: &[String]) -> bool {
+  args.iter().any(|a| a == "--retry-failed") || parse_kv(args, "--retry-failed").and_then(|v| parse_bool(&v)).unwrap_or(false)
+}
+
+/// `--quiet` (or `-q`, `--quiet=0|1`): no progress lines, only the summary.
+pub fn parse_quiet(args: &[String]) -> bool {
+  args.iter().any(|a| a == "--quiet" || a == "-q") || parse_kv(args, "--quiet").and_then(|v| parse_bool(&v)).unwrap_or(false)
+}
+
+/// `--offline=0|1|prefer`.
+pub fn parse_network(args: &[String]) -> Network {
+  match parse_kv(args, "--offline") {
+    None => Network::Online,
+    Some(v) => Network::parse(&v).unwrap_or_else(|| {
+      exitcode::usage(format!("invalid --offline={v} (expected 0, 1 or prefer)"));
+    }),
+  }
+}
+
+fn plan_json(
+  plan: &[usize],
+  opts: ExecOpts,
+  stages: &[Script],
+  slots: usize,
+  workers: &[String],
+  estimate: Option<Duration>,
+) -> Value {
+  let env = || {
+    Value::obj(
+      exec::env(opts)
+        .into_iter()
+        .map(|(k, v)| (k, Value::from(v))),
+    )
+  };
+  let tasks = plan
+    .iter()
+    .map(|&n| {
+      let command = |script| Value::Arr(exec::argv(n, ExecOpts { script, ..opts }).into_iter().map(Value::from).collect());
+      let mut fields = vec![
+        ("frame", Value::from(n)),
+        ("package", Value::from(frame_pkg(n))),
+        ("command", command(stages[stages.len() - 1])),
+        ("env", env()),
+      ];
+      if stages.len() > 1 {
+        let all = stages
+          .iter()
+          .map(|&s| Value::obj([("stage", Value::from(s.name())), ("command", command(s))]))
+          .collect();
+        fields.push(("stages", Value::Arr(all)));
+      }
+      Value::obj(fields)
+    })
+    .collect();
+  Value::obj([
+    ("frames", Value::Arr(plan.iter().map(|&n| Value::from(n)).collect())),
+    ("concurrency", Value::from(slots)),
+    (
+      "workers",
+      Value::Arr(workers.iter().map(|w| Value::from(w.as_str())).collect()),
+    ),
+    (
+      "estimated_secs",
+      estimate.map(|d| Value::from(d.as_secs_f64())).unwrap_or(Value::Null),
+    ),
+    ("tasks", Value::Arr(tasks)),
+  ])
+}
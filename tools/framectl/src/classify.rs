@@ -0,0 +1,111 @@
+//! Buckets for failed builds, from their captured stderr.
+
+use crate::{budget, strict};
+
+/// A short class for the failure: `oom`, `TS<code>`, `module-not-found`,
+/// `filter-miss`, `missing-script`, `offline`, `tests`, `spawn`, `hook`, `warning`
+/// (see `--strict-stderr`), `budget` (see `--max-dist-size`) or `other`.
+pub fn classify(stderr: &str) -> String {
+  if stderr.starts_with("spawn failed") {
+    return "spawn".to_string();
+  }
+  if stderr.starts_with("pre-hook failed") || stderr.starts_with("post-hook failed") {
+    return "hook".to_string();
+  }
+  if stderr.trim_end().lines().last().is_some_and(|l| l.starts_with(strict::MARK)) {
+    return "warning".to_string();
+  }
+  if stderr.trim_end().lines().last().is_some_and(|l| l.starts_with(budget::MARK)) {
+    return "budget".to_string();
+  }
+  if is_oom(stderr) {
+    return "oom".to_string();
+  }
+  if is_offline_miss(stderr) {
+    return "offline".to_string();
+  }
+  if stderr.contains("No projects matched the filters") {
+    return "filter-miss".to_string();
+  }
+  if stderr.contains("ERR_PNPM_NO_SCRIPT")
+    || stderr.contains("ERR_PNPM_RECURSIVE_RUN_NO_SCRIPT")
+    || stderr.contains("Missing script:")
+  {
+    return "missing-script".to_string();
+  }
+  if !failed_tests(stderr).is_empty() {
+    return "tests".to_string();
+  }
+  if let Some(code) = ts_code(stderr) {
+    return code;
+  }
+  if stderr.contains("Module not found") || stderr.contains("Can't resolve") {
+    return "module-not-found".to_string();
+  }
+  "other".to_string()
+}
+
+/// A JS heap OOM, or a build SIGKILLed (usually by the kernel OOM killer).
+pub fn is_oom(stderr: &str) -> bool {
+  stderr.contains("JavaScript heap out of memory")
+    || stderr.contains("Reached heap limit")
+    || stderr.contains("Allocation failed - process out of memory")
+    || stderr.contains("SIGKILL")
+}
+
+/// pnpm/npm refusing to fetch something that isn't in the store.
+pub fn is_offline_miss(stderr: &str) -> bool {
+  stderr.contains("ERR_PNPM_NO_OFFLINE")
+    || stderr.contains("ERR_PNPM_OFFLINE")
+    || stderr.contains("ENOTCACHED")
+}
+
+/// First `TS1234`-style diagnostic code.
+fn ts_code(s: &str) -> Option<String> {
+  let b = s.as_bytes();
+  let mut i = 0;
+  while let Some(off) = s[i..].find("TS") {
+    let at = i + off;
+    let digits = b[at + 2..].iter().take_while(|c| c.is_ascii_digit()).count();
+    let boundary = at == 0 || !b[at - 1].is_ascii_alphanumeric();
+    if boundary && (4..=5).contains(&digits) {
+      return Some(s[at..at + 2 + digits].to_string());
+    }
+    i = at + 2;
+  }
+  None
+}
+
+/// Names of failing tests in test runner output, in order, deduplicated.
+/// Understands TAP (`node --test`), vitest (`FAIL  file > suite > name`)
+/// and jest (`● suite › name`).
+pub fn failed_tests(output: &str) -> Vec<String> {
+  let mut out: Vec<String> = Vec::new();
+  for line in output.lines() {
+    let line = line.trim();
+    let name = if let Some(rest) = line.strip_prefix("not ok ") {
+      let rest = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+      let rest = rest.trim_start().strip_prefix('-').unwrap_or(rest);
+      match rest.split_once(" # ") {
+        Some((_, directive)) if directive.starts_with("TODO") || directive.starts_with("SKIP") => continue,
+        Some((name, _)) => name,
+        None => rest,
+      }
+    } else if let Some(rest) = line.strip_prefix("FAIL ") {
+      rest
+    } else if let Some(rest) = line.strip_prefix("\u{25cf} ") {
+      // Jest also bullets captured console output.
+      if rest.starts_with("Console") {
+        continue;
+      }
+      rest
+    } else {
+      continue;
+    };
+    let name = name.trim();
+    if !name.is_empty() && !out.iter().any(|n| n == name) {
+      out.push(name.to_string());
+    }
+  }
+  out
+}
@@ -0,0 +1,448 @@
+//! The commands and flags framectl takes, read from the synopsis in
+//! `USAGE` so that help, completions and the man page can't disagree.
+//!
+//! Each `framectl NAME ...` line (and its indented continuations) is one
+//! command: `[--key=VALUE]` and `--key=VALUE` are its flags, `[a|b]` and
+//! `a|b` its positional choices, `[build options]` pulls in another
+//! command's flags, and the `COMMAND` line's flags apply to every command.
+//!
+//! `normalize` checks a command line against it, so a misspelled flag is an
+//! error rather than a silently ignored setting.
+
+use std::sync::OnceLock;
+
+use crate::exitcode;
+use crate::USAGE;
+
+/// What a flag's value looks like, for completion.
+#[derive(Clone, PartialEq)]
+pub enum Hint {
+  /// A bare switch (`--quiet`).
+  Switch,
+  Choices(Vec<String>),
+  File,
+  /// Anything else; the placeholder names it (`N`, `URL`, `60s`).
+  Value,
+}
+
+#[derive(Clone)]
+pub struct Flag {
+  /// Without the leading `--`.
+  pub name: String,
+  /// The text after `=` in the synopsis, e.g. `0|1` or `PATH`.
+  pub placeholder: String,
+  pub hint: Hint,
+}
+
+pub struct Command {
+  pub name: String,
+  /// Positional words, e.g. `N` or `bash|zsh|fish`.
+  pub positional: Vec<String>,
+  pub choices: Vec<String>,
+  pub flags: Vec<Flag>,
+  /// The first sentence of its bullet in the notes, if it has one.
+  pub about: String,
+  /// Its synopsis lines as written.
+  pub usage: Vec<String>,
+}
+
+pub struct Spec {
+  /// The synopsis lines as written, without the indent.
+  pub synopsis: Vec<String>,
+  pub commands: Vec<Command>,
+  /// Flags every command takes (`--use-profile`, `--config`).
+  pub global: Vec<Flag>,
+  /// The notes, one bullet per entry with its lines joined.
+  pub notes: Vec<String>,
+}
+
+const PATH_WORDS: [&str; 5] = ["PATH", "FILE", "DIR", "VIDEO", "IMAGE"];
+
+fn is_placeholder(s: &str) -> bool {
+  s.chars().any(|c| c.is_ascii_uppercase()) && !s.chars().any(|c| c.is_ascii_lowercase())
+}
+
+/// `PATH`, `report.xml`, `.framectl/visual`; not `X.Y.Z`, `0.02` or `./Frame`.
+fn pathlike(s: &str) -> bool {
+  if is_placeholder(s) {
+    return PATH_WORDS.contains(&s);
+  }
+  if s.parse::<f64>().is_ok() || s.starts_with("./") {
+    return false;
+  }
+  let ext = s.rsplit_once('.').map(|(_, e)| e).unwrap_or("");
+  s.contains('/') || (!ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+fn hint(value: Option<&str>) -> Hint {
+  let Some(v) = value else {
+    return Hint::Switch;
+  };
+  let alts: Vec<&str> = v.split('|').collect();
+  if alts.iter().any(|a| pathlike(a)) {
+    return Hint::File;
+  }
+  if alts.len() > 1 {
+    let choices: Vec<String> = alts.iter().filter(|a| !is_placeholder(a)).map(|a| a.to_string()).collect();
+    if choices.len() > 1 {
+      return Hint::Choices(choices);
+    }
+  }
+  Hint::Value
+}
+
+/// `[...]` groups and bare words of a synopsis line.
+fn tokens(line: &str) -> Vec<(bool, String)> {
+  let mut out = Vec::new();
+  let mut rest = line.trim();
+  while !rest.is_empty() {
+    if let Some(inner) = rest.strip_prefix('[') {
+      let end = inner.find(']').unwrap_or(inner.len());
+      out.push((true, inner[..end].to_string()));
+      rest = inner.get(end + 1..).unwrap_or("").trim_start();
+    } else {
+      let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+      out.push((false, rest[..end].to_string()));
+      rest = rest[end..].trim_start();
+    }
+  }
+  out
+}
+
+fn flag(token: &str) -> Flag {
+  let (name, value) = match token.trim_start_matches('-').split_once('=') {
+    Some((n, v)) => (n, Some(v)),
+    None => (token.trim_start_matches('-'), None),
+  };
+  Flag {
+    name: name.to_string(),
+    placeholder: value.unwrap_or("").to_string(),
+    hint: hint(value),
+  }
+}
+
+const ABOUT_MAX: usize = 80;
+
+/// First sentence of `note` if it is about `name`.
+fn about(name: &str, notes: &[String]) -> String {
+  let Some(note) = notes.iter().find(|n| n.strip_prefix(name).is_some_and(|r| r.starts_with(' '))) else {
+    return String::new();
+  };
+  // Drop parentheticals, then stop at the end of the first clause.
+  let mut text = String::new();
+  let mut depth = 0;
+  for c in note[name.len()..].trim().chars() {
+    match c {
+      '(' => depth += 1,
+      ')' if depth > 0 => depth -= 1,
+      _ if depth == 0 => text.push(c),
+      _ => {}
+    }
+  }
+  let text = text.split_whitespace().collect::<Vec<_>>().join(" ").replace(" ,", ",");
+  let mut end = [". ", "; ", ": ", ", and ", ", so "]
+    .iter()
+    .filter_map(|p| text.find(p))
+    .min()
+    .unwrap_or(text.len());
+  // Keep it to a line in a completion menu.
+  if end > ABOUT_MAX {
+    let head = &text[..text.floor_char_boundary(ABOUT_MAX)];
+    end = head.rfind(", ").or_else(|| head.rfind(' ')).unwrap_or(head.len());
+  }
+  text[..end].trim_end_matches(['.', ',', ' ']).trim().to_string()
+}
+
+fn parse(usage: &str) -> Spec {
+  let mut lines = usage.lines();
+  // Each command's synopsis joined into one line, and as written.
+  let mut synopsis: Vec<(String, Vec<String>)> = Vec::new();
+  let mut raw: Vec<String> = Vec::new();
+  for line in lines.by_ref() {
+    if line.trim() == "Notes:" {
+      break;
+    }
+    let Some(l) = line.strip_prefix("  ").map(str::trim_end) else {
+      continue;
+    };
+    raw.push(l.to_string());
+    if let Some(rest) = l.strip_prefix("framectl ") {
+      synopsis.push((rest.to_string(), vec![l.to_string()]));
+    } else if let Some((joined, lines)) = synopsis.last_mut().filter(|_| l.starts_with(' ')) {
+      joined.push(' ');
+      joined.push_str(l.trim());
+      lines.push(l.to_string());
+    }
+  }
+  let mut notes: Vec<String> = Vec::new();
+  for line in lines {
+    if let Some(bullet) = line.trim_start().strip_prefix("- ") {
+      notes.push(bullet.trim().to_string());
+    } else if let Some(last) = notes.last_mut().filter(|_| !line.trim().is_empty()) {
+      last.push(' ');
+      last.push_str(line.trim());
+    }
+  }
+
+  let mut commands: Vec<Command> = Vec::new();
+  let mut global = Vec::new();
+  for (line, usage) in synopsis {
+    let mut toks = tokens(&line).into_iter();
+    let Some((_, name)) = toks.next() else {
+      continue;
+    };
+    let mut cmd = Command {
+      about: about(&name, &notes),
+      name,
+      positional: Vec::new(),
+      choices: Vec::new(),
+      flags: Vec::new(),
+      usage,
+    };
+    for (grouped, tok) in toks {
+      if tok.starts_with("--") {
+        cmd.flags.push(flag(&tok));
+      } else if grouped && tok.contains(' ') {
+        // `[build options]`, `[same options as build]`
+        let from = tok.split(' ').find_map(|w| commands.iter().find(|c| c.name == w));
+        if let Some(from) = from {
+          let inherited: Vec<Flag> = from.flags.iter().filter(|f| !cmd.flags.iter().any(|g| g.name == f.name)).cloned().collect();
+          cmd.flags.extend(inherited);
+        }
+      } else if tok.contains('|') && !is_placeholder(&tok) {
+        cmd.choices.extend(tok.split('|').map(str::to_string));
+        cmd.positional.push(tok);
+      } else if tok != "options" {
+        cmd.positional.push(tok);
+      }
+    }
+    if cmd.name == "COMMAND" {
+      global = cmd.flags;
+    } else {
+      commands.push(cmd);
+    }
+  }
+  Spec {
+    synopsis: raw,
+    commands,
+    global,
+    notes,
+  }
+}
+
+pub fn spec() -> &'static Spec {
+  static SPEC: OnceLock<Spec> = OnceLock::new();
+  SPEC.get_or_init(|| parse(USAGE))
+}
+
+impl Spec {
+  pub fn command(&self, name: &str) -> Option<&Command> {
+    self.commands.iter().find(|c| c.name == name)
+  }
+}
+
+impl Command {
+  pub fn flag(&self, name: &str) -> Option<&Flag> {
+    self.flags.iter().chain(&spec().global).find(|f| f.name == name)
+  }
+}
+
+impl Flag {
+  /// Whether a bare `--name` can mean `--name=1`.
+  fn on_off(&self) -> bool {
+    matches!(&self.hint, Hint::Choices(c) if c.iter().any(|v| v == "1"))
+  }
+}
+
+/// Short forms and the flag each stands for.
+const SHORT: [(&str, &str); 3] = [("-h", "help"), ("-q", "quiet"), ("-v", "verbose")];
+
+/// Edit distance counting a swap of neighbours as one edit, for
+/// suggesting what a typo meant.
+fn distance(a: &str, b: &str) -> usize {
+  let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+  let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+  for (i, row) in d.iter_mut().enumerate() {
+    row[0] = i;
+  }
+  for (j, cell) in d[0].iter_mut().enumerate() {
+    *cell = j;
+  }
+  for i in 1..=a.len() {
+    for j in 1..=b.len() {
+      let cost = usize::from(a[i - 1] != b[j - 1]);
+      d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+      if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+        d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+      }
+    }
+  }
+  d[a.len()][b.len()]
+}
+
+/// The closest of `candidates` to `word`, if it is close enough to be a typo.
+pub fn suggest<'a>(word: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+  let max = (word.chars().count() / 3).max(1);
+  candidates
+    .into_iter()
+    .map(|c| (distance(word, c), c))
+    .filter(|&(d, _)| d <= max)
+    .min_by_key(|&(d, _)| d)
+    .map(|(_, c)| c)
+}
+
+/// Wrap a note as a `- ` bullet the way `USAGE` lays them out.
+fn bullet(text: &str) -> String {
+  let mut out = String::from("  -");
+  let mut width = out.len();
+  for word in text.split_whitespace() {
+    if width + 1 + word.len() > 88 {
+      out.push_str("\n    ");
+      width = 4;
+    } else {
+      out.push(' ');
+      width += 1;
+    }
+    out.push_str(word);
+    width += word.len();
+  }
+  out
+}
+
+/// `framectl NAME --help`: its synopsis and the notes on it or its flags.
+pub fn help(cmd: &Command) -> String {
+  let mut out = format!("Usage:\n  {}\n", cmd.usage.join("\n  "));
+  let notes: Vec<&String> = spec()
+    .notes
+    .iter()
+    .filter(|n| {
+      let first = n.split([' ', '=', '/']).next().unwrap_or("");
+      first == cmd.name || first.strip_prefix("--").is_some_and(|f| cmd.flags.iter().any(|g| g.name == f))
+    })
+    .collect();
+  if !notes.is_empty() {
+    out.push_str("\nNotes:\n");
+    for n in notes {
+      out.push_str(&bullet(n));
+      out.push('\n');
+    }
+  }
+  out
+}
+
+fn unknown_flag(cmd: &Command, key: &str) -> String {
+  let mut msg = format!("{}: unknown flag --{key}", cmd.name);
+  let names = cmd.flags.iter().chain(&spec().global).map(|f| f.name.as_str());
+  if let Some(s) = suggest(key, names) {
+    msg.push_str(&format!(" (did you mean --{s}?)"));
+  } else {
+    let others: Vec<&str> = spec()
+      .commands
+      .iter()
+      .filter(|c| c.flags.iter().any(|f| f.name == key))
+      .map(|c| c.name.as_str())
+      .collect();
+    if !others.is_empty() {
+      let more = if others.len() > 3 { ", ..." } else { "" };
+      msg.push_str(&format!(" (taken by {}{more})", others[..others.len().min(3)].join(", ")));
+    }
+  }
+  msg.push_str(&format!("; see framectl {} --help", cmd.name));
+  msg
+}
+
+/// Check `args` against `cmd`'s synopsis and rewrite them the way the
+/// commands read them: `--key value` and bare switches become `--key=value`
+/// and `--key` (`--key=1` for 0|1 flags), short forms their long ones.
+/// Everything after `--` is positional.
+pub fn normalize(cmd: &Command, args: &[String]) -> Result<Vec<String>, String> {
+  let mut out = Vec::with_capacity(args.len());
+  let mut it = args.iter().peekable();
+  while let Some(arg) = it.next() {
+    if arg == "--" {
+      out.extend(it.by_ref().cloned());
+      break;
+    }
+    let long = match SHORT.iter().find(|(s, _)| s == arg) {
+      Some((_, l)) => format!("--{l}"),
+      None => arg.clone(),
+    };
+    let Some(body) = long.strip_prefix("--") else {
+      if cmd.positional.is_empty() {
+        return Err(format!("{}: unexpected argument {arg}; see framectl {} --help", cmd.name, cmd.name));
+      }
+      out.push(arg.clone());
+      continue;
+    };
+    let (key, value) = match body.split_once('=') {
+      Some((k, v)) => (k, Some(v.to_string())),
+      None => (body, None),
+    };
+    if key == "help" {
+      print!("{}", help(cmd));
+      std::process::exit(0);
+    }
+    let Some(flag) = cmd.flag(key) else {
+      return Err(unknown_flag(cmd, key));
+    };
+    match value {
+      Some(v) => out.push(format!("--{key}={v}")),
+      None if flag.hint == Hint::Switch => out.push(long.clone()),
+      None if flag.on_off() => {
+        let next = it.next_if(|n| matches!(&flag.hint, Hint::Choices(c) if c.contains(n)));
+        out.push(format!("--{key}={}", next.map_or("1", String::as_str)));
+      }
+      None => match it.next_if(|n| !n.starts_with("--")) {
+        Some(v) => out.push(format!("--{key}={v}")),
+        None => return Err(format!("{}: --{key} needs a value (--{key}={})", cmd.name, flag.placeholder)),
+      },
+    }
+  }
+  Ok(out)
+}
+
+/// `normalize`, exiting with the error on bad arguments.
+pub fn args(cmd: &Command, args: &[String]) -> Vec<String> {
+  normalize(cmd, args).unwrap_or_else(|e| {
+    exitcode::usage(e);
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn run(cmd: &str, args: &[&str]) -> Result<Vec<String>, String> {
+    let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+    normalize(spec().command(cmd).unwrap(), &args)
+  }
+
+  #[test]
+  fn rewrites_to_key_value() {
+    let out = run("build", &["--start", "3", "--end=9", "--silent", "--dry-run", "0"]).unwrap();
+    assert_eq!(out, ["--start=3", "--end=9", "--silent=1", "--dry-run=0"]);
+  }
+
+  #[test]
+  fn global_flags_and_positionals() {
+    let out = run("build-one", &["7", "-v", "--use-profile", "prod", "--", "--start"]).unwrap();
+    assert_eq!(out, ["7", "--verbose", "--use-profile=prod", "--start"]);
+  }
+
+  #[test]
+  fn bad_arguments() {
+    assert_eq!(
+      run("build", &["--statr=3"]).unwrap_err(),
+      "build: unknown flag --statr (did you mean --start?); see framectl build --help"
+    );
+    assert_eq!(run("build", &["--start"]).unwrap_err(), "build: --start needs a value (--start=N)");
+    assert_eq!(run("build", &["7"]).unwrap_err(), "build: unexpected argument 7; see framectl build --help");
+  }
+
+  #[test]
+  fn suggestions() {
+    assert_eq!(suggest("concurency", ["concurrency", "silent"]), Some("concurrency"));
+    assert_eq!(suggest("sielnt", ["concurrency", "silent"]), Some("silent"));
+    assert_eq!(suggest("zzz", ["concurrency", "silent"]), None);
+  }
+}
@@ -0,0 +1,240 @@
+//! `completions bash|zsh|fish` and `man`: shell completion scripts and a
+//! roff man page, both generated from the CLI definition in `cli`.
+
+use std::fmt::Write as _;
+
+use crate::cli::{self, Command, Flag, Hint};
+
+/// A command's own flags plus the global ones.
+fn flags(cmd: &Command) -> Vec<&Flag> {
+  let spec = cli::spec();
+  cmd.flags.iter().chain(spec.global.iter().filter(|g| !cmd.flags.iter().any(|f| f.name == g.name))).collect()
+}
+
+fn bash() -> String {
+  let spec = cli::spec();
+  let names: Vec<&str> = spec.commands.iter().map(|c| c.name.as_str()).collect();
+  let mut out = String::new();
+  let _ = writeln!(out, "# bash completion for framectl. Generated by `framectl completions bash`.");
+  out.push_str(
+    r#"_framectl() {
+  local line=${COMP_LINE:0:COMP_POINT}
+  local cur=${line##*[[:space:]]}
+  local cmd=${COMP_WORDS[1]}
+  local words="" files=0
+  if [[ $COMP_CWORD -le 1 && $cur != -* ]]; then
+"#,
+  );
+  let _ = writeln!(out, "    COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", names.join(" "));
+  out.push_str(
+    r#"    return
+  fi
+  case $cur in
+    --*=*)
+      local key=${cur%%=*} val=${cur#*=}
+      case "$cmd" in
+"#,
+  );
+  for cmd in &spec.commands {
+    let mut arms: Vec<(String, Vec<String>)> = Vec::new();
+    for f in flags(cmd) {
+      let action = match &f.hint {
+        Hint::Choices(c) => format!("words=\"{}\"", c.join(" ")),
+        Hint::File => "files=1".to_string(),
+        _ => continue,
+      };
+      match arms.iter_mut().find(|(a, _)| *a == action) {
+        Some((_, keys)) => keys.push(format!("--{}", f.name)),
+        None => arms.push((action, vec![format!("--{}", f.name)])),
+      }
+    }
+    if arms.is_empty() {
+      continue;
+    }
+    let _ = writeln!(out, "        {})", cmd.name);
+    let _ = writeln!(out, "          case $key in");
+    for (action, keys) in arms {
+      let _ = writeln!(out, "            {}) {action} ;;", keys.join("|"));
+    }
+    let _ = writeln!(out, "          esac ;;");
+  }
+  out.push_str(
+    r#"      esac
+      # With `=` in COMP_WORDBREAKS only the value is replaced.
+      local prefix=""
+      [[ $COMP_WORDBREAKS == *=* ]] || prefix="$key="
+      if (( files )); then
+        COMPREPLY=($(compgen -f -P "$prefix" -- "$val"))
+      else
+        COMPREPLY=($(compgen -W "$words" -P "$prefix" -- "$val"))
+      fi
+      ;;
+    -*)
+      case "$cmd" in
+"#,
+  );
+  for cmd in &spec.commands {
+    let list: Vec<String> = flags(cmd)
+      .iter()
+      .map(|f| if f.hint == Hint::Switch { format!("--{}", f.name) } else { format!("--{}=", f.name) })
+      .collect();
+    let _ = writeln!(out, "        {}) words=\"{}\" ;;", cmd.name, list.join(" "));
+  }
+  out.push_str(
+    r#"      esac
+      COMPREPLY=($(compgen -W "$words" -- "$cur"))
+      [[ ${COMPREPLY[0]} == *= ]] && compopt -o nospace
+      ;;
+    *)
+      case "$cmd" in
+"#,
+  );
+  for cmd in spec.commands.iter().filter(|c| !c.choices.is_empty()) {
+    let _ = writeln!(out, "        {}) words=\"{}\" ;;", cmd.name, cmd.choices.join(" "));
+  }
+  out.push_str(
+    r#"      esac
+      COMPREPLY=($(compgen -W "$words" -- "$cur"))
+      ;;
+  esac
+}
+complete -F _framectl framectl
+"#,
+  );
+  out
+}
+
+/// Text inside a zsh single-quoted `_arguments` spec, where `:`, `[` and `]`
+/// are syntax.
+fn zsh_escape(s: &str) -> String {
+  s.replace('\'', "'\\''").replace(':', "\\:").replace('[', "\\[").replace(']', "\\]")
+}
+
+fn zsh() -> String {
+  let spec = cli::spec();
+  let mut out = String::new();
+  let _ = writeln!(out, "#compdef framectl");
+  let _ = writeln!(out, "# Generated by `framectl completions zsh`.");
+  let _ = writeln!(out, "_framectl() {{");
+  let _ = writeln!(out, "  local -a commands");
+  let _ = writeln!(out, "  commands=(");
+  for cmd in &spec.commands {
+    let _ = writeln!(out, "    '{}:{}'", cmd.name, zsh_escape(&cmd.about));
+  }
+  let _ = writeln!(out, "  )");
+  let _ = writeln!(out, "  if (( CURRENT == 2 )); then");
+  let _ = writeln!(out, "    _describe -t commands 'framectl command' commands");
+  let _ = writeln!(out, "    return");
+  let _ = writeln!(out, "  fi");
+  let _ = writeln!(out, "  local cmd=$words[2]");
+  let _ = writeln!(out, "  shift words");
+  let _ = writeln!(out, "  (( CURRENT-- ))");
+  let _ = writeln!(out, "  case $cmd in");
+  for cmd in &spec.commands {
+    let _ = writeln!(out, "    {})", cmd.name);
+    let _ = write!(out, "      _arguments -S");
+    for f in flags(cmd) {
+      let p = zsh_escape(&f.placeholder);
+      let spec = match &f.hint {
+        Hint::Switch => format!("--{}", f.name),
+        Hint::Choices(c) => format!("--{}=-[{p}]:{}:({})", f.name, f.name, c.join(" ")),
+        Hint::File => format!("--{}=-[{p}]:file:_files", f.name),
+        Hint::Value => format!("--{}=-[{p}]:{p}: ", f.name),
+      };
+      let _ = write!(out, " \\\n        '{spec}'");
+    }
+    if !cmd.choices.is_empty() {
+      let _ = write!(out, " \\\n        '1:{}:({})'", cmd.name, cmd.choices.join(" "));
+    }
+    let _ = writeln!(out, "\n      ;;");
+  }
+  let _ = writeln!(out, "  esac");
+  let _ = writeln!(out, "}}");
+  let _ = writeln!(out, "_framectl \"$@\"");
+  out
+}
+
+fn fish_escape(s: &str) -> String {
+  s.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn fish() -> String {
+  let spec = cli::spec();
+  let mut out = String::new();
+  let _ = writeln!(out, "# fish completion for framectl. Generated by `framectl completions fish`.");
+  let _ = writeln!(out, "complete -c framectl -f");
+  for cmd in &spec.commands {
+    let about = if cmd.about.is_empty() { String::new() } else { format!(" -d '{}'", fish_escape(&cmd.about)) };
+    let _ = writeln!(out, "complete -c framectl -n __fish_use_subcommand -a {}{about}", cmd.name);
+  }
+  for cmd in &spec.commands {
+    let when = format!("-n '__fish_seen_subcommand_from {}'", cmd.name);
+    for f in flags(cmd) {
+      let rest = match &f.hint {
+        Hint::Switch => String::new(),
+        Hint::Choices(c) => format!(" -x -a '{}'", fish_escape(&c.join(" "))),
+        Hint::File => " -r -F".to_string(),
+        Hint::Value => format!(" -x -d '{}'", fish_escape(&f.placeholder)),
+      };
+      let _ = writeln!(out, "complete -c framectl {when} -l {}{rest}", f.name);
+    }
+    if !cmd.choices.is_empty() {
+      let _ = writeln!(out, "complete -c framectl {when} -a '{}'", fish_escape(&cmd.choices.join(" ")));
+    }
+  }
+  out
+}
+
+pub fn run(args: &[String]) {
+  let text = match args.iter().find(|a| !a.starts_with("--")).map(String::as_str) {
+    Some("bash") => bash(),
+    Some("zsh") => zsh(),
+    Some("fish") => fish(),
+    Some(other) => {
+      eprintln!("completions: unknown shell {other} (expected bash, zsh or fish)");
+      std::process::exit(2);
+    }
+    None => {
+      eprintln!("usage: framectl completions bash|zsh|fish");
+      std::process::exit(2);
+    }
+  };
+  print!("{text}");
+}
+
+/// Text for roff: backslashes and dashes escaped, no line starting a request.
+fn roff(s: &str) -> String {
+  let s = s.replace('\\', "\\e").replace('-', "\\-");
+  if s.starts_with(['.', '\'']) {
+    format!("\\&{s}")
+  } else {
+    s
+  }
+}
+
+pub fn man(_args: &[String]) {
+  let spec = cli::spec();
+  let mut out = String::new();
+  let _ = writeln!(out, ".\\\" Generated by `framectl man`.");
+  let _ = writeln!(out, ".TH FRAMECTL 1 \"\" \"framectl {}\" \"User Commands\"", env!("CARGO_PKG_VERSION"));
+  let _ = writeln!(out, ".SH NAME");
+  let _ = writeln!(out, "framectl \\- build, check and ship the Bad Apple frame remotes");
+  let _ = writeln!(out, ".SH SYNOPSIS");
+  let _ = writeln!(out, ".nf");
+  for line in &spec.synopsis {
+    let _ = writeln!(out, "{}", roff(line));
+  }
+  let _ = writeln!(out, ".fi");
+  let _ = writeln!(out, ".SH COMMANDS");
+  for cmd in spec.commands.iter().filter(|c| !c.about.is_empty()) {
+    let _ = writeln!(out, ".TP");
+    let _ = writeln!(out, ".B {}", roff(&cmd.name));
+    let _ = writeln!(out, "{}", roff(&cmd.about));
+  }
+  let _ = writeln!(out, ".SH NOTES");
+  for note in &spec.notes {
+    let _ = writeln!(out, ".IP \\(bu 2");
+    let _ = writeln!(out, "{}", roff(note));
+  }
+  print!("{out}");
+}
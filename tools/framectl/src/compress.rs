@@ -0,0 +1,184 @@
+//! `compress`: precompressed `.br` / `.gz` siblings for everything in the
+//! frame dists, so the CDN can serve them as-is.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use crate::queue::par_each;
+use crate::{default_concurrency, frames, parse_bool, parse_kv, targets};
+
+// Same set serve-frames.mjs compresses on the fly.
+const EXTENSIONS: [&str; 8] = ["js", "mjs", "css", "html", "json", "map", "wasm", "txt"];
+const MIN_SIZE: u64 = 1024;
+
+#[derive(Clone, Copy)]
+enum Algo {
+  Brotli,
+  Gzip,
+}
+
+impl Algo {
+  fn ext(self) -> &'static str {
+    match self {
+      Algo::Brotli => "br",
+      Algo::Gzip => "gz",
+    }
+  }
+
+  fn command(self, level: Option<u32>, input: &Path) -> Command {
+    let mut cmd = match self {
+      Algo::Brotli => {
+        let mut c = Command::new("brotli");
+        c.arg("-c").arg("-q").arg(level.unwrap_or(11).min(11).to_string());
+        c
+      }
+      Algo::Gzip => {
+        let mut c = Command::new("gzip");
+        c.arg("-c").arg("-n").arg(format!("-{}", level.unwrap_or(9).clamp(1, 9)));
+        c
+      }
+    };
+    cmd.arg(input);
+    cmd
+  }
+
+  fn tool(self) -> &'static str {
+    match self {
+      Algo::Brotli => "brotli",
+      Algo::Gzip => "gzip",
+    }
+  }
+}
+
+/// Every file under `dir`, recursively.
+pub fn walk(dir: &Path, out: &mut Vec<PathBuf>) {
+  let Ok(rd) = std::fs::read_dir(dir) else {
+    return;
+  };
+  for ent in rd.flatten() {
+    let p = ent.path();
+    match ent.file_type() {
+      Ok(t) if t.is_dir() => walk(&p, out),
+      Ok(t) if t.is_file() => out.push(p),
+      _ => {}
+    }
+  }
+}
+
+fn compressible(p: &Path) -> bool {
+  let ext = p.extension().and_then(|e| e.to_str()).unwrap_or("");
+  EXTENSIONS.contains(&ext) && p.metadata().map(|m| m.len() >= MIN_SIZE).unwrap_or(false)
+}
+
+fn up_to_date(src: &Path, dst: &Path) -> bool {
+  let (Ok(s), Ok(d)) = (src.metadata(), dst.metadata()) else {
+    return false;
+  };
+  matches!((s.modified(), d.modified()), (Ok(s), Ok(d)) if d >= s)
+}
+
+pub fn run(args: &[String]) {
+  let algos: Vec<Algo> = parse_kv(args, "--algo")
+    .unwrap_or_else(|| "br,gzip".to_string())
+    .split(',')
+    .filter_map(|a| match a.trim() {
+      "br" | "brotli" => Some(Algo::Brotli),
+      "gz" | "gzip" => Some(Algo::Gzip),
+      "" => None,
+      other => {
+        eprintln!("compress: unknown algo {other:?} (use br, gzip)");
+        std::process::exit(2);
+      }
+    })
+    .collect();
+  let level: Option<u32> = parse_kv(args, "--level").and_then(|v| v.parse().ok());
+  let force: bool = parse_kv(args, "--force")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(false);
+  let concurrency: usize = parse_kv(args, "--concurrency")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or_else(default_concurrency);
+
+  for a in &algos {
+    let found = Command::new(a.tool())
+      .arg("--version")
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .status()
+      .is_ok();
+    if !found {
+      eprintln!("compress: `{}` not found on PATH", a.tool());
+      std::process::exit(2);
+    }
+  }
+
+  targets::dists("compress", args);
+  let mut files = Vec::new();
+  for n in frames::selected(args) {
+    walk(&frames::dist_dir(n), &mut files);
+  }
+  files.retain(|p| compressible(p));
+
+  let jobs: Vec<(PathBuf, Algo)> = files
+    .iter()
+    .flat_map(|p| algos.iter().map(move |a| (p.clone(), *a)))
+    .collect();
+  eprintln!(
+    "compress: {} file(s) x {} algo(s) concurrency={concurrency}",
+    files.len(),
+    algos.len()
+  );
+
+  let written = AtomicUsize::new(0);
+  let skipped = AtomicUsize::new(0);
+  let failed = AtomicUsize::new(0);
+  let bytes_in = AtomicU64::new(0);
+  let bytes_out = AtomicU64::new(0);
+  par_each(&jobs, concurrency, |(src, algo)| {
+    let mut name = src.as_os_str().to_owned();
+    name.push(".");
+    name.push(algo.ext());
+    let dst = PathBuf::from(name);
+    if !force && up_to_date(src, &dst) {
+      skipped.fetch_add(1, Ordering::Relaxed);
+      return;
+    }
+    let out = algo.command(level, src).stdin(Stdio::null()).output();
+    let res = match out {
+      Ok(o) if o.status.success() => {
+        let tmp = dst.with_extension(format!("{}.tmp", algo.ext()));
+        std::fs::write(&tmp, &o.stdout)
+          .and_then(|_| std::fs::rename(&tmp, &dst))
+          .map(|_| o.stdout.len() as u64)
+          .map_err(|e| e.to_string())
+      }
+      Ok(o) => Err(String::from_utf8_lossy(&o.stderr).trim().to_string()),
+      Err(e) => Err(e.to_string()),
+    };
+    match res {
+      Ok(len) => {
+        written.fetch_add(1, Ordering::Relaxed);
+        bytes_in.fetch_add(src.metadata().map(|m| m.len()).unwrap_or(0), Ordering::Relaxed);
+        bytes_out.fetch_add(len, Ordering::Relaxed);
+      }
+      Err(e) => {
+        failed.fetch_add(1, Ordering::Relaxed);
+        eprintln!("compress: {} ({}): {e}", src.display(), algo.ext());
+      }
+    }
+  });
+
+  let (bi, bo) = (bytes_in.into_inner(), bytes_out.into_inner());
+  eprintln!(
+    "compress: wrote={} up_to_date={} failed={} {}KB -> {}KB",
+    written.into_inner(),
+    skipped.into_inner(),
+    failed.load(Ordering::Relaxed),
+    bi / 1024,
+    bo / 1024
+  );
+  if failed.into_inner() > 0 {
+    std::process::exit(1);
+  }
+}
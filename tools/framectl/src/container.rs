@@ -0,0 +1,132 @@
+//! `--container=IMAGE`: each frame's pnpm runs in a throwaway docker (or
+//! podman) container with the workspace mounted at `/workspace`, so a local
+//! run uses the same Node and OS as CI. The image is pulled once before the
+//! first build; `--container-cpus` and `--container-memory` cap each
+//! container.
+//!
+//! Files the build writes stay owned by the invoking user (`--user` with
+//! docker, `--userns=keep-id` with rootless podman). pnpm comes from the
+//! image's corepack, cached under `.framectl/corepack`.
+
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use crate::exitcode::{self, Kind};
+use crate::{parse_bool, parse_kv};
+
+/// Where the workspace is mounted inside the container.
+const MOUNT: &str = "/workspace";
+
+pub struct Container {
+  pub runtime: &'static str,
+  pub image: String,
+  cpus: Option<String>,
+  memory: Option<String>,
+  workspace: String,
+}
+
+static CONTAINER: OnceLock<Container> = OnceLock::new();
+
+pub fn get() -> Option<&'static Container> {
+  CONTAINER.get()
+}
+
+/// docker or podman, whichever is on PATH first.
+fn find_runtime() -> Option<&'static str> {
+  ["docker", "podman"].into_iter().find(|tool| {
+    Command::new(tool)
+      .arg("--version")
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .status()
+      .is_ok()
+  })
+}
+
+/// Turn container mode on for this process from the flags, pulling the
+/// image unless `--container-pull=0` or `pull` is false (dry runs).
+pub fn install(args: &[String], pull: bool) -> Option<&'static Container> {
+  let image = parse_kv(args, "--container").filter(|i| !i.is_empty())?;
+  let runtime = match parse_kv(args, "--container-runtime").as_deref() {
+    None => find_runtime().unwrap_or_else(|| {
+      exitcode::fail(Kind::Environment, "--container needs docker or podman on PATH");
+    }),
+    Some("docker") => "docker",
+    Some("podman") => "podman",
+    Some(other) => {
+      exitcode::usage(format!("invalid --container-runtime={other} (expected docker or podman)"));
+    }
+  };
+  let workspace = std::env::current_dir()
+    .map(|d| d.display().to_string())
+    .unwrap_or_else(|e| {
+      exitcode::fail(Kind::Environment, format!("--container: cannot resolve the workspace directory: {e}"));
+    });
+  let pull = pull && parse_kv(args, "--container-pull").and_then(|v| parse_bool(&v)).unwrap_or(true);
+  if pull {
+    eprintln!("container: pulling {image} with {runtime}");
+    let status = Command::new(runtime).args(["pull", "--quiet"]).arg(&image).stdout(Stdio::null()).status();
+    match status {
+      Ok(s) if s.success() => {}
+      Ok(s) => exitcode::fail(
+        Kind::Environment,
+        format!("container: {runtime} pull {image} exited with {s} (--container-pull=0 uses a local image)"),
+      ),
+      Err(e) => exitcode::fail(Kind::Environment, format!("container: {runtime}: {e}")),
+    }
+  }
+  let _ = CONTAINER.set(Container {
+    runtime,
+    image,
+    cpus: parse_kv(args, "--container-cpus"),
+    memory: parse_kv(args, "--container-memory"),
+    workspace,
+  });
+  CONTAINER.get()
+}
+
+#[cfg(unix)]
+fn user() -> Option<String> {
+  extern "C" {
+    fn getuid() -> u32;
+    fn getgid() -> u32;
+  }
+  // SAFETY: neither can fail.
+  Some(unsafe { format!("{}:{}", getuid(), getgid()) })
+}
+
+#[cfg(not(unix))]
+fn user() -> Option<String> {
+  None
+}
+
+impl Container {
+  /// `inner` (a pnpm command line) run in a fresh container with `env`.
+  pub fn wrap(&self, inner: Vec<String>, env: &[(String, String)]) -> Vec<String> {
+    let mut argv: Vec<String> = vec![self.runtime.into(), "run".into(), "--rm".into(), "--init".into()];
+    argv.push("-v".into());
+    argv.push(format!("{}:{MOUNT}", self.workspace));
+    argv.extend(["-w".into(), MOUNT.into()]);
+    match (self.runtime, user()) {
+      ("podman", _) => argv.push("--userns=keep-id".into()),
+      (_, Some(u)) => argv.extend(["--user".into(), u]),
+      _ => {}
+    }
+    if let Some(c) = &self.cpus {
+      argv.push(format!("--cpus={c}"));
+    }
+    if let Some(m) = &self.memory {
+      argv.push(format!("--memory={m}"));
+    }
+    let corepack = [("HOME", "/tmp".to_string()), ("COREPACK_HOME", format!("{MOUNT}/.framectl/corepack"))];
+    for (k, v) in corepack.iter().map(|(k, v)| (k.to_string(), v.clone())).chain(env.iter().cloned()) {
+      argv.extend(["-e".into(), format!("{k}={v}")]);
+    }
+    argv.push(self.image.clone());
+    if inner.first().is_some_and(|c| c == "pnpm") {
+      argv.push("corepack".into());
+    }
+    argv.extend(inner);
+    argv
+  }
+}
@@ -0,0 +1,215 @@
+//! Control socket of a running local build, for `framectl top`: a TCP
+//! listener on 127.0.0.1 whose address and token are in `.framectl/control`.
+//! Each connection sends one line, `TOKEN COMMAND`, and reads the reply until
+//! the build closes it.
+//!
+//! ```text
+//! ps              `slots LIMIT MAX done DONE total TOTAL`, then
+//!                 `FRAME PID ELAPSED_MS` per frame in flight
+//! kill FRAME      terminate the frame's build (it fails like any other)
+//! concurrency N   build at most N frames at once, 1..=MAX
+//! ```
+
+use std::collections::BTreeSet;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::exec;
+use crate::queue::TaskQueue;
+use crate::state_dir;
+
+/// Frames killed through the socket, so their failure says why.
+static KILLED: Mutex<BTreeSet<usize>> = Mutex::new(BTreeSet::new());
+
+pub fn path() -> PathBuf {
+  state_dir().join("control")
+}
+
+/// Whether frame `n`'s build was killed from `top`; clears the mark.
+pub fn take_killed(n: usize) -> bool {
+  KILLED.lock().unwrap().remove(&n)
+}
+
+#[derive(Clone, Copy, Default)]
+struct Status {
+  limit: usize,
+  done: usize,
+  total: usize,
+}
+
+pub struct Control {
+  status: Arc<Mutex<Status>>,
+  requests: Receiver<usize>,
+}
+
+impl Control {
+  /// Listen and publish the address; `None` (with a warning) if we can't.
+  pub fn start(queue: Arc<TaskQueue>, slots: usize) -> Option<Control> {
+    let (listener, addr) = match TcpListener::bind("127.0.0.1:0").and_then(|l| l.local_addr().map(|a| (l, a))) {
+      Ok(v) => v,
+      Err(e) => {
+        eprintln!("warning: no control socket for `framectl top`: {e}");
+        return None;
+      }
+    };
+    let token = format!(
+      "{:x}",
+      SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0) ^ ((std::process::id() as u128) << 64)
+    );
+    let _ = std::fs::create_dir_all(state_dir());
+    if let Err(e) = std::fs::write(path(), format!("{addr} {token}\n")) {
+      eprintln!("warning: no control socket for `framectl top`: {}: {e}", path().display());
+      return None;
+    }
+    let status = Arc::new(Mutex::new(Status {
+      limit: slots,
+      ..Status::default()
+    }));
+    let (tx, requests) = mpsc::channel();
+    let shared = Arc::clone(&status);
+    thread::spawn(move || {
+      for stream in listener.incoming().flatten() {
+        let _ = handle(stream, &token, &queue, &shared, slots, &tx);
+      }
+    });
+    Some(Control { status, requests })
+  }
+
+  /// What `ps` reports besides the frames in flight.
+  pub fn update(&self, limit: usize, done: usize, total: usize) {
+    *self.status.lock().unwrap() = Status { limit, done, total };
+  }
+
+  /// Concurrency asked for since the last call, the latest request winning.
+  pub fn requested_limit(&self) -> Option<usize> {
+    self.requests.try_iter().last()
+  }
+
+  pub fn stop(&self) {
+    let _ = std::fs::remove_file(path());
+  }
+}
+
+fn handle(
+  stream: TcpStream,
+  token: &str,
+  queue: &TaskQueue,
+  status: &Mutex<Status>,
+  slots: usize,
+  requests: &Sender<usize>,
+) -> std::io::Result<()> {
+  stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+  let mut line = String::new();
+  BufReader::new(&stream).read_line(&mut line)?;
+  let mut words = line.split_whitespace();
+  let mut out = &stream;
+  if words.next() != Some(token) {
+    return writeln!(out, "error: bad token");
+  }
+  match (words.next(), words.next().and_then(|v| v.parse::<usize>().ok())) {
+    (Some("ps"), _) => {
+      let st = *status.lock().unwrap();
+      let pids = exec::running_pids();
+      let mut reply = format!("slots {} {slots} done {} total {}\n", st.limit, st.done, st.total);
+      for (n, elapsed) in queue.in_flight() {
+        let pid = pids.get(&n).copied().unwrap_or(0);
+        reply.push_str(&format!("{n} {pid} {}\n", elapsed.as_millis()));
+      }
+      out.write_all(reply.as_bytes())
+    }
+    (Some("kill"), Some(n)) => match exec::running_pids().get(&n) {
+      Some(&pid) => {
+        KILLED.lock().unwrap().insert(n);
+        match kill_tree(pid) {
+          Ok(()) => writeln!(out, "ok: killed frame-{n:04} (pid {pid})"),
+          Err(e) => {
+            KILLED.lock().unwrap().remove(&n);
+            writeln!(out, "error: pid {pid}: {e}")
+          }
+        }
+      }
+      None => writeln!(out, "error: frame-{n:04} has no running build"),
+    },
+    (Some("concurrency"), Some(c)) => {
+      let c = c.clamp(1, slots);
+      // Right away, so a frame finishing before the main loop hears of it
+      // doesn't start another; the loop keeps its OOM backoff under it.
+      queue.set_limit(c);
+      let _ = requests.send(c);
+      writeln!(out, "ok: concurrency {c}")
+    }
+    _ => writeln!(out, "error: expected ps, kill FRAME or concurrency N"),
+  }
+}
+
+/// Send `TOKEN COMMAND` to the running build and return its reply.
+pub fn request(command: &str) -> Result<String, String> {
+  let text = std::fs::read_to_string(path()).map_err(|_| format!("no running build ({} not found)", path().display()))?;
+  let (addr, token) = text.trim().split_once(' ').ok_or_else(|| format!("{} is malformed", path().display()))?;
+  let mut stream = TcpStream::connect(addr).map_err(|e| format!("no running build at {addr} ({e})"))?;
+  writeln!(stream, "{token} {command}").map_err(|e| e.to_string())?;
+  let mut reply = String::new();
+  std::io::Read::read_to_string(&mut stream, &mut reply).map_err(|e| e.to_string())?;
+  match reply.strip_prefix("error: ") {
+    Some(e) => Err(e.trim().to_string()),
+    None => Ok(reply),
+  }
+}
+
+/// Set by `kill_all`: builds spawned after it are stopped as they start.
+static HALTED: AtomicBool = AtomicBool::new(false);
+
+pub fn halted() -> bool {
+  HALTED.load(Ordering::SeqCst)
+}
+
+/// Terminate every build in flight, and any started from now on; returns how
+/// many were signalled.
+pub fn kill_all() -> usize {
+  HALTED.store(true, Ordering::SeqCst);
+  exec::running_pids().into_values().filter(|&pid| kill_tree(pid).is_ok()).count()
+}
+
+/// Terminate `pid` and everything it started: pnpm leaves the real work to
+/// node children that would otherwise outlive it.
+#[cfg(unix)]
+pub fn kill_tree(pid: u32) -> std::io::Result<()> {
+  extern "C" {
+    fn kill(pid: i32, sig: i32) -> i32;
+  }
+  const SIGTERM: i32 = 15;
+  let mut all = crate::procinfo::descendants(pid);
+  all.push(pid);
+  for p in all.into_iter().rev() {
+    // SAFETY: plain syscall; a pid that already exited only makes it fail.
+    if unsafe { kill(p as i32, SIGTERM) } != 0 && p == pid {
+      return Err(std::io::Error::last_os_error());
+    }
+  }
+  Ok(())
+}
+
+#[cfg(windows)]
+pub fn kill_tree(pid: u32) -> std::io::Result<()> {
+  let status = std::process::Command::new("taskkill")
+    .args(["/T", "/F", "/PID"])
+    .arg(pid.to_string())
+    .stdout(std::process::Stdio::null())
+    .status()?;
+  if status.success() {
+    Ok(())
+  } else {
+    Err(std::io::Error::other(format!("taskkill exited with {status}")))
+  }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn kill_tree(_pid: u32) -> std::io::Result<()> {
+  Err(std::io::Error::other("killing builds is not supported on this platform"))
+}
@@ -0,0 +1,298 @@
+//! `dedupe-dist`: byte-identical files across the frame dists replaced by
+//! links to one copy: reflinks where the filesystem has them (each file
+//! stays its own, sharing blocks), hardlinks otherwise. Thousands of
+//! near-identical bundles carry the same chunks, so this reclaims most of
+//! the space they take locally and in CI caches.
+//!
+//! Hardlinked copies are one file: something rewriting a dist file in place
+//! would change every frame's. Builds replace dist outright, so run it after
+//! the build (and `compress`), not between them.
+
+use std::collections::BTreeMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::compress::walk;
+use crate::dupes::fnv;
+use crate::gc::fmt_bytes;
+use crate::queue::par_each;
+use crate::{default_concurrency, frames, parse_bool, parse_kv, targets};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+  Reflink,
+  Hardlink,
+}
+
+struct Copy {
+  n: usize,
+  path: PathBuf,
+  /// Device and inode, to skip copies already linked.
+  inode: Option<(u64, u64)>,
+  mtime: Option<SystemTime>,
+}
+
+#[cfg(unix)]
+fn inode(m: &Metadata) -> Option<(u64, u64)> {
+  use std::os::unix::fs::MetadataExt;
+  Some((m.dev(), m.ino()))
+}
+
+#[cfg(not(unix))]
+fn inode(_: &Metadata) -> Option<(u64, u64)> {
+  None
+}
+
+/// Where a replacement is staged before it is renamed over `path`.
+fn staging(path: &Path) -> PathBuf {
+  let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+  path.with_file_name(format!(".{name}.framectl-dedupe"))
+}
+
+/// Replace `path` with a link to `keep`, atomically.
+fn link(keep: &Path, path: &Path, mode: Mode) -> Result<(), String> {
+  let tmp = staging(path);
+  let _ = std::fs::remove_file(&tmp);
+  match mode {
+    Mode::Hardlink => std::fs::hard_link(keep, &tmp).map_err(|e| e.to_string())?,
+    Mode::Reflink => {
+      let flag = if cfg!(target_os = "macos") { "-c" } else { "--reflink=always" };
+      let out = Command::new("cp")
+        .arg(flag)
+        .arg(keep)
+        .arg(&tmp)
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| format!("cp: {e}"))?;
+      if !out.status.success() {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(String::from_utf8_lossy(&out.stderr).trim().to_string());
+      }
+      // A reflink is its own file: keep the one it replaces' mtime, or
+      // every deduped file would look freshly written.
+      if let Ok(t) = std::fs::metadata(path).and_then(|m| m.modified()) {
+        let _ = std::fs::File::options().write(true).open(&tmp).and_then(|f| f.set_modified(t));
+      }
+    }
+  }
+  std::fs::rename(&tmp, path).map_err(|e| {
+    let _ = std::fs::remove_file(&tmp);
+    e.to_string()
+  })
+}
+
+pub fn run(args: &[String]) {
+  let requested = match parse_kv(args, "--mode").as_deref() {
+    None | Some("auto") => None,
+    Some("reflink") => Some(Mode::Reflink),
+    Some("hardlink") => Some(Mode::Hardlink),
+    Some(v) => {
+      eprintln!("invalid --mode={v} (expected auto, reflink or hardlink)");
+      std::process::exit(2);
+    }
+  };
+  let min_size: u64 = parse_kv(args, "--min-size")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(1024);
+  let dry_run: bool = parse_kv(args, "--dry-run")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(false);
+  let concurrency: usize = parse_kv(args, "--concurrency")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or_else(default_concurrency);
+  let (start, end) = frames::range_args(args);
+
+  targets::dists("dedupe-dist", args);
+  let built: Vec<usize> = frames::selected(args)
+    .into_iter()
+    .filter(|&n| frames::dist_dir(n).is_dir())
+    .collect();
+  if built.is_empty() {
+    eprintln!("dedupe-dist: no built frames (start={start} end={end}); build first");
+    std::process::exit(2);
+  }
+
+  // (hash, size) -> copies, in frame order.
+  let groups: Mutex<BTreeMap<(u64, u64), Vec<Copy>>> = Mutex::new(BTreeMap::new());
+  let totals = Mutex::new((0usize, 0u64));
+  par_each(&built, concurrency, |&n| {
+    let mut files: Vec<PathBuf> = Vec::new();
+    walk(&frames::dist_dir(n), &mut files);
+    for path in files {
+      let Ok(meta) = path.metadata() else {
+        continue;
+      };
+      {
+        let mut t = totals.lock().unwrap();
+        t.0 += 1;
+        t.1 += meta.len();
+      }
+      if meta.len() < min_size.max(1) {
+        continue;
+      }
+      let Ok(bytes) = std::fs::read(&path) else {
+        continue;
+      };
+      let copy = Copy {
+        n,
+        inode: inode(&meta),
+        mtime: meta.modified().ok(),
+        path,
+      };
+      groups.lock().unwrap().entry((fnv(&bytes), bytes.len() as u64)).or_default().push(copy);
+    }
+  });
+  let (files, bytes) = totals.into_inner().unwrap();
+  let mut groups: Vec<((u64, u64), Vec<Copy>)> = groups.into_inner().unwrap().into_iter().filter(|(_, c)| c.len() > 1).collect();
+  for (_, copies) in &mut groups {
+    copies.sort_by(|a, b| (a.n, &a.path).cmp(&(b.n, &b.path)));
+  }
+  eprintln!(
+    "dedupe-dist: {files} file(s), {} in {} frame(s); {} set(s) of identical files",
+    fmt_bytes(bytes),
+    built.len(),
+    groups.len()
+  );
+
+  let mut mode = requested.unwrap_or(Mode::Reflink);
+  let mut linked = 0usize;
+  let mut already = 0usize;
+  let mut reclaimed = 0u64;
+  let mut failed = 0usize;
+  for ((_, size), copies) in &groups {
+    let keep = &copies[0];
+    let Ok(keep_bytes) = std::fs::read(&keep.path) else {
+      continue;
+    };
+    let mut newest = keep.mtime;
+    let mut changed = false;
+    let mut seen = vec![keep.inode];
+    for c in &copies[1..] {
+      if c.inode.is_some() && seen.contains(&c.inode) {
+        already += 1;
+        continue;
+      }
+      // Same hash and size almost always means same bytes; make sure.
+      if std::fs::read(&c.path).ok().as_deref() != Some(&keep_bytes[..]) {
+        continue;
+      }
+      seen.push(c.inode);
+      if dry_run {
+        linked += 1;
+        reclaimed += size;
+        continue;
+      }
+      let mut result = link(&keep.path, &c.path, mode);
+      if result.is_err() && mode == Mode::Reflink && requested.is_none() {
+        eprintln!("dedupe-dist: this filesystem can't reflink; hardlinking instead");
+        mode = Mode::Hardlink;
+        result = link(&keep.path, &c.path, mode);
+      }
+      match result {
+        Ok(()) => {
+          linked += 1;
+          reclaimed += size;
+          changed = true;
+          newest = newest.max(c.mtime);
+        }
+        Err(e) => {
+          failed += 1;
+          eprintln!("dedupe-dist: {}: {e}", c.path.display());
+        }
+      }
+    }
+    // Hardlinks share one mtime: the newest, so nothing looks stale.
+    if changed && mode == Mode::Hardlink {
+      if let Some(t) = newest {
+        let _ = std::fs::File::options().write(true).open(&keep.path).and_then(|f| f.set_modified(t));
+      }
+    }
+  }
+
+  let how = match (dry_run, mode) {
+    _ if linked == 0 => "linked",
+    (true, _) => "would link",
+    (false, Mode::Reflink) => "reflinked",
+    (false, Mode::Hardlink) => "hardlinked",
+  };
+  let pct = if bytes > 0 { reclaimed as f64 * 100.0 / bytes as f64 } else { 0.0 };
+  eprintln!(
+    "dedupe-dist: {how} {linked} file(s), {} {} ({pct:.1}% of the dists){}",
+    fmt_bytes(reclaimed),
+    if dry_run { "to reclaim" } else { "reclaimed" },
+    if already > 0 { format!("; {already} already linked") } else { String::new() }
+  );
+  if failed > 0 {
+    eprintln!("dedupe-dist: {failed} file(s) could not be linked");
+    std::process::exit(1);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("framectl-dedupe-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  #[test]
+  fn stages_next_to_the_file() {
+    assert_eq!(staging(Path::new("dist/static/js/1.js")), Path::new("dist/static/js/.1.js.framectl-dedupe"));
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn hardlinks_in_place() {
+    let d = dir("hard");
+    let (keep, path) = (d.join("a.js"), d.join("b.js"));
+    std::fs::write(&keep, "same").unwrap();
+    std::fs::write(&path, "same").unwrap();
+    link(&keep, &path, Mode::Hardlink).unwrap();
+    let (a, b) = (keep.metadata().unwrap(), path.metadata().unwrap());
+    let left: Vec<_> = std::fs::read_dir(&d).unwrap().flatten().map(|e| e.file_name()).collect();
+    let _ = std::fs::remove_dir_all(&d);
+    assert_eq!(inode(&a), inode(&b));
+    assert_eq!(left.len(), 2, "staging file left behind");
+  }
+
+  #[test]
+  fn failed_link_keeps_the_file() {
+    let d = dir("fail");
+    let path = d.join("b.js");
+    std::fs::write(&path, "mine").unwrap();
+    let err = link(&d.join("missing.js"), &path, Mode::Hardlink);
+    let body = std::fs::read_to_string(&path).unwrap();
+    let staged = staging(&path).exists();
+    let _ = std::fs::remove_dir_all(&d);
+    assert!(err.is_err());
+    assert_eq!(body, "mine");
+    assert!(!staged);
+  }
+
+  #[test]
+  fn reflink_keeps_mtime_or_fails_cleanly() {
+    let d = dir("reflink");
+    let (keep, path) = (d.join("a.js"), d.join("b.js"));
+    std::fs::write(&keep, "same").unwrap();
+    std::fs::write(&path, "same").unwrap();
+    let old = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+    std::fs::File::options().write(true).open(&path).unwrap().set_modified(old).unwrap();
+    // Not every filesystem can reflink; either way the file is intact.
+    let res = link(&keep, &path, Mode::Reflink);
+    let mtime = path.metadata().unwrap().modified().unwrap();
+    let body = std::fs::read_to_string(&path).unwrap();
+    let staged = staging(&path).exists();
+    let _ = std::fs::remove_dir_all(&d);
+    assert_eq!(body, "same");
+    assert!(!staged);
+    assert_eq!(mtime, old, "{res:?}");
+  }
+}
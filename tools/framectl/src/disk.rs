@@ -0,0 +1,171 @@
+//! `--min-free` / `--abort-free`: free space on the volume the frames build
+//! into, checked while a run goes. Below the soft threshold no new frame
+//! starts until space comes back; below the hard one the run ends cleanly
+//! instead of leaving thousands of half-written dists behind.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::exitcode;
+use crate::gc::fmt_bytes;
+use crate::parse_kv;
+
+const POLL: Duration = Duration::from_secs(5);
+
+/// What the last check asks the run to do.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Level {
+  Ok,
+  Pause,
+  Abort,
+}
+
+pub struct Monitor {
+  dir: PathBuf,
+  min_free: Option<u64>,
+  abort_free: Option<u64>,
+  last: Option<Instant>,
+  level: Level,
+  free: u64,
+}
+
+/// `10G`, `500M`, `64K` or plain bytes; binary units.
+pub fn parse_size(s: &str) -> Option<u64> {
+  let s = s.trim();
+  let (digits, mult) = match s.char_indices().last()? {
+    (i, 'k' | 'K') => (&s[..i], 1u64 << 10),
+    (i, 'm' | 'M') => (&s[..i], 1 << 20),
+    (i, 'g' | 'G') => (&s[..i], 1 << 30),
+    (i, 't' | 'T') => (&s[..i], 1 << 40),
+    _ => (s, 1),
+  };
+  let v: f64 = digits.trim().parse().ok().filter(|v: &f64| *v >= 0.0)?;
+  Some((v * mult as f64) as u64)
+}
+
+impl Monitor {
+  /// `None` when neither threshold is set, or free space can't be read here.
+  pub fn from_args(args: &[String], dir: &Path) -> Option<Monitor> {
+    let size = |key: &str| {
+      parse_kv(args, key).map(|v| {
+        parse_size(&v).unwrap_or_else(|| {
+          exitcode::usage(format!("invalid {key}={v} (expected a size like 10G or 500M)"));
+        })
+      })
+    };
+    let min_free = size("--min-free");
+    let abort_free = size("--abort-free");
+    if let (Some(min), Some(abort)) = (min_free, abort_free) {
+      if abort >= min {
+        exitcode::usage("--abort-free must be below --min-free");
+      }
+    }
+    if min_free.is_none() && abort_free.is_none() {
+      return None;
+    }
+    if free_bytes(dir).is_none() {
+      eprintln!("warning: free disk space unavailable for {}; ignoring --min-free/--abort-free", dir.display());
+      return None;
+    }
+    Some(Monitor {
+      dir: dir.to_path_buf(),
+      min_free,
+      abort_free,
+      last: None,
+      level: Level::Ok,
+      free: 0,
+    })
+  }
+
+  /// Re-reads free space at most every few seconds and reports when the
+  /// level changes; `None` means nothing new.
+  pub fn check(&mut self) -> Option<Level> {
+    if self.last.is_some_and(|t| t.elapsed() < POLL) {
+      return None;
+    }
+    self.last = Some(Instant::now());
+    // A failed read keeps the last level rather than flapping.
+    self.free = free_bytes(&self.dir)?;
+    let level = if self.abort_free.is_some_and(|a| self.free < a) {
+      Level::Abort
+    } else if self.min_free.is_some_and(|m| self.free < m) {
+      Level::Pause
+    } else {
+      Level::Ok
+    };
+    if level == self.level {
+      return None;
+    }
+    self.level = level;
+    Some(level)
+  }
+
+  /// One line on the current free space against the threshold in play.
+  pub fn describe(&self) -> String {
+    let threshold = match self.level {
+      Level::Abort => self.abort_free,
+      _ => self.min_free.or(self.abort_free),
+    };
+    let cmp = if self.level == Level::Ok { ">=" } else { "<" };
+    format!(
+      "{} free on {} ({cmp} {})",
+      fmt_bytes(self.free),
+      self.dir.display(),
+      threshold.map(fmt_bytes).unwrap_or_default()
+    )
+  }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn free_bytes(dir: &Path) -> Option<u64> {
+  use std::ffi::CString;
+  use std::os::raw::{c_char, c_int, c_ulong};
+  use std::os::unix::ffi::OsStrExt;
+
+  #[cfg(target_os = "linux")]
+  type BlkCnt = c_ulong;
+  #[cfg(target_os = "macos")]
+  type BlkCnt = u32;
+
+  /// Only the leading fields are read; the rest is room for the tail,
+  /// which differs between platforms.
+  #[repr(C)]
+  struct StatVfs {
+    f_bsize: c_ulong,
+    f_frsize: c_ulong,
+    _f_blocks: BlkCnt,
+    _f_bfree: BlkCnt,
+    f_bavail: BlkCnt,
+    _rest: [u64; 16],
+  }
+  extern "C" {
+    fn statvfs(path: *const c_char, buf: *mut StatVfs) -> c_int;
+  }
+  let path = CString::new(dir.as_os_str().as_bytes()).ok()?;
+  // SAFETY: all-zero is a valid `StatVfs`, and it is larger than the real struct.
+  let mut st: StatVfs = unsafe { std::mem::zeroed() };
+  // SAFETY: `path` is NUL-terminated and `st` outlives the call.
+  if unsafe { statvfs(path.as_ptr(), &mut st) } != 0 {
+    return None;
+  }
+  let frsize = if st.f_frsize != 0 { st.f_frsize } else { st.f_bsize };
+  Some(st.f_bavail as u64 * frsize as u64)
+}
+
+#[cfg(windows)]
+fn free_bytes(dir: &Path) -> Option<u64> {
+  use std::os::windows::ffi::OsStrExt;
+  extern "system" {
+    fn GetDiskFreeSpaceExW(dir: *const u16, avail: *mut u64, total: *mut u64, free: *mut u64) -> i32;
+  }
+  let wide: Vec<u16> = dir.as_os_str().encode_wide().chain(Some(0)).collect();
+  let mut avail = 0u64;
+  // SAFETY: `wide` is NUL-terminated; null out-pointers are allowed.
+  let ok = unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut avail, std::ptr::null_mut(), std::ptr::null_mut()) };
+  (ok != 0).then_some(avail)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn free_bytes(_dir: &Path) -> Option<u64> {
+  None
+}
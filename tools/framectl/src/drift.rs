@@ -0,0 +1,254 @@
+//! `drift`: compare every frame's package.json and rsbuild config field by
+//! field against the template (or, where it has none, the majority).
+//!
+//! Values are normalized per frame first (`frame_0042` -> `frame_{{id}}`,
+//! port 4142 -> `{{n}}+4100`) so that only real differences show up.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::frames::{self, fmt_ranges};
+use crate::json::{self, Value};
+use crate::parse_kv;
+use crate::template;
+
+/// `file:field` -> normalized value, for one frame.
+pub type Fields = BTreeMap<String, String>;
+
+const MISSING: &str = "(missing)";
+
+pub fn normalize(v: &str, n: usize) -> String {
+  let id = format!("{n:04}");
+  v.replace(&format!("frame_{id}"), "frame_{{id}}")
+    .replace(&format!("frame-{id}"), "frame-{{id}}")
+    .replace(&id, "{{id}}")
+}
+
+/// Template placeholders written the way `normalize` leaves frame values.
+fn normalize_template(v: &str) -> String {
+  v.replace("{{scope}}", "frame_{{id}}")
+    .replace("{{package}}", &frames::frame_pkg(0).replace("0000", "{{id}}"))
+}
+
+fn flatten(prefix: &str, v: &Value, n: usize, out: &mut Fields) {
+  match v {
+    Value::Obj(fields) if !fields.is_empty() => {
+      for (k, v) in fields {
+        flatten(&format!("{prefix}.{k}"), v, n, out);
+      }
+    }
+    Value::Str(s) => {
+      out.insert(prefix.to_string(), normalize(s, n));
+    }
+    other => {
+      out.insert(prefix.to_string(), normalize(&other.compact(), n));
+    }
+  }
+}
+
+pub fn manifest_fields(doc: &Value, n: usize) -> Fields {
+  let mut out = Fields::new();
+  if let Value::Obj(fields) = doc {
+    for (k, v) in fields {
+      flatten(&format!("package.json:{k}"), v, n, &mut out);
+    }
+  }
+  out
+}
+
+/// The `{...}` or `[...]` that follows `key:`, whitespace collapsed.
+fn block_after(src: &str, key: &str) -> Option<String> {
+  let at = src.find(&format!("{key}:"))?;
+  let rest = &src[at + key.len() + 1..];
+  let open = rest.trim_start().chars().next().filter(|c| matches!(c, '{' | '['))?;
+  let close = if open == '{' { '}' } else { ']' };
+  let start = rest.find(open)?;
+  let mut depth = 0;
+  for (i, c) in rest[start..].char_indices() {
+    if c == open {
+      depth += 1;
+    } else if c == close {
+      depth -= 1;
+      if depth == 0 {
+        let inner = rest[start + 1..start + i].split_whitespace().collect::<Vec<_>>();
+        return Some(format!("{open}{}{close}", inner.join(" ")));
+      }
+    }
+  }
+  None
+}
+
+/// Top-level calls in a `plugins: [...]` body: `pluginModuleFederation,withZephyr`.
+fn plugin_calls(block: &str) -> String {
+  let mut names = Vec::new();
+  let mut depth = 0;
+  let mut ident = String::new();
+  for c in block.chars() {
+    match c {
+      '(' => {
+        if depth == 0 && !ident.is_empty() {
+          names.push(std::mem::take(&mut ident));
+        }
+        depth += 1;
+      }
+      ')' => depth -= 1,
+      c if depth == 0 && (c.is_ascii_alphanumeric() || c == '_' || c == '$') => ident.push(c),
+      _ => ident.clear(),
+    }
+  }
+  names.join(",")
+}
+
+pub fn config_fields(src: &str, n: usize) -> Fields {
+  let mut out = Fields::new();
+  let mut put = |k: &str, v: Option<String>| {
+    if let Some(v) = v {
+      out.insert(format!("rsbuild.config.mjs:{k}"), normalize(&v, n));
+    }
+  };
+  put("output.assetPrefix", template::extract(src, "assetPrefix"));
+  let port = template::extract(src, "port").map(|p| match p.parse::<usize>() {
+    Ok(p) if p >= n => format!("{{{{n}}}}+{}", p - n),
+    _ => p,
+  });
+  put("server.port", port);
+  put("source.entry", block_after(src, "entry"));
+  put("plugins", block_after(src, "plugins").map(|b| plugin_calls(&b)));
+  if let Some(at) = src.find("pluginModuleFederation(") {
+    let mf = &src[at..];
+    put("federation.name", template::extract(mf, "name"));
+    put("federation.filename", template::extract(mf, "filename"));
+    put("federation.exposes", block_after(mf, "exposes"));
+    put("federation.shared", block_after(mf, "shared"));
+    put("federation.externalRuntime", template::extract(mf, "externalRuntime"));
+  }
+  put("rspack.output.uniqueName", template::extract(src, "uniqueName"));
+  out
+}
+
+/// Fields of frame `n`; unreadable files become a single `(unreadable)` field.
+pub fn frame_fields(n: usize) -> Fields {
+  let dir = frames::frame_dir(n);
+  let mut out = match json::read(&dir.join("package.json")) {
+    Ok(doc) => manifest_fields(&doc, n),
+    Err(_) => Fields::from([("package.json".to_string(), "(unreadable)".to_string())]),
+  };
+  match std::fs::read_to_string(dir.join("rsbuild.config.mjs")) {
+    Ok(src) => out.extend(config_fields(&src, n)),
+    Err(_) => {
+      out.insert("rsbuild.config.mjs".to_string(), MISSING.to_string());
+    }
+  }
+  out
+}
+
+/// What a frame is expected to look like. `fields` is authoritative for the
+/// files in `files`; paths in `per_frame`, and files the template doesn't
+/// have, are compared against the most common value instead.
+#[derive(Default)]
+pub struct Template {
+  pub fields: Fields,
+  pub files: Vec<&'static str>,
+  pub per_frame: Vec<String>,
+}
+
+/// Template from a template dir (see `sync-template`). Placeholders filled
+/// from the frame itself (`{{port}}`, `{{assetPrefix}}`) count as per-frame.
+fn template_from_dir(dir: &Path) -> Template {
+  let mut t = Template::default();
+  if let Ok(doc) = json::read(&dir.join("package.json")) {
+    t.fields.extend(manifest_fields(&doc, 0));
+    t.files.push("package.json");
+  }
+  if let Ok(src) = std::fs::read_to_string(dir.join("rsbuild.config.mjs")) {
+    t.fields.extend(config_fields(&src, 0));
+    t.files.push("rsbuild.config.mjs");
+  }
+  let (per_frame, fields): (Fields, Fields) = std::mem::take(&mut t.fields)
+    .into_iter()
+    .partition(|(_, v)| v.contains("{{port}}") || v.contains("{{assetPrefix}}"));
+  t.per_frame = per_frame.into_keys().collect();
+  t.fields = fields
+    .into_iter()
+    .map(|(k, v)| (k, normalize_template(&v)))
+    .collect();
+  t
+}
+
+/// Print drifting fields and return how many frames drift at all.
+pub fn report(all: &BTreeMap<usize, Fields>, template: &Template) -> usize {
+  let mut paths: Vec<&String> = all
+    .values()
+    .flat_map(|f| f.keys())
+    .chain(template.fields.keys())
+    .collect();
+  paths.sort();
+  paths.dedup();
+
+  let mut drifting: Vec<usize> = Vec::new();
+  let mut lines = Vec::new();
+  for path in paths {
+    let file = path.split(':').next().unwrap_or("");
+    let mut by_value: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (n, f) in all {
+      by_value.entry(f.get(path).map_or(MISSING, String::as_str)).or_default().push(*n);
+    }
+    let authoritative = template.files.contains(&file) && !template.per_frame.contains(path);
+    let expected = match template.fields.get(path) {
+      Some(v) if authoritative => v.as_str(),
+      None if authoritative => MISSING,
+      _ => match by_value.iter().max_by_key(|(_, ns)| ns.len()) {
+        Some((v, _)) => v,
+        None => continue,
+      },
+    };
+    for (value, ns) in &by_value {
+      if *value == expected {
+        continue;
+      }
+      drifting.extend(ns);
+      lines.push(format!(
+        "  {path}: {} frame(s) {}: {value} (expected {expected})",
+        ns.len(),
+        fmt_ranges(ns)
+      ));
+    }
+  }
+  drifting.sort_unstable();
+  drifting.dedup();
+  if !drifting.is_empty() {
+    eprintln!("drift: {} frame(s) diverge: {}", drifting.len(), fmt_ranges(&drifting));
+    for l in lines {
+      eprintln!("{l}");
+    }
+  }
+  drifting.len()
+}
+
+pub fn run(args: &[String]) {
+  let template_dir = parse_kv(args, "--template").map(PathBuf::from);
+  let all: BTreeMap<usize, Fields> = frames::selected(args)
+    .into_iter()
+    .map(|n| (n, frame_fields(n)))
+    .collect();
+  if all.is_empty() {
+    eprintln!("drift: no frames in range");
+    std::process::exit(2);
+  }
+  let template = match &template_dir {
+    Some(dir) => {
+      let t = template_from_dir(dir);
+      if t.files.is_empty() {
+        eprintln!("drift: no package.json or rsbuild.config.mjs in {}", dir.display());
+        std::process::exit(2);
+      }
+      t
+    }
+    None => Template::default(),
+  };
+  let drifting = report(&all, &template);
+  if drifting > 0 {
+    std::process::exit(1);
+  }
+  eprintln!("drift: {} frame(s) consistent", all.len());
+}
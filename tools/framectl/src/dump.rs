@@ -0,0 +1,171 @@
+//! Scheduler state dumps for runs that look wedged: `kill -USR1 <pid>` or
+//! `framectl dump` (which finds the pid in `.framectl/build.pid`). The dump
+//! goes to stderr and `.framectl/dump.txt`.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::frames::fmt_ranges;
+use crate::{fmt_dur, state_dir};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+pub fn pid_path() -> PathBuf {
+  state_dir().join("build.pid")
+}
+
+fn dump_path() -> PathBuf {
+  state_dir().join("dump.txt")
+}
+
+#[cfg(unix)]
+mod sys {
+  use std::os::raw::c_int;
+
+  #[cfg(target_os = "macos")]
+  pub const SIGUSR1: c_int = 30;
+  #[cfg(not(target_os = "macos"))]
+  pub const SIGUSR1: c_int = 10;
+
+  extern "C" {
+    pub fn signal(sig: c_int, handler: usize) -> usize;
+    pub fn kill(pid: c_int, sig: c_int) -> c_int;
+  }
+}
+
+#[cfg(unix)]
+extern "C" fn on_usr1(_: std::os::raw::c_int) {
+  REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Install the SIGUSR1 handler and record our pid for `framectl dump`.
+pub fn install() {
+  #[cfg(unix)]
+  // SAFETY: the handler only stores to an atomic.
+  unsafe {
+    sys::signal(sys::SIGUSR1, on_usr1 as extern "C" fn(std::os::raw::c_int) as usize);
+  }
+  let _ = std::fs::create_dir_all(state_dir());
+  let _ = std::fs::write(pid_path(), format!("{}\n", std::process::id()));
+}
+
+pub fn uninstall() {
+  let _ = std::fs::remove_file(pid_path());
+}
+
+/// Whether a dump was asked for since the last call.
+pub fn requested() -> bool {
+  REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+pub struct Failure {
+  pub n: usize,
+  pub class: String,
+  pub at: Instant,
+}
+
+/// Failures kept for dumps.
+pub const RECENT_FAILURES: usize = 10;
+
+pub struct State<'a> {
+  pub total: usize,
+  pub done: usize,
+  pub ok: usize,
+  pub elapsed: Duration,
+  pub pending: Vec<usize>,
+  pub in_flight: Vec<(usize, Duration)>,
+  pub pids: BTreeMap<usize, u32>,
+  pub recent_failures: &'a VecDeque<Failure>,
+}
+
+pub fn write(s: &State) {
+  let mut out = String::new();
+  let _ = writeln!(
+    out,
+    "framectl state after {} (pid {})",
+    fmt_dur(s.elapsed),
+    std::process::id()
+  );
+  let _ = writeln!(
+    out,
+    "counts: total={} done={} ok={} failed={} pending={} in_flight={}",
+    s.total,
+    s.done,
+    s.ok,
+    s.done.saturating_sub(s.ok),
+    s.pending.len(),
+    s.in_flight.len()
+  );
+  if !s.pending.is_empty() {
+    let next: Vec<String> = s.pending.iter().take(20).map(|n| n.to_string()).collect();
+    let more = if s.pending.len() > next.len() { ",..." } else { "" };
+    let _ = writeln!(out, "next up: {}{more}", next.join(","));
+    let mut sorted = s.pending.clone();
+    sorted.sort_unstable();
+    let _ = writeln!(out, "pending: {}", fmt_ranges(&sorted));
+  }
+  if !s.in_flight.is_empty() {
+    let _ = writeln!(out, "in flight:");
+    let mut by_age = s.in_flight.clone();
+    by_age.sort_by_key(|(_, d)| std::cmp::Reverse(*d));
+    for (n, d) in by_age {
+      let pid = s.pids.get(&n).map(|p| format!("pid={p}")).unwrap_or_else(|| "no local child".to_string());
+      let _ = writeln!(out, "  frame-{n:04} {} {pid}", fmt_dur(d));
+    }
+  }
+  if !s.recent_failures.is_empty() {
+    let _ = writeln!(out, "recent failures:");
+    for f in s.recent_failures.iter().rev() {
+      let _ = writeln!(out, "  frame-{:04} {} ({} ago)", f.n, f.class, fmt_dur(f.at.elapsed()));
+    }
+  }
+  eprint!("{out}");
+  let tmp = dump_path().with_extension("tmp");
+  if let Err(e) = std::fs::write(&tmp, &out).and_then(|_| std::fs::rename(&tmp, dump_path())) {
+    eprintln!("warning: could not write {}: {e}", dump_path().display());
+  }
+}
+
+/// `framectl dump`: signal the running build and print what it wrote.
+pub fn run(args: &[String]) {
+  let pid: Option<i32> = crate::parse_kv(args, "--pid")
+    .or_else(|| std::fs::read_to_string(pid_path()).ok())
+    .and_then(|v| v.trim().parse().ok());
+  let Some(pid) = pid else {
+    eprintln!("dump: no running build ({} not found; pass --pid=N)", pid_path().display());
+    std::process::exit(2);
+  };
+  let before = dump_path().metadata().and_then(|m| m.modified()).ok();
+  signal(pid);
+  let t = Instant::now();
+  while t.elapsed() < Duration::from_secs(5) {
+    std::thread::sleep(Duration::from_millis(100));
+    let now: Option<SystemTime> = dump_path().metadata().and_then(|m| m.modified()).ok();
+    if now.is_some() && now != before {
+      if let Ok(text) = std::fs::read_to_string(dump_path()) {
+        print!("{text}");
+        return;
+      }
+    }
+  }
+  eprintln!("dump: pid {pid} did not write {} within 5s", dump_path().display());
+  std::process::exit(1);
+}
+
+#[cfg(unix)]
+fn signal(pid: i32) {
+  // SAFETY: plain syscall; a stale pid only makes it fail.
+  if unsafe { sys::kill(pid, sys::SIGUSR1) } != 0 {
+    eprintln!("dump: pid {pid}: {}", std::io::Error::last_os_error());
+    std::process::exit(1);
+  }
+}
+
+#[cfg(not(unix))]
+fn signal(_pid: i32) {
+  eprintln!("dump: signals are not supported on this platform");
+  std::process::exit(2);
+}
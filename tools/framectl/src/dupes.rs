@@ -0,0 +1,195 @@
+//! `dupes`: chunk files emitted byte-for-byte identical by several frames,
+//! e.g. the React runtime bundled into every remote instead of shared as a
+//! singleton. Each group shows how many frames carry it and the bytes the
+//! copies past the first waste, largest first.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::compress::walk;
+use crate::gc::fmt_bytes;
+use crate::queue::par_each;
+use crate::{default_concurrency, frames, json::Value, parse_kv, targets};
+
+/// A file's identity: FNV-1a of its contents and its size.
+type Key = (u64, u64);
+
+#[derive(Default)]
+struct Group {
+  /// Frames carrying it, with the dist-relative path it has there.
+  copies: Vec<(usize, String)>,
+  /// What the chunk is, from the first `@license` banner in it.
+  license: Option<String>,
+}
+
+impl Group {
+  fn frames(&self) -> Vec<usize> {
+    let mut f: Vec<usize> = self.copies.iter().map(|(n, _)| *n).collect();
+    f.dedup();
+    f
+  }
+
+  /// Its most common path, and how many other paths it goes by.
+  fn name(&self) -> (String, usize) {
+    let mut names: BTreeMap<&str, usize> = BTreeMap::new();
+    for (_, p) in &self.copies {
+      *names.entry(p).or_default() += 1;
+    }
+    let top = names.iter().max_by_key(|(_, c)| **c).map(|(p, _)| p.to_string()).unwrap_or_default();
+    (top, names.len() - 1)
+  }
+}
+
+/// Emitted chunks: everything but source maps and the precompressed copies
+/// of other files.
+fn is_chunk(p: &Path) -> bool {
+  !matches!(p.extension().and_then(|e| e.to_str()), Some("map" | "br" | "gz"))
+}
+
+fn license(bytes: &[u8]) -> Option<String> {
+  const TAG: &[u8] = b"@license ";
+  let head = &bytes[..bytes.len().min(4096)];
+  let at = head.windows(TAG.len()).position(|w| w == TAG)? + TAG.len();
+  let rest = String::from_utf8_lossy(&head[at..]);
+  let name = rest.split(['\n', '*']).next().unwrap_or("").trim();
+  (!name.is_empty()).then(|| name.chars().take(40).collect())
+}
+
+pub fn fnv(bytes: &[u8]) -> u64 {
+  let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+  for &b in bytes {
+    h ^= b as u64;
+    h = h.wrapping_mul(0x0100_0000_01b3);
+  }
+  h
+}
+
+pub fn run(args: &[String]) {
+  let top: usize = parse_kv(args, "--top")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(20);
+  let min_frames: usize = parse_kv(args, "--min-frames")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(2)
+    .max(2);
+  let concurrency: usize = parse_kv(args, "--concurrency")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or_else(default_concurrency);
+  let (start, end) = frames::range_args(args);
+  let out = parse_kv(args, "--out");
+
+  targets::dists("dupes", args);
+  let built: Vec<usize> = frames::selected(args)
+    .into_iter()
+    .filter(|&n| frames::dist_dir(n).is_dir())
+    .collect();
+  if built.is_empty() {
+    eprintln!("dupes: no built frames (start={start} end={end}); build first");
+    std::process::exit(2);
+  }
+
+  let groups: Mutex<BTreeMap<Key, Group>> = Mutex::new(BTreeMap::new());
+  let total_bytes = Mutex::new((0usize, 0u64));
+  par_each(&built, concurrency, |&n| {
+    let dist = frames::dist_dir(n);
+    let mut files: Vec<PathBuf> = Vec::new();
+    walk(&dist, &mut files);
+    files.retain(|p| is_chunk(p));
+    for p in files {
+      let Ok(bytes) = std::fs::read(&p) else {
+        continue;
+      };
+      let rel = p.strip_prefix(&dist).unwrap_or(&p).to_string_lossy().replace('\\', "/");
+      let key = (fnv(&bytes), bytes.len() as u64);
+      {
+        let mut t = total_bytes.lock().unwrap();
+        t.0 += 1;
+        t.1 += key.1;
+      }
+      let mut groups = groups.lock().unwrap();
+      let g = groups.entry(key).or_default();
+      if g.copies.is_empty() {
+        g.license = license(&bytes);
+      }
+      g.copies.push((n, rel));
+    }
+  });
+  let (files, bytes) = total_bytes.into_inner().unwrap();
+
+  // Copies within one frame are the bundler's business; across frames they
+  // are what sharing would save.
+  let mut dupes: Vec<(u64, u64, Group)> = groups
+    .into_inner()
+    .unwrap()
+    .into_iter()
+    .filter_map(|((_, size), mut g)| {
+      g.copies.sort();
+      let copies = g.frames().len() as u64;
+      (size > 0 && copies >= min_frames as u64).then(|| (size, size * (copies - 1), g))
+    })
+    .collect();
+  dupes.sort_by(|a, b| b.1.cmp(&a.1).then(b.0.cmp(&a.0)));
+  let wasted: u64 = dupes.iter().map(|d| d.1).sum();
+
+  eprintln!(
+    "dupes: {files} file(s), {} in {} frame(s)",
+    fmt_bytes(bytes),
+    built.len()
+  );
+  for (size, waste, g) in dupes.iter().take(top) {
+    let (name, aliases) = g.name();
+    let carried = g.frames();
+    println!(
+      "  {:>8} x{:<5} wasted {:>8}  {name}{}{}  (frames {})",
+      fmt_bytes(*size),
+      carried.len(),
+      fmt_bytes(*waste),
+      if aliases > 0 { format!(" (+{aliases} other name(s))") } else { String::new() },
+      g.license.as_deref().map(|l| format!(" [{l}]")).unwrap_or_default(),
+      frames::fmt_ranges(&carried)
+    );
+  }
+  if dupes.len() > top {
+    println!("  ... {} more (--top=N)", dupes.len() - top);
+  }
+  let pct = if bytes > 0 { wasted as f64 * 100.0 / bytes as f64 } else { 0.0 };
+  eprintln!(
+    "dupes: {} chunk(s) duplicated across frames, {} of {} ({pct:.1}%) is copies",
+    dupes.len(),
+    fmt_bytes(wasted),
+    fmt_bytes(bytes)
+  );
+
+  if let Some(out) = out {
+    let doc = Value::obj([
+      ("files", Value::Num(files as f64)),
+      ("bytes", Value::Num(bytes as f64)),
+      ("wasted", Value::Num(wasted as f64)),
+      (
+        "chunks",
+        Value::Arr(
+          dupes
+            .iter()
+            .map(|(size, waste, g)| {
+              let carried = g.frames();
+              Value::obj([
+                ("name", Value::from(g.name().0)),
+                ("size", Value::Num(*size as f64)),
+                ("frames", Value::Num(carried.len() as f64)),
+                ("wasted", Value::Num(*waste as f64)),
+                ("ranges", Value::from(frames::fmt_ranges(&carried))),
+                ("license", g.license.as_deref().map_or(Value::Null, Value::from)),
+              ])
+            })
+            .collect(),
+        ),
+      ),
+    ]);
+    if let Err(e) = std::fs::write(&out, doc.pretty()) {
+      eprintln!("dupes: write {out}: {e}");
+      std::process::exit(1);
+    }
+    eprintln!("dupes: wrote {out}");
+  }
+}
@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::weights::Weights;
+
+const ALPHA: f64 = 0.2;
+
+/// Remaining-time estimate from an EMA of per-frame build durations, seeded
+/// by (and calibrated against) durations recorded in previous runs.
+pub struct Estimator {
+  history: BTreeMap<usize, Duration>,
+  // Frames' relative cost; the EMA is per unit of weight.
+  weights: Option<Weights>,
+  ema: Option<f64>,
+  ema_var: f64,
+  // Observed vs. recorded seconds for frames seen in both, so history from a
+  // faster or slower machine is scaled to this run.
+  seen_obs: f64,
+  seen_hist: f64,
+}
+
+pub struct Eta {
+  pub mid: Duration,
+  pub low: Duration,
+  pub high: Duration,
+}
+
+impl Eta {
+  /// The same estimate `d` later, e.g. for pauses still to come.
+  pub fn delayed(self, d: Duration) -> Eta {
+    Eta {
+      mid: self.mid + d,
+      low: self.low + d,
+      high: self.high + d,
+    }
+  }
+}
+
+impl Estimator {
+  pub fn new(history: BTreeMap<usize, Duration>) -> Self {
+    Estimator {
+      history,
+      weights: None,
+      ema: None,
+      ema_var: 0.0,
+      seen_obs: 0.0,
+      seen_hist: 0.0,
+    }
+  }
+
+  pub fn weighted(mut self, weights: Option<Weights>) -> Self {
+    self.weights = weights;
+    self
+  }
+
+  fn weight(&self, n: usize) -> f64 {
+    self.weights.as_ref().map(|w| w.get(n)).unwrap_or(1.0)
+  }
+
+  pub fn observe(&mut self, n: usize, dur: Duration) {
+    let x = dur.as_secs_f64() / self.weight(n);
+    match self.ema {
+      None => self.ema = Some(x),
+      Some(m) => {
+        let diff = x - m;
+        let next = m + ALPHA * diff;
+        self.ema_var = (1.0 - ALPHA) * (self.ema_var + ALPHA * diff * diff);
+        self.ema = Some(next);
+      }
+    }
+    if let Some(h) = self.history.get(&n) {
+      self.seen_obs += dur.as_secs_f64();
+      self.seen_hist += h.as_secs_f64();
+    }
+  }
+
+  /// `None` until there is either a finished frame or history to go on.
+  pub fn estimate(&self, remaining: impl Iterator<Item = usize>, slots: usize) -> Option<Eta> {
+    let hist_mean = if self.history.is_empty() {
+      None
+    } else {
+      let sum: f64 = self.history.values().map(|d| d.as_secs_f64()).sum();
+      Some(sum / self.history.len() as f64)
+    };
+    let scale = if self.seen_hist > 0.0 {
+      self.seen_obs / self.seen_hist
+    } else {
+      1.0
+    };
+    let per_frame = self.ema.or(hist_mean.map(|m| m * scale))?;
+    let sd = if self.ema.is_some() {
+      self.ema_var.sqrt()
+    } else {
+      // Nothing observed yet: history alone is a rough guess.
+      per_frame * 0.5
+    };
+
+    let mut mid = 0.0;
+    let mut count = 0usize;
+    for n in remaining {
+      count += 1;
+      mid += match self.history.get(&n) {
+        Some(h) if self.seen_hist > 0.0 || self.ema.is_none() => h.as_secs_f64() * scale,
+        _ => per_frame * self.weight(n),
+      };
+    }
+    let slots = slots.max(1).min(count.max(1)) as f64;
+    // Independent durations: the sum's spread grows with the square root.
+    let spread = sd * (count as f64).sqrt();
+    let secs = |v: f64| Duration::from_secs_f64((v / slots).max(0.0));
+    Some(Eta {
+      mid: secs(mid),
+      low: secs(mid - spread),
+      high: secs(mid + spread),
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn secs(s: u64) -> Duration {
+    Duration::from_secs(s)
+  }
+
+  #[test]
+  fn nothing_to_go_on() {
+    assert!(Estimator::new(BTreeMap::new()).estimate(1..=5, 1).is_none());
+  }
+
+  #[test]
+  fn observed_rate() {
+    let mut est = Estimator::new(BTreeMap::new());
+    est.observe(1, secs(10));
+    let eta = est.estimate(2..=5, 1).unwrap();
+    assert_eq!((eta.mid, eta.low, eta.high), (secs(40), secs(40), secs(40)));
+    assert_eq!(est.estimate(2..=5, 2).unwrap().mid, secs(20));
+    // No more slots than frames left.
+    assert_eq!(est.estimate(2..=2, 4).unwrap().mid, secs(10));
+  }
+
+  #[test]
+  fn history_only() {
+    let est = Estimator::new(BTreeMap::from([(1, secs(10)), (2, secs(20))]));
+    let eta = est.estimate(1..=3, 1).unwrap();
+    // Frame 3 has no history and counts as the mean, 15s.
+    assert_eq!(eta.mid, secs(45));
+    // Spread: half the mean per frame, times the square root of the count.
+    let spread = 7.5 * 3f64.sqrt();
+    assert!((eta.low.as_secs_f64() - (45.0 - spread)).abs() < 1e-6);
+    assert!((eta.high.as_secs_f64() - (45.0 + spread)).abs() < 1e-6);
+  }
+
+  #[test]
+  fn history_scaled_to_this_run() {
+    let mut est = Estimator::new(BTreeMap::from([(1, secs(10)), (2, secs(20))]));
+    est.observe(1, secs(20));
+    // Frame 1 took twice its recorded time, so frame 2 should too.
+    assert_eq!(est.estimate(2..=2, 1).unwrap().mid, secs(40));
+  }
+}
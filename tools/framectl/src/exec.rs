@@ -0,0 +1,418 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::frames::{self, frame_pkg};
+use crate::rusage::{self, Usage};
+use crate::{budget, container, control, filterexpr, profiles, state_dir, strict, targets, warnings};
+
+/// Child PID per frame currently running in this process, for state dumps.
+static RUNNING: Mutex<BTreeMap<usize, u32>> = Mutex::new(BTreeMap::new());
+
+pub fn running_pids() -> BTreeMap<usize, u32> {
+  RUNNING.lock().unwrap().clone()
+}
+
+/// Spawns that hit a process or file descriptor limit since the build loop
+/// last looked; it backs concurrency off while they keep coming.
+static EXHAUSTED: AtomicUsize = AtomicUsize::new(0);
+
+pub fn take_exhausted() -> usize {
+  EXHAUSTED.swap(0, Ordering::Relaxed)
+}
+
+/// Waits between spawn attempts while the machine is out of processes or
+/// file descriptors, about 30s in all before the frame fails.
+const SPAWN_BACKOFF: [Duration; 6] = [
+  Duration::from_millis(500),
+  Duration::from_secs(1),
+  Duration::from_secs(2),
+  Duration::from_secs(4),
+  Duration::from_secs(8),
+  Duration::from_secs(15),
+];
+
+/// EAGAIN, ENOMEM from fork, EMFILE or ENFILE: transient on a busy runner,
+/// unlike a missing binary.
+fn exhausted(e: &io::Error) -> bool {
+  matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::OutOfMemory)
+    || (cfg!(unix) && matches!(e.raw_os_error(), Some(23 | 24)))
+}
+
+fn spawn(n: usize, cmd: &mut Command) -> io::Result<std::process::Child> {
+  let mut waits = SPAWN_BACKOFF.iter();
+  loop {
+    match cmd.spawn() {
+      Err(e) if exhausted(&e) => {
+        EXHAUSTED.fetch_add(1, Ordering::Relaxed);
+        let Some(wait) = waits.next() else {
+          return Err(e);
+        };
+        eprintln!("warning: {}: spawn failed: {e}; retrying in {}s", frames::label(n), wait.as_secs_f64());
+        thread::sleep(*wait);
+      }
+      r => return r,
+    }
+  }
+}
+
+/// The pnpm command a run invokes in each frame.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Script {
+  Install,
+  Typecheck,
+  Build,
+  Test,
+  Publish,
+}
+
+impl Script {
+  pub fn name(self) -> &'static str {
+    match self {
+      Script::Install => "install",
+      Script::Typecheck => "typecheck",
+      Script::Build => "build",
+      Script::Test => "test",
+      Script::Publish => "publish",
+    }
+  }
+
+  /// Pipeline stages, which are also what a remote worker may be asked to run.
+  pub fn parse(s: &str) -> Option<Script> {
+    match s {
+      "install" => Some(Script::Install),
+      "typecheck" => Some(Script::Typecheck),
+      "build" => Some(Script::Build),
+      "test" => Some(Script::Test),
+      _ => None,
+    }
+  }
+
+  /// `built` / `tested`, for the end-of-run line.
+  pub fn past(self) -> &'static str {
+    match self {
+      Script::Install => "installed",
+      Script::Typecheck => "typechecked",
+      Script::Build => "built",
+      Script::Test => "tested",
+      Script::Publish => "published",
+    }
+  }
+}
+
+/// How much builds may rely on the registry (`--offline=0|1|prefer`).
+#[derive(Clone, Copy, PartialEq)]
+pub enum Network {
+  Online,
+  PreferOffline,
+  Offline,
+}
+
+impl Network {
+  pub fn parse(s: &str) -> Option<Network> {
+    match s {
+      "prefer" => Some(Network::PreferOffline),
+      other => crate::parse_bool(other).map(|b| if b { Network::Offline } else { Network::Online }),
+    }
+  }
+
+  /// The matching `pnpm install` flag.
+  pub fn install_flag(self) -> Option<&'static str> {
+    match self {
+      Network::Online => None,
+      Network::PreferOffline => Some("--prefer-offline"),
+      Network::Offline => Some("--offline"),
+    }
+  }
+}
+
+#[derive(Clone, Copy)]
+pub struct ExecOpts {
+  pub script: Script,
+  pub silent: bool,
+  pub dry_run: bool,
+  pub network: Network,
+  /// Node major version to run under (`--node`), through fnm or Volta.
+  pub node: Option<u32>,
+}
+
+/// fnm or Volta, whichever is on PATH, for `--node`.
+pub fn node_manager() -> Option<&'static str> {
+  static FOUND: std::sync::OnceLock<Option<&'static str>> = std::sync::OnceLock::new();
+  *FOUND.get_or_init(|| {
+    ["fnm", "volta"].into_iter().find(|tool| {
+      Command::new(tool)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok()
+    })
+  })
+}
+
+pub struct Outcome {
+  pub ok: bool,
+  pub err_tail: String,
+  pub usage: Option<Usage>,
+}
+
+/// Timing of one pipeline stage of a frame.
+#[derive(Clone, Copy)]
+pub struct StageTime {
+  pub script: Script,
+  pub dur: Duration,
+  pub ok: bool,
+}
+
+/// Add one stage's usage to a frame's total: cpu sums, peak rss is the max.
+pub fn add_usage(total: Option<Usage>, stage: Option<Usage>) -> Option<Usage> {
+  match (total, stage) {
+    (Some(a), Some(b)) => Some(Usage {
+      cpu: a.cpu + b.cpu,
+      max_rss_kb: a.max_rss_kb.max(b.max_rss_kb),
+    }),
+    (a, b) => a.or(b),
+  }
+}
+
+/// The exact command line run for frame `n`, in its container with
+/// `--container`.
+pub fn argv(n: usize, opts: ExecOpts) -> Vec<String> {
+  let mut argv: Vec<String> = match (opts.node, node_manager()) {
+    (Some(v), Some("volta")) => vec!["volta".into(), "run".into(), "--node".into(), v.to_string()],
+    (Some(v), Some(_)) => vec!["fnm".into(), "exec".into(), format!("--using={v}"), "--".into()],
+    _ => Vec::new(),
+  };
+  let script = profiles::script_name(opts.script.name()).unwrap_or(opts.script.name());
+  argv.extend([
+    "pnpm".to_string(),
+    "--filter".to_string(),
+    frame_pkg(n),
+    targets::script_name(n, opts.script, script),
+  ]);
+  match container::get() {
+    Some(c) => c.wrap(argv, &env(opts)),
+    None => argv,
+  }
+}
+
+/// Environment variables set on top of the inherited environment: the
+/// network mode, through pnpm's config env rather than flags (which `pnpm
+/// run` would hand to the script), the `--target`, and the `--use-profile`'s
+/// env table.
+pub fn env(opts: ExecOpts) -> Vec<(String, String)> {
+  let mut env = Vec::new();
+  match opts.network {
+    Network::Online => {}
+    Network::PreferOffline => env.push(("npm_config_prefer_offline".to_string(), "true".to_string())),
+    Network::Offline => env.push(("npm_config_offline".to_string(), "true".to_string())),
+  }
+  if let Some(t) = targets::active() {
+    env.push((targets::ENV.to_string(), t.to_string()));
+  }
+  env.extend(profiles::env().iter().cloned());
+  env
+}
+
+/// Command for frame `n` with args and env applied; stdio is left to the caller.
+pub fn command(n: usize, opts: ExecOpts) -> Command {
+  let argv = argv(n, opts);
+  let mut cmd = Command::new(&argv[0]);
+  cmd.args(&argv[1..]);
+  cmd.envs(env(opts));
+  cmd
+}
+
+/// Run the script for frame `n`. Stdout is captured with stderr (and still
+/// echoed unless silent): test runners report failures there, and webpack
+/// its warnings.
+pub fn run_frame(n: usize, opts: ExecOpts) -> Outcome {
+  run_command(n, opts, command(n, opts))
+}
+
+/// Run `stages` in order for frame `n`, stopping at the first failure. The
+/// outcome carries the failing stage's tail and usage summed over stages.
+pub fn run_pipeline(n: usize, opts: ExecOpts, stages: &[Script]) -> (Outcome, Vec<StageTime>) {
+  run_pipeline_with(opts, stages, |o| run_frame(n, o))
+}
+
+/// `run_pipeline` with each stage run by `run` (e.g. on another machine).
+pub fn run_pipeline_with(opts: ExecOpts, stages: &[Script], run: impl Fn(ExecOpts) -> Outcome) -> (Outcome, Vec<StageTime>) {
+  let mut times = Vec::new();
+  let mut usage = None;
+  let mut last = Outcome {
+    ok: true,
+    err_tail: String::new(),
+    usage: None,
+  };
+  for &script in stages {
+    let t = Instant::now();
+    last = run(ExecOpts { script, ..opts });
+    usage = add_usage(usage, last.usage);
+    times.push(StageTime {
+      script,
+      dur: t.elapsed(),
+      ok: last.ok,
+    });
+    if !last.ok {
+      break;
+    }
+  }
+  last.usage = usage;
+  (last, times)
+}
+
+/// `run_frame` with a caller-adjusted `command(n, opts)`.
+pub fn run_command(n: usize, opts: ExecOpts, mut cmd: Command) -> Outcome {
+  if opts.dry_run {
+    return Outcome {
+      ok: true,
+      err_tail: String::new(),
+      usage: None,
+    };
+  }
+
+  cmd.stdin(Stdio::null());
+  // Captured even when silent, for test results and warning counts.
+  cmd.stdout(Stdio::piped());
+  cmd.stderr(Stdio::piped());
+
+  let mut child = match spawn(n, &mut cmd) {
+    Ok(c) => c,
+    Err(e) => {
+      return Outcome {
+        ok: false,
+        err_tail: format!("spawn failed: {e}"),
+        usage: None,
+      }
+    }
+  };
+
+  {
+    let mut running = RUNNING.lock().unwrap();
+    running.insert(n, child.id());
+    // Spawned as the run was halted: `kill_all` has already looked.
+    if control::halted() {
+      let _ = control::kill_tree(child.id());
+    }
+  }
+  let out_reader = drain(child.stdout.take(), !opts.silent);
+  let err_reader = drain(child.stderr.take(), false);
+  let waited = rusage::wait(&mut child);
+  RUNNING.lock().unwrap().remove(&n);
+  let mut output = out_reader.join().unwrap_or_default();
+  let stderr = err_reader.join().unwrap_or_default();
+  let warned = strict::violation(&String::from_utf8_lossy(&stderr));
+  output.extend(stderr);
+  if control::take_killed(n) {
+    output.extend_from_slice(b"\nframectl: build killed from `framectl top`\n");
+  } else if let Ok((status, _)) = &waited {
+    if killed(status) {
+      output.extend_from_slice(b"\nframectl: build killed by SIGKILL\n");
+    }
+  }
+  let mut output = String::from_utf8_lossy(&output).into_owned();
+  warnings::scan(n, opts.script, &output);
+
+  let (mut ok, usage) = match waited {
+    Ok((status, usage)) => (status.success(), usage),
+    Err(e) => {
+      return Outcome {
+        ok: false,
+        err_tail: format!("wait failed: {e}"),
+        usage: None,
+      }
+    }
+  };
+  if let Some(w) = warned.filter(|_| ok) {
+    ok = false;
+    output.push_str(&format!("\n{w}\n"));
+  }
+  if let Some(b) = budget::violation(n).filter(|_| ok && opts.script == Script::Build) {
+    ok = false;
+    output.push_str(&format!("\n{b}\n"));
+  }
+  if !ok {
+    write_log(n, opts.script, &output);
+  }
+  Outcome {
+    ok,
+    err_tail: tail(&output, 3000),
+    usage,
+  }
+}
+
+#[cfg(unix)]
+fn killed(status: &std::process::ExitStatus) -> bool {
+  use std::os::unix::process::ExitStatusExt;
+  status.signal() == Some(9)
+}
+
+#[cfg(not(unix))]
+fn killed(_status: &std::process::ExitStatus) -> bool {
+  false
+}
+
+/// Read `pipe` to the end on its own thread, copying to our stdout if `echo`.
+fn drain<R: Read + Send + 'static>(pipe: Option<R>, echo: bool) -> JoinHandle<Vec<u8>> {
+  thread::spawn(move || {
+    let mut buf = Vec::new();
+    let Some(mut p) = pipe else {
+      return buf;
+    };
+    let mut chunk = [0u8; 8192];
+    loop {
+      match p.read(&mut chunk) {
+        Ok(0) | Err(_) => break,
+        Ok(k) => {
+          if echo {
+            let _ = io::stdout().write_all(&chunk[..k]);
+          }
+          buf.extend_from_slice(&chunk[..k]);
+        }
+      }
+    }
+    buf
+  })
+}
+
+pub fn log_dir() -> PathBuf {
+  state_dir().join("logs")
+}
+
+/// `frame-XXXX.log` for builds, `frame-XXXX.<script>.log` otherwise.
+pub fn log_path(n: usize, script: Script) -> PathBuf {
+  let stem = match filterexpr::get(n) {
+    Some(expr) => format!("filter-{}", filterexpr::slug(expr)),
+    None => format!("frame-{n:04}"),
+  };
+  let name = match script {
+    Script::Build => format!("{stem}.log"),
+    other => format!("{stem}.{}.log", other.name()),
+  };
+  log_dir().join(name)
+}
+
+/// Keep the output of a failed run around; best effort.
+pub fn write_log(n: usize, script: Script, stderr: &str) {
+  let path = log_path(n, script);
+  if let Some(dir) = path.parent() {
+    let _ = std::fs::create_dir_all(dir);
+  }
+  let _ = std::fs::write(path, stderr);
+}
+
+/// Last `keep` bytes of `s`, moved forward to a char boundary.
+pub fn tail(s: &str, keep: usize) -> String {
+  let mut start = s.len().saturating_sub(keep);
+  while !s.is_char_boundary(start) {
+    start += 1;
+  }
+  s[start..].to_string()
+}
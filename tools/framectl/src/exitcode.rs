@@ -0,0 +1,146 @@
+//! Exit codes by kind of failure, so wrappers and CI steps can branch on the
+//! code instead of matching `exit:` lines:
+//!
+//! 1 build failures, 2 usage error, 3 interrupted (stop file, SIGINT or
+//! SIGTERM), 4 empty plan, 5 timeout, 6 environment (preflight, lock, disk,
+//! unreachable workers, missing tools).
+//!
+//! With `--error-format=json` the last stderr line of a failed run is also a
+//! JSON object with the kind, code and message (and, for runs, the counts and
+//! failed frames).
+//!
+//! SIGINT and SIGTERM are caught while a run is scheduling: the queue closes,
+//! local builds in flight are stopped (remote ones are not) and the summary is
+//! still written. A second signal exits at once, with 128 + its number.
+
+use std::fmt::Display;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use crate::json::Value;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Kind {
+  Build,
+  Usage,
+  Interrupted,
+  Empty,
+  Timeout,
+  Environment,
+}
+
+impl Kind {
+  pub fn code(self) -> i32 {
+    match self {
+      Kind::Build => 1,
+      Kind::Usage => 2,
+      Kind::Interrupted => 3,
+      Kind::Empty => 4,
+      Kind::Timeout => 5,
+      Kind::Environment => 6,
+    }
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Kind::Build => "build-failed",
+      Kind::Usage => "usage",
+      Kind::Interrupted => "interrupted",
+      Kind::Empty => "empty-plan",
+      Kind::Timeout => "timeout",
+      Kind::Environment => "environment",
+    }
+  }
+}
+
+/// The command being run, when errors are JSON.
+static JSON: OnceLock<Option<String>> = OnceLock::new();
+
+/// `--error-format=text|json` for `command` (one that takes the flag), read
+/// from the raw arguments so argument errors come out in the format too.
+pub fn install(command: &str, argv: &[String]) {
+  let mut value = None;
+  for (i, a) in argv.iter().enumerate() {
+    if let Some(v) = a.strip_prefix("--error-format=") {
+      value = Some(v.to_string());
+    } else if a == "--error-format" {
+      value = argv.get(i + 1).cloned();
+    } else if a == "--" {
+      break;
+    }
+  }
+  let json = match value.as_deref() {
+    None | Some("text") => false,
+    Some("json") => true,
+    Some(v) => {
+      let _ = JSON.set(None);
+      usage(format!("invalid --error-format={v} (expected text or json)"));
+    }
+  };
+  let _ = JSON.set(json.then(|| command.to_string()));
+}
+
+/// Print `message` and exit with `kind`'s code.
+pub fn fail(kind: Kind, message: impl Display) -> ! {
+  let message = message.to_string();
+  eprintln!("{message}");
+  exit(kind, &message, Vec::new())
+}
+
+/// A usage error: print `message` and exit 2.
+pub fn usage(message: impl Display) -> ! {
+  fail(Kind::Usage, message)
+}
+
+/// Exit with `kind`'s code, after the JSON error object if asked for; the
+/// plain message has already been printed.
+pub fn exit(kind: Kind, message: &str, fields: Vec<(&str, Value)>) -> ! {
+  if let Some(Some(command)) = JSON.get() {
+    let message = message.strip_prefix("exit: ").unwrap_or(message);
+    let mut doc = vec![
+      ("error", Value::from(kind.name())),
+      ("code", Value::from(kind.code() as usize)),
+      ("command", Value::from(command.as_str())),
+      ("message", Value::from(message)),
+    ];
+    doc.extend(fields);
+    eprintln!("{}", Value::obj(doc).compact());
+  }
+  std::process::exit(kind.code())
+}
+
+static SIGNALS: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(unix)]
+extern "C" fn on_signal(sig: std::os::raw::c_int) {
+  if SIGNALS.fetch_add(1, Ordering::Relaxed) > 0 {
+    extern "C" {
+      fn _exit(code: std::os::raw::c_int) -> !;
+    }
+    // SAFETY: _exit is async-signal-safe.
+    unsafe { _exit(128 + sig) }
+  }
+}
+
+/// Catch SIGINT and SIGTERM for the rest of the process.
+pub fn catch_signals() {
+  #[cfg(unix)]
+  {
+    extern "C" {
+      fn signal(sig: std::os::raw::c_int, handler: usize) -> usize;
+    }
+    const SIGINT: std::os::raw::c_int = 2;
+    const SIGTERM: std::os::raw::c_int = 15;
+    let handler = on_signal as extern "C" fn(std::os::raw::c_int) as usize;
+    // SAFETY: the handler only touches an atomic or calls _exit.
+    unsafe {
+      signal(SIGINT, handler);
+      signal(SIGTERM, handler);
+    }
+  }
+}
+
+/// Whether SIGINT or SIGTERM has arrived.
+pub fn interrupted() -> bool {
+  SIGNALS.load(Ordering::Relaxed) > 0
+}
@@ -0,0 +1,134 @@
+//! `--failure-bundle=DIR`: for each frame a run leaves failed, a
+//! `framectl-failure-NNNN.tar.gz` holding what a teammate needs to debug it
+//! elsewhere: the frame's package.json, configs and sources, its logs, the
+//! settings the run resolved (`print-config`), the exact command, and the
+//! tool versions on this machine.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::exec::{self, ExecOpts};
+use crate::frames::{self, frame_pkg};
+use crate::summary::FrameRecord;
+use crate::{parse_bool, parse_kv, printconfig, state_dir};
+
+/// Frame files larger than this are left out (stray build output, media).
+const MAX_FILE: u64 = 1024 * 1024;
+/// Workspace files that shape every frame's install and build.
+const WORKSPACE_FILES: [&str; 4] = ["package.json", "pnpm-workspace.yaml", "pnpm-lock.yaml", "framectl.toml"];
+
+/// Where bundles go: `--failure-bundle=DIR`, `1` for here, unset or `0` for
+/// none.
+pub fn dir(args: &[String]) -> Option<PathBuf> {
+  let v = parse_kv(args, "--failure-bundle")?;
+  match parse_bool(&v) {
+    Some(false) => None,
+    Some(true) => Some(PathBuf::from(".")),
+    None => Some(PathBuf::from(v)),
+  }
+}
+
+/// `tool --version`, first line.
+fn version(tool: &str, arg: &str) -> String {
+  Command::new(tool)
+    .arg(arg)
+    .stdin(Stdio::null())
+    .stderr(Stdio::null())
+    .output()
+    .ok()
+    .filter(|o| o.status.success())
+    .and_then(|o| String::from_utf8_lossy(&o.stdout).lines().next().map(|l| l.trim().to_string()))
+    .unwrap_or_else(|| "not found".to_string())
+}
+
+fn environment() -> String {
+  let mut out = format!(
+    "framectl {}\nos {} {}\n",
+    env!("CARGO_PKG_VERSION"),
+    std::env::consts::OS,
+    std::env::consts::ARCH
+  );
+  for (tool, arg) in [("node", "--version"), ("pnpm", "--version"), ("uname", "-a")] {
+    out.push_str(&format!("{tool} {}\n", version(tool, arg)));
+  }
+  out
+}
+
+/// Copy the frame dir into `to`, minus dependencies and build output.
+fn copy_frame(from: &Path, to: &Path) -> std::io::Result<()> {
+  std::fs::create_dir_all(to)?;
+  for ent in std::fs::read_dir(from)?.flatten() {
+    let name = ent.file_name();
+    if matches!(name.to_str(), Some("node_modules" | "dist" | ".npmrc")) {
+      continue;
+    }
+    let path = ent.path();
+    if path.is_dir() {
+      copy_frame(&path, &to.join(&name))?;
+    } else if ent.metadata().is_ok_and(|m| m.len() <= MAX_FILE) {
+      std::fs::copy(&path, to.join(&name))?;
+    }
+  }
+  Ok(())
+}
+
+/// Write the bundle for failed frame `n` into `dir`.
+pub fn write(dir: &Path, n: usize, record: &FrameRecord, opts: ExecOpts, args: &[String], command: &str) -> Result<PathBuf, String> {
+  let name = format!("framectl-failure-{n:04}");
+  let stage = state_dir().join("failure-bundle").join(&name);
+  let _ = std::fs::remove_dir_all(&stage);
+  let io = |what: &str, e: std::io::Error| format!("{what}: {e}");
+  copy_frame(&frames::frame_dir(n), &stage.join(format!("frame-{n:04}"))).map_err(|e| io("copying the frame", e))?;
+  for f in WORKSPACE_FILES.iter().filter(|f| Path::new(f).is_file()) {
+    std::fs::create_dir_all(stage.join("workspace")).map_err(|e| io("workspace", e))?;
+    std::fs::copy(f, stage.join("workspace").join(f)).map_err(|e| io(f, e))?;
+  }
+  let logs = stage.join("logs");
+  std::fs::create_dir_all(&logs).map_err(|e| io("logs", e))?;
+  for st in &record.stages {
+    let log = exec::log_path(n, st.script);
+    if let Some(file) = log.file_name().filter(|_| log.is_file()) {
+      std::fs::copy(&log, logs.join(file)).map_err(|e| io(&log.display().to_string(), e))?;
+    }
+  }
+
+  let failed = record.failed_stage().unwrap_or(opts.script);
+  let argv = exec::argv(n, ExecOpts { script: failed, ..opts });
+  let env: Vec<String> = exec::env(opts).into_iter().map(|(k, v)| format!("{k}={v}")).collect();
+  let info = format!(
+    "frame {n:04} ({})\nfailed at {}: {}\ncommand: {}\nenv: {}\nframectl {command} {}\n",
+    frame_pkg(n),
+    failed.name(),
+    record.error.as_deref().unwrap_or("unknown"),
+    argv.join(" "),
+    if env.is_empty() { "(none)".to_string() } else { env.join(" ") },
+    args.join(" ")
+  );
+  let files = [
+    ("failure.txt", info),
+    ("config.toml", printconfig::render(args, command)),
+    ("environment.txt", environment()),
+  ];
+  for (file, text) in files {
+    std::fs::write(stage.join(file), text).map_err(|e| io(file, e))?;
+  }
+
+  std::fs::create_dir_all(dir).map_err(|e| io(&dir.display().to_string(), e))?;
+  let out = dir.join(format!("{name}.tar.gz"));
+  let parent = stage.parent().unwrap_or(Path::new("."));
+  let tar = Command::new("tar")
+    .arg("-czf")
+    .arg(std::path::absolute(&out).unwrap_or_else(|_| out.clone()))
+    .arg("-C")
+    .arg(parent)
+    .arg(&name)
+    .stdin(Stdio::null())
+    .stderr(Stdio::piped())
+    .output()
+    .map_err(|e| format!("tar: {e}"))?;
+  let _ = std::fs::remove_dir_all(&stage);
+  if !tar.status.success() {
+    return Err(format!("tar: {}", String::from_utf8_lossy(&tar.stderr).trim()));
+  }
+  Ok(out)
+}
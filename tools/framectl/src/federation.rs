@@ -0,0 +1,442 @@
+//! `verify-federation`: each built frame's remote checked against what the
+//! host expects of it, so a frame that would fail to load at runtime is
+//! caught before it is deployed.
+//!
+//! The host loads `frame_NNNN/Frame` from `static/js/remoteEntry.js` and
+//! shares nothing; `mf-manifest.json`, when present, must agree with that.
+//!
+//! A contract file (`--contract`, default `federation-contract.json` when
+//! there is one) pins what every frame must expose and share:
+//!
+//! ```json
+//! {
+//!   "exposes": ["./Frame"],
+//!   "shared": { "react": { "requiredVersion": "^18.3.1", "singleton": true } }
+//! }
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::frames::{self, fmt_ranges, ENTRY};
+use crate::json::{self, Value};
+use crate::template::extract;
+use crate::{parse_bool, parse_kv, targets};
+
+const DEFAULT_CONTRACT: &str = "federation-contract.json";
+
+/// A shared dependency as a manifest declares it, or a contract requires it.
+struct Shared {
+  name: String,
+  version: String,
+  singleton: Option<bool>,
+}
+
+/// What every frame must expose and share.
+struct Contract {
+  exposes: Vec<String>,
+  shared: Vec<Shared>,
+}
+
+fn shared_entry(name: String, s: &Value) -> Shared {
+  let version = s
+    .get("requiredVersion")
+    .or_else(|| s.get("version"))
+    .and_then(Value::as_str)
+    .unwrap_or("*")
+    .to_string();
+  let singleton = match s.get("singleton") {
+    Some(Value::Bool(b)) => Some(*b),
+    _ => None,
+  };
+  Shared { name, version, singleton }
+}
+
+fn read_contract(path: &str) -> Result<Contract, String> {
+  let doc = json::read(std::path::Path::new(path))?;
+  let exposes = match doc.get("exposes") {
+    Some(Value::Arr(list)) => list
+      .iter()
+      .map(|e| e.as_str().map(str::to_string).ok_or("\"exposes\" must list module paths"))
+      .collect::<Result<_, _>>()?,
+    None => vec!["./Frame".to_string()],
+    Some(_) => return Err("\"exposes\" must be an array".to_string()),
+  };
+  let shared = match doc.get("shared") {
+    Some(Value::Obj(fields)) => fields.iter().map(|(name, s)| shared_entry(name.clone(), s)).collect(),
+    None => Vec::new(),
+    Some(_) => return Err("\"shared\" must be an object of name -> { requiredVersion, singleton }".to_string()),
+  };
+  Ok(Contract { exposes, shared })
+}
+
+/// The container name a package should have: `@bad-apple/frame-0001` ->
+/// `frame_0001`.
+fn container_name(pkg: &str) -> String {
+  let base = pkg.rsplit('/').next().unwrap_or(pkg);
+  base.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
+/// Each shared dependency in a manifest.
+fn shared(doc: &Value) -> Vec<Shared> {
+  let Some(Value::Arr(list)) = doc.get("shared") else {
+    return Vec::new();
+  };
+  list
+    .iter()
+    .filter_map(|s| Some(shared_entry(s.get("name")?.as_str()?.to_string(), s)))
+    .collect()
+}
+
+/// Problems with frame `n`'s manifest, shared dependencies aside. With
+/// `exact`, exposing anything beyond `exposes` is one too.
+fn check_manifest(n: usize, doc: &Value, exposes: &[String], exact: bool, add: &mut impl FnMut(String)) {
+  let scope = format!("frame_{n:04}");
+  let meta = doc.get("metaData");
+  let str_at = |v: Option<&Value>, key: &str| v.and_then(|v| v.get(key)).and_then(Value::as_str).map(str::to_string);
+
+  match str_at(Some(doc), "name") {
+    Some(name) if name == scope => {}
+    Some(name) => add(format!("manifest name {name} (expected {scope})")),
+    None => add("manifest has no name".to_string()),
+  }
+  if let Some(global) = str_at(meta, "globalName").filter(|g| *g != scope) {
+    add(format!("globalName {global} (expected {scope})"));
+  }
+
+  let entry = meta.and_then(|m| m.get("remoteEntry"));
+  let entry_path = match (str_at(entry, "path"), str_at(entry, "name")) {
+    (_, None) => None,
+    (Some(dir), Some(name)) if !dir.is_empty() => Some(format!("{}/{name}", dir.trim_end_matches('/'))),
+    (_, Some(name)) => Some(name),
+  };
+  match entry_path {
+    Some(p) if p == ENTRY => {}
+    Some(p) => add(format!("remoteEntry {p} (expected {ENTRY})")),
+    None => add("manifest has no remoteEntry".to_string()),
+  }
+
+  // `auto` resolves against remoteEntry.js at runtime; anything else must be
+  // what the frame's config asks for, and never another frame's directory.
+  if let Some(public) = str_at(meta, "publicPath").filter(|p| p != "auto") {
+    let config = std::fs::read_to_string(frames::frame_dir(n).join("rsbuild.config.mjs")).unwrap_or_default();
+    let want = extract(&config, "assetPrefix");
+    let own = format!("frame-{n:04}/");
+    if want.as_deref().is_some_and(|w| w != public) {
+      add(format!("publicPath {public} (config assetPrefix {})", want.unwrap_or_default()));
+    } else if public.contains("frame-") && !public.ends_with(&own) {
+      add(format!("publicPath {public} points outside {own}"));
+    }
+  }
+
+  let have: Vec<String> = match doc.get("exposes") {
+    Some(Value::Arr(list)) => list.iter().filter_map(|e| str_at(Some(e), "path")).collect(),
+    _ => Vec::new(),
+  };
+  for want in exposes {
+    if !have.contains(want) {
+      let list = if have.is_empty() { "nothing".to_string() } else { have.join(", ") };
+      add(format!("does not expose {want} (exposes: {list})"));
+    }
+  }
+  if exact {
+    for extra in have.iter().filter(|h| !exposes.contains(h)) {
+      add(format!("also exposes {extra}, which the contract does not"));
+    }
+  }
+}
+
+/// The federation `name` in frame `n`'s config against the one its
+/// package.json name implies, so it is caught before a build.
+fn check_container(n: usize, add: &mut impl FnMut(String)) {
+  let dir = frames::frame_dir(n);
+  let Some(pkg) = json::read(&dir.join("package.json"))
+    .ok()
+    .and_then(|doc| doc.get("name").and_then(Value::as_str).map(str::to_string))
+  else {
+    add("package.json has no name".to_string());
+    return;
+  };
+  let config = std::fs::read_to_string(dir.join("rsbuild.config.mjs")).unwrap_or_default();
+  let want = container_name(&pkg);
+  match extract(&config, "name") {
+    Some(name) if name == want => {}
+    Some(name) => add(format!("container {name} does not match package {pkg} (expected {want})")),
+    None => add("rsbuild.config.mjs sets no federation name".to_string()),
+  }
+}
+
+/// name -> (version, singleton) frames must share.
+type Wanted = BTreeMap<String, (String, Option<bool>)>;
+
+/// Problems with each frame's shared dependencies: against `want` (from
+/// `--shared` or the contract), all of which every frame must share, else
+/// against the version most frames share.
+fn check_shared(by_frame: &BTreeMap<usize, Vec<Shared>>, want: Option<&Wanted>, add: &mut impl FnMut(usize, String)) {
+  let expected: Wanted = want.cloned().unwrap_or_else(|| {
+    let mut counts: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+    for list in by_frame.values() {
+      for s in list {
+        *counts.entry((&s.name, &s.version)).or_default() += 1;
+      }
+    }
+    let mut best: BTreeMap<String, (String, usize)> = BTreeMap::new();
+    for ((name, version), c) in counts {
+      let e = best.entry(name.to_string()).or_insert_with(|| (version.to_string(), 0));
+      if c > e.1 {
+        *e = (version.to_string(), c);
+      }
+    }
+    best.into_iter().map(|(k, (v, _))| (k, (v, None))).collect()
+  });
+  for (&n, list) in by_frame {
+    for s in list {
+      let (name, version) = (&s.name, &s.version);
+      match expected.get(name) {
+        Some((v, _)) if v != version && v != "*" => add(n, format!("shares {name}@{version} (expected {v})")),
+        Some((_, Some(single))) if s.singleton != Some(*single) => add(n, format!(
+          "shares {name} {} (expected {})",
+          if s.singleton == Some(true) { "as a singleton" } else { "not as a singleton" },
+          if *single { "singleton" } else { "not singleton" }
+        )),
+        Some(_) => {}
+        None => add(n, format!("shares {name}@{version}, which the host does not")),
+      }
+    }
+    if want.is_some() {
+      for name in expected.keys().filter(|k| !list.iter().any(|s| s.name == **k)) {
+        add(n, format!("does not share {name}"));
+      }
+    }
+  }
+}
+
+pub fn run(args: &[String]) {
+  let require_manifest = parse_kv(args, "--manifest").and_then(|v| parse_bool(&v)).unwrap_or(false);
+  let contract_path = parse_kv(args, "--contract")
+    .or_else(|| std::path::Path::new(DEFAULT_CONTRACT).is_file().then(|| DEFAULT_CONTRACT.to_string()));
+  let contract = contract_path.as_deref().map(|path| {
+    read_contract(path).unwrap_or_else(|e| {
+      eprintln!("verify-federation: {path}: {e}");
+      std::process::exit(2);
+    })
+  });
+  let exposes: Vec<String> = match (parse_kv(args, "--expose"), &contract) {
+    (Some(list), _) => list
+      .split(',')
+      .map(str::trim)
+      .filter(|e| !e.is_empty())
+      .map(str::to_string)
+      .collect(),
+    (None, Some(c)) => c.exposes.clone(),
+    (None, None) => vec!["./Frame".to_string()],
+  };
+  // name -> (version, singleton); without it frames must agree with each
+  // other.
+  let want_shared: Option<Wanted> = match (parse_kv(args, "--shared"), &contract) {
+    (Some(spec), _) => Some(
+      spec
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| match s[1..].find('@') {
+          Some(at) => (s[..at + 1].to_string(), (s[at + 2..].to_string(), None)),
+          None => (s.to_string(), ("*".to_string(), None)),
+        })
+        .collect(),
+    ),
+    (None, Some(c)) => Some(c.shared.iter().map(|s| (s.name.clone(), (s.version.clone(), s.singleton))).collect()),
+    (None, None) => None,
+  };
+
+  targets::dists("verify-federation", args);
+  let found: Vec<usize> = frames::selected(args);
+  if found.is_empty() {
+    eprintln!("verify-federation: no frame-XXXX dirs found under apps/frames");
+    std::process::exit(2);
+  }
+
+  let mut problems: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+  let mut unbuilt = Vec::new();
+  let mut manifests = 0usize;
+  let mut shared_by_frame: BTreeMap<usize, Vec<Shared>> = BTreeMap::new();
+  for &n in &found {
+    let dist = frames::dist_dir(n);
+    let mut msgs = Vec::new();
+    check_container(n, &mut |m| msgs.push(m));
+    match std::fs::read_to_string(dist.join(ENTRY)) {
+      Ok(src) => {
+        if !src.contains(&format!("frame_{n:04}")) {
+          msgs.push(format!("{ENTRY} does not define frame_{n:04}"));
+        }
+      }
+      Err(_) if !dist.is_dir() => {
+        unbuilt.push(n);
+        if !msgs.is_empty() {
+          problems.insert(n, msgs);
+        }
+        continue;
+      }
+      Err(e) => msgs.push(format!("{ENTRY}: {e}")),
+    }
+    let path = dist.join("mf-manifest.json");
+    if path.is_file() {
+      manifests += 1;
+      match json::read(&path) {
+        Ok(doc) => {
+          check_manifest(n, &doc, &exposes, contract.is_some(), &mut |m| msgs.push(m));
+          shared_by_frame.insert(n, shared(&doc));
+        }
+        Err(e) => msgs.push(format!("mf-manifest.json: {e}")),
+      }
+    } else if require_manifest {
+      msgs.push("no mf-manifest.json".to_string());
+    }
+    if !msgs.is_empty() {
+      problems.insert(n, msgs);
+    }
+  }
+
+  check_shared(&shared_by_frame, want_shared.as_ref(), &mut |n, m| problems.entry(n).or_default().push(m));
+
+  eprintln!(
+    "verify-federation: {} frame(s) expose={} manifests={manifests}{}",
+    found.len(),
+    exposes.join(","),
+    contract_path.as_deref().map(|p| format!(" contract={p}")).unwrap_or_default()
+  );
+  for (n, msgs) in &problems {
+    eprintln!("frame-{n:04}: {}", msgs.join("; "));
+  }
+  if !unbuilt.is_empty() {
+    eprintln!("not built (no dist): {}", fmt_ranges(&unbuilt));
+  }
+  if problems.is_empty() && unbuilt.is_empty() {
+    eprintln!("ok: {} remote(s) match the host contract", found.len());
+    return;
+  }
+  let bad: BTreeSet<usize> = problems.keys().chain(&unbuilt).copied().collect();
+  let bad: Vec<usize> = bad.into_iter().collect();
+  eprintln!("exit: {} frame(s) would break the host: {}", bad.len(), fmt_ranges(&bad));
+  std::process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn problems(doc: &str, exposes: &[&str], exact: bool) -> Vec<String> {
+    let doc = json::parse(doc).unwrap();
+    let exposes: Vec<String> = exposes.iter().map(|e| e.to_string()).collect();
+    let mut out = Vec::new();
+    check_manifest(7, &doc, &exposes, exact, &mut |m| out.push(m));
+    out
+  }
+
+  fn share(name: &str, version: &str, singleton: Option<bool>) -> Shared {
+    Shared {
+      name: name.to_string(),
+      version: version.to_string(),
+      singleton,
+    }
+  }
+
+  fn shared_problems(by_frame: Vec<(usize, Vec<Shared>)>, want: Option<&[(&str, &str, Option<bool>)]>) -> Vec<(usize, String)> {
+    let want: Option<Wanted> = want.map(|w| w.iter().map(|(n, v, s)| (n.to_string(), (v.to_string(), *s))).collect());
+    let mut out = Vec::new();
+    check_shared(&by_frame.into_iter().collect(), want.as_ref(), &mut |n, m| out.push((n, m)));
+    out
+  }
+
+  const GOOD: &str = r#"{
+    "name": "frame_0007",
+    "metaData": {"globalName": "frame_0007", "publicPath": "auto", "remoteEntry": {"path": "static/js", "name": "remoteEntry.js"}},
+    "exposes": [{"path": "./Frame"}]
+  }"#;
+
+  #[test]
+  fn container_names() {
+    assert_eq!(container_name("@bad-apple/frame-0001"), "frame_0001");
+    assert_eq!(container_name("frame.v2"), "frame_v2");
+  }
+
+  #[test]
+  fn good_manifest() {
+    assert!(problems(GOOD, &["./Frame"], true).is_empty());
+  }
+
+  #[test]
+  fn manifest_problems() {
+    let doc = r#"{
+      "name": "frame_0008",
+      "metaData": {"globalName": "other", "remoteEntry": {"name": "remoteEntry.js"}},
+      "exposes": [{"path": "./Frame"}, {"path": "./Debug"}]
+    }"#;
+    assert_eq!(
+      problems(doc, &["./Frame", "./Audio"], true),
+      [
+        "manifest name frame_0008 (expected frame_0007)",
+        "globalName other (expected frame_0007)",
+        &format!("remoteEntry remoteEntry.js (expected {ENTRY})"),
+        "does not expose ./Audio (exposes: ./Frame, ./Debug)",
+        "also exposes ./Debug, which the contract does not",
+      ]
+    );
+    // Extra exposes are fine without a contract.
+    assert_eq!(problems(doc, &["./Frame"], false).len(), 3);
+    assert_eq!(problems("{}", &["./Frame"], false), ["manifest has no name", "manifest has no remoteEntry", "does not expose ./Frame (exposes: nothing)"]);
+  }
+
+  #[test]
+  fn shared_against_contract() {
+    let want = [("react", "^18.3.1", Some(true)), ("react-dom", "^18.3.1", None)];
+    let got = shared_problems(
+      vec![
+        (1, vec![share("react", "^18.3.1", Some(true)), share("react-dom", "^18.3.1", None)]),
+        (2, vec![share("react", "^18.2.0", Some(true)), share("lodash", "^4", None)]),
+        (3, vec![share("react", "^18.3.1", Some(false)), share("react-dom", "^18.3.1", Some(true))]),
+      ],
+      Some(&want),
+    );
+    let got: Vec<(usize, &str)> = got.iter().map(|(n, m)| (*n, m.as_str())).collect();
+    assert_eq!(
+      got,
+      [
+        (2, "shares react@^18.2.0 (expected ^18.3.1)"),
+        (2, "shares lodash@^4, which the host does not"),
+        (2, "does not share react-dom"),
+        (3, "shares react not as a singleton (expected singleton)"),
+      ]
+    );
+  }
+
+  #[test]
+  fn shared_by_majority() {
+    let got = shared_problems(
+      vec![
+        (1, vec![share("react", "^18.3.1", None)]),
+        (2, vec![share("react", "^18.3.1", None)]),
+        (3, vec![share("react", "^17", None)]),
+        (4, vec![]),
+      ],
+      None,
+    );
+    // Without a contract sharing nothing isn't a problem, only disagreeing.
+    assert_eq!(got, [(3, "shares react@^17 (expected ^18.3.1)".to_string())]);
+  }
+
+  #[test]
+  fn reads_contracts() {
+    let path = std::env::temp_dir().join(format!("framectl-contract-{}.json", std::process::id()));
+    let read = |text: &str| {
+      std::fs::write(&path, text).unwrap();
+      read_contract(&path.to_string_lossy())
+    };
+    let c = read(r#"{"shared": {"react": {"requiredVersion": "^18.3.1", "singleton": true}}}"#).unwrap();
+    assert_eq!(c.exposes, ["./Frame"]);
+    assert_eq!((c.shared[0].name.as_str(), c.shared[0].version.as_str(), c.shared[0].singleton), ("react", "^18.3.1", Some(true)));
+    assert_eq!(read(r#"{"exposes": "./Frame"}"#).err().unwrap(), "\"exposes\" must be an array");
+    assert_eq!(read(r#"{"exposes": [1]}"#).err().unwrap(), "\"exposes\" must list module paths");
+    assert!(read(r#"{"shared": []}"#).err().unwrap().starts_with("\"shared\" must be an object"));
+    let _ = std::fs::remove_file(&path);
+  }
+}
@@ -0,0 +1,115 @@
+//! `--filter-expr=EXPR`: workspace packages built in the same run as the
+//! frame range, like `'@bad-apple/host...'` for the host app and the shared
+//! packages it depends on. Each expression is one task, `pnpm --filter EXPR
+//! SCRIPT`, so pnpm picks the packages and orders them by dependency; the
+//! scheduler, progress, retries and summary handle it like a frame.
+//!
+//! Tasks are numbered from `FIRST` within a run; the summary and the
+//! duration history record the expression itself, so `--retry-failed`
+//! rebuilds a failed one whenever it is in `--filter-expr` again, wherever in
+//! the list.
+
+use std::sync::Mutex;
+
+use crate::{exitcode, parse_kv};
+
+/// Task number of the first expression, well past any frame.
+pub const FIRST: usize = 1_000_000;
+
+/// Every expression seen this run, by task number: `--filter-expr`'s, then
+/// any only read back from a summary.
+static EXPRS: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+/// `--filter-expr=A,B` for this process, as task numbers.
+pub fn install(args: &[String]) -> Vec<usize> {
+  let Some(spec) = parse_kv(args, "--filter-expr") else {
+    return Vec::new();
+  };
+  let mut tasks: Vec<usize> = Vec::new();
+  for e in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+    if e.starts_with('-') {
+      exitcode::usage(format!("invalid --filter-expr={spec} (expected pnpm selectors like @bad-apple/host...)"));
+    }
+    let n = id(e);
+    if !tasks.contains(&n) {
+      tasks.push(n);
+    }
+  }
+  if tasks.is_empty() {
+    exitcode::usage("--filter-expr needs at least one expression");
+  }
+  tasks
+}
+
+/// The task number for `expr`, numbering it if it's new.
+pub fn id(expr: &str) -> usize {
+  let mut all = EXPRS.lock().unwrap();
+  let i = match all.iter().position(|have| *have == expr) {
+    Some(i) => i,
+    None => {
+      all.push(Box::leak(expr.to_string().into_boxed_str()));
+      all.len() - 1
+    }
+  };
+  FIRST + i
+}
+
+/// The task number for `expr`, if this run has seen it.
+pub fn find(expr: &str) -> Option<usize> {
+  EXPRS.lock().unwrap().iter().position(|have| *have == expr).map(|i| FIRST + i)
+}
+
+/// Whether `n` numbers a filter task rather than a frame.
+pub fn is_task(n: usize) -> bool {
+  n >= FIRST
+}
+
+/// The expression task `n` builds, if this run has it.
+pub fn get(n: usize) -> Option<&'static str> {
+  let i = n.checked_sub(FIRST)?;
+  EXPRS.lock().unwrap().get(i).copied()
+}
+
+/// `@bad-apple/host...` as `bad-apple-host`, for log file names.
+pub fn slug(expr: &str) -> String {
+  let mut out = String::new();
+  for c in expr.chars() {
+    if c.is_ascii_alphanumeric() {
+      out.push(c);
+    } else if !out.is_empty() && !out.ends_with('-') {
+      out.push('-');
+    }
+  }
+  out.trim_end_matches('-').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn numbers_by_expression() {
+    let a = id("@filterexpr-test/a...");
+    assert!(is_task(a) && !is_task(FIRST - 1));
+    assert_eq!(id("@filterexpr-test/a..."), a);
+    assert_eq!(find("@filterexpr-test/a..."), Some(a));
+    assert_eq!(get(a), Some("@filterexpr-test/a..."));
+    assert_eq!(find("@filterexpr-test/unseen"), None);
+    assert_eq!(get(7), None);
+  }
+
+  #[test]
+  fn installs_each_once() {
+    let args = vec!["--filter-expr= @filterexpr-test/b , @filterexpr-test/c,,@filterexpr-test/b".to_string()];
+    let tasks = install(&args);
+    assert_eq!(tasks, [id("@filterexpr-test/b"), id("@filterexpr-test/c")]);
+    assert!(install(&[]).is_empty());
+  }
+
+  #[test]
+  fn slugs() {
+    assert_eq!(slug("@bad-apple/host..."), "bad-apple-host");
+    assert_eq!(slug("./packages/**"), "packages");
+    assert_eq!(slug("{shared}^..."), "shared");
+  }
+}
@@ -0,0 +1,215 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{filterexpr, parse_kv, targets};
+
+pub fn frames_dir() -> PathBuf {
+  PathBuf::from("apps").join("frames")
+}
+
+pub fn infer_end(frames_dir: &Path) -> Option<usize> {
+  discover(frames_dir).last().copied()
+}
+
+/// Sorted frame numbers that have an `apps/frames/frame-XXXX` dir.
+pub fn discover(frames_dir: &Path) -> Vec<usize> {
+  let mut found = Vec::new();
+  let Ok(rd) = std::fs::read_dir(frames_dir) else {
+    return found;
+  };
+  for ent in rd.flatten() {
+    let name = ent.file_name();
+    let name = name.to_string_lossy();
+    if !name.starts_with("frame-") {
+      continue;
+    }
+    let Some(num) = name.strip_prefix("frame-") else {
+      continue;
+    };
+    if num.len() != 4 {
+      continue;
+    }
+    if let Ok(n) = num.parse::<usize>() {
+      found.push(n);
+    }
+  }
+  found.sort_unstable();
+  found
+}
+
+/// A frame's remote entry, relative to its dist.
+pub const ENTRY: &str = "static/js/remoteEntry.js";
+
+/// Highest frame number: frame directories have four digits.
+pub const MAX_FRAME: usize = 9999;
+
+/// `--start`/`--end`, every frame when not given.
+pub fn range_args(args: &[String]) -> (usize, usize) {
+  let start: usize = parse_kv(args, "--start")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(1);
+  let end: usize = parse_kv(args, "--end")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(usize::MAX);
+  (start, end)
+}
+
+/// The frame packages within `--start`/`--end`, ascending.
+pub fn selected(args: &[String]) -> Vec<usize> {
+  let (start, end) = range_args(args);
+  discover(&frames_dir()).into_iter().filter(|n| (start..=end).contains(n)).collect()
+}
+
+/// `1-6570` (or a single `42`) as an inclusive range.
+pub fn parse_range(s: &str) -> Option<(usize, usize)> {
+  let (a, b) = s.split_once('-').unwrap_or((s, s));
+  let (a, b) = (a.trim().parse().ok()?, b.trim().parse().ok()?);
+  (a <= b).then_some((a, b))
+}
+
+/// `100-250,1337` into its ranges.
+pub fn parse_ranges(s: &str) -> Option<Vec<(usize, usize)>> {
+  s.split(',').filter(|p| !p.trim().is_empty()).map(parse_range).collect()
+}
+
+/// The package frame `n` is, or a `--filter-expr` task's expression.
+pub fn frame_pkg(n: usize) -> String {
+  if let Some(expr) = filterexpr::get(n) {
+    return expr.to_string();
+  }
+  format!("@bad-apple/frame-{:04}", n)
+}
+
+/// Compress sorted frame numbers into inclusive `(first, last)` runs.
+pub fn ranges(frames: &[usize]) -> Vec<(usize, usize)> {
+  let mut out: Vec<(usize, usize)> = Vec::new();
+  for &n in frames {
+    match out.last_mut() {
+      Some((_, last)) if *last + 1 == n => *last = n,
+      _ => out.push((n, n)),
+    }
+  }
+  out
+}
+
+/// Frame `n`'s directory; the workspace root for a `--filter-expr` task.
+pub fn frame_dir(n: usize) -> PathBuf {
+  if filterexpr::get(n).is_some() {
+    return PathBuf::from(".");
+  }
+  frames_dir().join(format!("frame-{n:04}"))
+}
+
+/// Latest modification time of `path` or anything under it.
+pub fn newest_mtime(path: &Path) -> Option<SystemTime> {
+  let meta = path.metadata().ok()?;
+  if !meta.is_dir() {
+    return meta.modified().ok();
+  }
+  std::fs::read_dir(path)
+    .ok()?
+    .flatten()
+    .filter_map(|e| newest_mtime(&e.path()))
+    .max()
+}
+
+/// Frame `n`'s build output: `dist`, or `dist/NAME` under `--target=NAME`.
+pub fn dist_dir(n: usize) -> PathBuf {
+  let dist = frame_dir(n).join("dist");
+  match targets::active() {
+    Some(t) => dist.join(t),
+    None => dist,
+  }
+}
+
+/// Frame `n`'s remote entry in its [`dist_dir`].
+pub fn entry_path(n: usize) -> PathBuf {
+  dist_dir(n).join(ENTRY)
+}
+
+/// Targets frame `n` is built for: the `dist/NAME` dirs holding a remote entry.
+pub fn built_targets(n: usize) -> Vec<String> {
+  let Ok(rd) = std::fs::read_dir(frame_dir(n).join("dist")) else {
+    return Vec::new();
+  };
+  let mut found: Vec<String> = rd
+    .flatten()
+    .filter(|e| e.path().join(ENTRY).is_file())
+    .map(|e| e.file_name().to_string_lossy().into_owned())
+    .collect();
+  found.sort_unstable();
+  found
+}
+
+/// The dist is current if its remote entry is newer than the config and
+/// sources. package.json is left out: version bumps touch it.
+pub fn dist_current(n: usize) -> bool {
+  current_in(n, &dist_dir(n))
+}
+
+/// [`dist_current`] for the build output in `dist`.
+pub fn current_in(n: usize, dist: &Path) -> bool {
+  let dir = frame_dir(n);
+  let Some(built) = newest_mtime(&dist.join(ENTRY)) else {
+    return false;
+  };
+  [dir.join("rsbuild.config.mjs"), dir.join("src")]
+    .iter()
+    .filter_map(|p| newest_mtime(p))
+    .all(|t| t <= built)
+}
+
+/// `frame-0042` for log lines, or a `--filter-expr` task's expression.
+pub fn label(n: usize) -> String {
+  match filterexpr::get(n) {
+    Some(expr) => expr.to_string(),
+    None => format!("frame-{n:04}"),
+  }
+}
+
+/// Render frame numbers as `1-3,5` for log lines, `--filter-expr` tasks by
+/// their expression after the frames.
+pub fn fmt_ranges(frames: &[usize]) -> String {
+  let (tasks, frames): (Vec<usize>, Vec<usize>) = frames.iter().copied().partition(|&n| filterexpr::get(n).is_some());
+  ranges(&frames)
+    .iter()
+    .map(|&(a, b)| if a == b { format!("{a}") } else { format!("{a}-{b}") })
+    .chain(tasks.into_iter().map(label))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Directory holding the extracted `frameNNNN.png` source images.
+pub fn sources_dir() -> PathBuf {
+  PathBuf::from("frames")
+}
+
+/// Source image for frame `n`, accepting the same names as the generator.
+pub fn source_image(dir: &Path, n: usize) -> Option<PathBuf> {
+  [format!("frame{n}.png"), format!("frame{n:04}.png"), format!("frame-{n:04}.png")]
+    .into_iter()
+    .map(|f| dir.join(f))
+    .find(|p| p.is_file())
+}
+
+/// Sorted frame numbers of `frame*.png` files in `dir`.
+pub fn discover_sources(dir: &Path) -> Vec<usize> {
+  let mut found = Vec::new();
+  let Ok(rd) = std::fs::read_dir(dir) else {
+    return found;
+  };
+  for ent in rd.flatten() {
+    let name = ent.file_name();
+    let name = name.to_string_lossy().to_ascii_lowercase();
+    let Some(rest) = name.strip_prefix("frame").and_then(|r| r.strip_suffix(".png")) else {
+      continue;
+    };
+    let rest = rest.trim_start_matches(['-', '_']);
+    if let Ok(n) = rest.parse::<usize>() {
+      found.push(n);
+    }
+  }
+  found.sort_unstable();
+  found.dedup();
+  found
+}
@@ -0,0 +1,166 @@
+//! `gc`: reclaim disk from frame dists, bundler caches and failure logs
+//! that are old or no longer match their frame.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::exec;
+use crate::frames;
+use crate::{parse_bool, parse_kv};
+
+/// `7d`, `12h`, `30m`, `90s`.
+fn parse_age(s: &str) -> Option<Duration> {
+  let (num, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit())?);
+  let n: u64 = num.parse().ok()?;
+  let secs = match unit {
+    "s" => 1,
+    "m" => 60,
+    "h" => 3600,
+    "d" => 86400,
+    "w" => 7 * 86400,
+    _ => return None,
+  };
+  Some(Duration::from_secs(n * secs))
+}
+
+pub fn size(path: &Path) -> u64 {
+  let Ok(meta) = path.symlink_metadata() else {
+    return 0;
+  };
+  if !meta.is_dir() {
+    return meta.len();
+  }
+  std::fs::read_dir(path)
+    .map(|rd| rd.flatten().map(|e| size(&e.path())).sum())
+    .unwrap_or(0)
+}
+
+pub fn fmt_bytes(b: u64) -> String {
+  const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+  let mut v = b as f64;
+  let mut unit = 0;
+  while v >= 1024.0 && unit + 1 < UNITS.len() {
+    v /= 1024.0;
+    unit += 1;
+  }
+  if unit == 0 {
+    format!("{b}B")
+  } else {
+    format!("{v:.1}{}", UNITS[unit])
+  }
+}
+
+#[derive(Default)]
+struct Tally {
+  count: usize,
+  bytes: u64,
+}
+
+pub fn run(args: &[String]) {
+  let age = match parse_kv(args, "--older-than") {
+    Some(v) => parse_age(&v).unwrap_or_else(|| {
+      eprintln!("gc: bad --older-than {v:?} (e.g. 7d, 12h)");
+      std::process::exit(2);
+    }),
+    None => Duration::from_secs(7 * 86400),
+  };
+  let dry_run: bool = parse_kv(args, "--dry-run")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(false);
+  let cutoff = SystemTime::now().checked_sub(age).unwrap_or(SystemTime::UNIX_EPOCH);
+  let old = |p: &Path| frames::newest_mtime(p).is_none_or(|t| t < cutoff);
+
+  let mut failed = 0usize;
+  let mut remove = |p: &Path, tally: &mut Tally| {
+    let bytes = size(p);
+    if !dry_run {
+      let res = if p.is_dir() {
+        std::fs::remove_dir_all(p)
+      } else {
+        std::fs::remove_file(p)
+      };
+      if let Err(e) = res {
+        eprintln!("gc: {}: {e}", p.display());
+        failed += 1;
+        return;
+      }
+    }
+    tally.count += 1;
+    tally.bytes += bytes;
+  };
+
+  let present = frames::discover(&frames::frames_dir());
+  let (mut dists, mut caches, mut logs) = (Tally::default(), Tally::default(), Tally::default());
+  for &n in &present {
+    let dir = frames::frame_dir(n);
+    let dist = dir.join("dist");
+    // A dist older than its sources would be rebuilt anyway. Built per
+    // target, each `dist/NAME` is judged on its own.
+    let targets = frames::built_targets(n);
+    if targets.is_empty() && dist.is_dir() && (old(&dist) || !frames::current_in(n, &dist)) {
+      remove(&dist, &mut dists);
+    }
+    for t in &targets {
+      let dist = dist.join(t);
+      if old(&dist) || !frames::current_in(n, &dist) {
+        remove(&dist, &mut dists);
+      }
+    }
+    let cache = dir.join("node_modules").join(".cache");
+    if cache.is_dir() && old(&cache) {
+      remove(&cache, &mut caches);
+    }
+  }
+
+  let mut entries: Vec<PathBuf> = std::fs::read_dir(exec::log_dir())
+    .map(|rd| rd.flatten().map(|e| e.path()).collect())
+    .unwrap_or_default();
+  entries.sort();
+  for p in entries {
+    let name = p.file_name().map(|f| f.to_string_lossy().into_owned()).unwrap_or_default();
+    let frame = name
+      .strip_prefix("frame-")
+      .and_then(|r| r.get(..4))
+      .and_then(|d| d.parse::<usize>().ok());
+    let orphan = frame.is_some_and(|n| present.binary_search(&n).is_err());
+    if orphan || old(&p) {
+      remove(&p, &mut logs);
+    }
+  }
+
+  let verb = if dry_run { "would remove" } else { "removed" };
+  for (what, t) in [("dists", &dists), ("caches", &caches), ("logs", &logs)] {
+    if t.count > 0 {
+      eprintln!("gc: {verb} {} {what} ({})", t.count, fmt_bytes(t.bytes));
+    }
+  }
+  eprintln!(
+    "gc: {} {}",
+    if dry_run { "would reclaim" } else { "reclaimed" },
+    fmt_bytes(dists.bytes + caches.bytes + logs.bytes)
+  );
+  if failed > 0 {
+    std::process::exit(1);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn ages() {
+    assert_eq!(parse_age("90s"), Some(Duration::from_secs(90)));
+    assert_eq!(parse_age("30m"), Some(Duration::from_secs(1800)));
+    assert_eq!(parse_age("12h"), Some(Duration::from_secs(12 * 3600)));
+    assert_eq!(parse_age("7d"), Some(Duration::from_secs(7 * 86400)));
+    assert_eq!(parse_age("2w"), Some(Duration::from_secs(14 * 86400)));
+  }
+
+  #[test]
+  fn bad_ages() {
+    for s in ["", "7", "d", "7y", "1.5h", "-1d", "7 d"] {
+      assert_eq!(parse_age(s), None, "{s}");
+    }
+  }
+}
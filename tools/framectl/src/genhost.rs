@@ -0,0 +1,114 @@
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use crate::frames;
+use crate::parse_kv;
+
+const DEFAULT_OUT: &str = "apps/host/src/frames.generated.ts";
+
+pub fn run(args: &[String]) {
+  let out = PathBuf::from(parse_kv(args, "--out").unwrap_or_else(|| DEFAULT_OUT.to_string()));
+  let found: Vec<usize> = frames::selected(args);
+  if found.is_empty() {
+    eprintln!("gen-host: no frame-XXXX dirs found under apps/frames");
+    std::process::exit(2);
+  }
+
+  let src = render(&frames::ranges(&found), found.len());
+  if out.as_os_str() == "-" {
+    print!("{src}");
+    return;
+  }
+  if let Err(e) = std::fs::write(&out, src) {
+    eprintln!("gen-host: write {}: {e}", out.display());
+    std::process::exit(1);
+  }
+  eprintln!("gen-host: wrote {} ({} frames)", out.display(), found.len());
+}
+
+fn render(ranges: &[(usize, usize)], count: usize) -> String {
+  let mut list = String::new();
+  for (a, b) in ranges {
+    let _ = writeln!(list, "  [{a}, {b}],");
+  }
+
+  format!(
+    r#"// Generated by `framectl gen-host`. Do not edit by hand.
+// Re-run after adding or removing apps/frames/frame-XXXX packages.
+
+type Container = {{
+  init: (shareScope: unknown) => Promise<void> | void;
+  get: (module: string) => Promise<() => unknown>;
+}};
+
+// Inclusive [first, last] runs of frames present in the workspace.
+export const FRAME_RANGES: ReadonlyArray<readonly [number, number]> = [
+{list}];
+
+export const FRAME_COUNT = {count};
+
+export const hasFrame = (n: number) =>
+  FRAME_RANGES.some(([first, last]) => n >= first && n <= last);
+
+export const frameId = (n: number) => String(n).padStart(4, '0');
+export const frameScope = (n: number) => `frame_${{frameId(n)}}`;
+export const frameEntryUrl = (baseUrl: string, n: number) =>
+  `${{baseUrl.replace(/\/$/, '')}}/frame-${{frameId(n)}}/{entry}`;
+
+const entries = new Map<string, Promise<void>>();
+const initialized = new Set<string>();
+
+const loadEntry = (url: string, scope: string) => {{
+  const cached = entries.get(scope);
+  if (cached) return cached;
+
+  const promise = new Promise<void>((resolve, reject) => {{
+    const script = document.createElement('script');
+    script.src = url;
+    script.type = 'text/javascript';
+    script.async = true;
+    script.onload = () => resolve();
+    script.onerror = () => {{
+      entries.delete(scope);
+      script.remove();
+      reject(new Error(`Failed to load remote entry: ${{url}}`));
+    }};
+    document.head.appendChild(script);
+  }});
+  entries.set(scope, promise);
+  return promise;
+}};
+
+const initSharing = async (scope: string, container: Container) => {{
+  if (initialized.has(scope)) return;
+  const g = globalThis as typeof globalThis & {{
+    __webpack_init_sharing__?: (scope: string) => Promise<void>;
+    __webpack_share_scopes__?: {{ default?: unknown }};
+    __rspack_init_sharing__?: (scope: string) => Promise<void>;
+    __rspack_share_scopes__?: {{ default?: unknown }};
+  }};
+  const init = g.__webpack_init_sharing__ ?? g.__rspack_init_sharing__;
+  const scopes = g.__webpack_share_scopes__ ?? g.__rspack_share_scopes__;
+  if (typeof init === 'function' && scopes?.default) {{
+    await init('default');
+    await container.init(scopes.default);
+  }}
+  initialized.add(scope);
+}};
+
+export const loadFrame = async <T,>(n: number, baseUrl: string): Promise<T> => {{
+  if (!hasFrame(n)) throw new Error(`Frame not generated: ${{frameId(n)}}`);
+  const scope = frameScope(n);
+  await loadEntry(frameEntryUrl(baseUrl, n), scope);
+
+  const container = (window as Window & Record<string, Container>)[scope];
+  if (!container) throw new Error(`Container not found: ${{scope}}`);
+  await initSharing(scope, container);
+
+  const factory = await container.get('./Frame');
+  return factory() as T;
+}};
+"#,
+    entry = frames::ENTRY
+  )
+}
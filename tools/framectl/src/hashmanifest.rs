@@ -0,0 +1,269 @@
+//! `hash-manifest`: a content hash per frame dist, the hashed upload path
+//! (`/frame-0001.<hash>/`) for each, and the routing manifest the host reads
+//! to find a frame's remote entry there. Unchanged frames keep their path, so
+//! the CDN can cache everything as immutable.
+//!
+//! With `--deployed` (the manifest currently live, as a file or URL), frames
+//! whose hash it already has are left out of the upload plan: their hashed
+//! path is on the CDN already, so a redeploy only uploads what changed.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use crate::compress::walk;
+use crate::frames::{self, ENTRY};
+use crate::json::{self, Value};
+use crate::queue::par_each;
+use crate::{default_concurrency, parse_kv, targets, template};
+
+const DEFAULT_OUT: &str = "frames-manifest.json";
+const HASH_LEN: usize = 12;
+
+/// FNV-1a over each file's dist-relative path and contents, in path order.
+/// Precompressed siblings are left out; they follow from the originals.
+fn dist_hash(dist: &Path) -> std::io::Result<String> {
+  let mut files: Vec<PathBuf> = Vec::new();
+  walk(dist, &mut files);
+  files.retain(|p| !matches!(p.extension().and_then(|e| e.to_str()), Some("br" | "gz")));
+  files.sort();
+
+  let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+  let mut feed = |bytes: &[u8]| {
+    for &b in bytes {
+      h ^= b as u64;
+      h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+  };
+  for p in &files {
+    let rel = p.strip_prefix(dist).unwrap_or(p);
+    feed(rel.to_string_lossy().as_bytes());
+    feed(&[0]);
+    feed(&std::fs::read(p)?);
+    feed(&[0]);
+  }
+  Ok(format!("{h:016x}")[..HASH_LEN].to_string())
+}
+
+/// Frame -> hash of a routing manifest at a path or an http(s) URL
+/// (fetched with curl).
+pub fn deployed_hashes(src: &str) -> Result<BTreeMap<usize, String>, String> {
+  let doc = if src.starts_with("http://") || src.starts_with("https://") {
+    let out = Command::new("curl")
+      .args(["-fsSL", src])
+      .stdin(Stdio::null())
+      .output()
+      .map_err(|e| format!("curl: {e}"))?;
+    if !out.status.success() {
+      return Err(String::from_utf8_lossy(&out.stderr).trim().to_string());
+    }
+    json::parse(&String::from_utf8_lossy(&out.stdout))?
+  } else {
+    json::read(Path::new(src))?
+  };
+  let Some(Value::Obj(routes)) = doc.get("frames") else {
+    return Err("no \"frames\" object".to_string());
+  };
+  Ok(routes
+    .iter()
+    .filter_map(|(id, route)| Some((id.parse().ok()?, route.get("hash")?.as_str()?.to_string())))
+    .collect())
+}
+
+/// Sorted `hashes` against the deployed ones: frames to upload, frames
+/// deployed as they are, and deployed frames within `start..=end` that are
+/// no longer built.
+fn diff(hashes: &[(usize, String)], deployed: &BTreeMap<usize, String>, (start, end): (usize, usize)) -> (Vec<usize>, Vec<usize>, Vec<usize>) {
+  let mut upload = Vec::new();
+  let mut skipped = Vec::new();
+  for (n, h) in hashes {
+    if deployed.get(n) == Some(h) {
+      skipped.push(*n);
+    } else {
+      upload.push(*n);
+    }
+  }
+  let gone: Vec<usize> = deployed
+    .keys()
+    .copied()
+    .filter(|n| (start..=end).contains(n) && hashes.binary_search_by_key(n, |(f, _)| *f).is_err())
+    .collect();
+  (upload, skipped, gone)
+}
+
+pub fn run(args: &[String]) {
+  let out = parse_kv(args, "--out").unwrap_or_else(|| DEFAULT_OUT.to_string());
+  let base_url = parse_kv(args, "--base-url").unwrap_or_default();
+  let base_url = base_url.trim_end_matches('/');
+  let concurrency: usize = parse_kv(args, "--concurrency")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or_else(default_concurrency);
+  let (start, end) = frames::range_args(args);
+  let deployed = parse_kv(args, "--deployed").map(|src| {
+    deployed_hashes(&src).unwrap_or_else(|e| {
+      eprintln!("hash-manifest: --deployed={src}: {e}");
+      std::process::exit(1);
+    })
+  });
+
+  targets::dists("hash-manifest", args);
+  let found: Vec<usize> = frames::selected(args);
+  if found.is_empty() {
+    eprintln!("hash-manifest: no frame-XXXX dirs found under apps/frames");
+    std::process::exit(2);
+  }
+  let missing: Vec<usize> = found
+    .iter()
+    .copied()
+    .filter(|&n| !frames::entry_path(n).is_file())
+    .collect();
+  if !missing.is_empty() {
+    eprintln!(
+      "hash-manifest: {} frame(s) have no built dist: {} (build them first)",
+      missing.len(),
+      frames::fmt_ranges(&missing)
+    );
+    std::process::exit(1);
+  }
+  let stale: Vec<usize> = found.iter().copied().filter(|&n| !frames::dist_current(n)).collect();
+  if !stale.is_empty() {
+    eprintln!(
+      "hash-manifest: warning: {} dist(s) older than their sources: {}",
+      stale.len(),
+      frames::fmt_ranges(&stale)
+    );
+  }
+  // Chunks load from assetPrefix; anything but `auto` still points at the
+  // unhashed path.
+  let fixed: Vec<usize> = found
+    .iter()
+    .copied()
+    .filter(|&n| {
+      let cfg = std::fs::read_to_string(frames::frame_dir(n).join("rsbuild.config.mjs")).unwrap_or_default();
+      template::extract(&cfg, "assetPrefix").as_deref() != Some("auto")
+    })
+    .collect();
+  if !fixed.is_empty() {
+    eprintln!(
+      "hash-manifest: warning: {} frame(s) have an assetPrefix other than 'auto' and will load chunks from their unhashed path: {}",
+      fixed.len(),
+      frames::fmt_ranges(&fixed)
+    );
+  }
+
+  let hashes: Mutex<Vec<(usize, String)>> = Mutex::new(Vec::new());
+  let failed = Mutex::new(Vec::new());
+  par_each(&found, concurrency, |&n| match dist_hash(&frames::dist_dir(n)) {
+    Ok(h) => hashes.lock().unwrap().push((n, h)),
+    Err(e) => {
+      eprintln!("hash-manifest: frame-{n:04}: {e}");
+      failed.lock().unwrap().push(n);
+    }
+  });
+  if !failed.into_inner().unwrap().is_empty() {
+    std::process::exit(1);
+  }
+  let mut hashes = hashes.into_inner().unwrap();
+  hashes.sort();
+
+  let (upload, skipped, gone) = deployed.as_ref().map(|d| diff(&hashes, d, (start, end))).unwrap_or_default();
+  let mut routes = Vec::new();
+  for (n, h) in &hashes {
+    let dir = format!("/frame-{n:04}.{h}/");
+    // Upload plan on stdout: local dist, then the path to sync it to.
+    if skipped.binary_search(n).is_err() {
+      println!("{}\t{dir}", frames::dist_dir(*n).display());
+    }
+    routes.push((
+      format!("{n:04}"),
+      Value::obj([
+        ("hash", Value::from(h.as_str())),
+        ("path", Value::from(format!("{base_url}{dir}"))),
+        ("entry", Value::from(format!("{base_url}{dir}{ENTRY}"))),
+      ]),
+    ));
+  }
+  let doc = Value::obj([("frames", Value::obj(routes))]);
+  let tmp = format!("{out}.tmp");
+  if let Err(e) = std::fs::write(&tmp, doc.pretty()).and_then(|_| std::fs::rename(&tmp, &out)) {
+    eprintln!("hash-manifest: write {out}: {e}");
+    std::process::exit(1);
+  }
+  eprintln!("hash-manifest: wrote {out} ({} frames)", hashes.len());
+  if deployed.is_some() {
+    let fmt = |v: &[usize]| if v.is_empty() { "none".to_string() } else { frames::fmt_ranges(v) };
+    eprintln!("hash-manifest: upload {} changed frame(s): {}", upload.len(), fmt(&upload));
+    eprintln!("hash-manifest: skip {} frame(s) already deployed: {}", skipped.len(), fmt(&skipped));
+    if !gone.is_empty() {
+      eprintln!("hash-manifest: {} deployed frame(s) no longer in the manifest: {}", gone.len(), fmt(&gone));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn dist(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("framectl-hash-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (rel, body) in files {
+      let p = dir.join(rel);
+      std::fs::create_dir_all(p.parent().unwrap()).unwrap();
+      std::fs::write(p, body).unwrap();
+    }
+    dir
+  }
+
+  #[test]
+  fn hash_follows_paths_and_contents() {
+    let base = [(ENTRY, "entry"), ("static/js/1.js", "chunk")];
+    let dirs = [
+      dist("a", &base),
+      dist("same", &base),
+      dist("content", &[(ENTRY, "entry"), ("static/js/1.js", "chunk!")]),
+      dist("path", &[(ENTRY, "entry"), ("static/js/2.js", "chunk")]),
+      dist("zipped", &[(ENTRY, "entry"), ("static/js/1.js", "chunk"), ("static/js/1.js.br", "br"), ("static/js/1.js.gz", "gz")]),
+    ];
+    let hashes: Vec<String> = dirs.iter().map(|d| dist_hash(d).unwrap()).collect();
+    for d in &dirs {
+      let _ = std::fs::remove_dir_all(d);
+    }
+    assert_eq!(hashes[0].len(), HASH_LEN);
+    assert_eq!(hashes[1], hashes[0]);
+    assert_ne!(hashes[2], hashes[0]);
+    assert_ne!(hashes[3], hashes[0]);
+    // Precompressed siblings don't count.
+    assert_eq!(hashes[4], hashes[0]);
+  }
+
+  fn pairs(v: &[(usize, &str)]) -> Vec<(usize, String)> {
+    v.iter().map(|(n, h)| (*n, h.to_string())).collect()
+  }
+
+  #[test]
+  fn diffs_against_deployed() {
+    let built = pairs(&[(1, "aaa"), (2, "bbb"), (3, "ccc")]);
+    let deployed: BTreeMap<usize, String> = pairs(&[(1, "aaa"), (2, "old"), (4, "ddd"), (9, "zzz")]).into_iter().collect();
+    let (upload, skipped, gone) = diff(&built, &deployed, (1, 5));
+    assert_eq!(upload, [2, 3]);
+    assert_eq!(skipped, [1]);
+    // 9 is outside --start/--end, so not gone, just not looked at.
+    assert_eq!(gone, [4]);
+    assert_eq!(diff(&built, &BTreeMap::new(), (1, 5)), (vec![1, 2, 3], vec![], vec![]));
+  }
+
+  #[test]
+  fn reads_deployed_manifest() {
+    let path = std::env::temp_dir().join(format!("framectl-deployed-{}.json", std::process::id()));
+    let doc = r#"{"frames": {"0001": {"hash": "aaa", "path": "/frame-0001.aaa/"}, "0002": {"path": "/x/"}, "bad": {"hash": "b"}}}"#;
+    std::fs::write(&path, doc).unwrap();
+    let got = deployed_hashes(&path.to_string_lossy());
+    std::fs::write(&path, r#"{"routes": {}}"#).unwrap();
+    let err = deployed_hashes(&path.to_string_lossy());
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(got.unwrap(), pairs(&[(1, "aaa")]).into_iter().collect::<BTreeMap<_, _>>());
+    assert_eq!(err.unwrap_err(), "no \"frames\" object");
+  }
+}
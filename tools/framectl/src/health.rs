@@ -0,0 +1,237 @@
+//! `health`: a sample of deployed frames loaded the way the host loads them,
+//! in headless Chrome: remote entry, container init, the exposed module. A
+//! frame can answer 200 and still fail there (wrong container name, a chunk
+//! under the wrong public path, a shared-scope mismatch), which only shows
+//! at playback otherwise.
+//!
+//! Without `--base-url` the local dists are checked over the same throwaway
+//! server `visual-check` uses.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use crate::frames::{self, fmt_ranges, ENTRY};
+use crate::queue::par_each;
+use crate::visual::{self, BROWSERS};
+use crate::{parse_kv, playlist, state_dir, targets};
+
+/// Marks the harness's verdict in the dumped DOM.
+const RESULT_ID: &str = "framectl-health";
+
+enum Verdict {
+  Ok,
+  /// The remote entry didn't answer 200.
+  Http(String),
+  /// It did, but the federation runtime failed.
+  Runtime(String),
+}
+
+/// Page that loads frame `n`'s container from `entry` and imports `expose`,
+/// leaving `ok` or `error: ...` in `#framectl-health`.
+fn harness(n: usize, entry: &str, expose: &str) -> String {
+  let entry = entry.replace('\\', "\\\\").replace('\'', "\\'");
+  let expose = expose.replace('\\', "\\\\").replace('\'', "\\'");
+  format!(
+    r#"<!doctype html>
+<html><body>
+<script>
+  const done = (msg) => {{
+    if (document.getElementById('{RESULT_ID}')) return;
+    const p = document.createElement('pre');
+    p.id = '{RESULT_ID}';
+    p.textContent = msg;
+    document.body.appendChild(p);
+  }};
+  window.addEventListener('error', (e) => done('error: ' + e.message));
+  window.addEventListener('unhandledrejection', (e) => done('error: ' + (e.reason?.message ?? e.reason)));
+  const s = document.createElement('script');
+  s.src = '{entry}';
+  s.onerror = () => done('error: remoteEntry.js did not load');
+  s.onload = async () => {{
+    try {{
+      const c = window['frame_{n:04}'];
+      if (!c) throw new Error('remoteEntry.js did not define frame_{n:04}');
+      const init = globalThis.__webpack_init_sharing__ ?? globalThis.__rspack_init_sharing__;
+      const scopes = globalThis.__webpack_share_scopes__ ?? globalThis.__rspack_share_scopes__;
+      if (typeof init === 'function' && scopes?.default) {{
+        await init('default');
+        await c.init(scopes.default);
+      }}
+      const mod = (await c.get('{expose}'))();
+      if (typeof mod?.mount !== 'function') throw new Error('{expose} has no mount()');
+      done('ok');
+    }} catch (e) {{
+      done('error: ' + (e?.message ?? e));
+    }}
+  }};
+  document.head.appendChild(s);
+</script></body></html>
+"#
+  )
+}
+
+/// HTTP status of `url`, via curl.
+fn http_status(url: &str) -> Result<String, String> {
+  let out = Command::new("curl")
+    .args(["-sS", "-o", "/dev/null", "-w", "%{http_code}", "--max-time", "20", url])
+    .stdin(Stdio::null())
+    .output()
+    .map_err(|e| format!("curl: {e}"))?;
+  let code = String::from_utf8_lossy(&out.stdout).trim().to_string();
+  if !out.status.success() && code == "000" {
+    return Err(String::from_utf8_lossy(&out.stderr).trim().to_string());
+  }
+  Ok(code)
+}
+
+/// The harness verdict from Chrome's `--dump-dom` of `page`.
+fn run_harness(browser: &str, page: &Path) -> Result<(), String> {
+  let url = format!("file://{}", page.display());
+  let out = Command::new(browser)
+    .args(["--headless=new", "--disable-gpu", "--no-first-run"])
+    .arg("--virtual-time-budget=10000")
+    .arg("--dump-dom")
+    .arg(url)
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .output()
+    .map_err(|e| format!("{browser}: {e}"))?;
+  let dom = String::from_utf8_lossy(&out.stdout);
+  let marker = format!("id=\"{RESULT_ID}\">");
+  let Some(at) = dom.find(&marker) else {
+    let err = String::from_utf8_lossy(&out.stderr);
+    return Err(match err.lines().last() {
+      Some(l) if !dom.contains("<body") => l.trim().to_string(),
+      _ => "timed out before the module loaded".to_string(),
+    });
+  };
+  let rest = &dom[at + marker.len()..];
+  let msg = rest[..rest.find("</pre>").unwrap_or(rest.len())]
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&amp;", "&");
+  match msg.strip_prefix("error: ") {
+    None if msg == "ok" => Ok(()),
+    Some(e) => Err(e.to_string()),
+    None => Err(msg),
+  }
+}
+
+pub fn run(args: &[String]) {
+  let count: usize = parse_kv(args, "--sample")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(20);
+  let concurrency: usize = parse_kv(args, "--concurrency")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(4)
+    .max(1);
+  let expose = parse_kv(args, "--expose").unwrap_or_else(|| "./Frame".to_string());
+  let (start, end) = frames::range_args(args);
+  let base_url = parse_kv(args, "--base-url").map(|b| b.trim_end_matches('/').to_string());
+
+  let Some(browser) = visual::find_browser(parse_kv(args, "--browser")) else {
+    eprintln!("health: no headless Chrome found (tried {}; set --browser or CHROME)", BROWSERS.join(", "));
+    std::process::exit(2);
+  };
+
+  // Frame -> remote entry URL.
+  let entries: Vec<(usize, String)> = match (parse_kv(args, "--manifest"), &base_url) {
+    (Some(m), _) => playlist::from_manifest(Path::new(&m))
+      .unwrap_or_else(|e| {
+        eprintln!("health: {m}: {e}");
+        std::process::exit(1);
+      })
+      .into_iter()
+      .map(|(n, entry)| match &base_url {
+        Some(base) if entry.starts_with('/') => (n, format!("{base}{entry}")),
+        _ => (n, entry),
+      })
+      .collect(),
+    (None, Some(base)) => frames::discover(&frames::frames_dir())
+      .into_iter()
+      .map(|n| (n, format!("{base}/frame-{n:04}/{ENTRY}")))
+      .collect(),
+    (None, None) => {
+      targets::dists("health", args);
+      let port = visual::start_server((480, 360)).unwrap_or_else(|e| {
+        eprintln!("health: cannot listen: {e}");
+        std::process::exit(1);
+      });
+      frames::discover(&frames::frames_dir())
+        .into_iter()
+        .filter(|&n| frames::entry_path(n).is_file())
+        .map(|n| (n, format!("http://127.0.0.1:{port}/frame-{n:04}/{ENTRY}")))
+        .collect()
+    }
+  };
+  let entries: Vec<(usize, String)> = entries.into_iter().filter(|(n, _)| (start..=end).contains(n)).collect();
+  if entries.is_empty() || count == 0 {
+    eprintln!("health: no frames to check (start={start} end={end} sample={count})");
+    std::process::exit(2);
+  }
+  if entries.iter().any(|(_, e)| !e.contains("://")) {
+    eprintln!("health: the manifest has relative entries; pass --base-url to resolve them");
+    std::process::exit(2);
+  }
+  let ids: Vec<usize> = entries.iter().map(|(n, _)| *n).collect();
+  let picked = visual::sample(&ids, count);
+
+  let dir = state_dir().join("health");
+  let _ = std::fs::remove_dir_all(&dir);
+  if let Err(e) = std::fs::create_dir_all(&dir) {
+    eprintln!("health: {}: {e}", dir.display());
+    std::process::exit(1);
+  }
+  let dir = std::fs::canonicalize(&dir).unwrap_or(dir);
+  eprintln!(
+    "health: loading {} frame(s) from {} with {browser}: {} expose={expose}",
+    picked.len(),
+    base_url.as_deref().unwrap_or("the local dists"),
+    fmt_ranges(&picked)
+  );
+
+  let verdicts: Mutex<Vec<(usize, Verdict)>> = Mutex::new(Vec::new());
+  par_each(&picked, concurrency, |&n| {
+    let entry = &entries.iter().find(|(f, _)| *f == n).expect("picked from entries").1;
+    let verdict = match http_status(entry) {
+      Ok(code) if code == "200" => {
+        let page = dir.join(format!("frame-{n:04}.html"));
+        match std::fs::write(&page, harness(n, entry, &expose)) {
+          Err(e) => Verdict::Runtime(format!("harness: {e}")),
+          Ok(()) => match run_harness(&browser, &page) {
+            Ok(()) => Verdict::Ok,
+            Err(e) => Verdict::Runtime(e),
+          },
+        }
+      }
+      Ok(code) => Verdict::Http(format!("HTTP {code}")),
+      Err(e) => Verdict::Http(e),
+    };
+    match &verdict {
+      Verdict::Ok => eprintln!("frame-{n:04}: ok"),
+      Verdict::Http(e) => eprintln!("frame-{n:04}: {entry}: {e}"),
+      Verdict::Runtime(e) => eprintln!("frame-{n:04}: loads over HTTP but fails at runtime: {e}"),
+    }
+    verdicts.lock().unwrap().push((n, verdict));
+  });
+  let mut verdicts = verdicts.into_inner().unwrap();
+  verdicts.sort_by_key(|(n, _)| *n);
+
+  let pick = |f: fn(&Verdict) -> bool| -> Vec<usize> { verdicts.iter().filter(|(_, v)| f(v)).map(|(n, _)| *n).collect() };
+  let ok = pick(|v| matches!(v, Verdict::Ok));
+  let http = pick(|v| matches!(v, Verdict::Http(_)));
+  let runtime = pick(|v| matches!(v, Verdict::Runtime(_)));
+  eprintln!("health: {}/{} frame(s) load", ok.len(), picked.len());
+  if !http.is_empty() {
+    eprintln!("health: unreachable: {}", fmt_ranges(&http));
+  }
+  if !runtime.is_empty() {
+    eprintln!("health: fail at federation runtime: {}", fmt_ranges(&runtime));
+  }
+  if !http.is_empty() || !runtime.is_empty() {
+    std::process::exit(1);
+  }
+}
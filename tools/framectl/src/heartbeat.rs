@@ -0,0 +1,86 @@
+//! `--heartbeat-file`: a small JSON status snapshot rewritten every few
+//! seconds, for dashboards and watchdogs that shouldn't parse stderr.
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::eta::Eta;
+use crate::json::Value;
+
+pub const EVERY: Duration = Duration::from_secs(2);
+
+pub struct Snapshot<'a> {
+  pub state: &'a str,
+  pub total: usize,
+  pub done: usize,
+  pub ok: usize,
+  pub elapsed: Duration,
+  pub eta: Option<Eta>,
+  pub in_flight: Vec<usize>,
+}
+
+impl Snapshot<'_> {
+  /// Progress fields, `state` first; also what `--web` streams.
+  pub fn fields(&self) -> Vec<(&'static str, Value)> {
+    let secs = |d: Duration| Value::from(d.as_secs_f64());
+    let eta = |f: fn(&Eta) -> Duration| self.eta.as_ref().map(|e| secs(f(e))).unwrap_or(Value::Null);
+    vec![
+      ("state", Value::from(self.state)),
+      ("elapsed_secs", secs(self.elapsed)),
+      ("total", Value::from(self.total)),
+      ("done", Value::from(self.done)),
+      ("ok", Value::from(self.ok)),
+      ("failed", Value::from(self.done.saturating_sub(self.ok))),
+      ("rate", Value::from(self.done as f64 / self.elapsed.as_secs_f64().max(0.0001))),
+      ("eta_secs", eta(|e| e.mid)),
+      ("eta_low_secs", eta(|e| e.low)),
+      ("eta_high_secs", eta(|e| e.high)),
+      ("in_flight", Value::Arr(self.in_flight.iter().map(|&n| Value::from(n)).collect())),
+    ]
+  }
+}
+
+pub struct Heartbeat {
+  path: PathBuf,
+  last: Option<Instant>,
+  started: u64,
+}
+
+fn unix_now() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or(0)
+}
+
+impl Heartbeat {
+  pub fn new(path: impl Into<PathBuf>) -> Self {
+    Heartbeat {
+      path: path.into(),
+      last: None,
+      started: unix_now(),
+    }
+  }
+
+  pub fn due(&self) -> bool {
+    self.last.is_none_or(|t| t.elapsed() >= EVERY)
+  }
+
+  /// Replace the file atomically; failures only warn.
+  pub fn write(&mut self, s: &Snapshot) {
+    self.last = Some(Instant::now());
+    let mut fields = vec![
+      ("state", Value::from(s.state)),
+      ("pid", Value::from(std::process::id() as usize)),
+      ("started_at", Value::from(self.started as f64)),
+      ("updated_at", Value::from(unix_now() as f64)),
+    ];
+    fields.extend(s.fields().into_iter().skip(1));
+    let doc = Value::obj(fields);
+    let tmp = self.path.with_extension("tmp");
+    let res = std::fs::write(&tmp, doc.pretty()).and_then(|_| std::fs::rename(&tmp, &self.path));
+    if let Err(e) = res {
+      eprintln!("warning: could not write heartbeat {}: {e}", self.path.display());
+    }
+  }
+}
@@ -0,0 +1,110 @@
+//! Per-frame build durations persisted across runs in `.framectl/history.tsv`
+//! (`<frame>\t<millis>` per line, last successful build wins). Test runs keep
+//! theirs in `history-test.tsv`, and each pipeline stage in its own file.
+//! `--filter-expr` tasks are keyed `filter:EXPR`, as their numbers change
+//! from run to run.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::exec::Script;
+use crate::{filterexpr, state_dir};
+
+const TASK: &str = "filter:";
+
+fn path(script: Script) -> PathBuf {
+  match script {
+    Script::Build => state_dir().join("history.tsv"),
+    other => state_dir().join(format!("history-{}.tsv", other.name())),
+  }
+}
+
+/// Every line as key and duration, tasks' keys included.
+fn load_keys(script: Script) -> BTreeMap<String, Duration> {
+  let mut out = BTreeMap::new();
+  let Ok(text) = std::fs::read_to_string(path(script)) else {
+    return out;
+  };
+  for line in text.lines() {
+    let mut parts = line.split('\t');
+    let (Some(key), Some(ms)) = (parts.next(), parts.next()) else {
+      continue;
+    };
+    if let Ok(ms) = ms.parse::<u64>() {
+      out.insert(key.to_string(), Duration::from_millis(ms));
+    }
+  }
+  out
+}
+
+/// Durations by frame, with the tasks of this run's `--filter-expr`.
+pub fn load(script: Script) -> BTreeMap<usize, Duration> {
+  load_keys(script)
+    .into_iter()
+    .filter_map(|(k, d)| {
+      let n = match k.strip_prefix(TASK) {
+        Some(expr) => filterexpr::find(expr)?,
+        None => k.parse().ok()?,
+      };
+      Some((n, d))
+    })
+    .collect()
+}
+
+/// Whole-pipeline durations: the sum over `stages`, for frames every stage
+/// has a duration for.
+pub fn load_stages(stages: &[Script]) -> BTreeMap<usize, Duration> {
+  let mut maps = stages.iter().map(|&s| load(s));
+  let Some(mut out) = maps.next() else {
+    return BTreeMap::new();
+  };
+  for m in maps {
+    out.retain(|n, _| m.contains_key(n));
+    for (n, d) in out.iter_mut() {
+      *d += m[n];
+    }
+  }
+  out
+}
+
+/// Merge `fresh` into the stored history and write it back.
+pub fn save(script: Script, fresh: &BTreeMap<usize, Duration>) -> std::io::Result<()> {
+  if fresh.is_empty() {
+    return Ok(());
+  }
+  let mut frames: BTreeMap<usize, Duration> = BTreeMap::new();
+  let mut tasks: BTreeMap<String, Duration> = BTreeMap::new();
+  for (k, d) in load_keys(script) {
+    match k.parse() {
+      Ok(n) => {
+        frames.insert(n, d);
+      }
+      Err(_) => {
+        tasks.insert(k, d);
+      }
+    }
+  }
+  for (&n, &d) in fresh {
+    match filterexpr::get(n) {
+      Some(expr) => {
+        tasks.insert(format!("{TASK}{expr}"), d);
+      }
+      None => {
+        frames.insert(n, d);
+      }
+    }
+  }
+
+  let mut text = String::new();
+  for (n, d) in &frames {
+    text.push_str(&format!("{n}\t{}\n", d.as_millis()));
+  }
+  for (k, d) in &tasks {
+    text.push_str(&format!("{k}\t{}\n", d.as_millis()));
+  }
+  std::fs::create_dir_all(state_dir())?;
+  let tmp = path(script).with_extension("tsv.tmp");
+  std::fs::write(&tmp, text)?;
+  std::fs::rename(tmp, path(script))
+}
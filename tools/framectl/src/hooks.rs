@@ -0,0 +1,102 @@
+//! `--pre-hook` / `--post-hook`: shell commands run around each local
+//! frame build, e.g. to stamp build metadata or to upload a frame as soon as
+//! it is built.
+//!
+//! Both see `FRAME_INDEX`, `FRAME_PKG` and `FRAME_DIR`; the post hook also
+//! gets `FRAME_STATUS` (`ok` or `failed`). A failing pre hook fails the
+//! frame without building it; a failing post hook fails a frame that built.
+
+use std::process::{Command, Stdio};
+
+use crate::exec::{self, Outcome, Script, StageTime};
+use crate::frames::{self, frame_pkg};
+use crate::parse_kv;
+
+pub struct Hooks {
+  pre: Option<String>,
+  post: Option<String>,
+  silent: bool,
+}
+
+impl Hooks {
+  pub fn from_args(args: &[String], silent: bool) -> Hooks {
+    Hooks {
+      pre: parse_kv(args, "--pre-hook").filter(|c| !c.trim().is_empty()),
+      post: parse_kv(args, "--post-hook").filter(|c| !c.trim().is_empty()),
+      silent,
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.pre.is_none() && self.post.is_none()
+  }
+
+  /// Run `build` for frame `n` between the hooks. Skipped on dry runs.
+  pub fn around(
+    &self,
+    n: usize,
+    script: Script,
+    dry_run: bool,
+    build: impl FnOnce() -> (Outcome, Vec<StageTime>),
+  ) -> (Outcome, Vec<StageTime>) {
+    if dry_run {
+      return build();
+    }
+    if let Some(cmd) = &self.pre {
+      if let Err(e) = self.run(cmd, n, None) {
+        return (failed(n, script, format!("pre-hook failed: {e}")), Vec::new());
+      }
+    }
+    let (mut out, stages) = build();
+    if let Some(cmd) = &self.post {
+      match self.run(cmd, n, Some(out.ok)) {
+        Err(e) if out.ok => {
+          let usage = out.usage;
+          out = failed(n, script, format!("post-hook failed: {e}"));
+          out.usage = usage;
+        }
+        Err(e) => eprintln!("warning: {}: post-hook failed: {e}", frames::label(n)),
+        Ok(()) => {}
+      }
+    }
+    (out, stages)
+  }
+
+  fn run(&self, cmd: &str, n: usize, ok: Option<bool>) -> Result<(), String> {
+    let mut c = if cfg!(windows) {
+      let mut c = Command::new("cmd");
+      c.arg("/C").arg(cmd);
+      c
+    } else {
+      let mut c = Command::new("sh");
+      c.arg("-c").arg(cmd);
+      c
+    };
+    c.env("FRAME_INDEX", n.to_string())
+      .env("FRAME_PKG", frame_pkg(n))
+      .env("FRAME_DIR", frames::frame_dir(n));
+    if let Some(ok) = ok {
+      c.env("FRAME_STATUS", if ok { "ok" } else { "failed" });
+    }
+    let out = c
+      .stdin(Stdio::null())
+      .stdout(if self.silent { Stdio::null() } else { Stdio::inherit() })
+      .stderr(Stdio::piped())
+      .output()
+      .map_err(|e| format!("spawn failed: {e}"))?;
+    if out.status.success() {
+      return Ok(());
+    }
+    let err = exec::tail(&String::from_utf8_lossy(&out.stderr), 1500);
+    Err(format!("`{cmd}` exited with {}\n{}", out.status, err.trim_end()))
+  }
+}
+
+fn failed(n: usize, script: Script, err_tail: String) -> Outcome {
+  exec::write_log(n, script, &err_tail);
+  Outcome {
+    ok: false,
+    err_tail,
+    usage: None,
+  }
+}
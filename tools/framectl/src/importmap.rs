@@ -0,0 +1,138 @@
+//! `importmap`: where the host finds each frame's remote, as an MF runtime
+//! `remotes` list or an import map. With `--chunk` the list is split into
+//! files of N frames plus an index, so the host fetches only the chunk
+//! around the playhead instead of bundling thousands of entries.
+
+use std::path::{Path, PathBuf};
+
+use crate::frames::{self, ENTRY};
+use crate::json::Value;
+use crate::playlist::from_manifest;
+use crate::parse_kv;
+
+
+#[derive(Clone, Copy)]
+enum Format {
+  /// `{"remotes": [{"name", "entry"}]}`, for `init({ remotes })`.
+  Mf,
+  /// `{"imports": {"frame_NNNN": entry}}`.
+  ImportMap,
+}
+
+fn render(format: Format, remotes: &[(usize, String)]) -> Value {
+  let scope = |n: usize| format!("frame_{n:04}");
+  match format {
+    Format::Mf => Value::obj([(
+      "remotes",
+      Value::Arr(
+        remotes
+          .iter()
+          .map(|(n, entry)| Value::obj([("name", Value::from(scope(*n))), ("entry", Value::from(entry.as_str()))]))
+          .collect(),
+      ),
+    )]),
+    Format::ImportMap => Value::obj([(
+      "imports",
+      Value::obj(remotes.iter().map(|(n, entry)| (scope(*n), Value::from(entry.as_str())))),
+    )]),
+  }
+}
+
+fn write(path: &Path, doc: &Value) {
+  let tmp = path.with_extension("json.tmp");
+  if let Err(e) = std::fs::write(&tmp, doc.pretty()).and_then(|_| std::fs::rename(&tmp, path)) {
+    eprintln!("importmap: write {}: {e}", path.display());
+    std::process::exit(1);
+  }
+}
+
+pub fn run(args: &[String]) {
+  let format = match parse_kv(args, "--format").as_deref() {
+    None | Some("mf") => Format::Mf,
+    Some("importmap") => Format::ImportMap,
+    Some(other) => {
+      eprintln!("invalid --format={other} (expected mf or importmap)");
+      std::process::exit(2);
+    }
+  };
+  let chunk: Option<usize> = parse_kv(args, "--chunk").map(|v| {
+    v.parse().ok().filter(|c| *c > 0).unwrap_or_else(|| {
+      eprintln!("invalid --chunk={v} (expected frames per chunk)");
+      std::process::exit(2);
+    })
+  });
+  let base_url = parse_kv(args, "--base-url");
+  let (start, end) = frames::range_args(args);
+
+  let remotes: Vec<(usize, String)> = match (parse_kv(args, "--manifest"), base_url) {
+    (Some(m), _) => from_manifest(Path::new(&m)).unwrap_or_else(|e| {
+      eprintln!("importmap: {m}: {e}");
+      std::process::exit(1);
+    }),
+    (None, Some(base)) => {
+      let base = base.trim_end_matches('/').to_string();
+      frames::discover(&frames::frames_dir())
+        .into_iter()
+        .map(|n| (n, format!("{base}/frame-{n:04}/{ENTRY}")))
+        .collect()
+    }
+    (None, None) => {
+      eprintln!("importmap: needs --base-url=URL (or --manifest=frames-manifest.json)");
+      std::process::exit(2);
+    }
+  };
+  let remotes: Vec<(usize, String)> = remotes.into_iter().filter(|(n, _)| (start..=end).contains(n)).collect();
+  if remotes.is_empty() {
+    eprintln!("importmap: no frames in range (start={start} end={end})");
+    std::process::exit(1);
+  }
+
+  let Some(size) = chunk else {
+    let out = parse_kv(args, "--out").unwrap_or_else(|| "remotes.json".to_string());
+    let doc = render(format, &remotes);
+    if out == "-" {
+      print!("{}", doc.pretty());
+      return;
+    }
+    write(Path::new(&out), &doc);
+    eprintln!("importmap: wrote {out} ({} frames)", remotes.len());
+    return;
+  };
+
+  // Chunk k holds frames k*size+1..=(k+1)*size, so the host finds a frame's
+  // chunk by arithmetic even across gaps.
+  let dir = PathBuf::from(parse_kv(args, "--out").unwrap_or_else(|| "remotes".to_string()));
+  if let Err(e) = std::fs::create_dir_all(&dir) {
+    eprintln!("importmap: create {}: {e}", dir.display());
+    std::process::exit(1);
+  }
+  let mut index = Vec::new();
+  let mut rest = remotes.as_slice();
+  while let Some((first, _)) = rest.first() {
+    let k = (first - 1) / size;
+    let (lo, hi) = (k * size + 1, (k + 1) * size);
+    let len = rest.iter().take_while(|(n, _)| *n <= hi).count();
+    let (part, tail) = rest.split_at(len);
+    let file = format!("remotes-{lo:04}-{hi:04}.json");
+    write(&dir.join(&file), &render(format, part));
+    index.push(Value::obj([
+      ("first", Value::from(lo)),
+      ("last", Value::from(hi)),
+      ("count", Value::from(part.len())),
+      ("url", Value::from(file)),
+    ]));
+    rest = tail;
+  }
+  let chunks = index.len();
+  let doc = Value::obj([
+    ("chunkSize", Value::from(size)),
+    ("frameCount", Value::from(remotes.len())),
+    ("chunks", Value::Arr(index)),
+  ]);
+  write(&dir.join("index.json"), &doc);
+  eprintln!(
+    "importmap: wrote {} ({} frames in {chunks} chunk(s) of up to {size})",
+    dir.join("index.json").display(),
+    remotes.len()
+  );
+}
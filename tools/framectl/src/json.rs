@@ -0,0 +1,354 @@
+//! Just enough JSON for the files framectl reads and writes.
+
+use std::fmt::Write as _;
+
+#[derive(PartialEq)]
+pub enum Value {
+  Null,
+  Bool(bool),
+  Num(f64),
+  Str(String),
+  Arr(Vec<Value>),
+  Obj(Vec<(String, Value)>),
+}
+
+impl Value {
+  pub fn obj<K: Into<String>>(fields: impl IntoIterator<Item = (K, Value)>) -> Value {
+    Value::Obj(fields.into_iter().map(|(k, v)| (k.into(), v)).collect())
+  }
+
+  pub fn get(&self, key: &str) -> Option<&Value> {
+    match self {
+      Value::Obj(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+      _ => None,
+    }
+  }
+
+  pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+    match self {
+      Value::Obj(fields) => fields.iter_mut().find(|(k, _)| k == key).map(|(_, v)| v),
+      _ => None,
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Value::Str(s) => Some(s),
+      _ => None,
+    }
+  }
+
+  pub fn pretty(&self) -> String {
+    let mut out = String::new();
+    self.write(&mut out, 0);
+    out.push('\n');
+    out
+  }
+
+  /// Single line, for `$GITHUB_OUTPUT` and the like.
+  pub fn compact(&self) -> String {
+    let mut out = String::new();
+    self.write_compact(&mut out);
+    out
+  }
+
+  fn write_compact(&self, out: &mut String) {
+    match self {
+      Value::Arr(items) => {
+        out.push('[');
+        for (i, v) in items.iter().enumerate() {
+          if i > 0 {
+            out.push(',');
+          }
+          v.write_compact(out);
+        }
+        out.push(']');
+      }
+      Value::Obj(fields) => {
+        out.push('{');
+        for (i, (k, v)) in fields.iter().enumerate() {
+          if i > 0 {
+            out.push(',');
+          }
+          quote(out, k);
+          out.push(':');
+          v.write_compact(out);
+        }
+        out.push('}');
+      }
+      scalar => scalar.write(out, 0),
+    }
+  }
+
+  fn write(&self, out: &mut String, indent: usize) {
+    match self {
+      Value::Null => out.push_str("null"),
+      Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+      Value::Num(n) => {
+        if n.is_finite() && n.fract() == 0.0 && n.abs() < 1e15 {
+          let _ = write!(out, "{}", *n as i64);
+        } else if n.is_finite() {
+          let _ = write!(out, "{n}");
+        } else {
+          out.push_str("null");
+        }
+      }
+      Value::Str(s) => quote(out, s),
+      Value::Arr(items) => {
+        // Arrays of scalars stay on one line; frame lists get long.
+        if items.iter().all(|v| !matches!(v, Value::Arr(_) | Value::Obj(_))) {
+          out.push('[');
+          for (i, v) in items.iter().enumerate() {
+            if i > 0 {
+              out.push_str(", ");
+            }
+            v.write(out, indent);
+          }
+          out.push(']');
+          return;
+        }
+        out.push_str("[\n");
+        for (i, v) in items.iter().enumerate() {
+          pad(out, indent + 1);
+          v.write(out, indent + 1);
+          out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+        }
+        pad(out, indent);
+        out.push(']');
+      }
+      Value::Obj(fields) => {
+        if fields.is_empty() {
+          out.push_str("{}");
+          return;
+        }
+        out.push_str("{\n");
+        for (i, (k, v)) in fields.iter().enumerate() {
+          pad(out, indent + 1);
+          quote(out, k);
+          out.push_str(": ");
+          v.write(out, indent + 1);
+          out.push_str(if i + 1 < fields.len() { ",\n" } else { "\n" });
+        }
+        pad(out, indent);
+        out.push('}');
+      }
+    }
+  }
+}
+
+impl From<&str> for Value {
+  fn from(s: &str) -> Value {
+    Value::Str(s.to_string())
+  }
+}
+
+impl From<String> for Value {
+  fn from(s: String) -> Value {
+    Value::Str(s)
+  }
+}
+
+impl From<usize> for Value {
+  fn from(n: usize) -> Value {
+    Value::Num(n as f64)
+  }
+}
+
+impl From<f64> for Value {
+  fn from(n: f64) -> Value {
+    Value::Num(n)
+  }
+}
+
+impl From<bool> for Value {
+  fn from(b: bool) -> Value {
+    Value::Bool(b)
+  }
+}
+
+fn pad(out: &mut String, indent: usize) {
+  for _ in 0..indent {
+    out.push_str("  ");
+  }
+}
+
+fn quote(out: &mut String, s: &str) {
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => {
+        let _ = write!(out, "\\u{:04x}", c as u32);
+      }
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+}
+
+pub fn parse(text: &str) -> Result<Value, String> {
+  let mut p = Parser {
+    s: text.as_bytes(),
+    i: 0,
+  };
+  let v = p.value()?;
+  p.ws();
+  if p.i != p.s.len() {
+    return Err(p.err("trailing characters"));
+  }
+  Ok(v)
+}
+
+pub fn read(path: &std::path::Path) -> Result<Value, String> {
+  let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+  parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+struct Parser<'a> {
+  s: &'a [u8],
+  i: usize,
+}
+
+impl Parser<'_> {
+  fn err(&self, msg: &str) -> String {
+    format!("{msg} at byte {}", self.i)
+  }
+
+  fn ws(&mut self) {
+    while self.i < self.s.len() && self.s[self.i].is_ascii_whitespace() {
+      self.i += 1;
+    }
+  }
+
+  fn eat(&mut self, lit: &str) -> bool {
+    if self.s[self.i..].starts_with(lit.as_bytes()) {
+      self.i += lit.len();
+      true
+    } else {
+      false
+    }
+  }
+
+  fn value(&mut self) -> Result<Value, String> {
+    self.ws();
+    match self.s.get(self.i) {
+      None => Err(self.err("unexpected end")),
+      Some(b'{') => {
+        self.i += 1;
+        let mut fields = Vec::new();
+        self.ws();
+        if self.eat("}") {
+          return Ok(Value::Obj(fields));
+        }
+        loop {
+          self.ws();
+          let k = self.string()?;
+          self.ws();
+          if !self.eat(":") {
+            return Err(self.err("expected ':'"));
+          }
+          fields.push((k, self.value()?));
+          self.ws();
+          if self.eat(",") {
+            continue;
+          }
+          if self.eat("}") {
+            return Ok(Value::Obj(fields));
+          }
+          return Err(self.err("expected ',' or '}'"));
+        }
+      }
+      Some(b'[') => {
+        self.i += 1;
+        let mut items = Vec::new();
+        self.ws();
+        if self.eat("]") {
+          return Ok(Value::Arr(items));
+        }
+        loop {
+          items.push(self.value()?);
+          self.ws();
+          if self.eat(",") {
+            continue;
+          }
+          if self.eat("]") {
+            return Ok(Value::Arr(items));
+          }
+          return Err(self.err("expected ',' or ']'"));
+        }
+      }
+      Some(b'"') => Ok(Value::Str(self.string()?)),
+      Some(b't') if self.eat("true") => Ok(Value::Bool(true)),
+      Some(b'f') if self.eat("false") => Ok(Value::Bool(false)),
+      Some(b'n') if self.eat("null") => Ok(Value::Null),
+      Some(_) => {
+        let start = self.i;
+        while self.i < self.s.len() && matches!(self.s[self.i], b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') {
+          self.i += 1;
+        }
+        std::str::from_utf8(&self.s[start..self.i])
+          .ok()
+          .and_then(|t| t.parse::<f64>().ok())
+          .map(Value::Num)
+          .ok_or_else(|| self.err("invalid value"))
+      }
+    }
+  }
+
+  fn string(&mut self) -> Result<String, String> {
+    if !self.eat("\"") {
+      return Err(self.err("expected string"));
+    }
+    let mut out: Vec<u8> = Vec::new();
+    loop {
+      let Some(&c) = self.s.get(self.i) else {
+        return Err(self.err("unterminated string"));
+      };
+      self.i += 1;
+      match c {
+        b'"' => break,
+        b'\\' => {
+          let Some(&e) = self.s.get(self.i) else {
+            return Err(self.err("unterminated escape"));
+          };
+          self.i += 1;
+          match e {
+            b'n' => out.push(b'\n'),
+            b't' => out.push(b'\t'),
+            b'r' => out.push(b'\r'),
+            b'b' => out.push(8),
+            b'f' => out.push(12),
+            b'u' => {
+              let mut cp = self.hex4()?;
+              if (0xd800..0xdc00).contains(&cp) && self.eat("\\u") {
+                let lo = self.hex4()?;
+                cp = 0x10000 + ((cp - 0xd800) << 10) + (lo.wrapping_sub(0xdc00) & 0x3ff);
+              }
+              let ch = char::from_u32(cp).unwrap_or('\u{fffd}');
+              let mut buf = [0u8; 4];
+              out.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+            }
+            other => out.push(other),
+          }
+        }
+        c => out.push(c),
+      }
+    }
+    String::from_utf8(out).map_err(|_| self.err("invalid utf-8"))
+  }
+
+  fn hex4(&mut self) -> Result<u32, String> {
+    let h = self
+      .s
+      .get(self.i..self.i + 4)
+      .and_then(|b| std::str::from_utf8(b).ok())
+      .and_then(|t| u32::from_str_radix(t, 16).ok())
+      .ok_or_else(|| self.err("bad \\u escape"))?;
+    self.i += 4;
+    Ok(h)
+  }
+}
@@ -0,0 +1,85 @@
+//! `--junit-out`: the run as a JUnit XML test suite, one test case per
+//! frame, so CI UIs list failed frames without parsing framectl's output.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::exec::{self, Script};
+use crate::frames::frame_pkg;
+use crate::summary::{FrameRecord, Status, Totals};
+
+/// Log bytes included with each failure.
+const LOG_TAIL: usize = 4000;
+
+fn escape(s: &str) -> String {
+  let mut out = String::with_capacity(s.len());
+  for c in s.chars() {
+    match c {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '"' => out.push_str("&quot;"),
+      // Not allowed in XML 1.0 at all.
+      c if (c as u32) < 0x20 && !matches!(c, '\n' | '\r' | '\t') => {}
+      c => out.push(c),
+    }
+  }
+  out
+}
+
+fn render(records: &BTreeMap<usize, FrameRecord>, totals: &Totals, script: Script) -> String {
+  let count = |s: Status| records.values().filter(|r| r.status == s).count();
+  let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  let _ = writeln!(
+    out,
+    "<testsuites>\n  <testsuite name=\"framectl {}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" time=\"{:.3}\">",
+    script.name(),
+    records.len(),
+    count(Status::Failed),
+    count(Status::Skipped),
+    totals.elapsed.as_secs_f64()
+  );
+  for (&n, r) in records {
+    let _ = write!(
+      out,
+      "    <testcase classname=\"frames\" name=\"frame-{n:04}\" time=\"{:.3}\"",
+      r.dur.as_secs_f64()
+    );
+    match r.status {
+      Status::Ok => out.push_str("/>\n"),
+      Status::Skipped => out.push_str(">\n      <skipped message=\"skipped after failing\"/>\n    </testcase>\n"),
+      Status::Failed => {
+        let class = r.error.as_deref().unwrap_or("other");
+        let stage = r.failed_stage().filter(|_| r.stages.len() > 1);
+        let mut message = format!("{} {class}", frame_pkg(n));
+        if let Some(st) = stage {
+          let _ = write!(message, " at {}", st.name());
+        }
+        if !r.failed_tests.is_empty() {
+          let _ = write!(message, ": {}", r.failed_tests.join(", "));
+        }
+        let log = std::fs::read_to_string(exec::log_path(n, stage.unwrap_or(script))).unwrap_or_default();
+        let _ = write!(
+          out,
+          ">\n      <failure type=\"{}\" message=\"{}\">{}</failure>\n    </testcase>\n",
+          escape(class),
+          escape(&message),
+          escape(&exec::tail(&log, LOG_TAIL))
+        );
+      }
+    }
+  }
+  out.push_str("  </testsuite>\n</testsuites>\n");
+  out
+}
+
+pub fn write(path: impl AsRef<Path>, records: &BTreeMap<usize, FrameRecord>, totals: &Totals, script: Script) {
+  let path = path.as_ref();
+  if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+    let _ = std::fs::create_dir_all(dir);
+  }
+  if let Err(e) = std::fs::write(path, render(records, totals, script)) {
+    eprintln!("warning: could not write JUnit report {}: {e}", path.display());
+  }
+}
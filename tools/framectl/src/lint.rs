@@ -0,0 +1,210 @@
+//! `lint`: ESLint or Biome in every frame package, in parallel, with the
+//! diagnostics merged by rule and message. Frames come from one template, so
+//! most hits repeat in every frame; each is printed once with its frames.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use crate::drift::normalize;
+use crate::frames::{self, fmt_ranges};
+use crate::json::{self, Value};
+use crate::queue::par_each;
+use crate::{default_concurrency, exec, parse_bool, parse_kv};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Linter {
+  Eslint,
+  Biome,
+}
+
+impl Linter {
+  fn name(self) -> &'static str {
+    match self {
+      Linter::Eslint => "eslint",
+      Linter::Biome => "biome",
+    }
+  }
+
+  /// A biome.json at the root or in the first frame means Biome.
+  fn detect(first: usize) -> Linter {
+    let biome = ["biome.json", "biome.jsonc"]
+      .iter()
+      .any(|f| Path::new(f).is_file() || frames::frame_dir(first).join(f).is_file());
+    if biome { Linter::Biome } else { Linter::Eslint }
+  }
+
+  fn args(self, fix: bool) -> Vec<&'static str> {
+    let mut a = match self {
+      Linter::Eslint => vec!["exec", "eslint", "--format", "json"],
+      Linter::Biome => vec!["exec", "biome", "lint", "--reporter=json"],
+    };
+    if fix {
+      a.push(if self == Linter::Eslint { "--fix" } else { "--write" });
+    }
+    a.push(".");
+    a
+  }
+}
+
+/// One diagnostic, frame number normalized out of message and file.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Hit {
+  error: bool,
+  rule: String,
+  message: String,
+  file: String,
+}
+
+fn str_at<'a>(v: &'a Value, path: &[&str]) -> Option<&'a str> {
+  let mut cur = v;
+  for key in path {
+    cur = cur.get(key)?;
+  }
+  cur.as_str()
+}
+
+fn relative(file: &str, dir: &Path) -> String {
+  let abs = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+  Path::new(file)
+    .strip_prefix(&abs)
+    .map(|p| p.display().to_string())
+    .unwrap_or_else(|_| file.to_string())
+}
+
+fn parse_hits(linter: Linter, doc: &Value, n: usize) -> Vec<Hit> {
+  let dir = frames::frame_dir(n);
+  let mut out = Vec::new();
+  match (linter, doc) {
+    (Linter::Eslint, Value::Arr(files)) => {
+      for f in files {
+        let file = relative(str_at(f, &["filePath"]).unwrap_or(""), &dir);
+        let Some(Value::Arr(messages)) = f.get("messages") else {
+          continue;
+        };
+        for m in messages {
+          out.push(Hit {
+            error: matches!(m.get("severity"), Some(Value::Num(s)) if *s >= 2.0),
+            rule: str_at(m, &["ruleId"]).unwrap_or("(parse)").to_string(),
+            message: normalize(str_at(m, &["message"]).unwrap_or(""), n),
+            file: normalize(&file, n),
+          });
+        }
+      }
+    }
+    (Linter::Biome, doc) => {
+      let Some(Value::Arr(diags)) = doc.get("diagnostics") else {
+        return out;
+      };
+      for d in diags {
+        let message = str_at(d, &["description"]).or_else(|| str_at(d, &["message"])).unwrap_or("");
+        let file = str_at(d, &["location", "path", "file"]).unwrap_or("");
+        out.push(Hit {
+          error: str_at(d, &["severity"]) == Some("error"),
+          rule: str_at(d, &["category"]).unwrap_or("(unknown)").to_string(),
+          message: normalize(message, n),
+          file: normalize(&relative(file, &dir), n),
+        });
+      }
+    }
+    _ => {}
+  }
+  out
+}
+
+/// Lint frame `n`: its hits, or why the linter produced no report.
+fn lint_frame(linter: Linter, fix: bool, n: usize) -> Result<Vec<Hit>, String> {
+  let out = Command::new("pnpm")
+    .args(linter.args(fix))
+    .current_dir(frames::frame_dir(n))
+    .stdin(Stdio::null())
+    .stderr(Stdio::piped())
+    .output()
+    .map_err(|e| format!("spawn failed: {e}"))?;
+  // Both exit non-zero when they find errors; the report decides.
+  match json::parse(String::from_utf8_lossy(&out.stdout).trim()) {
+    Ok(doc) => Ok(parse_hits(linter, &doc, n)),
+    Err(_) => {
+      let err = exec::tail(&String::from_utf8_lossy(&out.stderr), 300);
+      Err(err.lines().last().unwrap_or("no report").trim().to_string())
+    }
+  }
+}
+
+pub fn run(args: &[String]) {
+  let fix = parse_kv(args, "--fix").and_then(|v| parse_bool(&v)).unwrap_or(false);
+  let concurrency: usize = parse_kv(args, "--concurrency")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or_else(default_concurrency);
+  let found: Vec<usize> = frames::selected(args);
+  if found.is_empty() {
+    eprintln!("lint: no frame-XXXX dirs found under apps/frames");
+    std::process::exit(2);
+  }
+  let linter = match parse_kv(args, "--linter").as_deref() {
+    None => Linter::detect(found[0]),
+    Some("eslint") => Linter::Eslint,
+    Some("biome") => Linter::Biome,
+    Some(other) => {
+      eprintln!("invalid --linter={other} (expected eslint or biome)");
+      std::process::exit(2);
+    }
+  };
+  eprintln!(
+    "lint: {} over {} frame(s) concurrency={concurrency}{}",
+    linter.name(),
+    found.len(),
+    if fix { " fix=1" } else { "" }
+  );
+
+  let hits: Mutex<BTreeMap<Hit, Vec<usize>>> = Mutex::new(BTreeMap::new());
+  let broken: Mutex<Vec<(usize, String)>> = Mutex::new(Vec::new());
+  par_each(&found, concurrency, |&n| match lint_frame(linter, fix, n) {
+    Ok(list) => {
+      let mut hits = hits.lock().unwrap();
+      for h in list {
+        hits.entry(h).or_default().push(n);
+      }
+    }
+    Err(e) => broken.lock().unwrap().push((n, e)),
+  });
+  let hits = hits.into_inner().unwrap();
+  let mut broken = broken.into_inner().unwrap();
+
+  // Errors first, then the most widespread.
+  let mut merged: Vec<(Hit, Vec<usize>)> = hits.into_iter().collect();
+  for (_, frames) in &mut merged {
+    frames.sort_unstable();
+    frames.dedup();
+  }
+  merged.sort_by(|a, b| b.0.error.cmp(&a.0.error).then(b.1.len().cmp(&a.1.len())));
+  let total: usize = merged.iter().map(|(_, f)| f.len()).sum();
+  let errors = merged.iter().filter(|(h, _)| h.error).count();
+  for (h, frames) in &merged {
+    eprintln!(
+      "  {:<7} {}  {}  ({} frame(s): {}) {}",
+      if h.error { "error" } else { "warning" },
+      h.rule,
+      h.message,
+      frames.len(),
+      fmt_ranges(frames),
+      h.file
+    );
+  }
+  if !broken.is_empty() {
+    broken.sort();
+    let frames: Vec<usize> = broken.iter().map(|(n, _)| *n).collect();
+    eprintln!(
+      "lint: {} frame(s) gave no report: {} (frame-{:04}: {})",
+      frames.len(),
+      fmt_ranges(&frames),
+      broken[0].0,
+      broken[0].1
+    );
+  }
+  eprintln!("lint: {} unique diagnostic(s), {total} hit(s), {errors} unique error(s)", merged.len());
+  if errors > 0 || !broken.is_empty() {
+    std::process::exit(1);
+  }
+}
@@ -0,0 +1,73 @@
+//! Workspace lock: one build at a time per workspace. Two overlapping runs
+//! write the same `dist/` directories and `.framectl` state and leave both
+//! half-corrupted, so a second run refuses to start, or with `--wait` queues
+//! behind the first.
+//!
+//! The lock is an OS advisory lock on `.framectl/lock`, released when the
+//! process exits however it exits; the file itself only says who holds it.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, Write};
+
+use crate::exitcode::{self, Kind};
+use crate::{parse_bool, parse_kv, state_dir};
+
+/// Held for as long as the run lasts.
+pub struct Lock {
+  _file: File,
+}
+
+/// Who holds the lock, as the holder wrote it.
+fn holder(file: &mut File) -> String {
+  let mut text = String::new();
+  let _ = file.rewind().and_then(|_| file.read_to_string(&mut text));
+  let text = text.trim();
+  if text.is_empty() {
+    "unknown process".to_string()
+  } else {
+    text.to_string()
+  }
+}
+
+/// Take the workspace lock for `what` (e.g. `build`), exiting if another run
+/// holds it unless `--wait` says to block until it's released.
+pub fn acquire(args: &[String], what: &str) -> Lock {
+  let wait = parse_kv(args, "--wait").and_then(|v| parse_bool(&v)).unwrap_or(false);
+  let dir = state_dir();
+  let path = dir.join("lock");
+  let opened = std::fs::create_dir_all(&dir).and_then(|_| {
+    OpenOptions::new()
+      .read(true)
+      .write(true)
+      .create(true)
+      .truncate(false)
+      .open(&path)
+  });
+  let mut file = match opened {
+    Ok(f) => f,
+    Err(e) => {
+      exitcode::fail(Kind::Environment, format!("lock: {}: {e}", path.display()));
+    }
+  };
+
+  match file.try_lock() {
+    Ok(()) => {}
+    Err(TryLockError::WouldBlock) if wait => {
+      eprintln!("lock: workspace busy ({}); waiting", holder(&mut file));
+      if let Err(e) = file.lock() {
+        exitcode::fail(Kind::Environment, format!("lock: {}: {e}", path.display()));
+      }
+    }
+    Err(TryLockError::WouldBlock) => {
+      let who = holder(&mut file);
+      exitcode::fail(Kind::Environment, format!("another framectl run holds this workspace ({who}); use --wait=1 to queue behind it"));
+    }
+    Err(TryLockError::Error(e)) => {
+      exitcode::fail(Kind::Environment, format!("lock: {}: {e}", path.display()));
+    }
+  }
+
+  let info = format!("pid {} framectl {what}\n", std::process::id());
+  let _ = file.set_len(0).and_then(|_| file.rewind()).and_then(|_| file.write_all(info.as_bytes()));
+  Lock { _file: file }
+}
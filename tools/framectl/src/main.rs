@@ -1,23 +1,486 @@
 use std::env;
 use std::path::PathBuf;
-use std::process::{Command, Stdio};
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
-use std::time::{Duration, Instant};
-
-fn usage() -> ! {
-  eprintln!(
-    r#"framectl
+use std::time::Duration;
+
+mod annotations;
+mod assets;
+mod audit;
+mod batch;
+mod bisect;
+mod budget;
+mod build;
+mod classify;
+mod cli;
+mod completions;
+mod compress;
+mod container;
+mod control;
+mod dedupe;
+mod disk;
+mod drift;
+mod dump;
+mod dupes;
+mod eta;
+mod exec;
+mod exitcode;
+mod failbundle;
+mod federation;
+mod filterexpr;
+mod frames;
+mod gc;
+mod genhost;
+mod hashmanifest;
+mod health;
+mod heartbeat;
+mod history;
+mod hooks;
+mod importmap;
+mod json;
+mod junit;
+mod lint;
+mod lock;
+mod matrix;
+mod nodematrix;
+mod optimize;
+mod order;
+mod pack;
+mod parity;
+mod pattern;
+mod perf;
+mod playlist;
+mod png;
+mod prefetch;
+mod preflight;
+mod printconfig;
+mod priority;
+mod procinfo;
+mod profiles;
+mod prompt;
+mod publish;
+mod purge;
+mod queue;
+mod record;
+mod remote;
+mod repro;
+mod rusage;
+mod serverconfig;
+mod ssh;
+mod strict;
+mod summary;
+mod targets;
+mod template;
+mod throttle;
+mod thumbs;
+mod top;
+mod trace;
+mod typereport;
+mod upgrade;
+mod visual;
+mod warnings;
+mod web;
+mod weights;
+
+/// The CLI definition: `usage()` prints it, and `completions`/`man` (and
+/// flag checking) are generated from its synopsis lines.
+const USAGE: &str = r#"framectl
 
 Usage:
   framectl build [--start=N] [--end=N] [--concurrency=N] [--silent=0|1] [--dry-run=0|1]
+                 [--workers=HOST:PORT,...] [--stagger-ms=N] [--spawn-rate=N]
+                 [--plan-out=PATH] [--preflight=0|1] [--interactive=0|1]
+                 [--summary-out=PATH] [--keep-going=0|1] [--max-load=N]
+                 [--heartbeat-file=PATH] [--offline=0|1|prefer]
+                 [--stages=install,typecheck,build] [--profile=trace.json]
+                 [--exclude=100-250,1337]
+                 [--order=sequential|shuffle|failed-first|slowest-first]
+                 [--stop-file=PATH] [--oom-retries=N] [--web=:8080]
+                 [--nice=0..19] [--ionice=idle] [--canary=N]
+                 [--pre-hook=CMD] [--post-hook=CMD] [--frames=LIST|-]
+                 [--junit-out=report.xml] [--node=20] [--node-versions=18,20,22]
+                 [--node-sample=N] [--min-free=10G] [--abort-free=2G]
+                 [--strict-stderr=0|1] [--stderr-patterns=FILE] [--stderr-allow=FILE]
+                 [--max-dist-size=2M] [--max-entry-size=64K]
+                 [--wait=0|1] [--weights=FILE] [--container=node:20-alpine]
+                 [--container-runtime=docker|podman] [--container-cpus=N]
+                 [--container-memory=4g] [--container-pull=0|1]
+                 [--ssh-hosts=user@host,...] [--ssh-slots=N] [--ssh-dir=PATH] [--ssh-sync=0|1]
+                 [--warnings-out=warnings.json] [--batch-size=N] [--cooldown=60s]
+                 [--progress-interval=1] [--quiet] [--failure-bundle=DIR]
+                 [--annotations=github|none] [--retry-failed]
+                 [--target=NAME] [--targets=esm,system] [--timeout=DURATION]
+                 [--error-format=text|json] [--filter-expr='@bad-apple/host...']
+  framectl test [same options as build]
+  framectl print-config [build|test] [build options] [--format=toml|json]
+  framectl COMMAND --use-profile=NAME [--config=framectl.toml] [options]
+  framectl build-one N [--verbose] [--dry-run=0|1] [--offline=0|1|prefer] [--node=20]
+                     [--wait=0|1] [--error-format=text|json]
+  framectl repro [--sample=5] [--concurrency=N] [--offline=0|1|prefer] [--start=N] [--end=N]
+                 [--wait=0|1]
+  framectl prefetch [--network-concurrency=N] [--interval=5]
+  framectl dump [--pid=N]
+  framectl top [--once=0|1] [--kill=FRAME] [--concurrency=N]
+  framectl worker [--listen=:7000] [--concurrency=N] [--silent=0|1] [--offline=0|1|prefer]
+                  [--nice=0..19] [--ionice=idle]
+  framectl gen-host [--out=PATH|-] [--start=N] [--end=N]
+  framectl gen-server-config [--format=nginx|caddy] [--base-url=URL] [--root=PATH]
+                             [--manifest=PATH] [--cors-origin=*] [--out=PATH|-]
+                             [--start=N] [--end=N] [--target=NAME]
+  framectl thumbs [--frames-dir=frames] [--out=thumbs] [--grid=30x20] [--cell=64x48]
+                  [--start=N] [--end=N] [--concurrency=N]
+  framectl validate-assets [--frames-dir=frames] [--size=WxH] [--bit-depth=N]
+                           [--start=N] [--end=N]
+  framectl compress [--algo=br,gzip] [--level=N] [--force=0|1] [--start=N] [--end=N]
+                    [--concurrency=N] [--target=NAME]
+  framectl verify-federation [--start=N] [--end=N] [--expose=./Frame] [--manifest=0|1]
+                             [--shared=NAME@VERSION,...] [--contract=federation-contract.json]
+                             [--target=NAME]
+  framectl optimize-assets [--frames-dir=frames] [--lossy=0|1] [--colors=16] [--dry-run=0|1]
+                           [--start=N] [--end=N] [--concurrency=N]
+  framectl upgrade-deps NAME@RANGE... [--range=A-B] [--install=0|1] [--dry-run=0|1]
+                        [--template=PATH] [--start=N] [--end=N]
+  framectl publish --bump=patch|minor|major|X.Y.Z [--registry=URL] [--tag=NAME]
+                   [--concurrency=N] [--publish-rate=N] [--rebuild=0|1] [--fresh=0|1]
+                   [--dry-run=0|1] [--start=N] [--end=N]
+  framectl gc [--older-than=7d] [--dry-run=0|1]
+  framectl ci-matrix --shards=N [--start=N] [--end=N] [--history=0|1] [--weights=FILE]
+  framectl drift [--template=DIR] [--start=N] [--end=N]
+  framectl bisect-failure N [--against=M]
+  framectl hash-manifest [--out=frames-manifest.json] [--base-url=URL] [--deployed=PATH|URL]
+                         [--start=N] [--end=N]
+                         [--concurrency=N] [--target=NAME]
+  framectl purge --deployed=PATH|URL --base-url=URL [--manifest=frames-manifest.json]
+                 [--zone=ID] [--also=URL,...] [--batch=30] [--interval=1] [--dry-run=0|1]
+  framectl dupes [--top=20] [--min-frames=2] [--out=PATH] [--start=N] [--end=N]
+                 [--concurrency=N] [--target=NAME]
+  framectl dedupe-dist [--mode=auto|hardlink|reflink] [--min-size=1024] [--dry-run=0|1]
+                       [--start=N] [--end=N] [--concurrency=N] [--target=NAME]
+  framectl pack [--out=dists.tar.zst] [--level=1..19] [--start=N] [--end=N] [--target=NAME]
+  framectl unpack [--in=dists.tar.zst]
+  framectl playlist [--fps=30] [--audio-offset=MS] [--preload=5] [--out=playlist.json|-]
+                    [--base-url=URL] [--manifest=frames-manifest.json] [--start=N] [--end=N]
+                    [--target=NAME]
+  framectl importmap --base-url=URL [--format=mf|importmap] [--chunk=N] [--out=remotes.json|DIR|-]
+                    [--manifest=frames-manifest.json] [--start=N] [--end=N]
+  framectl visual-check [--sample=20] [--threshold=0.02] [--frames-dir=frames] [--browser=BIN]
+                        [--out=.framectl/visual] [--start=N] [--end=N] [--target=NAME]
+  framectl health [--base-url=URL] [--manifest=PATH] [--sample=20] [--expose=./Frame]
+                  [--browser=BIN] [--concurrency=4] [--start=N] [--end=N] [--target=NAME]
+  framectl perf --base-url=URL [--manifest=PATH] [--sample=20] [--expose=./Frame]
+                [--out=PATH] [--baseline=PATH] [--tolerance=20] [--timeout=30]
+                [--browser=BIN] [--concurrency=1] [--start=N] [--end=N]
+  framectl record [--out=bad-apple.mp4|.webm] [--fps=24] [--size=WxH] [--audio=PATH|none]
+                  [--audio-offset=MS] [--browser=BIN] [--concurrency=N] [--keep-frames=0|1]
+                  [--start=N] [--end=N] [--target=NAME]
+  framectl parity --input=VIDEO [--fps=RATE]
+  framectl lint [--linter=eslint|biome] [--fix=0|1] [--concurrency=N] [--start=N] [--end=N]
+  framectl typereport [--from-logs=0|1] [--concurrency=N] [--start=N] [--end=N]
+  framectl audit [--fail-on=low|moderate|high|critical] [--prod=0|1]
+  framectl sync-template [--template=templates/frame] [--dry-run=0|1] [--start=N] [--end=N]
+  framectl completions bash|zsh|fish
+  framectl man
+  framectl help [COMMAND]
 
 Notes:
+  - Flags are `--key=value` or `--key value`; a 0|1 flag alone means =1. A flag the
+    command doesn't take is an error naming the closest one it does, and
+    `framectl COMMAND --help` (or `framectl help COMMAND`) shows its usage and notes.
   - Builds pnpm workspace packages named @bad-apple/frame-XXXX (4 digits).
   - If --end is omitted, inferred from apps/frames/frame-XXXX dirs.
-"#
-  );
+  - With --workers, frames are built by `framectl worker` processes (run from the
+    repo root on each machine) instead of locally; frames in flight on a worker
+    that disconnects are retried elsewhere. A worker has no authentication and so
+    listens on 127.0.0.1 for --listen=:7000; bind --listen=0.0.0.0:7000 (or an
+    interface's address) only on a trusted network. It builds at most --concurrency
+    frames at once, whoever asks.
+  - --stagger-ms delays each slot's first build by N ms per slot index;
+    --spawn-rate caps new builds started per second for the whole run.
+  - --max-load holds new builds while this machine's 1-minute load average is above N
+    and resumes once it drops; builds already running are left alone.
+  - --min-free pauses new builds while free space on the volume holding apps/frames
+    is below the given size (K/M/G/T) and resumes once it is back; below --abort-free
+    no new frames start, frames in flight finish and the run ends with its summary.
+  - --strict-stderr=1 fails a build that succeeded but printed a warning on stderr:
+    a line matching a default pattern (warn/warning, deprecat, peer dependency,
+    size limit) or one from --stderr-patterns, and no --stderr-allow pattern. Both
+    files hold one regex per line (`(?i)` for case-insensitive); class `warning`.
+  - --max-dist-size/--max-entry-size fail a build that succeeded but left the frame's
+    dist (or its remote entry) larger than the given size (K/M/G); class `budget`.
+    Local builds only.
+  - One build (or test, or build-one) at a time per workspace: a second run exits
+    naming the pid holding .framectl/lock, or with --wait=1 waits for it to finish.
+    --dry-run=1 doesn't take the lock.
+  - Per-frame build times are kept in .framectl/history.tsv and used for the ETA,
+    printed as `eta=MID (LOW..HIGH)`.
+  - --plan-out writes the ordered frames, command lines, env and estimated duration
+    as JSON; combine with --dry-run=1 to plan without building.
+  - Before building, every frame in range must have a package.json named after it and
+    matched by pnpm-workspace.yaml, and pnpm-lock.yaml must be in sync (not checked
+    with --dry-run=1).
+  - --offline=1 (or =prefer) runs pnpm with npm_config_offline (prefer_offline) and
+    the lockfile check with --offline, so a registry outage fails once up front; an
+    offline build missing a package in the store stops the run. Workers take their
+    own --offline.
+  - prefetch runs `pnpm fetch` to fill the pnpm store with every package in
+    pnpm-lock.yaml before a build, printing resolved/reused/downloaded counts every
+    --interval seconds, so parallel frame installs don't all hit the registry at once
+    (pair with build --offline=prefer).
+  - repro builds --sample frames (spread over the range) twice, each from an empty
+    node_modules/.cache, and compares the two dists file by file, listing the files
+    that differ for each nondeterministic frame. The first builds of those stay in
+    .framectl/repro; exits 1 if any frame differs or fails to build.
+  - --stages runs each listed script in order per frame (install, typecheck, build,
+    test), stopping the frame at the first failing stage; timings and failures are
+    reported per stage and each stage keeps its own duration history.
+  - --profile writes every frame attempt as a Chrome trace event, one track per build
+    slot (or worker slot), for chrome://tracing or Perfetto.
+  - --use-profile=NAME adds the keys of the [profile.NAME] section in framectl.toml
+    (or --config) as flags to any command (`concurrency = 4` is
+    --concurrency=4; flags on the command line win). Keys the command doesn't take are
+    skipped with a note, so one profile can hold base-url for hash-manifest and
+    max-dist-size for build; a key no command takes is an error. [profile.NAME.env]
+    sets env vars for every frame's pnpm, and build-script/test-script/... rename the
+    pnpm script a stage runs. print-config shows which settings came from the profile.
+  - --node=V runs each frame's pnpm under Node major V through fnm (`fnm exec`) or
+    Volta (`volta run`). --node-versions repeats the run once per version (with
+    --keep-going; --node-sample=N builds only N spread frames) and reports the frames
+    failing per version (summaries in .framectl/node-V.json), and those that fail on
+    only some of them.
+  - --container=IMAGE runs each frame's pnpm in a fresh docker/podman container (the
+    first found, or --container-runtime) with the workspace mounted at /workspace and
+    pnpm from the image's corepack; the image is pulled once first (--container-pull=0
+    uses a local one) and --container-cpus/--container-memory cap each container.
+    node_modules are shared with the host, so add install to --stages when the image's
+    libc differs. CPU time and peak RSS are the container client's. Not with --node.
+  - --ssh-hosts builds frames on other machines with plain ssh (BatchMode, so key auth)
+    instead of `framectl worker`s: --ssh-slots builds at once per host, in a checkout at
+    --ssh-dir (default: this workspace's path) with dependencies installed. --ssh-sync=1
+    rsyncs each frame's package there first; a built dist is rsynced back, so hooks and
+    later steps see it. Hosts that don't answer are skipped. Not with --workers, --node
+    or --container.
+  - --annotations=github prints a GitHub Actions `::error` per failure (at the file and
+    line from TypeScript/webpack/rspack errors in the log, else the frame's package.json;
+    one for every frame hitting the same error) and a `::warning` per warning category
+    on stdout, so they show inline on the PR.
+  - --targets=esm,system builds every frame once per target, each run as with
+    --target=NAME: FRAMECTL_TARGET=NAME in the build's env (the frame template builds
+    into dist/NAME in that target's container format from it) and the frame's
+    `build:NAME` script if it has one. The summary keeps each frame's status per
+    target; the run fails if any target does.
+  - Commands reading frame dists (hash-manifest, compress, pack, health, ...) read
+    dist/NAME with --target=NAME; without it, frames built only per target are an
+    error. gc checks each dist/NAME on its own, and unpack replaces only the targets
+    an archive holds.
+  - --filter-expr='@bad-apple/host...' also builds the workspace packages a pnpm filter
+    selects (here the host app and the shared packages it depends on) in the same run,
+    each comma-separated expression as one `pnpm --filter EXPR SCRIPT` task scheduled
+    first alongside the frames, with their progress, failure handling and summary
+    entry (`filter_expr`). --retry-failed retries a failed one if its expression is
+    in --filter-expr again, in any position. Local builds only.
+  - --junit-out writes a JUnit XML suite with one test case per frame (duration,
+    failure class and the tail of its log), for CI test report views.
+  - Every build's output is scanned for warnings (also with --silent) and the run ends
+    with their count per category (performance, deprecation, peer-dependency, module,
+    other) and a sample line; --warnings-out writes the counts as JSON, to follow them
+    across runs. Frames built by --workers aren't counted.
+  - --frames builds just the listed frames (`1,5,10-20`) instead of a --start/--end
+    range; --frames=- reads them from stdin, separated by whitespace or commas, e.g.
+    `diff-tool old.json new.json | framectl build --frames=-`.
+  - --exclude drops frames from the --start/--end range, e.g. the chunk another
+    machine is already building.
+  - --order=failed-first starts with the frames that failed last run (each run keeps
+    its summary in .framectl/summary.json); slowest-first starts the longest frames
+    by duration history first, so a full rebuild doesn't end waiting on a few slow
+    frames; the order line shows the estimated wall time against frame order.
+  - --weights=FILE gives frames a relative build cost (`1200-1450 3` per line, unlisted
+    frames 1) for frames without duration history: they order slowest-first (the
+    default order with --weights), weigh the ETA, and balance ci-matrix shards.
+  - --batch-size=N hands out N frames, waits for all of them to finish, then starts
+    nothing for --cooldown (default 60s; 90, 90s, 2m) before the next N. The ETA
+    counts the cooldowns still ahead; the heartbeat state is `cooldown` meanwhile.
+  - When the --stop-file appears, no new frames are started; frames in flight finish,
+    the summary is written and framectl exits 3 (1 if any of them failed). SIGINT,
+    SIGTERM and --timeout (a limit on the whole run: 90m, 2h) start nothing new either
+    but stop the local builds in flight; a build on a --workers machine runs to the end
+    there first, and one on an --ssh-hosts host loses its session but isn't stopped
+    on the host. A second signal exits at once.
+  - build, test and build-one exit 1 for build failures, 2 for usage errors, 3 when
+    interrupted or stopped, 4 for an empty plan (nothing left after --frames/--exclude;
+    --retry-failed with nothing failed is success), 5 on --timeout and 6 when the
+    environment fails them (preflight, the workspace lock, disk space, unreachable
+    workers, a missing tool). --error-format=json adds a last stderr line with the
+    kind as JSON: `{"error":"build-failed","code":1,"command":"build","message":...,
+    "total":...,"done":...,"ok":...,"failed":[...],"summary":PATH}`.
+  - A frame that runs out of memory (JS heap OOM or SIGKILL) is retried up to
+    --oom-retries times (default 2), halving concurrency each time; one slot comes
+    back every 30s.
+  - A build whose spawn fails for lack of processes or file descriptors (EAGAIN,
+    EMFILE, ENFILE) is retried in place with backoff for about 30s before it counts
+    as failed, and concurrency is halved while such failures keep coming (recovering
+    like after an OOM).
+  - --web serves a live dashboard at / from the running build, fed by /events
+    (server-sent JSON events: progress with per-slot counts, failure, done); /status
+    has the latest progress.
+  - --nice lowers the priority of framectl and every build it spawns (below-normal
+    priority class on Windows); --ionice=idle also gives them idle disk priority on
+    Linux.
+  - --canary=N builds N frames first (the first, the last and random ones between)
+    and starts the rest of the range only once they all pass; a canary failure stops
+    the run, even with --keep-going.
+  - print-config shows what build (or test) would run with for the same options: every
+    setting with its source (flag, default, or inferred like --end and --interactive),
+    and the pnpm declared in package.json and found on PATH. It builds nothing.
+  - --pre-hook/--post-hook run a shell command before/after each frame's build with
+    FRAME_INDEX, FRAME_PKG and FRAME_DIR set (plus FRAME_STATUS=ok|failed after); a
+    failing hook fails the frame. Local builds only (not with --workers).
+  - Failed builds leave their stderr in .framectl/logs/frame-XXXX.log. On a terminal
+    (or with --interactive=1) a failure asks to retry, skip, open the log or abort
+    instead of stopping the run.
+  - --failure-bundle=DIR (1 for here) writes DIR/framectl-failure-XXXX.tar.gz for each
+    frame left failed: the frame dir without node_modules/dist, the workspace manifests,
+    its logs, the print-config settings, the command and env, and node/pnpm/OS versions.
+  - The run ends with the slowest frames and per-build CPU time / peak RSS; --summary-out
+    writes the same per-frame numbers as JSON.
+  - A progress line is printed at most every --progress-interval seconds (0.2, 60s,
+    1m; default 1) as frames finish; --quiet prints none, only the summary, and with
+    --summary-out also leaves out each failure's stderr tail.
+  - --heartbeat-file rewrites a JSON status (state, done/ok/failed, rate, ETA, frames
+    in flight, updated_at) every 2s via rename, so watchdogs can spot a stalled run.
+  - A running build dumps its scheduler state (queue, frames in flight with elapsed
+    time and child pid, counts, recent failures) to stderr and .framectl/dump.txt on
+    SIGUSR1; `framectl dump` sends the signal and prints the result.
+  - top shows the frames a running local build has in flight (child pid, elapsed, CPU%
+    and RSS of its process tree on Linux) through the control socket whose address is
+    in .framectl/control; up/down and k kill a stuck frame (it fails like any other),
+    -/+ change the run's concurrency (up to its --concurrency). --once=1 (or no
+    terminal) prints the table once; --kill/--concurrency do just that and exit.
+  - --retry-failed builds just the frames that failed in the --summary-out file (the
+    last run's .framectl summary if there is none), merges the results into it and
+    reports which of them pass now.
+  - --keep-going builds the whole range regardless of failures; failures are counted by
+    class (oom, TSxxxx, module-not-found, filter-miss, missing-script, tests, spawn, other) at the end.
+  - test runs each frame package's `test` script with the same range, concurrency,
+    retry and summary handling as build; failing test names (TAP, vitest, jest) are
+    aggregated at the end and in --summary-out. Test logs are frame-XXXX.test.log.
+  - build-one runs the same command as build for one frame, with stdout/stderr live.
+  - gen-host writes the host's frame loader (default apps/host/src/frames.generated.ts)
+    from the frame dirs that exist.
+  - gen-server-config prints nginx location blocks (or Caddy routes) serving each
+    frame's dist under --base-url from --root (default: here): CORS, immutable caching
+    except the unhashed remoteEntry.js, and the precompressed .br/.gz files. With
+    --manifest the hashed hash-manifest paths are served, all immutable.
+  - thumbs composes contact sheets of frames/frameNNNN.png; missing frames are magenta,
+    unreadable ones red.
+  - validate-assets checks each frame has src/frame.js and a PNG source image of the
+    expected size, depth and color type (default: the most common), with no gaps.
+  - compress writes .br/.gz next to each compressible dist file >= 1KB using the
+    `brotli` / `gzip` CLIs; files whose sibling is newer are skipped.
+  - verify-federation checks each built remote: remoteEntry.js at static/js defining
+    frame_NNNN and, from mf-manifest.json (required with --manifest=1), the name, the
+    --expose list, a publicPath that is auto or the frame's assetPrefix, and shared
+    versions matching --shared (default: the most common version across frames).
+    Every frame's rsbuild config must name its container after its package
+    (@bad-apple/frame-0001 -> frame_0001). A --contract file (federation-contract.json
+    when present: {"exposes":[...],"shared":{"react":{"requiredVersion","singleton"}}})
+    fixes the exact exposes and the shared versions and singletons instead.
+  - optimize-assets re-encodes the source PNGs in place with `oxipng` (lossless, pixels
+    must match) or, with --lossy=1, `pngquant` to --colors; an image is only replaced
+    when the result is smaller and decodes to the same size. Sources stay PNG since
+    the generator reads them with pngjs and frames embed a sampled bitmap.
+  - upgrade-deps sets NAME's range in each frame package.json that already depends on
+    it (e.g. `upgrade-deps react@^18.3.0 --range=1-6570`), optionally runs
+    `pnpm install`, and reports manifest fields that differ from --template (a
+    package.json; default: the most common value of each field).
+  - sync-template writes every file under --template into each frame package with
+    {{id}}, {{n}}, {{package}}, {{scope}}, {{port}} and {{assetPrefix}} filled in;
+    port and asset prefix are kept from the frame's current rsbuild.config.mjs, and
+    src/frame.js / src/frame.css are never touched.
+  - publish bumps each frame's version, rebuilds frames whose dist is older than their
+    sources (or all with --rebuild=1) and runs `pnpm publish` at most --publish-rate
+    times per second (default 5). Progress is kept in .framectl/publish.tsv so a rerun
+    resumes with the same versions; --fresh=1 starts over. Frames must not be private.
+  - gc removes frame dists and node_modules/.cache dirs not touched within --older-than
+    (s/m/h/d/w; default 7d), dists older than their sources, and failure logs that are
+    old or belong to frames that no longer exist.
+  - ci-matrix prints `{"include":[{"shard":1,"start":1,"end":550,...}]}` on one line:
+    contiguous shards balanced on .framectl/history.tsv when it has the frames (frame
+    count otherwise). In a workflow step:
+    `echo "matrix=$(framectl ci-matrix --shards=12)" >> "$GITHUB_OUTPUT"`.
+  - drift compares each frame's package.json fields and rsbuild config (asset prefix,
+    port, entry, plugins, federation name/filename/exposes/shared, uniqueName) with the
+    --template dir, or with the most common value, after replacing the frame number
+    with a placeholder; exits 1 if any frame diverges.
+  - bisect-failure compares a failing frame with the nearest frame that passed the last
+    build (or has a current dist): package.json and rsbuild config fields as in drift,
+    then every other file outside node_modules and dist, frame numbers normalized.
+  - hash-manifest hashes each frame's dist and writes the host routing manifest
+    ({"frames":{"0001":{"hash","path","entry"}}}), printing `<dist>\t/frame-0001.<hash>/`
+    upload lines on stdout. Frames need assetPrefix 'auto' to load chunks from there.
+    --deployed takes the manifest that is live now (a file, or an http(s) URL fetched
+    with curl) and prints upload lines only for frames whose hash it doesn't have,
+    reporting the changed and skipped frames on stderr.
+  - purge purges the Cloudflare cache for the unhashed remoteEntry.js of every frame
+    whose hash differs between --manifest and --deployed (save the live manifest before
+    uploading), plus any --also URLs such as the manifest's own. Needs curl and
+    CLOUDFLARE_API_TOKEN; the zone is --zone or CLOUDFLARE_ZONE_ID. Requests carry up
+    to --batch URLs, --interval seconds apart, and back off on HTTP 429.
+  - dupes hashes every file in the built dists (not .map/.br/.gz) and lists the chunks
+    byte-identical in at least --min-frames frames, by bytes wasted on the copies past
+    the first, with the totals; --out writes them all as JSON.
+  - dedupe-dist replaces byte-identical dist files (at least --min-size bytes, maps and
+    compressed copies included) with links to the lowest frame's copy and reports the
+    space reclaimed. --mode=auto reflinks (`cp --reflink`) where the filesystem can and
+    hardlinks otherwise; hardlinked copies are one file, so run it after building and
+    compressing, not before editing a dist in place.
+  - pack tars every apps/frames/frame-XXXX/dist through `zstd -T0`; unpack replaces
+    the dists of the frames in the archive. Both need `tar` and `zstd` on PATH.
+  - playlist lists the built frames (or those in a hash-manifest --manifest) in order
+    with their remote name, entry URL, display time `atMs` ((N-1)/fps, plus
+    --audio-offset) and the next --preload remotes to fetch; a missing frame is
+    skipped and the previous one held on screen.
+  - importmap writes every frame's remote entry as an MF runtime `remotes` list
+    (--format=mf) or an import map; with --chunk=N it writes DIR/remotes-FIRST-LAST.json
+    for each run of N frame numbers plus DIR/index.json for the host to fetch lazily.
+  - record captures every built frame in headless Chrome (loaded like visual-check does)
+    and encodes the captures with `ffmpeg` at --fps, muxing --audio (default
+    apps/host/public/bad-apple.mp3 if present); a missing frame holds the one before it.
+    Captures go to .framectl/record, removed afterwards unless --keep-frames=1.
+  - parity asks `ffprobe` for --input's duration and frame rate and compares the frame
+    count at --fps (default the video's own) with the packages under apps/frames, listing
+    missing frames and packages past the end of the video. Exits 1 on any mismatch.
+  - lint runs `pnpm exec eslint` (or biome, if there is a biome.json) in each frame
+    package in parallel and prints every diagnostic once, with the frames it occurs in
+    (frame numbers normalized away); --fix passes --fix (biome: --write). Exits 1 on
+    any error-level diagnostic or a frame the linter produced no report for.
+  - typereport runs `pnpm exec tsc --noEmit` in each frame (--from-logs=1 reads the
+    build and typecheck logs in .framectl/logs instead) and merges the diagnostics by
+    error code and file, with the frames each one hits, most widespread first. Exits 1
+    on any diagnostic or a frame tsc failed in without reporting one.
+  - audit runs `pnpm audit --json` once for the workspace and prints each advisory
+    once with the frames (as ranges) and other packages it affects; --fail-on exits 1
+    if any is at or above that severity.
+  - visual-check serves the built dists locally, screenshots --sample frames (spread
+    over the range) in headless Chrome and fails any whose thresholded pixels differ
+    from the source image by more than --threshold; screenshots of failures are kept.
+  - health loads --sample frames' remote entries from --base-url (hash-manifest paths
+    with --manifest; the local dists without either) in headless Chrome, initializes
+    each container and imports --expose, separating frames whose remoteEntry.js does
+    not answer 200 (checked with curl) from those that do but fail at federation runtime.
+  - perf mounts --sample deployed frames one at a time in a host-sized page in headless
+    Chrome, each with a cold cache, and prints p50/p90/p95/max of time to first paint,
+    script cost (that time not spent on the network), long tasks and bytes transferred
+    (curl measures what the CDN hides cross-origin). --out saves the run as JSON;
+    --baseline compares with a saved one and exits 1 if a p50 or p95 got worse by more
+    than --tolerance percent.
+  - completions prints a bash, zsh or fish completion script covering commands, flags
+    and their values, generated from this text (e.g.
+    `framectl completions bash > /etc/bash_completion.d/framectl`).
+  - man prints this text as a roff man page
+    (`framectl man > ~/.local/share/man/man1/framectl.1`).
+"#;
+
+fn usage() -> ! {
+  eprintln!("{USAGE}");
   std::process::exit(2);
 }
 
@@ -39,30 +502,14 @@ fn parse_kv(args: &[String], key: &str) -> Option<String> {
   None
 }
 
-fn infer_end(frames_dir: &PathBuf) -> Option<usize> {
-  let mut max_n: Option<usize> = None;
-  let rd = std::fs::read_dir(frames_dir).ok()?;
-  for ent in rd.flatten() {
-    let name = ent.file_name();
-    let name = name.to_string_lossy();
-    if !name.starts_with("frame-") {
-      continue;
-    }
-    let Some(num) = name.strip_prefix("frame-") else {
-      continue;
-    };
-    if num.len() != 4 {
-      continue;
-    }
-    if let Ok(n) = num.parse::<usize>() {
-      max_n = Some(max_n.map(|m| m.max(n)).unwrap_or(n));
-    }
-  }
-  max_n
+/// Per-workspace state: duration history, failure logs.
+fn state_dir() -> PathBuf {
+  PathBuf::from(".framectl")
 }
 
-fn frame_pkg(n: usize) -> String {
-  format!("@bad-apple/frame-{:04}", n)
+fn default_concurrency() -> usize {
+  let ap = thread::available_parallelism().map(|n| n.get()).unwrap_or(8);
+  ap.clamp(1, 8)
 }
 
 fn fmt_dur(d: Duration) -> String {
@@ -76,192 +523,106 @@ fn fmt_dur(d: Duration) -> String {
   }
 }
 
-fn main() {
-  let argv: Vec<String> = env::args().collect();
-  if argv.len() < 2 {
-    usage();
-  }
-  let cmd = argv[1].as_str();
-  if cmd != "build" {
-    usage();
-  }
-
-  let args = &argv[2..];
-
-  let start: usize = parse_kv(args, "--start")
+fn worker(args: &[String]) {
+  let listen = remote::listen_addr(&parse_kv(args, "--listen").unwrap_or_else(|| ":7000".to_string()));
+  let slots: usize = parse_kv(args, "--concurrency")
     .and_then(|v| v.parse().ok())
-    .unwrap_or(1);
-
+    .unwrap_or_else(default_concurrency)
+    .max(1);
   let silent: bool = parse_kv(args, "--silent")
     .and_then(|v| parse_bool(&v))
     .unwrap_or(true);
+  let network = build::parse_network(args);
+  priority::apply_args(args);
 
-  let dry_run: bool = parse_kv(args, "--dry-run")
-    .and_then(|v| parse_bool(&v))
-    .unwrap_or(false);
-
-  let concurrency: usize = parse_kv(args, "--concurrency")
-    .and_then(|v| v.parse().ok())
-    .unwrap_or_else(|| {
-      let ap = thread::available_parallelism().map(|n| n.get()).unwrap_or(8);
-      ap.min(8).max(1)
-    });
-
-  let frames_dir = PathBuf::from("apps").join("frames");
-  let end: usize = match parse_kv(args, "--end").and_then(|v| v.parse().ok()) {
-    Some(v) => v,
-    None => infer_end(&frames_dir).unwrap_or(0),
-  };
-
-  if end < start || end == 0 {
-    eprintln!("invalid frame range: start={start} end={end}");
-    std::process::exit(2);
+  if let Err(e) = remote::serve(&listen, slots, silent, network) {
+    eprintln!("worker: {listen}: {e}");
+    std::process::exit(1);
   }
+}
 
-  let total = end - start + 1;
-  eprintln!(
-    "build frames: start={start} end={end} total={total} concurrency={concurrency} silent={} dry_run={}",
-    if silent { 1 } else { 0 },
-    if dry_run { 1 } else { 0 }
-  );
-
-  let stop = Arc::new(AtomicBool::new(false));
-  let done = Arc::new(AtomicUsize::new(0));
-  let ok = Arc::new(AtomicUsize::new(0));
-
-  let (task_tx, task_rx) = mpsc::sync_channel::<usize>(concurrency.saturating_mul(2).max(1));
-  let task_rx = Arc::new(Mutex::new(task_rx));
-  let (res_tx, res_rx) = mpsc::channel::<(usize, bool, String)>();
-
-  for _ in 0..concurrency {
-    let task_rx = Arc::clone(&task_rx);
-    let res_tx = res_tx.clone();
-    let stop = Arc::clone(&stop);
-
-    thread::spawn(move || loop {
-      if stop.load(Ordering::Relaxed) {
-        break;
-      }
-
-      let n = {
-        let guard = task_rx.lock().unwrap();
-        guard.recv()
-      };
-      let n = match n {
-        Ok(v) => v,
-        Err(_) => break,
-      };
-
-      if stop.load(Ordering::Relaxed) {
-        break;
-      }
-
-      let pkg = frame_pkg(n);
-      let mut err_tail = String::new();
-
-      let status_ok = if dry_run {
-        true
-      } else {
-        let mut cmd = Command::new("pnpm");
-        cmd.arg("--filter").arg(&pkg).arg("build");
-        cmd.stdin(Stdio::null());
-        if silent {
-          cmd.stdout(Stdio::null());
-        }
-        cmd.stderr(Stdio::piped());
-
-        match cmd.output() {
-          Ok(out) => {
-            if !out.stderr.is_empty() {
-              let s = String::from_utf8_lossy(&out.stderr);
-              let keep = 3000usize.min(s.len());
-              err_tail = s[s.len().saturating_sub(keep)..].to_string();
-            }
-            out.status.success()
-          }
-          Err(e) => {
-            err_tail = format!("spawn failed: {e}");
-            false
-          }
-        }
-      };
-
-      let _ = res_tx.send((n, status_ok, err_tail));
-      if !status_ok {
-        stop.store(true, Ordering::Relaxed);
-      }
-    });
+/// `framectl help [COMMAND]`, on stdout.
+fn help(args: &[String]) {
+  let Some(name) = args.first() else {
+    println!("{USAGE}");
+    return;
+  };
+  match cli::spec().command(name) {
+    Some(cmd) => print!("{}", cli::help(cmd)),
+    None => unknown_command(name),
   }
-  drop(res_tx);
-
-  // Producer: stop early if any worker flips stop=true.
-  thread::spawn({
-    let stop = Arc::clone(&stop);
-    move || {
-      for n in start..=end {
-        if stop.load(Ordering::Relaxed) {
-          break;
-        }
-        if task_tx.send(n).is_err() {
-          break;
-        }
-      }
-      // dropping sender closes channel -> workers exit.
-    }
-  });
-
-  let t0 = Instant::now();
-  let mut last_print = Instant::now();
-
-  let mut first_fail: Option<(usize, String)> = None;
-  while let Ok((n, status_ok, err_tail)) = res_rx.recv() {
-    done.fetch_add(1, Ordering::Relaxed);
-    if status_ok {
-      ok.fetch_add(1, Ordering::Relaxed);
-    } else if first_fail.is_none() {
-      first_fail = Some((n, err_tail.clone()));
-    }
+}
 
-    let d = done.load(Ordering::Relaxed);
-    if last_print.elapsed() >= Duration::from_secs(1) || d == total {
-      let elapsed = t0.elapsed().as_secs_f64().max(0.0001);
-      let rate = d as f64 / elapsed;
-      let left = total.saturating_sub(d);
-      let eta = if rate > 0.0 {
-        Duration::from_secs_f64(left as f64 / rate)
-      } else {
-        Duration::from_secs(0)
-      };
-      eprintln!(
-        "progress: done={d}/{total} ok={} failed={} rate={:.1}/s eta={}",
-        ok.load(Ordering::Relaxed),
-        d.saturating_sub(ok.load(Ordering::Relaxed)),
-        rate,
-        fmt_dur(eta)
-      );
-      last_print = Instant::now();
-    }
+fn unknown_command(name: &str) -> ! {
+  let names = cli::spec().commands.iter().map(|c| c.name.as_str());
+  exitcode::usage(match cli::suggest(name, names) {
+    Some(s) => format!("unknown command {name} (did you mean {s}?); see framectl --help"),
+    None => format!("unknown command {name}; see framectl --help"),
+  })
+}
 
-    if !status_ok {
-      eprintln!("failed: frame-{:04} ({})", n, frame_pkg(n));
-      if !err_tail.trim().is_empty() {
-        eprintln!("stderr tail:\n{err_tail}");
-      }
-      break;
-    }
+fn main() {
+  let argv: Vec<String> = env::args().collect();
+  if argv.len() < 2 {
+    usage();
   }
-
-  let d = done.load(Ordering::Relaxed);
-  let okv = ok.load(Ordering::Relaxed);
-  if d == total && okv == total {
-    eprintln!("success: built {okv} frames in {}", fmt_dur(t0.elapsed()));
+  let name = argv[1].as_str();
+  if matches!(name, "--help" | "-h") {
+    help(&[]);
     return;
   }
-
-  if let Some((n, _)) = first_fail {
-    eprintln!("exit: build failed at frame-{:04}", n);
-  } else {
-    eprintln!("exit: build stopped (done={d}/{total} ok={okv})");
+  let Some(cmd) = cli::spec().command(name) else {
+    unknown_command(name);
+  };
+  // The other commands report errors as text only; for them the flag is
+  // unknown.
+  if cmd.flag("error-format").is_some() {
+    exitcode::install(name, &argv[2..]);
+  }
+  let args = &cli::args(cmd, &profiles::expand(cmd, &argv[2..]));
+  match name {
+    "build" => build::run(args, exec::Script::Build),
+    "test" => build::run(args, exec::Script::Test),
+    "print-config" => printconfig::run(args),
+    "build-one" => build::run_one(args),
+    "worker" => worker(args),
+    "repro" => repro::run(args),
+    "prefetch" => prefetch::run(args),
+    "dump" => dump::run(args),
+    "top" => top::run(args),
+    "gen-host" => genhost::run(args),
+    "gen-server-config" => serverconfig::run(args),
+    "thumbs" => thumbs::run(args),
+    "validate-assets" => assets::run(args),
+    "compress" => compress::run(args),
+    "optimize-assets" => optimize::run(args),
+    "verify-federation" => federation::run(args),
+    "upgrade-deps" => upgrade::run(args),
+    "sync-template" => template::run(args),
+    "publish" => publish::run(args),
+    "gc" => gc::run(args),
+    "ci-matrix" => matrix::run(args),
+    "drift" => drift::run(args),
+    "bisect-failure" => bisect::run(args),
+    "hash-manifest" => hashmanifest::run(args),
+    "purge" => purge::run(args),
+    "dupes" => dupes::run(args),
+    "dedupe-dist" => dedupe::run(args),
+    "pack" => pack::pack(args),
+    "unpack" => pack::unpack(args),
+    "playlist" => playlist::run(args),
+    "importmap" => importmap::run(args),
+    "record" => record::run(args),
+    "visual-check" => visual::run(args),
+    "health" => health::run(args),
+    "perf" => perf::run(args),
+    "parity" => parity::run(args),
+    "lint" => lint::run(args),
+    "typereport" => typereport::run(args),
+    "audit" => audit::run(args),
+    "completions" => completions::run(args),
+    "man" => completions::man(args),
+    "help" => help(args),
+    _ => usage(),
   }
-  std::process::exit(1);
 }
@@ -0,0 +1,147 @@
+//! `ci-matrix`: split the frame range into contiguous shards of roughly
+//! equal build time, as a GitHub Actions matrix. Frames without duration
+//! history count by `--weights` (see `weights`).
+
+use crate::exec::Script;
+use crate::frames;
+use crate::history;
+use crate::json::Value;
+use crate::weights::{self, Weights};
+use crate::{parse_bool, parse_kv};
+
+/// Cut `weights` (in frame order) into at most `shards` contiguous runs of
+/// similar total weight. Returns (first index, last index, weight) per run.
+fn partition(weights: &[f64], shards: usize) -> Vec<(usize, usize, f64)> {
+  let mut out = Vec::new();
+  let mut remaining: f64 = weights.iter().sum();
+  let mut i = 0;
+  for k in (1..=shards).rev() {
+    if i >= weights.len() {
+      break;
+    }
+    let target = remaining / k as f64;
+    let (first, mut acc) = (i, 0.0);
+    // Leave at least one frame for each shard still to come.
+    let last_allowed = weights.len() - k.min(weights.len() - i);
+    while i <= last_allowed {
+      let w = weights[i];
+      // Stop before a frame that overshoots more than stopping short would.
+      if acc > 0.0 && acc + w - target > target - acc {
+        break;
+      }
+      acc += w;
+      i += 1;
+    }
+    if k == 1 {
+      acc += weights[i..].iter().sum::<f64>();
+      i = weights.len();
+    }
+    remaining -= acc;
+    out.push((first, i - 1, acc));
+  }
+  out
+}
+
+pub fn run(args: &[String]) {
+  let shards: usize = parse_kv(args, "--shards")
+    .and_then(|v| v.parse().ok())
+    .filter(|&n| n > 0)
+    .unwrap_or_else(|| {
+      eprintln!("usage: framectl ci-matrix --shards=N [--start=N] [--end=N] [--history=0|1] [--weights=FILE]");
+      std::process::exit(2);
+    });
+  let use_history: bool = parse_kv(args, "--history")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(true);
+  // CI checkouts may not have generated the packages yet; fall back on the
+  // source images they will be generated from.
+  let mut known = frames::discover(&frames::frames_dir());
+  if known.is_empty() {
+    known = frames::discover_sources(&frames::sources_dir());
+  }
+  let start: usize = parse_kv(args, "--start")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(1);
+  let end: usize = match parse_kv(args, "--end").and_then(|v| v.parse().ok()) {
+    Some(v) => v,
+    None => known.last().copied().unwrap_or(0),
+  };
+  if end < start || end == 0 {
+    eprintln!("ci-matrix: invalid frame range: start={start} end={end}");
+    std::process::exit(2);
+  }
+
+  let hist = if use_history {
+    history::load(Script::Build)
+  } else {
+    Default::default()
+  };
+  let weights = Weights::from_args(args);
+  let plan: Vec<usize> = (start..=end).collect();
+  let timed = plan.iter().any(|n| hist.contains_key(n));
+  let costs = weights::costs(&plan, &hist, weights.as_ref());
+
+  let include: Vec<Value> = partition(&costs, shards)
+    .into_iter()
+    .enumerate()
+    .map(|(i, (a, b, w))| {
+      let (first, last) = (plan[a], plan[b]);
+      eprintln!(
+        "shard {:>2}: frames {first}-{last} ({} frames{})",
+        i + 1,
+        b - a + 1,
+        if timed {
+          format!(", ~{:.0}s", w)
+        } else {
+          String::new()
+        }
+      );
+      let mut fields = vec![
+        ("shard", Value::from(i + 1)),
+        ("start", Value::from(first)),
+        ("end", Value::from(last)),
+        ("frames", Value::from(b - a + 1)),
+      ];
+      if timed {
+        fields.push(("estimated_secs", Value::from(w.round())));
+      }
+      Value::obj(fields)
+    })
+    .collect();
+  println!("{}", Value::obj([("include", Value::Arr(include))]).compact());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn partition_even() {
+    assert_eq!(partition(&[1.0; 4], 2), vec![(0, 1, 2.0), (2, 3, 2.0)]);
+  }
+
+  #[test]
+  fn partition_heavy_first() {
+    assert_eq!(partition(&[5.0, 1.0, 1.0, 1.0], 2), vec![(0, 0, 5.0), (1, 3, 3.0)]);
+  }
+
+  #[test]
+  fn partition_more_shards_than_frames() {
+    assert_eq!(partition(&[1.0, 1.0], 3), vec![(0, 0, 1.0), (1, 1, 1.0)]);
+    assert!(partition(&[], 3).is_empty());
+  }
+
+  #[test]
+  fn partition_covers_every_frame() {
+    let weights: Vec<f64> = (1..=20).map(|i| (i % 7) as f64 + 0.5).collect();
+    let runs = partition(&weights, 6);
+    assert_eq!(runs.len(), 6);
+    assert_eq!(runs[0].0, 0);
+    assert_eq!(runs[5].1, weights.len() - 1);
+    for pair in runs.windows(2) {
+      assert_eq!(pair[0].1 + 1, pair[1].0);
+    }
+    let total: f64 = runs.iter().map(|r| r.2).sum();
+    assert!((total - weights.iter().sum::<f64>()).abs() < 1e-9);
+  }
+}
@@ -0,0 +1,129 @@
+//! `--node-versions=18,20,22`: the same build once per Node major version
+//! (each a child `framectl` run with `--node`), then one report of which
+//! frames fail on which version.
+
+use std::collections::BTreeMap;
+use std::process::Command;
+
+use crate::exec::{self, Script};
+use crate::exitcode::{self, Kind};
+use crate::json::{self, Value};
+use crate::{frames, order, parse_kv, state_dir, summary};
+
+/// Flags the matrix sets for its children itself.
+const OWN: [&str; 6] = ["--node-versions", "--node-sample", "--node", "--summary-out", "--keep-going", "--interactive"];
+
+/// One version's result, from its summary.
+struct Run {
+  ok: usize,
+  failed: Vec<usize>,
+}
+
+fn outcome(path: &std::path::Path) -> Option<Run> {
+  let doc = json::read(path).ok()?;
+  let Some(Value::Arr(records)) = doc.get("frames") else {
+    return None;
+  };
+  let mut ok = 0;
+  let mut failed = Vec::new();
+  for r in records {
+    let Some(n) = summary::frame_of(r) else {
+      continue;
+    };
+    match r.get("status").and_then(Value::as_str) {
+      Some("ok") => ok += 1,
+      Some("failed") => failed.push(n),
+      _ => {}
+    }
+  }
+  Some(Run { ok, failed })
+}
+
+pub fn run(args: &[String], script: Script) {
+  let spec = parse_kv(args, "--node-versions").unwrap_or_default();
+  let versions: Vec<u32> = spec
+    .split(',')
+    .map(str::trim)
+    .filter(|v| !v.is_empty())
+    .map(|v| {
+      v.parse().unwrap_or_else(|_| {
+        exitcode::usage(format!("invalid --node-versions={spec} (expected major versions like 18,20,22)"));
+      })
+    })
+    .collect();
+  if versions.is_empty() {
+    exitcode::usage("--node-versions needs at least one version");
+  }
+  let Some(manager) = exec::node_manager() else {
+    exitcode::fail(Kind::Environment, "--node-versions needs fnm or volta on PATH");
+  };
+  if parse_kv(args, "--workers").is_some() {
+    exitcode::usage("--node-versions only runs local builds, not with --workers");
+  }
+
+  let mut child_args: Vec<String> = args
+    .iter()
+    .filter(|a| !OWN.iter().any(|k| a.starts_with(&format!("{k}="))))
+    .cloned()
+    .collect();
+  if let Some(count) = parse_kv(args, "--node-sample").and_then(|v| v.parse::<usize>().ok()) {
+    let found = frames::selected(args);
+    let sample: Vec<usize> = order::canary(&found, count).into_iter().collect();
+    let list: Vec<String> = sample.iter().map(|n| n.to_string()).collect();
+    child_args.retain(|a| !a.starts_with("--frames="));
+    child_args.push(format!("--frames={}", list.join(",")));
+    eprintln!("node-versions: sample of {} frame(s): {}", sample.len(), frames::fmt_ranges(&sample));
+  }
+  let exe = std::env::current_exe().unwrap_or_else(|_| "framectl".into());
+
+  let mut results: Vec<(u32, Option<Run>)> = Vec::new();
+  for &v in &versions {
+    eprintln!("node-versions: node {v} via {manager}");
+    let summary = state_dir().join(format!("node-{v}.json"));
+    let _ = std::fs::remove_file(&summary);
+    let status = Command::new(&exe)
+      .arg(script.name())
+      .args(&child_args)
+      .arg(format!("--node={v}"))
+      .arg("--keep-going=1")
+      .arg("--interactive=0")
+      .arg(format!("--summary-out={}", summary.display()))
+      .status();
+    if let Err(e) = status {
+      eprintln!("node-versions: node {v}: {e}");
+    }
+    results.push((v, outcome(&summary)));
+  }
+
+  eprintln!("node-versions: {}", script.name());
+  let mut by_frame: BTreeMap<usize, Vec<u32>> = BTreeMap::new();
+  let mut bad = false;
+  for (v, r) in &results {
+    match r {
+      Some(r) if r.failed.is_empty() => eprintln!("  node {v}: {} ok", r.ok),
+      Some(r) => {
+        bad = true;
+        eprintln!("  node {v}: {} ok, {} failed: {}", r.ok, r.failed.len(), frames::fmt_ranges(&r.failed));
+        for &n in &r.failed {
+          by_frame.entry(n).or_default().push(*v);
+        }
+      }
+      None => {
+        bad = true;
+        eprintln!("  node {v}: did not run (no summary)");
+      }
+    }
+  }
+  // Frames that fail everywhere are a build problem, not a Node one.
+  let partial: Vec<String> = by_frame
+    .iter()
+    .filter(|(_, vs)| vs.len() < results.len())
+    .map(|(n, vs)| format!("{n} (node {})", vs.iter().map(u32::to_string).collect::<Vec<_>>().join(",")))
+    .collect();
+  if !partial.is_empty() {
+    eprintln!("node-versions: fail only on some versions: {}", partial.join(", "));
+  }
+  if bad {
+    exitcode::exit(Kind::Build, "node-versions: failed on at least one version", Vec::new());
+  }
+}
@@ -0,0 +1,188 @@
+//! `optimize-assets`: re-encode the source frame images in place, in
+//! parallel, keeping a result only when it is smaller and still decodes.
+//!
+//! Sources stay PNG: the generator reads them with pngjs, and frame packages
+//! embed a bitmap sampled from each image rather than referencing the file,
+//! so there are no per-frame asset references to rewrite for WebP or AVIF.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::frames::{self, fmt_ranges};
+use crate::gc::fmt_bytes;
+use crate::png;
+use crate::queue::par_each;
+use crate::{default_concurrency, parse_bool, parse_kv};
+
+#[derive(Clone, Copy)]
+enum Tool {
+  /// Lossless; pixels must come back identical.
+  Oxipng,
+  /// Palette reduction to `colors`; only the size must match.
+  Pngquant { colors: u32 },
+}
+
+impl Tool {
+  fn name(self) -> &'static str {
+    match self {
+      Tool::Oxipng => "oxipng",
+      Tool::Pngquant { .. } => "pngquant",
+    }
+  }
+
+  /// Re-encode `src` into `tmp`.
+  fn command(self, src: &Path, tmp: &Path) -> Command {
+    let mut c = Command::new(self.name());
+    match self {
+      Tool::Oxipng => {
+        c.args(["-o", "4", "--strip", "safe", "--quiet", "--out"]).arg(tmp).arg(src);
+      }
+      Tool::Pngquant { colors } => {
+        c.args(["--force", "--strip", "--output"]).arg(tmp).arg(colors.to_string()).arg(src);
+      }
+    }
+    c
+  }
+}
+
+/// Re-encode `src`; `Ok` with the bytes saved, 0 if it is kept as is.
+fn optimize(tool: Tool, src: &Path, dry_run: bool) -> Result<u64, String> {
+  let before = std::fs::metadata(src).map_err(|e| e.to_string())?.len();
+  let tmp: PathBuf = src.with_extension("opt.tmp.png");
+  let out = tool
+    .command(src, &tmp)
+    .stdin(Stdio::null())
+    .stdout(Stdio::null())
+    .stderr(Stdio::piped())
+    .output()
+    .map_err(|e| format!("{}: {e}", tool.name()))?;
+  let res = if out.status.success() {
+    check(tool, src, &tmp, before)
+  } else {
+    let err = String::from_utf8_lossy(&out.stderr);
+    Err(format!("{} exited with {}: {}", tool.name(), out.status, err.trim()))
+  };
+  match res {
+    Ok(after) if after < before && !dry_run => {
+      std::fs::rename(&tmp, src).map_err(|e| e.to_string())?;
+      Ok(before - after)
+    }
+    Ok(after) => {
+      let _ = std::fs::remove_file(&tmp);
+      Ok(before.saturating_sub(after))
+    }
+    Err(e) => {
+      let _ = std::fs::remove_file(&tmp);
+      Err(e)
+    }
+  }
+}
+
+/// Size of the re-encoded `tmp`, once it decodes to what `src` shows.
+fn check(tool: Tool, src: &Path, tmp: &Path, before: u64) -> Result<u64, String> {
+  let after = std::fs::metadata(tmp).map_err(|e| format!("no output: {e}"))?.len();
+  if after >= before {
+    return Ok(after);
+  }
+  let old = png::decode_file(src)?;
+  let new = png::decode_file(tmp).map_err(|e| format!("re-encoded image does not decode: {e}"))?;
+  if (old.width, old.height) != (new.width, new.height) {
+    return Err(format!(
+      "re-encoded image is {}x{}, was {}x{}",
+      new.width, new.height, old.width, old.height
+    ));
+  }
+  if matches!(tool, Tool::Oxipng) && old.rgb != new.rgb {
+    return Err("lossless re-encode changed pixels".to_string());
+  }
+  Ok(after)
+}
+
+pub fn run(args: &[String]) {
+  let src_dir = parse_kv(args, "--frames-dir")
+    .map(PathBuf::from)
+    .unwrap_or_else(frames::sources_dir);
+  let lossy = parse_kv(args, "--lossy").and_then(|v| parse_bool(&v)).unwrap_or(false);
+  let colors: u32 = parse_kv(args, "--colors")
+    .and_then(|v| v.parse().ok())
+    .filter(|c| (2..=256).contains(c))
+    .unwrap_or(16);
+  let dry_run = parse_kv(args, "--dry-run").and_then(|v| parse_bool(&v)).unwrap_or(false);
+  let concurrency: usize = parse_kv(args, "--concurrency")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or_else(default_concurrency);
+  let (start, end) = frames::range_args(args);
+  let tool = if lossy { Tool::Pngquant { colors } } else { Tool::Oxipng };
+
+  let found = Command::new(tool.name())
+    .arg("--version")
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .status()
+    .is_ok();
+  if !found {
+    eprintln!("optimize-assets: `{}` not found on PATH", tool.name());
+    std::process::exit(2);
+  }
+
+  let images: Vec<(usize, PathBuf)> = frames::discover_sources(&src_dir)
+    .into_iter()
+    .filter(|n| (start..=end).contains(n))
+    .filter_map(|n| frames::source_image(&src_dir, n).map(|p| (n, p)))
+    .collect();
+  if images.is_empty() {
+    eprintln!("optimize-assets: no frame*.png images in {}", src_dir.display());
+    std::process::exit(2);
+  }
+  eprintln!(
+    "optimize-assets: {} image(s) in {} with {}{} concurrency={concurrency}{}",
+    images.len(),
+    src_dir.display(),
+    tool.name(),
+    if lossy { format!(" colors={colors}") } else { String::new() },
+    if dry_run { " dry_run=1" } else { "" }
+  );
+
+  let total_in = AtomicU64::new(0);
+  let saved = AtomicU64::new(0);
+  let smaller = AtomicUsize::new(0);
+  let failed: Mutex<Vec<(usize, String)>> = Mutex::new(Vec::new());
+  par_each(&images, concurrency, |(n, path)| {
+    total_in.fetch_add(path.metadata().map(|m| m.len()).unwrap_or(0), Ordering::Relaxed);
+    match optimize(tool, path, dry_run) {
+      Ok(0) => {}
+      Ok(s) => {
+        smaller.fetch_add(1, Ordering::Relaxed);
+        saved.fetch_add(s, Ordering::Relaxed);
+      }
+      Err(e) => failed.lock().unwrap().push((*n, e)),
+    }
+  });
+
+  let mut failed = failed.into_inner().unwrap();
+  if !failed.is_empty() {
+    failed.sort();
+    let frames: Vec<usize> = failed.iter().map(|(n, _)| *n).collect();
+    eprintln!(
+      "optimize-assets: {} image(s) left as they were: {} (frame {}: {})",
+      frames.len(),
+      fmt_ranges(&frames),
+      failed[0].0,
+      failed[0].1
+    );
+  }
+  let (total_in, saved) = (total_in.into_inner(), saved.into_inner());
+  eprintln!(
+    "optimize-assets: {} image(s) smaller, {} {} of {} ({:.1}%)",
+    smaller.into_inner(),
+    if dry_run { "would save" } else { "saved" },
+    fmt_bytes(saved),
+    fmt_bytes(total_in),
+    saved as f64 * 100.0 / total_in.max(1) as f64
+  );
+  if !failed.is_empty() {
+    std::process::exit(1);
+  }
+}
@@ -0,0 +1,128 @@
+//! `--order` and `--canary`: which frames the queue hands out first.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::exec::Script;
+use crate::weights::{self, Weights};
+use crate::{fmt_dur, history, summary};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Order {
+  Sequential,
+  Shuffle,
+  /// Frames that failed in the last run, then the rest.
+  FailedFirst,
+  /// Longest recorded duration (or heaviest `--weights`) first, so long
+  /// poles don't finish last.
+  SlowestFirst,
+}
+
+impl Order {
+  pub fn parse(s: &str) -> Option<Order> {
+    match s {
+      "sequential" => Some(Order::Sequential),
+      "shuffle" => Some(Order::Shuffle),
+      "failed-first" => Some(Order::FailedFirst),
+      "slowest-first" => Some(Order::SlowestFirst),
+      _ => None,
+    }
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Order::Sequential => "sequential",
+      Order::Shuffle => "shuffle",
+      Order::FailedFirst => "failed-first",
+      Order::SlowestFirst => "slowest-first",
+    }
+  }
+}
+
+/// Reorder `plan` in place, returning a note for the banner.
+pub fn apply(order: Order, plan: &mut [usize], stages: &[Script], weights: Option<&Weights>, slots: usize) -> String {
+  match order {
+    Order::Sequential => String::new(),
+    Order::Shuffle => {
+      shuffle(plan, time_seed());
+      String::new()
+    }
+    Order::FailedFirst => {
+      let script = stages[stages.len() - 1];
+      let failed = summary::last_failed(script);
+      // Stable: both groups keep their sequential order.
+      plan.sort_by_key(|n| !failed.contains(n));
+      let hits = plan.iter().filter(|n| failed.contains(n)).count();
+      format!("{hits} failed last run")
+    }
+    Order::SlowestFirst => {
+      let hist = history::load_stages(stages);
+      let cost: BTreeMap<usize, f64> = plan.iter().copied().zip(weights::costs(plan, &hist, weights)).collect();
+      let before = makespan(plan.iter().map(|n| cost[n]), slots);
+      plan.sort_by(|a, b| cost[b].total_cmp(&cost[a]));
+      let after = makespan(plan.iter().map(|n| cost[n]), slots);
+      let known = plan.iter().filter(|n| hist.contains_key(n)).count();
+      let weighted = weights.map(|w| format!(", weights for {}", w.listed(plan))).unwrap_or_default();
+      let gain = if known > 0 && after < before {
+        let secs = |s: f64| fmt_dur(Duration::from_secs_f64(s));
+        format!("; est. {} vs {} in frame order", secs(after), secs(before))
+      } else {
+        String::new()
+      };
+      format!("history for {known}/{} frames{weighted}{gain}", plan.len())
+    }
+  }
+}
+
+/// Wall time of running `costs` in order on `slots` builders, each frame
+/// going to whichever frees up first.
+fn makespan(costs: impl Iterator<Item = f64>, slots: usize) -> f64 {
+  let mut free = vec![0.0f64; slots.max(1)];
+  for c in costs {
+    let slot = free.iter_mut().min_by(|a, b| a.total_cmp(b)).unwrap();
+    *slot += c;
+  }
+  free.into_iter().fold(0.0, f64::max)
+}
+
+/// `--canary`: the first and last frame of `plan` plus random ones from
+/// between, `count` in all, in frame order.
+pub fn canary(plan: &[usize], count: usize) -> BTreeSet<usize> {
+  let mut sorted = plan.to_vec();
+  sorted.sort_unstable();
+  if count >= sorted.len() {
+    return sorted.into_iter().collect();
+  }
+  let mut pick: BTreeSet<usize> = BTreeSet::new();
+  if count == 0 {
+    return pick;
+  }
+  pick.insert(sorted[0]);
+  if count > 1 {
+    pick.insert(sorted[sorted.len() - 1]);
+    let mut middle = sorted[1..sorted.len() - 1].to_vec();
+    shuffle(&mut middle, time_seed());
+    pick.extend(middle.into_iter().take(count - 2));
+  }
+  pick
+}
+
+fn time_seed() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_nanos() as u64)
+    .unwrap_or(0)
+    ^ std::process::id() as u64
+}
+
+/// Fisher-Yates with xorshift64; no need for anything stronger.
+fn shuffle(plan: &mut [usize], seed: u64) {
+  let mut x = seed | 1;
+  for i in (1..plan.len()).rev() {
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    plan.swap(i, (x % (i as u64 + 1)) as usize);
+  }
+}
+
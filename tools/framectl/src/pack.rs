@@ -0,0 +1,212 @@
+//! `pack` / `unpack`: every frame dist in one zstd tarball, so CI shards can
+//! hand their build output to a deploy job as a single artifact. Shells out
+//! to `tar` and `zstd` (multithreaded with `-T0`).
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::frames::{self, ENTRY};
+use crate::{parse_kv, state_dir, targets};
+
+const DEFAULT_ARCHIVE: &str = "dists.tar.zst";
+
+fn require(tool: &str) {
+  let found = Command::new(tool)
+    .arg("--version")
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .status()
+    .is_ok();
+  if !found {
+    eprintln!("`{tool}` not found on PATH");
+    std::process::exit(2);
+  }
+}
+
+fn fail(cmd: &str, msg: impl std::fmt::Display) -> ! {
+  eprintln!("{cmd}: {msg}");
+  std::process::exit(1);
+}
+
+pub fn pack(args: &[String]) {
+  let out = parse_kv(args, "--out").unwrap_or_else(|| DEFAULT_ARCHIVE.to_string());
+  let level: u32 = parse_kv(args, "--level")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(3)
+    .clamp(1, 19);
+  require("tar");
+  require("zstd");
+
+  targets::dists("pack", args);
+  let (built, missing): (Vec<usize>, Vec<usize>) = frames::selected(args)
+    .into_iter()
+    .partition(|&n| frames::dist_dir(n).is_dir());
+  if !missing.is_empty() {
+    eprintln!(
+      "pack: warning: {} frame(s) have no dist: {}",
+      missing.len(),
+      frames::fmt_ranges(&missing)
+    );
+  }
+  if built.is_empty() {
+    fail("pack", "no frame dists to pack");
+  }
+
+  // Thousands of paths don't fit on a command line; tar reads them from a file.
+  let list = state_dir().join("pack-files.txt");
+  let sub = targets::active().map(|t| format!("/{t}")).unwrap_or_default();
+  let text: String = built.iter().map(|n| format!("frame-{n:04}/dist{sub}\n")).collect();
+  if let Err(e) = std::fs::create_dir_all(state_dir()).and_then(|_| std::fs::write(&list, text)) {
+    fail("pack", format!("write {}: {e}", list.display()));
+  }
+
+  let tmp = format!("{out}.tmp");
+  let mut tar = match Command::new("tar")
+    .arg("-cf")
+    .arg("-")
+    .arg("-C")
+    .arg(frames::frames_dir())
+    .arg("-T")
+    .arg(list.canonicalize().unwrap_or_else(|_| list.clone()))
+    .stdout(Stdio::piped())
+    .spawn()
+  {
+    Ok(c) => c,
+    Err(e) => fail("pack", format!("tar: {e}")),
+  };
+  let zstd = Command::new("zstd")
+    .args(["-q", "-f", "-T0", &format!("-{level}"), "-o", &tmp])
+    .stdin(tar.stdout.take().map(Stdio::from).unwrap_or_else(Stdio::null))
+    .status();
+  let tar_ok = tar.wait().map(|s| s.success()).unwrap_or(false);
+  let _ = std::fs::remove_file(&list);
+  match zstd {
+    Ok(s) if s.success() && tar_ok => {}
+    Ok(_) => {
+      let _ = std::fs::remove_file(&tmp);
+      fail("pack", "tar | zstd failed");
+    }
+    Err(e) => fail("pack", format!("zstd: {e}")),
+  }
+  if let Err(e) = std::fs::rename(&tmp, &out) {
+    fail("pack", format!("rename {tmp}: {e}"));
+  }
+  let size = Path::new(&out).metadata().map(|m| m.len()).unwrap_or(0);
+  eprintln!("pack: wrote {out} ({} frames, {}KB)", built.len(), size / 1024);
+}
+
+/// Decompressed tarball of `archive`, as a reader for tar's stdin.
+fn decompress(archive: &str) -> std::process::Child {
+  match Command::new("zstd")
+    .args(["-d", "-q", "-c", archive])
+    .stdout(Stdio::piped())
+    .spawn()
+  {
+    Ok(c) => c,
+    Err(e) => fail("unpack", format!("zstd: {e}")),
+  }
+}
+
+/// The dists in the archive, as frame and target (`frame-XXXX/dist/NAME`
+/// holding a remote entry, from `pack --target=NAME`); anything outside
+/// `frame-XXXX/dist` is refused.
+fn list(archive: &str) -> Vec<(usize, Option<String>)> {
+  let mut z = decompress(archive);
+  let tar = Command::new("tar")
+    .args(["-tf", "-"])
+    .stdin(z.stdout.take().map(Stdio::from).unwrap_or_else(Stdio::null))
+    .stdout(Stdio::piped())
+    .spawn();
+  let mut names = String::new();
+  let tar_ok = match tar {
+    Ok(mut t) => {
+      let _ = t.stdout.take().map(|mut o| o.read_to_string(&mut names));
+      t.wait().map(|s| s.success()).unwrap_or(false)
+    }
+    Err(e) => fail("unpack", format!("tar: {e}")),
+  };
+  if !z.wait().map(|s| s.success()).unwrap_or(false) || !tar_ok {
+    fail("unpack", format!("{archive}: not a readable .tar.zst"));
+  }
+
+  let mut frames: Vec<usize> = Vec::new();
+  let mut targeted: Vec<(usize, String)> = Vec::new();
+  for name in names.lines() {
+    let path = PathBuf::from(name.trim_start_matches("./"));
+    let mut parts = path.components().map(|c| c.as_os_str().to_string_lossy().into_owned());
+    let n = parts
+      .next()
+      .and_then(|d| d.strip_prefix("frame-").and_then(|id| id.parse::<usize>().ok()));
+    let in_dist = parts.next().as_deref() == Some("dist") && !name.contains("..");
+    match n {
+      Some(n) if in_dist => {
+        if frames.last() != Some(&n) {
+          frames.push(n);
+        }
+        let rest: Vec<String> = parts.collect();
+        if rest.len() > 1 && rest[1..].join("/") == ENTRY {
+          targeted.push((n, rest[0].clone()));
+        }
+      }
+      _ => fail("unpack", format!("{archive}: unexpected entry {name:?}")),
+    }
+  }
+  frames.sort_unstable();
+  frames.dedup();
+  targeted.sort_unstable();
+  targeted.dedup();
+  let mut out: Vec<(usize, Option<String>)> = Vec::new();
+  for n in frames {
+    let ts: Vec<Option<String>> = targeted.iter().filter(|(f, _)| *f == n).map(|(_, t)| Some(t.clone())).collect();
+    if ts.is_empty() {
+      out.push((n, None));
+    } else {
+      out.extend(ts.into_iter().map(|t| (n, t)));
+    }
+  }
+  out
+}
+
+pub fn unpack(args: &[String]) {
+  let archive = parse_kv(args, "--in").unwrap_or_else(|| DEFAULT_ARCHIVE.to_string());
+  require("tar");
+  require("zstd");
+  if !Path::new(&archive).is_file() {
+    fail("unpack", format!("{archive}: no such file"));
+  }
+
+  let dists = list(&archive);
+  let frames_dir = frames::frames_dir();
+  let mut found: Vec<usize> = dists.iter().map(|(n, _)| *n).collect();
+  found.dedup();
+  for &n in &found {
+    if !frames::frame_dir(n).is_dir() {
+      eprintln!("unpack: warning: frame-{n:04} is not in this workspace; restoring its dist anyway");
+    }
+  }
+  // Replace, don't merge: stale chunks would otherwise linger. A target's
+  // dist replaces only that target's.
+  for (n, target) in &dists {
+    let dist = frames::frame_dir(*n).join("dist");
+    let _ = std::fs::remove_dir_all(target.as_ref().map_or(dist.clone(), |t| dist.join(t)));
+  }
+
+  let mut z = decompress(&archive);
+  let tar = Command::new("tar")
+    .args(["-xf", "-", "-C"])
+    .arg(&frames_dir)
+    .stdin(z.stdout.take().map(Stdio::from).unwrap_or_else(Stdio::null))
+    .status();
+  let z_ok = z.wait().map(|s| s.success()).unwrap_or(false);
+  match tar {
+    Ok(s) if s.success() && z_ok => {}
+    Ok(_) => fail("unpack", "zstd | tar failed"),
+    Err(e) => fail("unpack", format!("tar: {e}")),
+  }
+  eprintln!(
+    "unpack: restored {} frame dist(s): {}",
+    found.len(),
+    frames::fmt_ranges(&found)
+  );
+}
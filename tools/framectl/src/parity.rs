@@ -0,0 +1,122 @@
+//! `parity`: whether the frame packages cover the source video. `ffprobe`
+//! gives the video's duration and frame rate; at `--fps` (the video's own
+//! rate by default) that is the number of frames the extraction should have
+//! produced, numbered from 1. Frames missing from `apps/frames`, or packages
+//! past the end of the video, are reported before a build or deploy is spent
+//! on an incomplete set.
+
+use std::collections::BTreeMap;
+use std::process::{Command, Stdio};
+
+use crate::frames::{self, fmt_ranges};
+use crate::parse_kv;
+
+/// What ffprobe says about the first video stream.
+struct Probe {
+  duration: Option<f64>,
+  rate: Option<f64>,
+  nb_frames: Option<usize>,
+}
+
+/// `30/1`, `30000/1001` or a plain number.
+fn parse_rate(s: &str) -> Option<f64> {
+  let r = match s.split_once('/') {
+    Some((a, b)) => a.parse::<f64>().ok()? / b.parse::<f64>().ok()?,
+    None => s.parse().ok()?,
+  };
+  (r.is_finite() && r > 0.0).then_some(r)
+}
+
+fn probe(input: &str) -> Result<Probe, String> {
+  let out = Command::new("ffprobe")
+    .args(["-v", "error", "-select_streams", "v:0"])
+    .args(["-show_entries", "stream=nb_frames,r_frame_rate,duration:format=duration"])
+    .args(["-of", "flat"])
+    .arg(input)
+    .stdin(Stdio::null())
+    .output()
+    .map_err(|e| format!("ffprobe: {e} (is ffmpeg installed?)"))?;
+  if !out.status.success() {
+    let err = String::from_utf8_lossy(&out.stderr);
+    return Err(format!("ffprobe: {}", err.lines().last().unwrap_or("failed").trim()));
+  }
+  // `streams.stream.0.duration="219.033333"`, `format.duration="219.04"`.
+  let fields: BTreeMap<String, String> = String::from_utf8_lossy(&out.stdout)
+    .lines()
+    .filter_map(|l| l.split_once('='))
+    .map(|(k, v)| (k.trim().to_string(), v.trim().trim_matches('"').to_string()))
+    .collect();
+  let stream = |key: &str| fields.get(&format!("streams.stream.0.{key}"));
+  if stream("r_frame_rate").is_none() && stream("duration").is_none() {
+    return Err("no video stream".to_string());
+  }
+  Ok(Probe {
+    duration: stream("duration")
+      .or_else(|| fields.get("format.duration"))
+      .and_then(|d| d.parse().ok())
+      .filter(|d: &f64| d.is_finite() && *d > 0.0),
+    rate: stream("r_frame_rate").and_then(|r| parse_rate(r)),
+    nb_frames: stream("nb_frames").and_then(|n| n.parse().ok()).filter(|&n| n > 0),
+  })
+}
+
+pub fn run(args: &[String]) {
+  let Some(input) = parse_kv(args, "--input") else {
+    eprintln!("parity: --input=VIDEO is required");
+    std::process::exit(2);
+  };
+  let fps = parse_kv(args, "--fps").map(|v| {
+    parse_rate(&v).unwrap_or_else(|| {
+      eprintln!("parity: --fps must be a positive number");
+      std::process::exit(2);
+    })
+  });
+  if !std::path::Path::new(&input).is_file() {
+    eprintln!("parity: {input} not found");
+    std::process::exit(2);
+  }
+  let p = probe(&input).unwrap_or_else(|e| {
+    eprintln!("parity: {input}: {e}");
+    std::process::exit(1);
+  });
+
+  // The stream's own count is exact when it is there and the rate is kept;
+  // resampling (ffmpeg's fps filter) rounds duration x rate.
+  let same_rate = match (fps, p.rate) {
+    (None, _) => true,
+    (Some(f), Some(r)) => (f - r).abs() < 1e-3,
+    (Some(_), None) => false,
+  };
+  let Some(rate) = fps.or(p.rate) else {
+    eprintln!("parity: {input}: ffprobe reported no frame rate; pass --fps");
+    std::process::exit(1);
+  };
+  let expected = match (p.nb_frames.filter(|_| same_rate), p.duration) {
+    (Some(n), _) => n,
+    (None, Some(d)) => (d * rate).round() as usize,
+    (None, None) => {
+      eprintln!("parity: {input}: ffprobe reported neither a duration nor a frame count");
+      std::process::exit(1);
+    }
+  };
+  let found = frames::discover(&frames::frames_dir());
+  eprintln!(
+    "parity: {input}: {} at {rate:.3} fps -> {expected} frame(s); {} package(s) under apps/frames",
+    p.duration.map(|d| format!("{d:.2}s")).unwrap_or_else(|| "unknown duration".to_string()),
+    found.len()
+  );
+
+  let missing: Vec<usize> = (1..=expected).filter(|n| found.binary_search(n).is_err()).collect();
+  let excess: Vec<usize> = found.iter().copied().filter(|&n| n == 0 || n > expected).collect();
+  if !missing.is_empty() {
+    eprintln!("  missing {} frame(s): {}", missing.len(), fmt_ranges(&missing));
+  }
+  if !excess.is_empty() {
+    eprintln!("  {} package(s) beyond the video: {}", excess.len(), fmt_ranges(&excess));
+  }
+  if missing.is_empty() && excess.is_empty() {
+    eprintln!("parity: ok");
+  } else {
+    std::process::exit(1);
+  }
+}
@@ -0,0 +1,390 @@
+//! Small regex for matching build output lines against user patterns:
+//! literals, `.`, classes (`[a-z]`, `[^0-9]`, `\d`, `\w`, `\s`), groups with
+//! `|`, the usual quantifiers (`*`, `+`, `?`, `{n,m}`), `^`, `$`, `\b`, and a
+//! leading `(?i)` for case-insensitive matching.
+
+enum Item {
+  Range(char, char),
+  /// `\d`, `\w`, `\s` and their negations.
+  Perl(char),
+}
+
+enum Node {
+  Char(char),
+  Any,
+  Class { items: Vec<Item>, negated: bool },
+  Start,
+  End,
+  WordBoundary,
+  Group(Vec<Vec<Node>>),
+  Repeat { node: Box<Node>, min: usize, max: Option<usize> },
+}
+
+pub struct Regex {
+  src: String,
+  alts: Vec<Vec<Node>>,
+  icase: bool,
+}
+
+struct Parser<'a> {
+  chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl Parser<'_> {
+  fn alts(&mut self, depth: usize) -> Result<Vec<Vec<Node>>, String> {
+    let mut alts = vec![self.seq(depth)?];
+    while self.chars.peek() == Some(&'|') {
+      self.chars.next();
+      alts.push(self.seq(depth)?);
+    }
+    Ok(alts)
+  }
+
+  fn seq(&mut self, depth: usize) -> Result<Vec<Node>, String> {
+    let mut seq = Vec::new();
+    while let Some(&c) = self.chars.peek() {
+      if c == '|' || (c == ')' && depth > 0) {
+        break;
+      }
+      let atom = self.atom(depth)?;
+      seq.push(self.quantified(atom)?);
+    }
+    Ok(seq)
+  }
+
+  fn atom(&mut self, depth: usize) -> Result<Node, String> {
+    let c = self.chars.next().ok_or("unexpected end")?;
+    Ok(match c {
+      '.' => Node::Any,
+      '^' => Node::Start,
+      '$' => Node::End,
+      '(' => {
+        if self.chars.peek() == Some(&'?') {
+          self.chars.next();
+          if self.chars.next() != Some(':') {
+            return Err("only (?:...) groups are supported".to_string());
+          }
+        }
+        let alts = self.alts(depth + 1)?;
+        if self.chars.next() != Some(')') {
+          return Err("unclosed (".to_string());
+        }
+        Node::Group(alts)
+      }
+      ')' => return Err("unmatched )".to_string()),
+      '[' => self.class()?,
+      '\\' => match self.chars.next().ok_or("trailing \\")? {
+        'b' => Node::WordBoundary,
+        p @ ('d' | 'D' | 'w' | 'W' | 's' | 'S') => Node::Class {
+          items: vec![Item::Perl(p)],
+          negated: false,
+        },
+        e => Node::Char(escaped(e)),
+      },
+      '*' | '+' | '?' => return Err(format!("nothing to repeat before {c}")),
+      c => Node::Char(c),
+    })
+  }
+
+  fn class(&mut self) -> Result<Node, String> {
+    let negated = self.chars.peek() == Some(&'^');
+    if negated {
+      self.chars.next();
+    }
+    let mut items = Vec::new();
+    let mut first = true;
+    loop {
+      let c = self.chars.next().ok_or("unclosed [")?;
+      if c == ']' && !first {
+        break;
+      }
+      first = false;
+      let lo = match c {
+        '\\' => match self.chars.next().ok_or("unclosed [")? {
+          p @ ('d' | 'D' | 'w' | 'W' | 's' | 'S') => {
+            items.push(Item::Perl(p));
+            continue;
+          }
+          e => escaped(e),
+        },
+        c => c,
+      };
+      let mut ahead = self.chars.clone();
+      if ahead.next() == Some('-') && ahead.peek().is_some_and(|&c| c != ']') {
+        self.chars.next();
+        let hi = match self.chars.next().ok_or("unclosed [")? {
+          '\\' => escaped(self.chars.next().ok_or("unclosed [")?),
+          c => c,
+        };
+        if hi < lo {
+          return Err(format!("bad range {lo}-{hi}"));
+        }
+        items.push(Item::Range(lo, hi));
+      } else {
+        items.push(Item::Range(lo, lo));
+      }
+    }
+    Ok(Node::Class { items, negated })
+  }
+
+  fn quantified(&mut self, atom: Node) -> Result<Node, String> {
+    let (min, max) = match self.chars.peek() {
+      Some('*') => (0, None),
+      Some('+') => (1, None),
+      Some('?') => (0, Some(1)),
+      Some('{') => {
+        // Not a valid count: a literal `{`, as most engines do.
+        let mut ahead = self.chars.clone();
+        ahead.next();
+        let body: String = ahead.by_ref().take_while(|&c| c != '}').collect();
+        let Some(range) = counts(&body) else {
+          return Ok(atom);
+        };
+        self.chars = ahead;
+        if matches!(atom, Node::Start | Node::End | Node::WordBoundary) {
+          return Err("nothing to repeat".to_string());
+        }
+        return Ok(self.lazy(Node::Repeat { node: Box::new(atom), min: range.0, max: range.1 }));
+      }
+      _ => return Ok(atom),
+    };
+    self.chars.next();
+    if matches!(atom, Node::Start | Node::End | Node::WordBoundary) {
+      return Err("nothing to repeat".to_string());
+    }
+    Ok(self.lazy(Node::Repeat { node: Box::new(atom), min, max }))
+  }
+
+  /// A trailing `?` (lazy) changes which match is found, not whether one is.
+  fn lazy(&mut self, node: Node) -> Node {
+    if self.chars.peek() == Some(&'?') {
+      self.chars.next();
+    }
+    node
+  }
+}
+
+fn escaped(e: char) -> char {
+  match e {
+    'n' => '\n',
+    't' => '\t',
+    'r' => '\r',
+    e => e,
+  }
+}
+
+/// `n`, `n,` or `n,m` from inside `{}`.
+fn counts(body: &str) -> Option<(usize, Option<usize>)> {
+  match body.split_once(',') {
+    None => body.parse().ok().map(|n| (n, Some(n))),
+    Some((a, "")) => a.parse().ok().map(|n| (n, None)),
+    Some((a, b)) => {
+      let (a, b): (usize, usize) = (a.parse().ok()?, b.parse().ok()?);
+      (a <= b).then_some((a, Some(b)))
+    }
+  }
+}
+
+fn is_word(c: char) -> bool {
+  c.is_alphanumeric() || c == '_'
+}
+
+impl Regex {
+  pub fn new(src: &str) -> Result<Regex, String> {
+    let (icase, body) = match src.strip_prefix("(?i)") {
+      Some(rest) => (true, rest),
+      None => (false, src),
+    };
+    let mut p = Parser { chars: body.chars().peekable() };
+    let alts = p.alts(0)?;
+    if p.chars.next().is_some() {
+      return Err("unmatched )".to_string());
+    }
+    Ok(Regex {
+      src: src.to_string(),
+      alts,
+      icase,
+    })
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.src
+  }
+
+  /// Whether the pattern matches anywhere in `text`.
+  pub fn is_match(&self, text: &str) -> bool {
+    let t: Vec<char> = text.chars().collect();
+    let starts: Vec<usize> = (0..=t.len()).collect();
+    !self.alts_ends(&self.alts, &t, &starts).is_empty()
+  }
+
+  // Matching tracks the set of positions each piece can end at, starting
+  // from every position at once, so a long line costs time in its length
+  // rather than stack, and nothing backtracks.
+
+  fn alts_ends(&self, alts: &[Vec<Node>], t: &[char], from: &[usize]) -> Vec<usize> {
+    let mut out: Vec<usize> = alts.iter().flat_map(|seq| self.seq_ends(seq, t, from)).collect();
+    out.sort_unstable();
+    out.dedup();
+    out
+  }
+
+  fn seq_ends(&self, seq: &[Node], t: &[char], from: &[usize]) -> Vec<usize> {
+    let mut cur = from.to_vec();
+    for node in seq {
+      if cur.is_empty() {
+        break;
+      }
+      cur = self.ends(node, t, &cur);
+    }
+    cur
+  }
+
+  /// Where `node` can end when started at any of `from` (ascending).
+  fn ends(&self, node: &Node, t: &[char], from: &[usize]) -> Vec<usize> {
+    match node {
+      Node::Start => from.iter().copied().filter(|&i| i == 0).collect(),
+      Node::End => from.iter().copied().filter(|&i| i == t.len()).collect(),
+      Node::WordBoundary => from
+        .iter()
+        .copied()
+        .filter(|&i| {
+          let before = i > 0 && is_word(t[i - 1]);
+          let after = i < t.len() && is_word(t[i]);
+          before != after
+        })
+        .collect(),
+      Node::Group(alts) => self.alts_ends(alts, t, from),
+      Node::Repeat { node, min, max } => {
+        let mut cur = from.to_vec();
+        for _ in 0..*min {
+          if cur.is_empty() {
+            break;
+          }
+          cur = self.ends(node, t, &cur);
+        }
+        // Each further round steps only from positions not reached
+        // before: reaching one again, later, can't lead anywhere new.
+        let mut seen = vec![false; t.len() + 1];
+        for &i in &cur {
+          seen[i] = true;
+        }
+        let mut out = cur.clone();
+        let (mut fresh, mut count) = (cur, *min);
+        while !fresh.is_empty() && max.is_none_or(|m| count < m) {
+          fresh = self.ends(node, t, &fresh);
+          fresh.retain(|&j| !std::mem::replace(&mut seen[j], true));
+          out.extend_from_slice(&fresh);
+          count += 1;
+        }
+        out.sort_unstable();
+        out
+      }
+      atom => from
+        .iter()
+        .copied()
+        .filter(|&i| i < t.len() && self.single(atom, t[i]))
+        .map(|i| i + 1)
+        .collect(),
+    }
+  }
+
+  fn single(&self, node: &Node, c: char) -> bool {
+    match node {
+      Node::Any => c != '\n',
+      Node::Char(p) => *p == c || (self.icase && p.to_lowercase().eq(c.to_lowercase())),
+      Node::Class { items, negated } => {
+        let hit = |c: char| {
+          items.iter().any(|it| match it {
+            Item::Range(lo, hi) => (*lo..=*hi).contains(&c),
+            Item::Perl(p) => {
+              let m = match p.to_ascii_lowercase() {
+                'd' => c.is_ascii_digit(),
+                'w' => is_word(c),
+                _ => c.is_whitespace(),
+              };
+              m != p.is_ascii_uppercase()
+            }
+          })
+        };
+        let found = hit(c) || (self.icase && (c.to_lowercase().any(hit) || c.to_uppercase().any(hit)));
+        found != *negated
+      }
+      _ => false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn matches(pat: &str, text: &str) -> bool {
+    Regex::new(pat).unwrap().is_match(text)
+  }
+
+  #[test]
+  fn literals_and_anchors() {
+    assert!(matches("warn", "a warning"));
+    assert!(!matches("^warn", "a warning"));
+    assert!(matches("ing$", "a warning"));
+    assert!(matches("a|z", "zed"));
+    assert!(matches("", ""));
+    assert!(matches(r"1\.0", "v1.0"));
+    assert!(!matches(r"1\.0", "v100"));
+  }
+
+  #[test]
+  fn classes() {
+    assert!(matches("[a-c]x", "bx"));
+    assert!(!matches("^[^a-c]x", "bx"));
+    assert!(matches(r"\d\d", "TS2304"));
+    assert!(!matches(r"^\D+$", "TS2304"));
+    assert!(matches(r"[\s-]v", "a-v"));
+    assert!(matches("[]]", "]"));
+    assert!(matches(r"^\w+$", "snake_case1"));
+  }
+
+  #[test]
+  fn counted() {
+    assert!(matches("^a{3}$", "aaa"));
+    assert!(!matches("^a{3}$", "aa"));
+    assert!(matches("^a{2,}$", "aaaa"));
+    assert!(matches("^a{1,2}b$", "aab"));
+    assert!(!matches("^a{1,2}b$", "aaab"));
+    assert!(matches("^(ab){2}$", "abab"));
+    // Not a count: a literal brace.
+    assert!(matches("a{x}", "a{x}"));
+    assert!(matches("^(a|ab)*c$", "abaabc"));
+    assert!(matches("^(a*)*$", "aaa"));
+  }
+
+  #[test]
+  fn word_boundaries() {
+    assert!(matches(r"\bwarn\b", "warn: x"));
+    assert!(!matches(r"\bwarn\b", "forewarned"));
+    assert!(matches(r"\bwarn\b", "(warn)"));
+  }
+
+  #[test]
+  fn case_insensitive() {
+    assert!(matches("(?i)deprecat", "DeprecationWarning"));
+    assert!(!matches("deprecat", "DeprecationWarning"));
+    assert!(matches("(?i)[a-z]+ warning", "PEER WARNING"));
+  }
+
+  #[test]
+  fn errors() {
+    for pat in ["(a", "a)", "*a", "[a", "[z-a]", "^*", "(?=a)", r"a\"] {
+      assert!(Regex::new(pat).is_err(), "{pat}");
+    }
+  }
+
+  #[test]
+  fn long_lines() {
+    let line = format!("warn {}", "y".repeat(200_000));
+    assert!(matches("warn.*y$", &line));
+    assert!(!matches("warn.*x$", &line));
+    assert!(matches(r"^(?:\w|\s)+$", &line));
+  }
+}
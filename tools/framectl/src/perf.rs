@@ -0,0 +1,437 @@
+//! `perf`: load timings of a sample of deployed frames, each mounted in a
+//! page laid out like the host shell, in headless Chrome with a cold cache:
+//! time to the first painted frame, script cost (the part of that not spent
+//! waiting on the network: evaluating the entry and chunks, container init,
+//! mount), long tasks, and bytes transferred. It reports percentiles across
+//! the sample; `--out` keeps them and `--baseline` compares with an earlier
+//! run, so a release that makes playback stutter shows before it ships.
+//!
+//! The page posts its measurements back to a loopback server here rather than
+//! going through `--dump-dom`, whose virtual time would skew the clock.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::frames::{self, fmt_ranges, ENTRY};
+use crate::gc::fmt_bytes;
+use crate::json::{self, Value};
+use crate::queue::par_each;
+use crate::visual::{self, BROWSERS};
+use crate::{parse_kv, playlist, state_dir};
+
+/// Width and height the host shell gives a frame by default.
+const FRAME_SIZE: (usize, usize) = (320, 240);
+
+/// Reported metrics: key in the page's result and `--out`, label.
+const METRICS: [(&str, &str); 4] = [
+  ("loadMs", "first paint"),
+  ("scriptMs", "script"),
+  ("longTaskMs", "long tasks"),
+  ("bytes", "transfer"),
+];
+
+type Results = Arc<Mutex<BTreeMap<usize, String>>>;
+
+/// Page that mounts frame `n` from `entry` the way the host does and posts
+/// its timings (or `{"error": ...}`) to `/result/N`.
+fn harness(n: usize, entry: &str, expose: &str) -> String {
+  let entry = entry.replace('\\', "\\\\").replace('\'', "\\'");
+  let expose = expose.replace('\\', "\\\\").replace('\'', "\\'");
+  let (w, h) = FRAME_SIZE;
+  format!(
+    r#"<!doctype html>
+<html><head><style>
+  html, body {{ margin: 0; background: #000; }}
+  :root {{ --frame-width: {w}px; --frame-height: {h}px; }}
+  #root {{ width: {w}px; height: {h}px; }}
+</style></head>
+<body><div id="root"></div>
+<script>
+  let longTaskMs = 0;
+  try {{
+    new PerformanceObserver((list) => {{
+      for (const t of list.getEntries()) longTaskMs += t.duration;
+    }}).observe({{ type: 'longtask', buffered: true }});
+  }} catch {{}}
+  let sent = false;
+  const send = (r) => {{
+    if (sent) return;
+    sent = true;
+    fetch('/result/{n}', {{ method: 'POST', body: JSON.stringify(r) }});
+  }};
+  const fail = (e) => send({{ error: String(e?.message ?? e) }});
+  window.addEventListener('error', (e) => fail(e.message));
+  window.addEventListener('unhandledrejection', (e) => fail(e.reason));
+  const t0 = performance.now();
+  const s = document.createElement('script');
+  s.src = '{entry}';
+  s.onerror = () => fail('remoteEntry.js did not load');
+  s.onload = async () => {{
+    try {{
+      const entryMs = performance.now() - t0;
+      const c = window['frame_{n:04}'];
+      if (!c) throw new Error('remoteEntry.js did not define frame_{n:04}');
+      const init = globalThis.__webpack_init_sharing__ ?? globalThis.__rspack_init_sharing__;
+      const scopes = globalThis.__webpack_share_scopes__ ?? globalThis.__rspack_share_scopes__;
+      if (typeof init === 'function' && scopes?.default) {{
+        await init('default');
+        await c.init(scopes.default);
+      }}
+      const mod = (await c.get('{expose}'))();
+      mod.mount(document.getElementById('root'));
+      await new Promise((r) => requestAnimationFrame(() => setTimeout(r)));
+      const loadMs = performance.now() - t0;
+      const res = performance.getEntriesByType('resource').filter((r) => r.startTime >= t0);
+      const spans = res.map((r) => [r.startTime, r.responseEnd]).sort((a, b) => a[0] - b[0]);
+      let wait = 0, until = t0;
+      for (const [a, b] of spans) {{
+        if (b > until) {{ wait += b - Math.max(a, until); until = b; }}
+      }}
+      send({{
+        loadMs, entryMs, longTaskMs,
+        scriptMs: Math.max(0, loadMs - wait),
+        resources: res.map((r) => ({{ url: r.name, bytes: r.transferSize || r.encodedBodySize || 0 }})),
+      }});
+    }} catch (e) {{
+      fail(e);
+    }}
+  }};
+  document.head.appendChild(s);
+</script></body></html>
+"#
+  )
+}
+
+/// GET `/perf/N` is frame N's harness; POST `/result/N` records its result.
+fn serve(stream: TcpStream, pages: &BTreeMap<usize, String>, results: &Results) -> std::io::Result<()> {
+  let mut reader = BufReader::new(stream.try_clone()?);
+  let mut line = String::new();
+  reader.read_line(&mut line)?;
+  let mut parts = line.split_whitespace();
+  let method = parts.next().unwrap_or("").to_string();
+  let path = parts.next().unwrap_or("/").to_string();
+  let mut length = 0usize;
+  loop {
+    let mut header = String::new();
+    if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+      break;
+    }
+    if let Some((k, v)) = header.split_once(':') {
+      if k.eq_ignore_ascii_case("content-length") {
+        length = v.trim().parse().unwrap_or(0);
+      }
+    }
+  }
+  let id = |prefix: &str| path.strip_prefix(prefix).and_then(|v| v.parse::<usize>().ok());
+  let (status, body) = match (method.as_str(), id("/perf/"), id("/result/")) {
+    ("GET", Some(n), _) if pages.contains_key(&n) => ("200 OK", pages[&n].clone()),
+    ("POST", _, Some(n)) => {
+      let mut buf = vec![0u8; length];
+      reader.read_exact(&mut buf)?;
+      results.lock().unwrap().insert(n, String::from_utf8_lossy(&buf).into_owned());
+      ("200 OK", String::new())
+    }
+    _ => ("404 Not Found", "not found".to_string()),
+  };
+  let mut w = stream;
+  write!(
+    w,
+    "HTTP/1.0 {status}\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+    body.len()
+  )
+}
+
+/// Bytes `url` transfers, compressed as a browser would ask for it; for
+/// resources whose timing the CDN doesn't expose cross-origin.
+fn transfer_size(url: &str) -> u64 {
+  let out = Command::new("curl")
+    .args(["-sS", "-o", "/dev/null", "-w", "%{size_download}", "--max-time", "20"])
+    .args(["-H", "Accept-Encoding: br, gzip"])
+    .arg(url)
+    .stdin(Stdio::null())
+    .stderr(Stdio::null())
+    .output();
+  out.ok().and_then(|o| String::from_utf8_lossy(&o.stdout).trim().parse().ok()).unwrap_or(0)
+}
+
+/// Load `url` in a fresh profile until the page reports for frame `n`.
+fn measure(browser: &str, url: &str, n: usize, profile: &Path, timeout: Duration, results: &Results) -> Result<Value, String> {
+  let _ = std::fs::remove_dir_all(profile);
+  let mut child = Command::new(browser)
+    .args(["--headless=new", "--disable-gpu", "--no-first-run", "--no-default-browser-check"])
+    .arg(format!("--user-data-dir={}", profile.display()))
+    .arg(format!("--window-size={},{}", FRAME_SIZE.0, FRAME_SIZE.1))
+    .arg(url)
+    .stdin(Stdio::null())
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .spawn()
+    .map_err(|e| format!("{browser}: {e}"))?;
+  let started = Instant::now();
+  let result = loop {
+    if let Some(r) = results.lock().unwrap().remove(&n) {
+      break Ok(r);
+    }
+    if let Ok(Some(status)) = child.try_wait() {
+      // Give a result posted just before exiting a moment to land.
+      thread::sleep(Duration::from_millis(200));
+      break results.lock().unwrap().remove(&n).ok_or_else(|| format!("{browser} exited ({status}) before the frame loaded"));
+    }
+    if started.elapsed() > timeout {
+      break Err(format!("no result within {}s", timeout.as_secs()));
+    }
+    thread::sleep(Duration::from_millis(50));
+  };
+  let _ = child.kill();
+  let _ = child.wait();
+  let _ = std::fs::remove_dir_all(profile);
+  let doc = json::parse(&result?)?;
+  if let Some(e) = doc.get("error").and_then(Value::as_str) {
+    return Err(e.to_string());
+  }
+  Ok(doc)
+}
+
+fn num(v: &Value, key: &str) -> Option<f64> {
+  match v.get(key) {
+    Some(Value::Num(x)) => Some(*x),
+    _ => None,
+  }
+}
+
+/// Nearest-rank percentile of `sorted`.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+  if sorted.is_empty() {
+    return 0.0;
+  }
+  let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+  sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn fmt_metric(key: &str, v: f64) -> String {
+  if key == "bytes" {
+    fmt_bytes(v as u64)
+  } else {
+    format!("{v:.0}ms")
+  }
+}
+
+pub fn run(args: &[String]) {
+  let count: usize = parse_kv(args, "--sample")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(20);
+  // Timings taken side by side compete for the CPU; one at a time by default.
+  let concurrency: usize = parse_kv(args, "--concurrency")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(1)
+    .max(1);
+  let timeout = Duration::from_secs(parse_kv(args, "--timeout").and_then(|v| v.parse().ok()).unwrap_or(30));
+  let expose = parse_kv(args, "--expose").unwrap_or_else(|| "./Frame".to_string());
+  let tolerance: f64 = parse_kv(args, "--tolerance")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(20.0);
+  let (start, end) = frames::range_args(args);
+  let base_url = parse_kv(args, "--base-url").map(|b| b.trim_end_matches('/').to_string());
+  let manifest = parse_kv(args, "--manifest");
+  if base_url.is_none() && manifest.is_none() {
+    eprintln!("perf: --base-url is required (the deployed frames to time)");
+    std::process::exit(2);
+  }
+  let baseline = parse_kv(args, "--baseline").map(|b| {
+    json::read(Path::new(&b)).unwrap_or_else(|e| {
+      eprintln!("perf: {b}: {e}");
+      std::process::exit(2);
+    })
+  });
+
+  let Some(browser) = visual::find_browser(parse_kv(args, "--browser")) else {
+    eprintln!("perf: no headless Chrome found (tried {}; set --browser or CHROME)", BROWSERS.join(", "));
+    std::process::exit(2);
+  };
+
+  let entries: Vec<(usize, String)> = match (&manifest, &base_url) {
+    (Some(m), _) => playlist::from_manifest(Path::new(m))
+      .unwrap_or_else(|e| {
+        eprintln!("perf: {m}: {e}");
+        std::process::exit(1);
+      })
+      .into_iter()
+      .map(|(n, entry)| match &base_url {
+        Some(base) if entry.starts_with('/') => (n, format!("{base}{entry}")),
+        _ => (n, entry),
+      })
+      .collect(),
+    (None, Some(base)) => frames::discover(&frames::frames_dir())
+      .into_iter()
+      .map(|n| (n, format!("{base}/frame-{n:04}/{ENTRY}")))
+      .collect(),
+    (None, None) => unreachable!(),
+  };
+  let entries: Vec<(usize, String)> = entries.into_iter().filter(|(n, _)| (start..=end).contains(n)).collect();
+  if entries.is_empty() || count == 0 {
+    eprintln!("perf: no frames to time (start={start} end={end} sample={count})");
+    std::process::exit(2);
+  }
+  if entries.iter().any(|(_, e)| !e.contains("://")) {
+    eprintln!("perf: the manifest has relative entries; pass --base-url to resolve them");
+    std::process::exit(2);
+  }
+  let ids: Vec<usize> = entries.iter().map(|(n, _)| *n).collect();
+  let picked = visual::sample(&ids, count);
+
+  let pages: BTreeMap<usize, String> = entries
+    .iter()
+    .filter(|(n, _)| picked.contains(n))
+    .map(|(n, entry)| (*n, harness(*n, entry, &expose)))
+    .collect();
+  let results: Results = Arc::new(Mutex::new(BTreeMap::new()));
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap_or_else(|e| {
+    eprintln!("perf: cannot listen: {e}");
+    std::process::exit(1);
+  });
+  let port = listener.local_addr().map(|a| a.port()).unwrap_or(0);
+  {
+    let pages = Arc::new(pages);
+    let results = Arc::clone(&results);
+    thread::spawn(move || {
+      for stream in listener.incoming().flatten() {
+        let (pages, results) = (Arc::clone(&pages), Arc::clone(&results));
+        thread::spawn(move || {
+          let _ = serve(stream, &pages, &results);
+        });
+      }
+    });
+  }
+
+  let dir = state_dir().join("perf");
+  let _ = std::fs::create_dir_all(&dir);
+  eprintln!(
+    "perf: timing {} frame(s) from {} with {browser}: {}",
+    picked.len(),
+    base_url.as_deref().unwrap_or("the manifest"),
+    fmt_ranges(&picked)
+  );
+
+  let timings: Mutex<Vec<(usize, Value)>> = Mutex::new(Vec::new());
+  let failed: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+  par_each(&picked, concurrency, |&n| {
+    let url = format!("http://127.0.0.1:{port}/perf/{n}");
+    let profile = dir.join(format!("profile-{n:04}"));
+    match measure(&browser, &url, n, &profile, timeout, &results) {
+      Ok(doc) => {
+        let mut bytes = 0u64;
+        if let Some(Value::Arr(res)) = doc.get("resources") {
+          for r in res {
+            bytes += match num(r, "bytes") {
+              Some(b) if b > 0.0 => b as u64,
+              _ => r.get("url").and_then(Value::as_str).map(transfer_size).unwrap_or(0),
+            };
+          }
+        }
+        let t = Value::obj([
+          ("frame", Value::from(n)),
+          ("loadMs", Value::from(num(&doc, "loadMs").unwrap_or(0.0).round())),
+          ("entryMs", Value::from(num(&doc, "entryMs").unwrap_or(0.0).round())),
+          ("scriptMs", Value::from(num(&doc, "scriptMs").unwrap_or(0.0).round())),
+          ("longTaskMs", Value::from(num(&doc, "longTaskMs").unwrap_or(0.0).round())),
+          ("bytes", Value::from(bytes as usize)),
+        ]);
+        let show = |k: &str| fmt_metric(k, num(&t, k).unwrap_or(0.0));
+        eprintln!(
+          "frame-{n:04}: paint {} script {} long tasks {} transfer {}",
+          show("loadMs"),
+          show("scriptMs"),
+          show("longTaskMs"),
+          show("bytes")
+        );
+        timings.lock().unwrap().push((n, t));
+      }
+      Err(e) => {
+        eprintln!("frame-{n:04}: {e}");
+        failed.lock().unwrap().push(n);
+      }
+    }
+  });
+  let _ = std::fs::remove_dir_all(&dir);
+  let mut timings = timings.into_inner().unwrap();
+  timings.sort_by_key(|(n, _)| *n);
+  let mut failed = failed.into_inner().unwrap();
+  failed.sort();
+  if timings.is_empty() {
+    eprintln!("perf: no frame loaded ({})", fmt_ranges(&failed));
+    std::process::exit(1);
+  }
+
+  // Metric -> [p50, p90, p95, max].
+  let mut stats: Vec<(&str, [f64; 4])> = Vec::new();
+  let head: Vec<String> = ["p50", "p90", "p95", "max"].iter().map(|p| format!("{p:>9}")).collect();
+  eprintln!("perf: {:<18}{}", format!("{} frame(s)", timings.len()), head.join(" "));
+  for (key, label) in METRICS {
+    let mut vs: Vec<f64> = timings.iter().filter_map(|(_, t)| num(t, key)).collect();
+    vs.sort_by(f64::total_cmp);
+    let s = [percentile(&vs, 50.0), percentile(&vs, 90.0), percentile(&vs, 95.0), *vs.last().unwrap_or(&0.0)];
+    let cols: Vec<String> = s.iter().map(|v| format!("{:>9}", fmt_metric(key, *v))).collect();
+    eprintln!("  {label:<22}{}", cols.join(" "));
+    stats.push((key, s));
+  }
+  if !failed.is_empty() {
+    eprintln!("perf: did not load: {}", fmt_ranges(&failed));
+  }
+
+  // Compare p50 and p95 with the baseline's; worse by more than --tolerance
+  // percent is a regression.
+  let mut regressed = false;
+  if let Some(base) = &baseline {
+    for (key, s) in &stats {
+      let label = METRICS.iter().find(|(k, _)| k == key).map(|(_, l)| *l).unwrap_or(key);
+      for (i, p) in [(0, "p50"), (2, "p95")] {
+        let Some(was) = base.get("percentiles").and_then(|b| b.get(key)).and_then(|b| num(b, p)) else {
+          continue;
+        };
+        let now = s[i];
+        let change = if was > 0.0 { (now - was) * 100.0 / was } else { 0.0 };
+        let bad = change > tolerance;
+        regressed |= bad;
+        if bad || change < -tolerance {
+          eprintln!(
+            "perf: {label} {p} {} -> {} ({change:+.0}%){}",
+            fmt_metric(key, was),
+            fmt_metric(key, now),
+            if bad { ", regressed" } else { "" }
+          );
+        }
+      }
+    }
+    if !regressed {
+      eprintln!("perf: within {tolerance}% of the baseline");
+    }
+  }
+
+  if let Some(out) = parse_kv(args, "--out") {
+    let percentiles = stats.iter().map(|(key, s)| {
+      let fields = ["p50", "p90", "p95", "max"].iter().zip(s).map(|(p, v)| (*p, Value::from(v.round())));
+      (*key, Value::obj(fields))
+    });
+    let doc = Value::obj([
+      ("baseUrl", base_url.as_deref().map(Value::from).unwrap_or(Value::Null)),
+      ("sampled", Value::from(picked.len())),
+      ("failed", Value::Arr(failed.iter().map(|&n| Value::from(n)).collect())),
+      ("percentiles", Value::obj(percentiles)),
+      ("frames", Value::Arr(timings.into_iter().map(|(_, t)| t).collect())),
+    ]);
+    if let Err(e) = std::fs::write(&out, doc.pretty()) {
+      eprintln!("perf: {out}: {e}");
+      std::process::exit(1);
+    }
+    eprintln!("perf: wrote {out}");
+  }
+  if regressed || !failed.is_empty() {
+    std::process::exit(1);
+  }
+}
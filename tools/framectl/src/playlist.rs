@@ -0,0 +1,123 @@
+//! `playlist`: the playback order and timing the host player follows, built
+//! from the frames that actually exist (a built dist, or an entry in the
+//! `hash-manifest` routing manifest with `--manifest`). Missing frames are
+//! skipped; the frame before one stays on screen until the next is due.
+
+use std::path::Path;
+
+use crate::frames::{self, ENTRY};
+use crate::json::{self, Value};
+use crate::{parse_kv, targets};
+
+const DEFAULT_OUT: &str = "playlist.json";
+
+/// Frames and their remote entry URLs, from a `hash-manifest` output file.
+pub fn from_manifest(path: &Path) -> Result<Vec<(usize, String)>, String> {
+  let doc = json::read(path)?;
+  let Some(Value::Obj(routes)) = doc.get("frames") else {
+    return Err("no \"frames\" object".to_string());
+  };
+  let mut out = Vec::new();
+  for (id, route) in routes {
+    let n: usize = id.parse().map_err(|_| format!("bad frame id {id:?}"))?;
+    let entry = route
+      .get("entry")
+      .and_then(|e| e.as_str())
+      .ok_or_else(|| format!("frame {id} has no entry"))?;
+    out.push((n, entry.to_string()));
+  }
+  out.sort();
+  Ok(out)
+}
+
+pub fn run(args: &[String]) {
+  let out = parse_kv(args, "--out").unwrap_or_else(|| DEFAULT_OUT.to_string());
+  let fps: f64 = parse_kv(args, "--fps")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(30.0);
+  let offset_ms: f64 = parse_kv(args, "--audio-offset")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(0.0);
+  let preload: usize = parse_kv(args, "--preload")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(5);
+  let base_url = parse_kv(args, "--base-url").unwrap_or_default();
+  let base_url = base_url.trim_end_matches('/');
+  let (start, end) = frames::range_args(args);
+  if !(fps.is_finite() && fps > 0.0) {
+    eprintln!("playlist: --fps must be a positive number");
+    std::process::exit(2);
+  }
+
+  targets::dists("playlist", args);
+  let listed: Vec<(usize, String)> = match parse_kv(args, "--manifest") {
+    Some(m) => from_manifest(Path::new(&m)).unwrap_or_else(|e| {
+      eprintln!("playlist: {m}: {e}");
+      std::process::exit(1);
+    }),
+    None => frames::discover(&frames::frames_dir())
+      .into_iter()
+      .filter(|&n| frames::entry_path(n).is_file())
+      .map(|n| (n, format!("{base_url}/frame-{n:04}/{ENTRY}")))
+      .collect(),
+  };
+  let listed: Vec<(usize, String)> = listed.into_iter().filter(|(n, _)| (start..=end).contains(n)).collect();
+  if listed.is_empty() {
+    eprintln!("playlist: no built frames in range (start={start} end={end})");
+    std::process::exit(1);
+  }
+
+  let frame_ms = 1000.0 / fps;
+  // Timestamps come from the frame number, not the list position, so a gap
+  // doesn't pull the rest of the video ahead of the audio.
+  let at = |n: usize| ((n - 1) as f64 * frame_ms + offset_ms).round();
+  let remote = |n: usize| format!("frame_{n:04}");
+  let mut items = Vec::new();
+  for (i, (n, entry)) in listed.iter().enumerate() {
+    let until = listed
+      .get(i + 1)
+      .map(|(next, _)| at(*next))
+      .unwrap_or_else(|| at(n + 1));
+    let next: Vec<Value> = listed[i + 1..]
+      .iter()
+      .take(preload)
+      .map(|(m, _)| Value::from(remote(*m)))
+      .collect();
+    items.push(Value::obj([
+      ("frame", Value::from(*n)),
+      ("remote", Value::from(remote(*n))),
+      ("entry", Value::from(entry.as_str())),
+      ("atMs", Value::from(at(*n))),
+      ("durationMs", Value::from(until - at(*n))),
+      ("preload", Value::Arr(next)),
+    ]));
+  }
+  let found: Vec<usize> = listed.iter().map(|(n, _)| *n).collect();
+  let (first, last) = (found[0], found[found.len() - 1]);
+  let gaps = (last - first + 1) - found.len();
+  let doc = Value::obj([
+    ("fps", Value::from(fps)),
+    ("frameDurationMs", Value::from(frame_ms)),
+    ("audioOffsetMs", Value::from(offset_ms)),
+    ("frameCount", Value::from(found.len())),
+    ("durationMs", Value::from(at(last + 1))),
+    ("frames", Value::Arr(items)),
+  ]);
+
+  if gaps > 0 {
+    eprintln!(
+      "playlist: warning: {gaps} frame(s) between {first} and {last} not built; held over: {}",
+      frames::fmt_ranges(&(first..=last).filter(|n| found.binary_search(n).is_err()).collect::<Vec<_>>())
+    );
+  }
+  if out == "-" {
+    print!("{}", doc.pretty());
+    return;
+  }
+  let tmp = format!("{out}.tmp");
+  if let Err(e) = std::fs::write(&tmp, doc.pretty()).and_then(|_| std::fs::rename(&tmp, &out)) {
+    eprintln!("playlist: write {out}: {e}");
+    std::process::exit(1);
+  }
+  eprintln!("playlist: wrote {out} ({} frames at {fps}fps)", found.len());
+}
@@ -0,0 +1,579 @@
+//! Minimal PNG reader/writer for frame images: non-interlaced, any bit depth
+//! and color type in, 8-bit RGB out.
+
+use std::path::Path;
+
+pub struct Header {
+  pub width: u32,
+  pub height: u32,
+  pub bit_depth: u8,
+  pub color_type: u8,
+  pub interlace: u8,
+}
+
+pub fn color_name(color_type: u8) -> &'static str {
+  match color_type {
+    0 => "gray",
+    2 => "rgb",
+    3 => "palette",
+    4 => "gray+alpha",
+    6 => "rgba",
+    _ => "unknown",
+  }
+}
+
+pub struct Image {
+  pub width: usize,
+  pub height: usize,
+  /// Row-major RGB, 3 bytes per pixel.
+  pub rgb: Vec<u8>,
+}
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Largest image decoded, in pixels: far past any frame, well short of
+/// what a bogus IHDR could ask memory for.
+const MAX_PIXELS: usize = 1 << 26;
+
+pub fn read_header(path: &Path) -> Result<Header, String> {
+  use std::io::Read;
+  let mut f = std::fs::File::open(path).map_err(|e| e.to_string())?;
+  let mut buf = [0u8; 33];
+  f.read_exact(&mut buf).map_err(|_| "truncated file".to_string())?;
+  parse_header(&buf)
+}
+
+fn parse_header(data: &[u8]) -> Result<Header, String> {
+  if data.len() < 33 || data[..8] != SIGNATURE {
+    return Err("not a PNG file".to_string());
+  }
+  if &data[12..16] != b"IHDR" {
+    return Err("missing IHDR".to_string());
+  }
+  let d = &data[16..29];
+  Ok(Header {
+    width: u32::from_be_bytes([d[0], d[1], d[2], d[3]]),
+    height: u32::from_be_bytes([d[4], d[5], d[6], d[7]]),
+    bit_depth: d[8],
+    color_type: d[9],
+    interlace: d[12],
+  })
+}
+
+pub fn decode_file(path: &Path) -> Result<Image, String> {
+  let data = std::fs::read(path).map_err(|e| e.to_string())?;
+  decode(&data)
+}
+
+pub fn decode(data: &[u8]) -> Result<Image, String> {
+  let h = parse_header(data)?;
+  if h.interlace != 0 {
+    return Err("interlaced PNGs are not supported".to_string());
+  }
+  let channels = match h.color_type {
+    0 | 3 => 1,
+    2 => 3,
+    4 => 2,
+    6 => 4,
+    t => return Err(format!("unknown color type {t}")),
+  };
+  let depth_ok = match h.color_type {
+    0 => matches!(h.bit_depth, 1 | 2 | 4 | 8 | 16),
+    3 => matches!(h.bit_depth, 1 | 2 | 4 | 8),
+    _ => matches!(h.bit_depth, 8 | 16),
+  };
+  if !depth_ok {
+    return Err(format!("bit depth {} is not valid for {}", h.bit_depth, color_name(h.color_type)));
+  }
+  let (w, ht) = (h.width as usize, h.height as usize);
+  let pixels = w.checked_mul(ht).filter(|&p| p > 0 && p <= MAX_PIXELS);
+  if pixels.is_none() {
+    return Err(format!("unsupported image size {w}x{ht}"));
+  }
+  let bits_pp = channels * h.bit_depth as usize;
+  let stride = (w * bits_pp).div_ceil(8);
+  let bpp = bits_pp.div_ceil(8).max(1);
+  let expected = (stride + 1) * ht;
+
+  let mut idat = Vec::new();
+  let mut palette: Vec<u8> = Vec::new();
+  let mut pos = 8;
+  while pos + 8 <= data.len() {
+    let len = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+    let kind = &data[pos + 4..pos + 8];
+    let body = data
+      .get(pos + 8..)
+      .and_then(|rest| rest.get(..len))
+      .ok_or_else(|| "truncated chunk".to_string())?;
+    match kind {
+      b"IDAT" => idat.extend_from_slice(body),
+      b"PLTE" => palette = body.to_vec(),
+      b"IEND" => break,
+      _ => {}
+    }
+    pos += 12 + len;
+  }
+  if idat.len() < 2 {
+    return Err("no image data".to_string());
+  }
+  let raw = inflate(&idat[2..], expected)?;
+  if raw.len() < expected {
+    return Err("image data too short".to_string());
+  }
+
+  let mut lines = vec![0u8; stride * ht];
+  for y in 0..ht {
+    let filter = raw[y * (stride + 1)];
+    let src = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+    let (done, cur) = lines.split_at_mut(y * stride);
+    let prev = if y > 0 { &done[(y - 1) * stride..] } else { &[][..] };
+    let cur = &mut cur[..stride];
+    for x in 0..stride {
+      let a = if x >= bpp { cur[x - bpp] as i32 } else { 0 };
+      let b = if y > 0 { prev[x] as i32 } else { 0 };
+      let c = if x >= bpp && y > 0 { prev[x - bpp] as i32 } else { 0 };
+      let pred = match filter {
+        0 => 0,
+        1 => a,
+        2 => b,
+        3 => (a + b) / 2,
+        4 => {
+          let p = a + b - c;
+          let (pa, pb, pc) = ((p - a).abs(), (p - b).abs(), (p - c).abs());
+          if pa <= pb && pa <= pc {
+            a
+          } else if pb <= pc {
+            b
+          } else {
+            c
+          }
+        }
+        f => return Err(format!("bad filter type {f}")),
+      };
+      cur[x] = src[x].wrapping_add(pred as u8);
+    }
+  }
+
+  let depth = h.bit_depth as usize;
+  let sample = |row: &[u8], i: usize| -> u8 {
+    match depth {
+      8 => row[i],
+      16 => row[i * 2],
+      d => {
+        let bit = i * d;
+        let v = (row[bit / 8] >> (8 - d - bit % 8)) & ((1 << d) - 1);
+        if h.color_type == 3 {
+          v
+        } else {
+          (v as u32 * 255 / ((1 << d) - 1)) as u8
+        }
+      }
+    }
+  };
+
+  let mut rgb = Vec::with_capacity(w * ht * 3);
+  for y in 0..ht {
+    let row = &lines[y * stride..(y + 1) * stride];
+    for x in 0..w {
+      let s = |c: usize| sample(row, x * channels + c);
+      match h.color_type {
+        0 | 4 => {
+          let g = s(0);
+          rgb.extend_from_slice(&[g, g, g]);
+        }
+        3 => {
+          let i = s(0) as usize * 3;
+          let p = palette.get(i..i + 3).unwrap_or(&[0, 0, 0]);
+          rgb.extend_from_slice(p);
+        }
+        _ => rgb.extend_from_slice(&[s(0), s(1), s(2)]),
+      }
+    }
+  }
+  Ok(Image {
+    width: w,
+    height: ht,
+    rgb,
+  })
+}
+
+pub fn encode_rgb(img: &Image) -> Vec<u8> {
+  let stride = img.width * 3;
+  let mut raw = Vec::with_capacity((stride + 1) * img.height);
+  for y in 0..img.height {
+    raw.push(0);
+    raw.extend_from_slice(&img.rgb[y * stride..(y + 1) * stride]);
+  }
+
+  let mut z = vec![0x78, 0x01];
+  z.extend(deflate(&raw));
+  z.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+  let mut ihdr = Vec::new();
+  ihdr.extend_from_slice(&(img.width as u32).to_be_bytes());
+  ihdr.extend_from_slice(&(img.height as u32).to_be_bytes());
+  ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+  let mut out = SIGNATURE.to_vec();
+  chunk(&mut out, b"IHDR", &ihdr);
+  chunk(&mut out, b"IDAT", &z);
+  chunk(&mut out, b"IEND", &[]);
+  out
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], body: &[u8]) {
+  out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+  let start = out.len();
+  out.extend_from_slice(kind);
+  out.extend_from_slice(body);
+  let crc = crc32(&out[start..]);
+  out.extend_from_slice(&crc.to_be_bytes());
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+  let mut crc = 0xffff_ffffu32;
+  for &b in data {
+    crc ^= b as u32;
+    for _ in 0..8 {
+      crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+    }
+  }
+  !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+  let (mut a, mut b) = (1u32, 0u32);
+  for chunk in data.chunks(5552) {
+    for &x in chunk {
+      a += x as u32;
+      b += a;
+    }
+    a %= 65521;
+    b %= 65521;
+  }
+  (b << 16) | a
+}
+
+const LEN_BASE: [u16; 29] = [
+  3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LEN_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [
+  1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+  8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+  0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+
+struct BitReader<'a> {
+  data: &'a [u8],
+  pos: usize,
+  buf: u64,
+  n: u32,
+}
+
+impl BitReader<'_> {
+  fn bits(&mut self, need: u32) -> Result<u32, String> {
+    while self.n < need {
+      let byte = *self.data.get(self.pos).ok_or("unexpected end of deflate data")?;
+      self.pos += 1;
+      self.buf |= (byte as u64) << self.n;
+      self.n += 8;
+    }
+    let v = (self.buf & ((1u64 << need) - 1)) as u32;
+    self.buf >>= need;
+    self.n -= need;
+    Ok(v)
+  }
+}
+
+/// Canonical Huffman table in the style of zlib's puff.c.
+struct Huffman {
+  count: [u16; 16],
+  symbol: Vec<u16>,
+}
+
+impl Huffman {
+  fn new(lengths: &[u8]) -> Huffman {
+    let mut count = [0u16; 16];
+    for &l in lengths {
+      count[l as usize] += 1;
+    }
+    count[0] = 0;
+    let mut offs = [0u16; 16];
+    for i in 1..16 {
+      offs[i] = offs[i - 1] + count[i - 1];
+    }
+    let mut symbol = vec![0u16; lengths.len()];
+    for (s, &l) in lengths.iter().enumerate() {
+      if l != 0 {
+        symbol[offs[l as usize] as usize] = s as u16;
+        offs[l as usize] += 1;
+      }
+    }
+    Huffman { count, symbol }
+  }
+
+  fn decode(&self, br: &mut BitReader) -> Result<u16, String> {
+    let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+    for len in 1..16 {
+      code |= br.bits(1)? as i32;
+      let count = self.count[len] as i32;
+      if code - count < first {
+        return Ok(self.symbol[(index + (code - first)) as usize]);
+      }
+      index += count;
+      first += count;
+      first <<= 1;
+      code <<= 1;
+    }
+    Err("bad huffman code".to_string())
+  }
+}
+
+/// The zlib payload `data` inflated, failing past `limit` bytes.
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+  let mut br = BitReader {
+    data,
+    pos: 0,
+    buf: 0,
+    n: 0,
+  };
+  let mut out: Vec<u8> = Vec::new();
+  loop {
+    let last = br.bits(1)?;
+    match br.bits(2)? {
+      0 => {
+        br.buf = 0;
+        br.n = 0;
+        let p = br.pos;
+        let hdr = data.get(p..p + 4).ok_or("truncated stored block")?;
+        let len = u16::from_le_bytes([hdr[0], hdr[1]]) as usize;
+        let block = data.get(p + 4..p + 4 + len).ok_or("truncated stored block")?;
+        out.extend_from_slice(block);
+        br.pos = p + 4 + len;
+        if out.len() > limit {
+          return Err("image data too long".to_string());
+        }
+      }
+      1 => {
+        let mut l = [0u8; 288];
+        l[..144].fill(8);
+        l[144..256].fill(9);
+        l[256..280].fill(7);
+        l[280..].fill(8);
+        inflate_block(&mut br, &mut out, limit, &Huffman::new(&l), &Huffman::new(&[5u8; 30]))?;
+      }
+      2 => {
+        let hlit = br.bits(5)? as usize + 257;
+        let hdist = br.bits(5)? as usize + 1;
+        let hclen = br.bits(4)? as usize + 4;
+        const ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+        let mut cl = [0u8; 19];
+        for &i in ORDER.iter().take(hclen) {
+          cl[i] = br.bits(3)? as u8;
+        }
+        let clh = Huffman::new(&cl);
+        let mut lengths = vec![0u8; hlit + hdist];
+        let mut i = 0;
+        while i < hlit + hdist {
+          let sym = clh.decode(&mut br)?;
+          let (val, rep) = match sym {
+            0..=15 => (sym as u8, 1),
+            16 => {
+              let prev = *lengths.get(i.wrapping_sub(1)).ok_or("repeat with no previous length")?;
+              (prev, 3 + br.bits(2)? as usize)
+            }
+            17 => (0, 3 + br.bits(3)? as usize),
+            _ => (0, 11 + br.bits(7)? as usize),
+          };
+          if i + rep > lengths.len() {
+            return Err("too many code lengths".to_string());
+          }
+          lengths[i..i + rep].fill(val);
+          i += rep;
+        }
+        let lit = Huffman::new(&lengths[..hlit]);
+        let dist = Huffman::new(&lengths[hlit..]);
+        inflate_block(&mut br, &mut out, limit, &lit, &dist)?;
+      }
+      _ => return Err("bad block type".to_string()),
+    }
+    if last == 1 {
+      return Ok(out);
+    }
+  }
+}
+
+fn inflate_block(br: &mut BitReader, out: &mut Vec<u8>, limit: usize, lit: &Huffman, dist: &Huffman) -> Result<(), String> {
+  loop {
+    if out.len() > limit {
+      return Err("image data too long".to_string());
+    }
+    let sym = lit.decode(br)? as usize;
+    if sym < 256 {
+      out.push(sym as u8);
+      continue;
+    }
+    if sym == 256 {
+      return Ok(());
+    }
+    let i = sym - 257;
+    if i >= 29 {
+      return Err("bad length symbol".to_string());
+    }
+    let len = LEN_BASE[i] as usize + br.bits(LEN_EXTRA[i] as u32)? as usize;
+    let d = dist.decode(br)? as usize;
+    if d >= 30 {
+      return Err("bad distance symbol".to_string());
+    }
+    let dist = DIST_BASE[d] as usize + br.bits(DIST_EXTRA[d] as u32)? as usize;
+    if dist > out.len() {
+      return Err("distance too far back".to_string());
+    }
+    let start = out.len() - dist;
+    for k in 0..len {
+      out.push(out[start + k]);
+    }
+  }
+}
+
+struct BitWriter {
+  out: Vec<u8>,
+  buf: u64,
+  n: u32,
+}
+
+impl BitWriter {
+  fn put(&mut self, v: u32, bits: u32) {
+    self.buf |= (v as u64) << self.n;
+    self.n += bits;
+    while self.n >= 8 {
+      self.out.push(self.buf as u8);
+      self.buf >>= 8;
+      self.n -= 8;
+    }
+  }
+
+  /// Huffman codes go out most-significant bit first.
+  fn code(&mut self, code: u32, bits: u32) {
+    self.put(code.reverse_bits() >> (32 - bits), bits);
+  }
+
+  fn lit(&mut self, sym: u32) {
+    match sym {
+      0..=143 => self.code(0x30 + sym, 8),
+      144..=255 => self.code(0x190 + sym - 144, 9),
+      256..=279 => self.code(sym - 256, 7),
+      _ => self.code(0xc0 + sym - 280, 8),
+    }
+  }
+}
+
+/// Single fixed-Huffman block with greedy hash-based LZ77 matching; plenty
+/// for mostly flat black-and-white frames.
+fn deflate(data: &[u8]) -> Vec<u8> {
+  const WINDOW: usize = 32768;
+  const HASH: usize = 1 << 15;
+  let mut w = BitWriter {
+    out: Vec::new(),
+    buf: 0,
+    n: 0,
+  };
+  w.put(1, 1);
+  w.put(1, 2);
+
+  let mut head = vec![usize::MAX; HASH];
+  let hash = |i: usize| {
+    ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize) & (HASH - 1)
+  };
+  let mut i = 0;
+  while i < data.len() {
+    let mut best = 0;
+    let mut best_dist = 0;
+    if i + 3 <= data.len() {
+      let h = hash(i);
+      let cand = head[h];
+      head[h] = i;
+      if cand != usize::MAX && i - cand <= WINDOW {
+        let max = (data.len() - i).min(258);
+        let mut l = 0;
+        while l < max && data[cand + l] == data[i + l] {
+          l += 1;
+        }
+        if l >= 3 {
+          best = l;
+          best_dist = i - cand;
+        }
+      }
+    }
+    if best == 0 {
+      w.lit(data[i] as u32);
+      i += 1;
+      continue;
+    }
+
+    let li = LEN_BASE.iter().rposition(|&b| b as usize <= best).unwrap();
+    w.lit(257 + li as u32);
+    w.put((best - LEN_BASE[li] as usize) as u32, LEN_EXTRA[li] as u32);
+    let di = DIST_BASE.iter().rposition(|&b| b as usize <= best_dist).unwrap();
+    w.code(di as u32, 5);
+    w.put((best_dist - DIST_BASE[di] as usize) as u32, DIST_EXTRA[di] as u32);
+
+    for k in 1..best {
+      if i + k + 3 <= data.len() {
+        head[hash(i + k)] = i + k;
+      }
+    }
+    i += best;
+  }
+  w.lit(256);
+  if w.n > 0 {
+    w.out.push(w.buf as u8);
+  }
+  w.out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn sample() -> Image {
+    let (width, height) = (37, 11);
+    let rgb = (0..width * height * 3).map(|i| (i * 7 % 251) as u8).collect();
+    Image { width, height, rgb }
+  }
+
+  #[test]
+  fn round_trip() {
+    let img = sample();
+    let back = decode(&encode_rgb(&img)).unwrap();
+    assert_eq!((back.width, back.height), (img.width, img.height));
+    assert_eq!(back.rgb, img.rgb);
+  }
+
+  #[test]
+  fn truncated_stream() {
+    let png = encode_rgb(&sample());
+    for len in [0, 8, 32, 40, png.len() / 2, png.len() - 20] {
+      assert!(decode(&png[..len]).is_err(), "decoded {len} of {} bytes", png.len());
+    }
+  }
+
+  #[test]
+  fn oversized_header() {
+    let mut png = encode_rgb(&sample());
+    png[16..24].copy_from_slice(&[0xff; 8]);
+    assert_eq!(decode(&png).err().unwrap(), "unsupported image size 4294967295x4294967295");
+    png[16..24].copy_from_slice(&[0; 8]);
+    assert!(decode(&png).is_err());
+  }
+
+  #[test]
+  fn invalid_bit_depth() {
+    let mut png = encode_rgb(&sample());
+    png[24] = 3;
+    assert_eq!(decode(&png).err().unwrap(), "bit depth 3 is not valid for rgb");
+  }
+}
@@ -0,0 +1,140 @@
+//! `prefetch`: every package in pnpm-lock.yaml fetched into the pnpm store
+//! with `pnpm fetch`, before a build. The frames' installs then link from
+//! the store instead of the first wave of parallel builds all hitting the
+//! registry at once. pnpm's own progress is condensed into a line every
+//! `--interval` seconds.
+
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::exec;
+use crate::{fmt_dur, parse_kv};
+
+const LOCKFILE: &str = "pnpm-lock.yaml";
+
+/// Entries in the lockfile's `packages:` section, what pnpm will resolve.
+fn count_packages(lockfile: &str) -> usize {
+  let mut in_packages = false;
+  let mut count = 0;
+  for line in lockfile.lines() {
+    if !line.starts_with(' ') && !line.is_empty() {
+      in_packages = line.trim_end() == "packages:";
+      continue;
+    }
+    let key = line.strip_prefix("  ").filter(|k| !k.starts_with(' '));
+    if in_packages && key.is_some_and(|k| k.trim_end().ends_with(':')) {
+      count += 1;
+    }
+  }
+  count
+}
+
+/// `Progress: resolved 120, reused 100, downloaded 20, added 0`.
+#[derive(Default)]
+struct Progress {
+  resolved: usize,
+  reused: usize,
+  downloaded: usize,
+}
+
+fn parse_progress(line: &str) -> Option<Progress> {
+  let rest = &line[line.find("Progress: ")? + "Progress: ".len()..];
+  let mut p = Progress::default();
+  for part in rest.split(',') {
+    let mut words = part.split_whitespace();
+    let (Some(key), Some(value)) = (words.next(), words.next().and_then(|v| v.parse().ok())) else {
+      continue;
+    };
+    match key {
+      "resolved" => p.resolved = value,
+      "reused" => p.reused = value,
+      "downloaded" => p.downloaded = value,
+      _ => {}
+    }
+  }
+  Some(p)
+}
+
+pub fn run(args: &[String]) {
+  let interval = Duration::from_secs_f64(
+    parse_kv(args, "--interval")
+      .and_then(|v| v.parse::<f64>().ok())
+      .filter(|s| s.is_finite() && *s > 0.0)
+      .unwrap_or(5.0),
+  );
+  let network_concurrency = parse_kv(args, "--network-concurrency");
+  let Ok(lockfile) = std::fs::read_to_string(LOCKFILE) else {
+    eprintln!("prefetch: no {LOCKFILE} here (`pnpm fetch` works from the lockfile; `pnpm install --lockfile-only` makes one)");
+    std::process::exit(2);
+  };
+  let total = count_packages(&lockfile);
+  eprintln!(
+    "prefetch: {total} package(s) in {LOCKFILE}{}",
+    network_concurrency.as_deref().map(|c| format!(" network-concurrency={c}")).unwrap_or_default()
+  );
+
+  let mut cmd = Command::new("pnpm");
+  cmd.args(["fetch", "--reporter=append-only"]);
+  if let Some(c) = &network_concurrency {
+    cmd.arg(format!("--network-concurrency={c}"));
+  }
+  let mut child = match cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn() {
+    Ok(c) => c,
+    Err(e) => {
+      eprintln!("prefetch: cannot run pnpm: {e}");
+      std::process::exit(1);
+    }
+  };
+  let mut stderr = child.stderr.take();
+  let err_reader = thread::spawn(move || {
+    let mut buf = String::new();
+    if let Some(s) = stderr.as_mut() {
+      let _ = s.read_to_string(&mut buf);
+    }
+    buf
+  });
+
+  let t0 = Instant::now();
+  let mut last = Progress::default();
+  let mut reported = Instant::now();
+  let mut output = String::new();
+  for line in BufReader::new(child.stdout.take().expect("piped stdout")).lines().map_while(Result::ok) {
+    match parse_progress(&line) {
+      Some(p) => last = p,
+      None => {
+        output.push_str(&line);
+        output.push('\n');
+      }
+    }
+    if reported.elapsed() >= interval {
+      reported = Instant::now();
+      let rate = last.resolved as f64 / t0.elapsed().as_secs_f64().max(0.001);
+      eprintln!(
+        "progress: resolved={}/{total} reused={} downloaded={} rate={rate:.1}/s",
+        last.resolved, last.reused, last.downloaded
+      );
+    }
+  }
+  let status = child.wait();
+  output.push_str(&err_reader.join().unwrap_or_default());
+  match status {
+    Ok(s) if s.success() => eprintln!(
+      "prefetch: done in {}: resolved {}, reused {} from the store, downloaded {}",
+      fmt_dur(t0.elapsed()),
+      last.resolved,
+      last.reused,
+      last.downloaded
+    ),
+    Ok(s) => {
+      eprintln!("prefetch: pnpm fetch exited with {s} after {}:", fmt_dur(t0.elapsed()));
+      eprintln!("{}", exec::tail(&output, 2000).trim_end());
+      std::process::exit(1);
+    }
+    Err(e) => {
+      eprintln!("prefetch: waiting for pnpm failed: {e}");
+      std::process::exit(1);
+    }
+  }
+}
@@ -0,0 +1,161 @@
+//! Checks run before a build so a broken workspace fails once with a clear
+//! report instead of once per frame.
+
+use std::process::{Command, Stdio};
+
+use crate::classify;
+use crate::exec::{self, Network};
+use crate::frames::{self, frame_pkg};
+use crate::{filterexpr, json};
+
+/// Problems found; empty means the build can start.
+pub fn check(plan: &[usize], lockfile: bool, network: Network) -> Vec<String> {
+  let mut problems = Vec::new();
+
+  let globs = workspace_globs();
+  if globs.is_empty() {
+    problems.push("pnpm-workspace.yaml: no `packages:` globs found".to_string());
+  }
+
+  let mut missing = Vec::new();
+  let mut unlisted = Vec::new();
+  let mut misnamed = Vec::new();
+  // --filter-expr tasks are pnpm's to resolve.
+  for &n in plan.iter().filter(|&&n| !filterexpr::is_task(n)) {
+    let dir = frames::frame_dir(n);
+    let manifest = dir.join("package.json");
+    if !manifest.is_file() {
+      missing.push(n);
+      continue;
+    }
+    let rel = dir.to_string_lossy().replace('\\', "/");
+    if !globs.is_empty() && !in_workspace(&globs, &rel) {
+      unlisted.push(n);
+    }
+    let name = json::read(&manifest)
+      .ok()
+      .and_then(|v| v.get("name").and_then(|s| s.as_str()).map(str::to_string));
+    if name.as_deref() != Some(frame_pkg(n).as_str()) {
+      misnamed.push(n);
+    }
+  }
+  if !missing.is_empty() {
+    problems.push(format!(
+      "{} frame(s) have no package.json: {}",
+      missing.len(),
+      frames::fmt_ranges(&missing)
+    ));
+  }
+  if !unlisted.is_empty() {
+    problems.push(format!(
+      "{} frame(s) not matched by pnpm-workspace.yaml globs: {}",
+      unlisted.len(),
+      frames::fmt_ranges(&unlisted)
+    ));
+  }
+  if !misnamed.is_empty() {
+    problems.push(format!(
+      "{} frame(s) whose package.json name is not @bad-apple/frame-XXXX: {}",
+      misnamed.len(),
+      frames::fmt_ranges(&misnamed)
+    ));
+  }
+
+  if lockfile {
+    if let Err(e) = check_lockfile(network) {
+      problems.push(e);
+    }
+  }
+  problems
+}
+
+/// `pnpm install --frozen-lockfile --lockfile-only` fails without touching
+/// node_modules when pnpm-lock.yaml no longer matches the manifests. Offline,
+/// it also fails when the store is missing a package.
+fn check_lockfile(network: Network) -> Result<(), String> {
+  let out = Command::new("pnpm")
+    .args(["install", "--frozen-lockfile", "--lockfile-only"])
+    .args(network.install_flag())
+    .stdin(Stdio::null())
+    .stdout(Stdio::null())
+    .stderr(Stdio::piped())
+    .output()
+    .map_err(|e| format!("pnpm-lock.yaml check: spawn failed: {e}"))?;
+  if out.status.success() {
+    return Ok(());
+  }
+  let err = exec::tail(&String::from_utf8_lossy(&out.stderr), 1500);
+  if network == Network::Offline && classify::is_offline_miss(&err) {
+    return Err(format!(
+      "the pnpm store is missing packages needed offline (run `pnpm install` online first):\n{}",
+      err.trim_end()
+    ));
+  }
+  Err(format!(
+    "pnpm-lock.yaml is out of sync with the workspace (run `pnpm install`):\n{}",
+    err.trim_end()
+  ))
+}
+
+fn workspace_globs() -> Vec<String> {
+  let Ok(text) = std::fs::read_to_string("pnpm-workspace.yaml") else {
+    return Vec::new();
+  };
+  let mut globs = Vec::new();
+  let mut in_packages = false;
+  for line in text.lines() {
+    let t = line.trim();
+    if t.is_empty() || t.starts_with('#') {
+      continue;
+    }
+    if !line.starts_with(' ') && !line.starts_with('-') {
+      in_packages = t == "packages:";
+      continue;
+    }
+    if in_packages {
+      if let Some(item) = t.strip_prefix('-') {
+        globs.push(item.trim().trim_matches(|c| c == '"' || c == '\'').to_string());
+      }
+    }
+  }
+  globs
+}
+
+fn in_workspace(globs: &[String], path: &str) -> bool {
+  let mut hit = false;
+  for g in globs {
+    if let Some(neg) = g.strip_prefix('!') {
+      if glob_match(neg, path) {
+        hit = false;
+      }
+    } else if glob_match(g, path) {
+      hit = true;
+    }
+  }
+  hit
+}
+
+fn glob_match(glob: &str, path: &str) -> bool {
+  let g: Vec<&str> = glob.trim_end_matches('/').split('/').collect();
+  let p: Vec<&str> = path.split('/').collect();
+  match_segs(&g, &p)
+}
+
+fn match_segs(g: &[&str], p: &[&str]) -> bool {
+  match (g.first(), p.first()) {
+    (None, None) => true,
+    (Some(&"**"), _) => match_segs(&g[1..], p) || (!p.is_empty() && match_segs(g, &p[1..])),
+    (Some(gs), Some(ps)) => match_seg(gs.as_bytes(), ps.as_bytes()) && match_segs(&g[1..], &p[1..]),
+    _ => false,
+  }
+}
+
+fn match_seg(g: &[u8], s: &[u8]) -> bool {
+  match (g.first(), s.first()) {
+    (None, None) => true,
+    (Some(b'*'), _) => match_seg(&g[1..], s) || (!s.is_empty() && match_seg(g, &s[1..])),
+    (Some(b'?'), Some(_)) => match_seg(&g[1..], &s[1..]),
+    (Some(a), Some(b)) if a == b => match_seg(&g[1..], &s[1..]),
+    _ => false,
+  }
+}
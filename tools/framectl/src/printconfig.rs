@@ -0,0 +1,213 @@
+//! `print-config`: the settings a `build` (or `test`) with the same flags
+//! would run with, each with where it came from (`flag`, `default` or
+//! `inferred`, or `profile` for a `--use-profile` section's), plus the package
+//! manager framectl drives.
+
+use std::fmt::Write as _;
+use std::process::{Command, Stdio};
+
+use crate::exec::Network;
+use crate::json::{self, Value};
+use crate::{build, default_concurrency, disk, frames, parse_bool, parse_kv, profiles, prompt, state_dir};
+
+struct Settings {
+  items: Vec<(&'static str, Value, &'static str)>,
+}
+
+impl Settings {
+  /// `--key` if given and valid, otherwise `default`.
+  fn put<T>(&mut self, args: &[String], key: &'static str, parse: impl Fn(&str) -> Option<T>, default: Value)
+  where
+    Value: From<T>,
+  {
+    match parse_kv(args, &format!("--{key}")).and_then(|v| parse(&v)) {
+      Some(v) => self.items.push((key, Value::from(v), "flag")),
+      None => self.items.push((key, default, "default")),
+    }
+  }
+
+  /// A flag without a default: unset unless given.
+  fn opt(&mut self, args: &[String], key: &'static str) {
+    let v = parse_kv(args, &format!("--{key}"));
+    let source = if v.is_some() { "flag" } else { "default" };
+    self.items.push((key, v.map(Value::from).unwrap_or(Value::Null), source));
+  }
+}
+
+fn num(s: &str) -> Option<usize> {
+  s.parse().ok()
+}
+
+/// `packageManager` from the root package.json, and what's on PATH.
+fn package_manager() -> Value {
+  let declared = json::read(std::path::Path::new("package.json"))
+    .ok()
+    .and_then(|doc| doc.get("packageManager").and_then(Value::as_str).map(str::to_string));
+  let installed = Command::new("pnpm")
+    .arg("--version")
+    .stdin(Stdio::null())
+    .stderr(Stdio::null())
+    .output()
+    .ok()
+    .filter(|o| o.status.success())
+    .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+    .filter(|v| !v.is_empty());
+  Value::obj([
+    ("name", Value::from("pnpm")),
+    ("declared", declared.map(Value::from).unwrap_or(Value::Null)),
+    ("installed", installed.map(Value::from).unwrap_or(Value::Null)),
+    ("lockfile", Value::from(std::path::Path::new("pnpm-lock.yaml").is_file())),
+  ])
+}
+
+fn resolve(args: &[String], command: &str) -> Settings {
+  let mut s = Settings { items: Vec::new() };
+  let source = if args.first().is_some_and(|a| a == command) { "flag" } else { "default" };
+  s.items.push(("command", Value::from(command), source));
+  s.put(args, "start", num, Value::from(1usize));
+  let found = frames::discover(&frames::frames_dir());
+  match parse_kv(args, "--end").and_then(|v| num(&v)) {
+    Some(end) => s.items.push(("end", Value::from(end), "flag")),
+    None => {
+      let end = frames::infer_end(&frames::frames_dir()).unwrap_or(0);
+      s.items.push(("end", Value::from(end), "inferred"));
+    }
+  }
+  s.opt(args, "frames");
+  s.opt(args, "exclude");
+  s.items.push(("frames_found", Value::from(found.len()), "inferred"));
+  // With --workers the slot count comes from the workers instead.
+  s.put(args, "concurrency", num, Value::from(default_concurrency()));
+  s.opt(args, "workers");
+  s.opt(args, "stages");
+  s.put(args, "silent", parse_bool, Value::from(true));
+  s.put(args, "dry-run", parse_bool, Value::from(false));
+  s.put(args, "keep-going", parse_bool, Value::from(false));
+  match parse_kv(args, "--interactive").and_then(|v| parse_bool(&v)) {
+    Some(b) => s.items.push(("interactive", Value::from(b), "flag")),
+    None => s.items.push(("interactive", Value::from(prompt::is_interactive()), "inferred")),
+  }
+  s.put(args, "preflight", parse_bool, Value::from(true));
+  let network = match build::parse_network(args) {
+    Network::Online => "online",
+    Network::PreferOffline => "prefer",
+    Network::Offline => "offline",
+  };
+  let source = if parse_kv(args, "--offline").is_some() { "flag" } else { "default" };
+  s.items.push(("offline", Value::from(network), source));
+  match parse_kv(args, "--order") {
+    Some(v) => s.items.push(("order", Value::from(v), "flag")),
+    None if parse_kv(args, "--weights").is_some() => s.items.push(("order", Value::from("slowest-first"), "inferred")),
+    None => s.items.push(("order", Value::from("sequential"), "default")),
+  }
+  s.opt(args, "weights");
+  s.put(args, "canary", num, Value::from(0usize));
+  s.put(args, "batch-size", num, Value::Null);
+  s.opt(args, "cooldown");
+  s.opt(args, "timeout");
+  s.put(args, "oom-retries", num, Value::from(2usize));
+  let source = if parse_kv(args, "--progress-interval").is_some() { "flag" } else { "default" };
+  s.items.push(("progress-interval", Value::from(build::parse_progress_interval(args).as_secs_f64()), source));
+  let quiet = build::parse_quiet(args);
+  s.items.push(("quiet", Value::from(quiet), if quiet { "flag" } else { "default" }));
+  let retry = build::parse_retry_failed(args);
+  s.items.push(("retry-failed", Value::from(retry), if retry { "flag" } else { "default" }));
+  s.put(args, "stagger-ms", num, Value::from(0usize));
+  s.put(args, "spawn-rate", |v| v.parse::<f64>().ok(), Value::Null);
+  s.put(args, "max-load", |v| v.parse::<f64>().ok(), Value::Null);
+  s.put(args, "min-free", |v| disk::parse_size(v).map(|b| b as usize), Value::Null);
+  s.put(args, "abort-free", |v| disk::parse_size(v).map(|b| b as usize), Value::Null);
+  s.put(args, "strict-stderr", parse_bool, Value::from(false));
+  s.opt(args, "stderr-patterns");
+  s.opt(args, "stderr-allow");
+  s.put(args, "max-dist-size", |v| disk::parse_size(v).map(|b| b as usize), Value::Null);
+  s.put(args, "max-entry-size", |v| disk::parse_size(v).map(|b| b as usize), Value::Null);
+  s.put(args, "wait", parse_bool, Value::from(false));
+  s.put(args, "nice", num, Value::Null);
+  s.opt(args, "ionice");
+  s.put(args, "node", num, Value::Null);
+  s.opt(args, "node-versions");
+  for key in ["container", "container-runtime", "container-cpus", "container-memory"] {
+    s.opt(args, key);
+  }
+  s.put(args, "container-pull", parse_bool, Value::from(true));
+  s.opt(args, "ssh-hosts");
+  s.put(args, "ssh-slots", num, Value::from(1usize));
+  s.opt(args, "ssh-dir");
+  s.put(args, "ssh-sync", parse_bool, Value::from(false));
+  for key in ["plan-out", "summary-out", "junit-out", "annotations", "target", "targets", "warnings-out", "failure-bundle", "heartbeat-file", "profile", "web", "stop-file", "pre-hook", "post-hook", "error-format", "filter-expr"] {
+    s.opt(args, key);
+  }
+  let active = profiles::active();
+  let source = if active.is_some() { "flag" } else { "default" };
+  s.items.push(("config-profile", active.map(Value::from).unwrap_or(Value::Null), source));
+  for item in &mut s.items {
+    if item.2 == "flag" && profiles::supplied(item.0) {
+      item.2 = "profile";
+    }
+  }
+  s.items.push(("frames_dir", Value::from(frames::frames_dir().display().to_string()), "default"));
+  s.items.push(("state_dir", Value::from(state_dir().display().to_string()), "default"));
+  s
+}
+
+fn toml(settings: &Settings, pm: &Value) -> String {
+  let mut out = String::new();
+  for (key, value, source) in &settings.items {
+    let key = key.replace('-', "_");
+    match value {
+      Value::Null => {
+        let _ = writeln!(out, "# {key} is unset ({source})");
+      }
+      v => {
+        let _ = writeln!(out, "{key} = {}  # {source}", v.compact());
+      }
+    }
+  }
+  out.push_str("\n[package_manager]\n");
+  if let Value::Obj(fields) = pm {
+    for (k, v) in fields {
+      match v {
+        Value::Null => {
+          let _ = writeln!(out, "# {k} is unknown");
+        }
+        v => {
+          let _ = writeln!(out, "{k} = {}", v.compact());
+        }
+      }
+    }
+  }
+  out
+}
+
+/// The TOML `print-config` shows, for a failure bundle.
+pub fn render(args: &[String], command: &str) -> String {
+  toml(&resolve(args, command), &package_manager())
+}
+
+pub fn run(args: &[String]) {
+  let command = args
+    .first()
+    .map(String::as_str)
+    .filter(|c| matches!(*c, "build" | "test"))
+    .unwrap_or("build");
+  let format = parse_kv(args, "--format").unwrap_or_else(|| "toml".to_string());
+  let settings = resolve(args, command);
+  let pm = package_manager();
+  match format.as_str() {
+    "toml" => print!("{}", toml(&settings, &pm)),
+    "json" => {
+      let fields: Vec<(String, Value)> = settings
+        .items
+        .into_iter()
+        .map(|(k, v, source)| (k.replace('-', "_"), Value::obj([("value", v), ("source", Value::from(source))])))
+        .collect();
+      let doc = Value::obj([("settings", Value::Obj(fields)), ("package_manager", pm)]);
+      print!("{}", doc.pretty());
+    }
+    other => {
+      eprintln!("invalid --format={other} (expected toml or json)");
+      std::process::exit(2);
+    }
+  }
+}
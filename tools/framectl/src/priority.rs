@@ -0,0 +1,126 @@
+//! `--nice` / `--ionice`: run builds below normal priority so a background
+//! rebuild leaves the machine usable. framectl lowers its own priority and
+//! every child it spawns inherits it.
+
+use std::io;
+
+use crate::exitcode;
+use crate::parse_kv;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Io {
+  Normal,
+  /// Only gets disk time nobody else wants (Linux).
+  Idle,
+}
+
+impl Io {
+  fn parse(s: &str) -> Option<Io> {
+    match s {
+      "normal" | "best-effort" | "0" => Some(Io::Normal),
+      "idle" | "1" => Some(Io::Idle),
+      _ => None,
+    }
+  }
+}
+
+#[cfg(unix)]
+fn set_nice(nice: i32) -> io::Result<()> {
+  extern "C" {
+    fn setpriority(which: std::os::raw::c_int, who: u32, prio: std::os::raw::c_int) -> std::os::raw::c_int;
+  }
+  const PRIO_PROCESS: std::os::raw::c_int = 0;
+  // SAFETY: plain syscall on our own process.
+  if unsafe { setpriority(PRIO_PROCESS, 0, nice) } != 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(())
+}
+
+/// Windows has priority classes rather than nice values; anything above 0
+/// means below-normal, which children inherit.
+#[cfg(windows)]
+fn set_nice(nice: i32) -> io::Result<()> {
+  use std::os::raw::c_void;
+  extern "system" {
+    fn GetCurrentProcess() -> *mut c_void;
+    fn SetPriorityClass(process: *mut c_void, class: u32) -> i32;
+  }
+  const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x4000;
+  const IDLE_PRIORITY_CLASS: u32 = 0x40;
+  let class = if nice >= 19 { IDLE_PRIORITY_CLASS } else { BELOW_NORMAL_PRIORITY_CLASS };
+  // SAFETY: the pseudo-handle for our own process is always valid.
+  if unsafe { SetPriorityClass(GetCurrentProcess(), class) } == 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn set_nice(_nice: i32) -> io::Result<()> {
+  Err(io::Error::new(io::ErrorKind::Unsupported, "not supported on this platform"))
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn set_io_idle() -> io::Result<()> {
+  extern "C" {
+    fn syscall(num: std::os::raw::c_long, ...) -> std::os::raw::c_long;
+  }
+  #[cfg(target_arch = "x86_64")]
+  const SYS_IOPRIO_SET: std::os::raw::c_long = 251;
+  #[cfg(target_arch = "aarch64")]
+  const SYS_IOPRIO_SET: std::os::raw::c_long = 30;
+  const IOPRIO_WHO_PROCESS: std::os::raw::c_int = 1;
+  const IOPRIO_CLASS_IDLE: std::os::raw::c_int = 3;
+  const IOPRIO_CLASS_SHIFT: u32 = 13;
+  // SAFETY: ioprio_set(which, who, ioprio) on our own process.
+  let r = unsafe { syscall(SYS_IOPRIO_SET, IOPRIO_WHO_PROCESS, 0, IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT) };
+  if r != 0 {
+    return Err(io::Error::last_os_error());
+  }
+  Ok(())
+}
+
+#[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+fn set_io_idle() -> io::Result<()> {
+  Err(io::Error::new(io::ErrorKind::Unsupported, "only supported on Linux"))
+}
+
+/// Apply what was asked for; failures only warn, the build still runs.
+fn lower(nice: Option<i32>, io: Io) {
+  if nice.is_none() && io == Io::Normal {
+    return;
+  }
+  let mut parts = Vec::new();
+  if let Some(n) = nice {
+    match set_nice(n.clamp(0, 19)) {
+      Ok(()) => parts.push(format!("nice={}", n.clamp(0, 19))),
+      Err(e) => eprintln!("warning: could not lower cpu priority: {e}"),
+    }
+  }
+  if io == Io::Idle {
+    match set_io_idle() {
+      Ok(()) => parts.push("io=idle".to_string()),
+      Err(e) => eprintln!("warning: could not set idle io priority: {e}"),
+    }
+  }
+  if !parts.is_empty() {
+    eprintln!("priority: {}", parts.join(" "));
+  }
+}
+
+/// `--nice=N` (0-19) and `--ionice=idle`, for `build`, `test` and `worker`.
+pub fn apply_args(args: &[String]) {
+  let nice: Option<i32> = parse_kv(args, "--nice").map(|v| {
+    v.parse().unwrap_or_else(|_| {
+      exitcode::usage(format!("invalid --nice={v} (expected 0-19)"));
+    })
+  });
+  let io = match parse_kv(args, "--ionice") {
+    None => Io::Normal,
+    Some(v) => Io::parse(&v).unwrap_or_else(|| {
+      exitcode::usage(format!("invalid --ionice={v} (expected idle or normal)"));
+    }),
+  };
+  lower(nice, io);
+}
@@ -0,0 +1,98 @@
+//! Live CPU time and RSS of a build's whole process tree, read from `/proc`:
+//! the pid framectl spawned is pnpm, and the bundler runs in its children.
+//! Elsewhere nothing is known and callers show blanks.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+#[derive(Clone, Copy)]
+pub struct Proc {
+  pub ppid: u32,
+  pub cpu: Duration,
+  pub rss_kb: u64,
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+  use std::os::raw::{c_int, c_long};
+
+  pub const SC_CLK_TCK: c_int = 2;
+  pub const SC_PAGESIZE: c_int = 30;
+
+  extern "C" {
+    pub fn sysconf(name: c_int) -> c_long;
+  }
+}
+
+/// Every process on the machine, by pid.
+#[cfg(target_os = "linux")]
+pub fn snapshot() -> BTreeMap<u32, Proc> {
+  // SAFETY: sysconf only reads configuration.
+  let (tck, page) = unsafe { (sys::sysconf(sys::SC_CLK_TCK), sys::sysconf(sys::SC_PAGESIZE)) };
+  let (tck, page_kb) = (tck.max(1) as f64, (page.max(1024) / 1024) as u64);
+  let mut out = BTreeMap::new();
+  let Ok(dir) = std::fs::read_dir("/proc") else {
+    return out;
+  };
+  for entry in dir.flatten() {
+    let Some(pid) = entry.file_name().to_str().and_then(|s| s.parse::<u32>().ok()) else {
+      continue;
+    };
+    let Ok(stat) = std::fs::read_to_string(entry.path().join("stat")) else {
+      continue;
+    };
+    // The command name is parenthesised and may hold spaces.
+    let Some((_, rest)) = stat.rsplit_once(") ") else {
+      continue;
+    };
+    let f: Vec<&str> = rest.split_whitespace().collect();
+    let field = |i: usize| f.get(i).and_then(|v| v.parse::<u64>().ok()).unwrap_or(0);
+    // Fields from `state` on: ppid 1, utime 11, stime 12, rss 21.
+    out.insert(
+      pid,
+      Proc {
+        ppid: field(1) as u32,
+        cpu: Duration::from_secs_f64((field(11) + field(12)) as f64 / tck),
+        rss_kb: field(21) * page_kb,
+      },
+    );
+  }
+  out
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn snapshot() -> BTreeMap<u32, Proc> {
+  BTreeMap::new()
+}
+
+/// Pids below `pid` in `procs`, parents before children.
+pub fn below(procs: &BTreeMap<u32, Proc>, pid: u32) -> Vec<u32> {
+  let mut out = Vec::new();
+  let mut frontier = vec![pid];
+  while let Some(p) = frontier.pop() {
+    for (&c, info) in procs {
+      if info.ppid == p && c != p {
+        out.push(c);
+        frontier.push(c);
+      }
+    }
+  }
+  out
+}
+
+pub fn descendants(pid: u32) -> Vec<u32> {
+  below(&snapshot(), pid)
+}
+
+/// CPU time and RSS summed over `pid` and its descendants; `None` when
+/// `pid` isn't visible.
+pub fn tree_usage(procs: &BTreeMap<u32, Proc>, pid: u32) -> Option<(Duration, u64)> {
+  let root = procs.get(&pid)?;
+  let mut cpu = root.cpu;
+  let mut rss = root.rss_kb;
+  for p in below(procs, pid) {
+    cpu += procs[&p].cpu;
+    rss += procs[&p].rss_kb;
+  }
+  Some((cpu, rss))
+}
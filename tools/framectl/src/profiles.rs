@@ -0,0 +1,346 @@
+//! Named profiles: `--use-profile=prod` picks `[profile.prod]` from
+//! `framectl.toml` (or `--config=PATH`) and adds its keys as flags, so a
+//! production build needn't be spelled out on every run. Flags given on the
+//! command line win over the profile's.
+//!
+//! ```toml
+//! [profile.prod]
+//! concurrency = 6
+//! offline = "prefer"
+//! max-dist-size = "2M"            # budgets: fail a frame built over them
+//! max-entry-size = "64K"
+//! base-url = "https://cdn.example.com/frames"   # for hash-manifest, playlist, ...
+//! build-script = "build:prod"     # pnpm script run for `build`
+//!
+//! [profile.prod.env]              # set for every frame's pnpm
+//! PUBLIC_PATH = "https://cdn.example.com/frames/"
+//! ```
+//!
+//! Profiles are expanded before the command line is checked, so their keys
+//! are validated like the flags typed out. A key the command doesn't take
+//! (`base-url` for `build`) is skipped with a note; one no command takes is
+//! an error.
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use crate::cli::{self, Command};
+use crate::exitcode;
+use crate::parse_kv;
+
+const DEFAULT_CONFIG: &str = "framectl.toml";
+
+/// Keys that configure framectl itself rather than becoming flags.
+const SCRIPT_KEYS: [&str; 5] = ["install-script", "typecheck-script", "build-script", "test-script", "publish-script"];
+
+#[derive(Default)]
+struct Profile {
+  flags: Vec<(String, String)>,
+  env: Vec<(String, String)>,
+}
+
+struct Applied {
+  name: String,
+  /// Flags the profile supplied (not overridden on the command line).
+  keys: Vec<String>,
+  env: Vec<(String, String)>,
+  scripts: BTreeMap<String, String>,
+}
+
+static APPLIED: OnceLock<Applied> = OnceLock::new();
+
+/// The profile in effect, if any.
+pub fn active() -> Option<&'static str> {
+  APPLIED.get().map(|a| a.name.as_str())
+}
+
+/// Whether `key` (without `--`) came from the profile.
+pub fn supplied(key: &str) -> bool {
+  APPLIED.get().is_some_and(|a| a.keys.iter().any(|k| k == key))
+}
+
+/// The profile's `[profile.NAME.env]`, for every frame's pnpm.
+pub fn env() -> &'static [(String, String)] {
+  APPLIED.get().map(|a| a.env.as_slice()).unwrap_or(&[])
+}
+
+/// The pnpm script the profile runs for `script` (e.g. `build`), if it
+/// renames it.
+pub fn script_name(script: &str) -> Option<&'static str> {
+  APPLIED.get()?.scripts.get(script).map(String::as_str)
+}
+
+/// A TOML value as flag text: strings unquoted, arrays comma-joined.
+fn value(raw: &str) -> Result<String, String> {
+  let raw = raw.trim();
+  if let Some(inner) = raw.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+    let items: Result<Vec<String>, String> = inner
+      .split(',')
+      .map(str::trim)
+      .filter(|i| !i.is_empty())
+      .map(value)
+      .collect();
+    return items.map(|i| i.join(","));
+  }
+  if let Some(s) = raw.strip_prefix('\'').and_then(|r| r.strip_suffix('\'')) {
+    return Ok(s.to_string());
+  }
+  if let Some(s) = raw.strip_prefix('"').and_then(|r| r.strip_suffix('"')) {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+      if c != '\\' {
+        out.push(c);
+        continue;
+      }
+      match chars.next() {
+        Some('n') => out.push('\n'),
+        Some('t') => out.push('\t'),
+        Some(c @ ('"' | '\\')) => out.push(c),
+        other => return Err(format!("unsupported escape \\{}", other.map(String::from).unwrap_or_default())),
+      }
+    }
+    return Ok(out);
+  }
+  match raw {
+    "true" => Ok("1".to_string()),
+    "false" => Ok("0".to_string()),
+    _ => {
+      // TOML numbers may group digits with `_`; a flag can't.
+      let n = raw.replace('_', "");
+      if n.parse::<f64>().is_ok() && !raw.starts_with('_') && !raw.ends_with('_') {
+        Ok(n)
+      } else {
+        Err(format!("unsupported value {raw}"))
+      }
+    }
+  }
+}
+
+/// `#` outside quotes starts a comment.
+fn strip_comment(line: &str) -> &str {
+  let mut quote = None;
+  for (i, c) in line.char_indices() {
+    match (quote, c) {
+      (None, '"' | '\'') => quote = Some(c),
+      (Some(q), _) if c == q => quote = None,
+      (None, '#') => return &line[..i],
+      _ => {}
+    }
+  }
+  line
+}
+
+/// The `[profile.*]` sections of a config file; other sections are left to
+/// whatever else reads the file.
+fn parse(text: &str) -> Result<BTreeMap<String, Profile>, String> {
+  let mut profiles: BTreeMap<String, Profile> = BTreeMap::new();
+  // (profile, in its env table)
+  let mut section: Option<(String, bool)> = None;
+  for (i, line) in text.lines().enumerate() {
+    let line = strip_comment(line).trim();
+    if line.is_empty() {
+      continue;
+    }
+    let at = |e: String| format!("line {}: {e}", i + 1);
+    if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+      let parts: Vec<&str> = header.trim().split('.').map(str::trim).collect();
+      section = match parts[..] {
+        ["profile", name] => Some((name.to_string(), false)),
+        ["profile", name, "env"] => Some((name.to_string(), true)),
+        ["profile", ..] => return Err(at(format!("unexpected section [{header}]"))),
+        _ => None,
+      };
+      if let Some((name, _)) = &section {
+        profiles.entry(name.clone()).or_default();
+      }
+      continue;
+    }
+    let Some((name, env)) = &section else {
+      continue;
+    };
+    let (key, raw) = line.split_once('=').ok_or_else(|| at(format!("expected key = value, got {line}")))?;
+    let key = key.trim().trim_matches('"').to_string();
+    let v = value(raw).map_err(at)?;
+    let p = profiles.get_mut(name).expect("section registered");
+    if *env {
+      p.env.push((key, v));
+    } else {
+      p.flags.push((key, v));
+    }
+  }
+  Ok(profiles)
+}
+
+/// `args` (as typed) with `--use-profile=NAME`'s flags added after the
+/// explicit ones, so those win. Anything else is returned unchanged.
+pub fn expand(cmd: &Command, args: &[String]) -> Vec<String> {
+  let given = cli::args(cmd, args);
+  let Some(name) = parse_kv(&given, "--use-profile") else {
+    return args.to_vec();
+  };
+  let explicit = parse_kv(&given, "--config");
+  let path = explicit.unwrap_or_else(|| DEFAULT_CONFIG.to_string());
+  let text = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+    exitcode::usage(format!("--use-profile={name}: cannot read {path}: {e}"));
+  });
+  let mut profiles = parse(&text).unwrap_or_else(|e| {
+    exitcode::usage(format!("{path}: {e}"));
+  });
+  let Some(profile) = profiles.remove(&name) else {
+    let known: Vec<&str> = profiles.keys().map(String::as_str).collect();
+    if known.is_empty() {
+      exitcode::usage(format!("unknown profile {name} ({path} has no [profile.NAME] sections)"));
+    }
+    exitcode::usage(format!("unknown profile {name} ({path} has {})", known.join(", ")));
+  };
+
+  let mut added = Vec::new();
+  let mut keys = Vec::new();
+  let mut skipped = Vec::new();
+  let mut scripts = BTreeMap::new();
+  for (key, v) in profile.flags {
+    if SCRIPT_KEYS.contains(&key.as_str()) {
+      scripts.insert(key.trim_end_matches("-script").to_string(), v);
+    } else if cmd.flag(&key).is_none() {
+      if !cli::spec().commands.iter().any(|c| c.flag(&key).is_some()) {
+        exitcode::usage(format!("{path}: [profile.{name}] {key} is not a flag of any command"));
+      }
+      // Meant for another command sharing the profile.
+      skipped.push(key);
+    } else if parse_kv(&given, &format!("--{key}")).is_none() {
+      added.push(format!("--{key}={v}"));
+      keys.push(key);
+    }
+  }
+  // Before any `--`, after which everything is positional.
+  let mut out = args.to_vec();
+  let at = out.iter().position(|a| a == "--").unwrap_or(out.len());
+  out.splice(at..at, added);
+  eprintln!(
+    "profile: {name} from {path} ({} flag(s){}{})",
+    keys.len(),
+    if profile.env.is_empty() { String::new() } else { format!(", {} env var(s)", profile.env.len()) },
+    if scripts.is_empty() { String::new() } else { format!(", {} script(s)", scripts.len()) }
+  );
+  if !skipped.is_empty() {
+    eprintln!("profile: {name}: skipping {} (not taken by {})", skipped.join(", "), cmd.name);
+  }
+  let _ = APPLIED.set(Applied {
+    name,
+    keys,
+    env: profile.env,
+    scripts,
+  });
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const CONFIG: &str = r#"
+[server]
+port = 8080
+
+[profile.prod]
+concurrency = 6
+offline = "prefer"   # not a comment inside "quotes # here"
+workers = ["a:7000", 'b:7000']
+dry-run = false
+build-script = "build:prod"
+
+[profile.prod.env]
+PUBLIC_PATH = "https://cdn.example.com/frames/"
+
+[profile.dev]
+"#;
+
+  fn strs(v: &[&str]) -> Vec<String> {
+    v.iter().map(|s| s.to_string()).collect()
+  }
+
+  #[test]
+  fn values() {
+    assert_eq!(value(r#""a\"b\\c\n""#).unwrap(), "a\"b\\c\n");
+    assert_eq!(value("'raw\\n'").unwrap(), "raw\\n");
+    assert_eq!(value("true").unwrap(), "1");
+    assert_eq!(value("1_000").unwrap(), "1000");
+    assert_eq!(value("[1, 2, ]").unwrap(), "1,2");
+    assert!(value("bare").is_err());
+    assert!(value(r#""\x""#).is_err());
+  }
+
+  #[test]
+  fn parses_profiles() {
+    let profiles = parse(CONFIG).unwrap();
+    assert_eq!(profiles.keys().collect::<Vec<_>>(), ["dev", "prod"]);
+    let prod = &profiles["prod"];
+    let flags: Vec<(&str, &str)> = prod.flags.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    assert_eq!(
+      flags,
+      [
+        ("concurrency", "6"),
+        ("offline", "prefer"),
+        ("workers", "a:7000,b:7000"),
+        ("dry-run", "0"),
+        ("build-script", "build:prod"),
+      ]
+    );
+    assert_eq!(prod.env, [("PUBLIC_PATH".to_string(), "https://cdn.example.com/frames/".to_string())]);
+    assert!(profiles["dev"].flags.is_empty());
+  }
+
+  #[test]
+  fn parse_errors() {
+    assert_eq!(parse("[profile.a.b]").err().unwrap(), "line 1: unexpected section [profile.a.b]");
+    assert_eq!(parse("[profile.a]\n\nkey").err().unwrap(), "line 3: expected key = value, got key");
+    assert_eq!(parse("[profile.a]\nk = what").err().unwrap(), "line 2: unsupported value what");
+    // Other sections aren't ours to check.
+    assert!(parse("[other]\nkey").unwrap().is_empty());
+  }
+
+  #[test]
+  fn expands_before_positionals() {
+    let path = std::env::temp_dir().join(format!("framectl-profiles-{}.toml", std::process::id()));
+    std::fs::write(&path, CONFIG).unwrap();
+    let cmd = cli::spec().command("build").unwrap();
+    let config = format!("--config={}", path.display());
+    let args = strs(&["--concurrency", "2", "--use-profile=prod", &config, "--", "x"]);
+    let out = expand(cmd, &args);
+    let _ = std::fs::remove_file(&path);
+
+    let mut want = args.clone();
+    want.splice(4..4, strs(&["--offline=prefer", "--workers=a:7000,b:7000", "--dry-run=0"]));
+    assert_eq!(out, want);
+    assert_eq!(active(), Some("prod"));
+    assert!(supplied("offline") && !supplied("concurrency"));
+    assert_eq!(script_name("build"), Some("build:prod"));
+    assert_eq!(env().len(), 1);
+  }
+
+  #[test]
+  fn skips_other_commands_keys() {
+    let path = std::env::temp_dir().join(format!("framectl-profiles-skip-{}.toml", std::process::id()));
+    let text = CONFIG.replace("build-script", "base-url = \"https://cdn.example.com\"\nmax-dist-size = \"2M\"\nbuild-script");
+    std::fs::write(&path, text).unwrap();
+    let config = format!("--config={}", path.display());
+    let args = strs(&["--concurrency=2", "--use-profile=prod", &config]);
+    let build = expand(cli::spec().command("build").unwrap(), &args);
+    let manifest = expand(cli::spec().command("hash-manifest").unwrap(), &args);
+    let _ = std::fs::remove_file(&path);
+
+    assert!(build.contains(&"--max-dist-size=2M".to_string()));
+    assert!(!build.iter().any(|a| a.starts_with("--base-url")));
+    let mut want = args.clone();
+    want.extend(strs(&["--base-url=https://cdn.example.com"]));
+    assert_eq!(manifest, want);
+  }
+
+  #[test]
+  fn no_profile_unchanged() {
+    let cmd = cli::spec().command("build").unwrap();
+    let args = strs(&["--start", "3"]);
+    assert_eq!(expand(cmd, &args), args);
+  }
+}
@@ -0,0 +1,52 @@
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::Command;
+
+use crate::exec::{self, Script};
+use crate::frames;
+
+pub enum Choice {
+  Retry,
+  Skip,
+  Abort,
+}
+
+pub fn is_interactive() -> bool {
+  io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+/// Ask what to do about failed frame `n`. EOF on stdin counts as abort.
+pub fn on_failure(n: usize, script: Script) -> Choice {
+  let stdin = io::stdin();
+  loop {
+    eprint!("{} failed: [r]etry, [s]kip, [o]pen log, [a]bort? ", frames::label(n));
+    let _ = io::stderr().flush();
+    let mut line = String::new();
+    if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+      eprintln!();
+      return Choice::Abort;
+    }
+    match line.trim() {
+      "r" | "retry" => return Choice::Retry,
+      "s" | "skip" => return Choice::Skip,
+      "a" | "abort" | "q" => return Choice::Abort,
+      "o" | "open" => open_log(n, script),
+      _ => {}
+    }
+  }
+}
+
+fn open_log(n: usize, script: Script) {
+  let path = exec::log_path(n, script);
+  if !path.is_file() {
+    eprintln!("no log at {}", path.display());
+    return;
+  }
+  let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+  let mut parts = pager.split_whitespace();
+  let Some(bin) = parts.next() else {
+    return;
+  };
+  if let Err(e) = Command::new(bin).args(parts).arg(&path).status() {
+    eprintln!("{pager}: {e} (log is at {})", path.display());
+  }
+}
@@ -0,0 +1,283 @@
+//! `publish`: bump, build (unless the dist is current) and publish frame
+//! packages in parallel under a rate limit.
+//!
+//! Progress is appended to `.framectl/publish.tsv` (`<frame>\t<version>\t<status>`,
+//! last line per frame wins) so an interrupted run picks up where it stopped
+//! instead of bumping again. The file is removed once every frame is out.
+
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::exec::{self, ExecOpts, Network, Script};
+use crate::frames::{self, fmt_ranges};
+use crate::json::{self, Value};
+use crate::queue::par_each;
+use crate::throttle::Throttle;
+use crate::{default_concurrency, fmt_dur, parse_bool, parse_kv, state_dir};
+
+fn state_path() -> PathBuf {
+  state_dir().join("publish.tsv")
+}
+
+fn load_state() -> BTreeMap<usize, (String, String)> {
+  let mut out = BTreeMap::new();
+  let Ok(text) = std::fs::read_to_string(state_path()) else {
+    return out;
+  };
+  for line in text.lines() {
+    let mut parts = line.split('\t');
+    if let (Some(n), Some(v), Some(st)) = (parts.next(), parts.next(), parts.next()) {
+      if let Ok(n) = n.parse::<usize>() {
+        out.insert(n, (v.to_string(), st.to_string()));
+      }
+    }
+  }
+  out
+}
+
+/// `patch` / `minor` / `major`, or an explicit version.
+fn bump(version: &str, how: &str) -> Option<String> {
+  let (core, pre) = version.split_once('-').unwrap_or((version, ""));
+  let mut nums = core.split('.').map(|p| p.parse::<u64>().ok());
+  let (x, y, z) = (nums.next()??, nums.next()??, nums.next()??);
+  let next = match how {
+    // A prerelease patch bump releases the version it was leading up to.
+    "patch" if !pre.is_empty() => (x, y, z),
+    "patch" => (x, y, z + 1),
+    "minor" => (x, y + 1, 0),
+    "major" => (x + 1, 0, 0),
+    explicit => {
+      let core = explicit.split('-').next().unwrap_or("");
+      let valid = core.split('.').filter(|p| p.parse::<u64>().is_ok()).count() == 3;
+      return valid.then(|| explicit.to_string());
+    }
+  };
+  Some(format!("{}.{}.{}", next.0, next.1, next.2))
+}
+
+fn set_version(path: &Path, version: &str) -> Result<(), String> {
+  let mut doc = json::read(path)?;
+  match doc.get_mut("version") {
+    Some(v) if v.as_str() == Some(version) => return Ok(()),
+    Some(v) => *v = Value::from(version),
+    None => {
+      if let Value::Obj(fields) = &mut doc {
+        fields.push(("version".to_string(), Value::from(version)));
+      }
+    }
+  }
+  let tmp = path.with_extension("json.tmp");
+  std::fs::write(&tmp, doc.pretty())
+    .and_then(|_| std::fs::rename(&tmp, path))
+    .map_err(|e| format!("{}: {e}", path.display()))
+}
+
+pub fn run(args: &[String]) {
+  let Some(how) = parse_kv(args, "--bump") else {
+    eprintln!("usage: framectl publish --bump=patch|minor|major|X.Y.Z [--registry=URL] ...");
+    std::process::exit(2);
+  };
+  let registry = parse_kv(args, "--registry");
+  let tag = parse_kv(args, "--tag");
+  let concurrency: usize = parse_kv(args, "--concurrency")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or_else(default_concurrency);
+  let rate: f64 = parse_kv(args, "--publish-rate")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(5.0);
+  let rebuild: bool = parse_kv(args, "--rebuild")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(false);
+  let fresh: bool = parse_kv(args, "--fresh")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(false);
+  let dry_run: bool = parse_kv(args, "--dry-run")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(false);
+  let state = if fresh { BTreeMap::new() } else { load_state() };
+  let frames: Vec<usize> = frames::selected(args);
+  if frames.is_empty() {
+    eprintln!("publish: no frames in range");
+    std::process::exit(2);
+  }
+
+  // Target version per frame: from the interrupted run if there is one,
+  // else the current manifest version bumped.
+  let mut plan: Vec<(usize, String)> = Vec::new();
+  let mut done_before = 0usize;
+  let mut private = Vec::new();
+  let mut problems = Vec::new();
+  for &n in &frames {
+    let path = frames::frame_dir(n).join("package.json");
+    let doc = match json::read(&path) {
+      Ok(d) => d,
+      Err(e) => {
+        problems.push(e);
+        continue;
+      }
+    };
+    if matches!(doc.get("private"), Some(Value::Bool(true))) {
+      private.push(n);
+    }
+    let target = match state.get(&n) {
+      Some((_, st)) if st == "published" => {
+        done_before += 1;
+        continue;
+      }
+      Some((v, _)) => v.clone(),
+      None => {
+        let current = doc.get("version").and_then(Value::as_str).unwrap_or("0.0.0");
+        match bump(current, &how) {
+          Some(v) => v,
+          None => {
+            problems.push(format!("frame-{n:04}: cannot bump version {current:?} with {how:?}"));
+            continue;
+          }
+        }
+      }
+    };
+    plan.push((n, target));
+  }
+  if !private.is_empty() {
+    problems.push(format!(
+      "{} package(s) are \"private\": true: {}",
+      private.len(),
+      fmt_ranges(&private)
+    ));
+  }
+  if !problems.is_empty() {
+    eprintln!("publish: cannot start:");
+    for p in &problems {
+      eprintln!("  - {p}");
+    }
+    std::process::exit(2);
+  }
+  if done_before > 0 {
+    eprintln!("publish: resuming {}; {done_before} frame(s) already published", state_path().display());
+  }
+  eprintln!(
+    "publish: {} frame(s) concurrency={concurrency} rate={rate}/s registry={}{}",
+    plan.len(),
+    registry.as_deref().unwrap_or("(default)"),
+    if dry_run { " dry_run=1" } else { "" }
+  );
+  if dry_run {
+    for (n, v) in plan.iter().take(10) {
+      eprintln!("  {}@{v}{}", frames::frame_pkg(*n), if frames::dist_current(*n) { "" } else { " (build)" });
+    }
+    if plan.len() > 10 {
+      eprintln!("  ... and {} more", plan.len() - 10);
+    }
+    return;
+  }
+
+  let log = std::fs::create_dir_all(state_dir()).and_then(|_| {
+    OpenOptions::new().create(true).append(true).open(state_path())
+  });
+  let log: Mutex<File> = match log {
+    Ok(f) => Mutex::new(f),
+    Err(e) => {
+      eprintln!("publish: {}: {e}", state_path().display());
+      std::process::exit(1);
+    }
+  };
+  let record = |n: usize, v: &str, status: &str| {
+    let _ = writeln!(log.lock().unwrap(), "{n}\t{v}\t{status}");
+  };
+  for (n, v) in &plan {
+    if !state.contains_key(n) {
+      record(*n, v, "pending");
+    }
+  }
+
+  let throttle = Throttle::new(Duration::ZERO, Some(rate));
+  let built = AtomicUsize::new(0);
+  let published = AtomicUsize::new(0);
+  let failed: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+  let progress = Mutex::new(Instant::now());
+  let t0 = Instant::now();
+  par_each(&plan, concurrency, |(n, version)| {
+    let n = *n;
+    let fail = |what: &str, detail: &str| {
+      eprintln!("failed: frame-{n:04} {what}");
+      if !detail.trim().is_empty() {
+        eprintln!("stderr tail:\n{detail}");
+      }
+      failed.lock().unwrap().push(n);
+    };
+    if let Err(e) = set_version(&frames::frame_dir(n).join("package.json"), version) {
+      return fail("version bump", &e);
+    }
+
+    if rebuild || !frames::dist_current(n) {
+      let opts = ExecOpts {
+        script: Script::Build,
+        silent: true,
+        dry_run: false,
+        network: Network::Online,
+        node: None,
+      };
+      let out = exec::run_frame(n, opts);
+      if !out.ok {
+        return fail("build", &out.err_tail);
+      }
+      built.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let opts = ExecOpts {
+      script: Script::Publish,
+      silent: true,
+      dry_run: false,
+      network: Network::Online,
+      node: None,
+    };
+    let mut cmd = exec::command(n, opts);
+    // The dist is built already; publish only packs it.
+    cmd.arg("--no-git-checks").arg("--ignore-scripts");
+    if let Some(r) = &registry {
+      cmd.arg(format!("--registry={r}"));
+    }
+    if let Some(t) = &tag {
+      cmd.arg(format!("--tag={t}"));
+    }
+    throttle.before_spawn();
+    let out = exec::run_command(n, opts, cmd);
+    let conflict = out.err_tail.contains("EPUBLISHCONFLICT")
+      || out.err_tail.contains("previously published")
+      || out.err_tail.contains("E409");
+    if !out.ok && !conflict {
+      return fail("publish", &out.err_tail);
+    }
+    record(n, version, "published");
+    let done = published.fetch_add(1, Ordering::Relaxed) + 1;
+    let mut last = progress.lock().unwrap();
+    if last.elapsed() >= Duration::from_secs(1) || done == plan.len() {
+      eprintln!("progress: published={done}/{} built={}", plan.len(), built.load(Ordering::Relaxed));
+      *last = Instant::now();
+    }
+  });
+
+  let mut failed = failed.into_inner().unwrap();
+  if failed.is_empty() {
+    let _ = std::fs::remove_file(state_path());
+    eprintln!(
+      "success: published {} frames in {} (rebuilt {})",
+      published.into_inner(),
+      fmt_dur(t0.elapsed()),
+      built.into_inner()
+    );
+    return;
+  }
+  failed.sort_unstable();
+  eprintln!(
+    "exit: {} frame(s) not published: {}; rerun publish to resume",
+    failed.len(),
+    fmt_ranges(&failed)
+  );
+  std::process::exit(1);
+}
@@ -0,0 +1,270 @@
+//! `purge`: after a deploy, the Cloudflare cache purged for exactly the
+//! frames the deploy changed. Hashed paths never go stale, but the unhashed
+//! `/frame-0001/static/js/remoteEntry.js` (and the routing manifest itself,
+//! with `--also`) keep serving the old release until they expire.
+//!
+//! The changed frames come from the same diff as `hash-manifest --deployed`:
+//! the new manifest against the one that was live before the upload. URLs go
+//! to the zone purge API in batches, spaced `--interval` apart, backing off
+//! when the API answers 429.
+
+use std::collections::BTreeMap;
+use std::io::Write as _;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::frames::{self, ENTRY};
+use crate::hashmanifest::deployed_hashes;
+use crate::json::{self, Value};
+use crate::{parse_bool, parse_kv};
+
+const DEFAULT_MANIFEST: &str = "frames-manifest.json";
+const API: &str = "https://api.cloudflare.com/client/v4/zones";
+/// Cloudflare's limit on URLs per purge request.
+const MAX_BATCH: usize = 30;
+const RETRIES: u32 = 4;
+
+/// A curl config line: `key = "value"`, so the token stays out of argv.
+fn config_line(key: &str, value: &str) -> String {
+  format!("{key} = \"{}\"\n", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+enum Outcome {
+  Done,
+  /// 429, with Retry-After if the API sent one.
+  Limited(Option<u64>),
+  Failed(String),
+}
+
+fn purge_batch(zone: &str, token: &str, urls: &[String]) -> Outcome {
+  let body = Value::obj([("files", Value::Arr(urls.iter().map(|u| Value::from(u.as_str())).collect()))]).compact();
+  let mut config = String::new();
+  config.push_str(&config_line("url", &format!("{API}/{zone}/purge_cache")));
+  config.push_str(&config_line("request", "POST"));
+  config.push_str(&config_line("header", &format!("Authorization: Bearer {token}")));
+  config.push_str(&config_line("header", "Content-Type: application/json"));
+  config.push_str(&config_line("data", &body));
+  // Status and Retry-After after the body, on their own lines.
+  config.push_str(&config_line("write-out", "\\n%{http_code}\\n%header{retry-after}"));
+
+  let child = Command::new("curl")
+    .args(["-sS", "--config", "-"])
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn();
+  let mut child = match child {
+    Ok(c) => c,
+    Err(e) => return Outcome::Failed(format!("curl: {e}")),
+  };
+  if let Some(mut stdin) = child.stdin.take() {
+    let _ = stdin.write_all(config.as_bytes());
+  }
+  let out = match child.wait_with_output() {
+    Ok(o) => o,
+    Err(e) => return Outcome::Failed(format!("curl: {e}")),
+  };
+  if !out.status.success() {
+    return Outcome::Failed(String::from_utf8_lossy(&out.stderr).trim().to_string());
+  }
+  reply(&String::from_utf8_lossy(&out.stdout))
+}
+
+/// curl's output for a purge request: the body, then the status and
+/// Retry-After on their own lines.
+fn reply(text: &str) -> Outcome {
+  let mut lines = text.rsplitn(3, '\n');
+  let retry_after = lines.next().and_then(|r| r.trim().parse().ok());
+  let code = lines.next().unwrap_or("").trim().to_string();
+  let body = lines.next().unwrap_or("");
+  if code == "429" {
+    return Outcome::Limited(retry_after);
+  }
+  let doc = json::parse(body).unwrap_or(Value::Null);
+  if code.starts_with('2') && doc.get("success") == Some(&Value::Bool(true)) {
+    return Outcome::Done;
+  }
+  let errors = match doc.get("errors") {
+    Some(Value::Arr(errs)) => errs
+      .iter()
+      .filter_map(|e| e.get("message").and_then(Value::as_str))
+      .collect::<Vec<_>>()
+      .join("; "),
+    _ => String::new(),
+  };
+  Outcome::Failed(format!("HTTP {code}{}", if errors.is_empty() { String::new() } else { format!(": {errors}") }))
+}
+
+/// `--batch`, at most Cloudflare's limit.
+fn batch_size(args: &[String]) -> usize {
+  parse_kv(args, "--batch")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(MAX_BATCH)
+    .clamp(1, MAX_BATCH)
+}
+
+/// Frames changed, added or removed between two manifests' hashes: any of
+/// them leaves the unhashed entry stale.
+fn changed(now: &BTreeMap<usize, String>, before: &BTreeMap<usize, String>) -> Vec<usize> {
+  let mut changed: Vec<usize> = now
+    .iter()
+    .filter(|(n, h)| before.get(n) != Some(h))
+    .map(|(n, _)| *n)
+    .chain(before.keys().copied().filter(|n| !now.contains_key(n)))
+    .collect();
+  changed.sort();
+  changed
+}
+
+/// The unhashed remote entry of each changed frame, then `also`.
+fn urls(base_url: &str, changed: &[usize], also: Vec<String>) -> Vec<String> {
+  let mut urls: Vec<String> = changed.iter().map(|n| format!("{base_url}/frame-{n:04}/{ENTRY}")).collect();
+  urls.extend(also);
+  urls
+}
+
+pub fn run(args: &[String]) {
+  let manifest = parse_kv(args, "--manifest").unwrap_or_else(|| DEFAULT_MANIFEST.to_string());
+  let Some(previous) = parse_kv(args, "--deployed") else {
+    eprintln!("purge: --deployed=PATH|URL is required (the manifest that was live before this deploy)");
+    std::process::exit(2);
+  };
+  let base_url = parse_kv(args, "--base-url").unwrap_or_default();
+  let base_url = base_url.trim_end_matches('/');
+  if !(base_url.starts_with("http://") || base_url.starts_with("https://")) {
+    eprintln!("purge: --base-url must be the absolute URL the frames are served from");
+    std::process::exit(2);
+  }
+  let batch = batch_size(args);
+  let interval = Duration::from_secs_f64(
+    parse_kv(args, "--interval")
+      .and_then(|v| v.parse::<f64>().ok())
+      .filter(|s| s.is_finite() && *s >= 0.0)
+      .unwrap_or(1.0),
+  );
+  let dry_run = parse_kv(args, "--dry-run")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(false);
+  let also: Vec<String> = parse_kv(args, "--also")
+    .map(|v| v.split(',').map(str::trim).filter(|u| !u.is_empty()).map(String::from).collect())
+    .unwrap_or_default();
+
+  let load = |src: &str| {
+    deployed_hashes(src).unwrap_or_else(|e| {
+      eprintln!("purge: {src}: {e}");
+      std::process::exit(1);
+    })
+  };
+  let now = load(&manifest);
+  let before = load(&previous);
+  let changed = changed(&now, &before);
+  if changed.is_empty() && also.is_empty() {
+    eprintln!("purge: no frame changed between {previous} and {manifest}; nothing to purge");
+    return;
+  }
+
+  let urls = urls(base_url, &changed, also);
+  let total = urls.len();
+  let batches: Vec<&[String]> = urls.chunks(batch).collect();
+  eprintln!(
+    "purge: {} changed frame(s): {}; {} URL(s) in {} request(s)",
+    changed.len(),
+    if changed.is_empty() { "none".to_string() } else { frames::fmt_ranges(&changed) },
+    total,
+    batches.len()
+  );
+  if dry_run {
+    for u in &urls {
+      println!("{u}");
+    }
+    return;
+  }
+
+  let env = |k: &str| std::env::var(k).ok().filter(|v| !v.is_empty());
+  let Some(token) = env("CLOUDFLARE_API_TOKEN") else {
+    eprintln!("purge: CLOUDFLARE_API_TOKEN is not set (a token with Zone.Cache Purge)");
+    std::process::exit(2);
+  };
+  let Some(zone) = parse_kv(args, "--zone").or_else(|| env("CLOUDFLARE_ZONE_ID")) else {
+    eprintln!("purge: --zone=ID (or CLOUDFLARE_ZONE_ID) is required");
+    std::process::exit(2);
+  };
+
+  let mut purged = 0;
+  for (i, chunk) in batches.iter().enumerate() {
+    if i > 0 {
+      std::thread::sleep(interval);
+    }
+    let mut attempt = 0;
+    loop {
+      match purge_batch(&zone, &token, chunk) {
+        Outcome::Done => break,
+        Outcome::Limited(after) if attempt < RETRIES => {
+          let wait = after.unwrap_or(1 << (attempt + 1));
+          eprintln!("purge: rate limited; retrying request {} in {wait}s", i + 1);
+          std::thread::sleep(Duration::from_secs(wait));
+          attempt += 1;
+        }
+        Outcome::Limited(_) => {
+          eprintln!("purge: still rate limited after {RETRIES} retries; {purged}/{total} URL(s) purged");
+          std::process::exit(1);
+        }
+        Outcome::Failed(e) => {
+          eprintln!("purge: request {} failed: {e}; {purged}/{total} URL(s) purged", i + 1);
+          std::process::exit(1);
+        }
+      }
+    }
+    purged += chunk.len();
+    eprintln!("purge: {purged}/{total} URL(s) purged");
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn hashes(v: &[(usize, &str)]) -> BTreeMap<usize, String> {
+    v.iter().map(|(n, h)| (*n, h.to_string())).collect()
+  }
+
+  #[test]
+  fn changed_added_and_removed() {
+    let before = hashes(&[(1, "a"), (2, "b"), (3, "c")]);
+    let now = hashes(&[(1, "a"), (2, "B"), (4, "d")]);
+    assert_eq!(changed(&now, &before), [2, 3, 4]);
+    assert!(changed(&before, &before).is_empty());
+  }
+
+  #[test]
+  fn batches_urls() {
+    let strs = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(batch_size(&[]), MAX_BATCH);
+    assert_eq!(batch_size(&strs(&["--batch=10"])), 10);
+    assert_eq!(batch_size(&strs(&["--batch=500"])), MAX_BATCH);
+    assert_eq!(batch_size(&strs(&["--batch=0"])), 1);
+
+    let changed: Vec<usize> = (1..=64).collect();
+    let urls = urls("https://cdn.example.com", &changed, strs(&["https://cdn.example.com/frames-manifest.json"]));
+    assert_eq!(urls[0], format!("https://cdn.example.com/frame-0001/{ENTRY}"));
+    assert_eq!(urls.last().unwrap(), "https://cdn.example.com/frames-manifest.json");
+    let sizes: Vec<usize> = urls.chunks(MAX_BATCH).map(<[String]>::len).collect();
+    assert_eq!(sizes, [30, 30, 5]);
+  }
+
+  #[test]
+  fn reads_replies() {
+    assert!(matches!(reply("{\"success\":true}\n200\n"), Outcome::Done));
+    assert!(matches!(reply("{}\n429\n7"), Outcome::Limited(Some(7))));
+    assert!(matches!(reply("{}\n429\n"), Outcome::Limited(None)));
+    let failed = reply("{\"success\":false,\"errors\":[{\"message\":\"bad zone\"},{\"message\":\"no\"}]}\n400\n");
+    assert!(matches!(failed, Outcome::Failed(e) if e == "HTTP 400: bad zone; no"));
+    // A 200 that doesn't say success isn't one.
+    assert!(matches!(reply("oops\n200\n"), Outcome::Failed(e) if e == "HTTP 200"));
+  }
+
+  #[test]
+  fn quotes_config_values() {
+    assert_eq!(config_line("header", r#"a "b" \c"#), "header = \"a \\\"b\\\" \\\\c\"\n");
+  }
+}
@@ -0,0 +1,173 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Shared frame queue. Unlike a channel, slots can hand a frame back
+/// (`requeue`) when the machine building it goes away mid-task.
+pub struct TaskQueue {
+  state: Mutex<State>,
+  cv: Condvar,
+}
+
+struct State {
+  pending: VecDeque<usize>,
+  /// Not handed out until `release`.
+  held: VecDeque<usize>,
+  /// Handed out, with when.
+  in_flight: BTreeMap<usize, Instant>,
+  closed: bool,
+  fail_fast: bool,
+  /// Most frames handed out at once; lowered while backing off.
+  limit: usize,
+  /// Nothing is handed out while set.
+  paused: bool,
+  /// Frames that may still be handed out, if capped (`--batch-size`).
+  budget: Option<usize>,
+}
+
+impl TaskQueue {
+  /// With `fail_fast` the first failure closes the queue; otherwise a failed
+  /// frame stays in flight until the caller `requeue`s or `finish`es it.
+  pub fn new(frames: impl IntoIterator<Item = usize>, fail_fast: bool) -> Self {
+    TaskQueue {
+      state: Mutex::new(State {
+        pending: frames.into_iter().collect(),
+        held: VecDeque::new(),
+        in_flight: BTreeMap::new(),
+        closed: false,
+        fail_fast,
+        limit: usize::MAX,
+        paused: false,
+        budget: None,
+      }),
+      cv: Condvar::new(),
+    }
+  }
+
+  /// Next frame to build. Blocks while the queue is empty but frames are
+  /// still in flight (they may be requeued) or held, or while the limit is
+  /// reached, the budget spent or the queue is paused. `None` means nothing
+  /// is left.
+  pub fn pop(&self) -> Option<usize> {
+    let mut st = self.state.lock().unwrap();
+    loop {
+      if st.closed {
+        return None;
+      }
+      if !st.paused && st.in_flight.len() < st.limit && st.budget != Some(0) {
+        if let Some(n) = st.pending.pop_front() {
+          st.in_flight.insert(n, Instant::now());
+          if let Some(b) = st.budget.as_mut() {
+            *b -= 1;
+          }
+          return Some(n);
+        }
+      }
+      if st.pending.is_empty() && st.in_flight.is_empty() && st.held.is_empty() {
+        return None;
+      }
+      st = self.cv.wait(st).unwrap();
+    }
+  }
+
+  pub fn finish(&self, n: usize) {
+    let mut st = self.state.lock().unwrap();
+    st.in_flight.remove(&n);
+    self.cv.notify_all();
+  }
+
+  pub fn fail(&self, n: usize) {
+    let mut st = self.state.lock().unwrap();
+    if st.fail_fast {
+      st.in_flight.remove(&n);
+      st.closed = true;
+      self.cv.notify_all();
+    }
+  }
+
+  pub fn requeue(&self, n: usize) {
+    let mut st = self.state.lock().unwrap();
+    st.in_flight.remove(&n);
+    st.pending.push_front(n);
+    self.cv.notify_all();
+  }
+
+  /// Frames handed out and not yet finished, failed frames awaiting a
+  /// decision included.
+  pub fn in_flight(&self) -> Vec<(usize, Duration)> {
+    let st = self.state.lock().unwrap();
+    st.in_flight.iter().map(|(n, t)| (*n, t.elapsed())).collect()
+  }
+
+  /// Frames not handed out yet, in the order they will be.
+  pub fn pending(&self) -> Vec<usize> {
+    let st = self.state.lock().unwrap();
+    st.pending.iter().chain(&st.held).copied().collect()
+  }
+
+  /// Keep `frames` back, after whatever is pending, until `release`.
+  pub fn hold(&self, frames: impl IntoIterator<Item = usize>) {
+    let mut st = self.state.lock().unwrap();
+    st.held.extend(frames);
+  }
+
+  pub fn release(&self) {
+    let mut st = self.state.lock().unwrap();
+    let held = std::mem::take(&mut st.held);
+    st.pending.extend(held);
+    self.cv.notify_all();
+  }
+
+  /// Cap the frames handed out at once; frames already out keep running.
+  pub fn set_limit(&self, limit: usize) {
+    let mut st = self.state.lock().unwrap();
+    st.limit = limit.max(1);
+    self.cv.notify_all();
+  }
+
+  /// Hold back every pending frame until unpaused; frames already out keep
+  /// running.
+  pub fn set_paused(&self, paused: bool) {
+    let mut st = self.state.lock().unwrap();
+    st.paused = paused;
+    self.cv.notify_all();
+  }
+
+  /// Hand out at most `budget` more frames (`None`: no cap) until set again.
+  pub fn set_budget(&self, budget: Option<usize>) {
+    let mut st = self.state.lock().unwrap();
+    st.budget = budget;
+    self.cv.notify_all();
+  }
+
+  pub fn budget(&self) -> Option<usize> {
+    self.state.lock().unwrap().budget
+  }
+
+  pub fn is_closed(&self) -> bool {
+    self.state.lock().unwrap().closed
+  }
+
+  /// Stop handing out frames; in-flight work is left to finish.
+  pub fn close(&self) {
+    let mut st = self.state.lock().unwrap();
+    st.closed = true;
+    self.cv.notify_all();
+  }
+}
+
+/// Run `f` over `items` on `workers` scoped threads, pulling from a shared
+/// `TaskQueue` so slow items don't hold up a fixed chunk.
+pub fn par_each<T: Sync>(items: &[T], workers: usize, f: impl Fn(&T) + Sync) {
+  let queue = TaskQueue::new(0..items.len(), false);
+  std::thread::scope(|s| {
+    for _ in 0..workers.clamp(1, items.len().max(1)) {
+      s.spawn(|| {
+        while let Some(i) = queue.pop() {
+          f(&items[i]);
+          queue.finish(i);
+        }
+      });
+    }
+  });
+}
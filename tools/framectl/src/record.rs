@@ -0,0 +1,202 @@
+//! `record`: the whole playback rendered to a video file, for attaching to a
+//! release. Each built frame is mounted in headless Chrome the way the host
+//! loads it (see `visual-check`) and captured, then `ffmpeg` encodes the
+//! captures at the playback rate with the host's audio track.
+//!
+//! Frames are captured one by one rather than screen-recording the host in
+//! real time, so a slow CI machine still gets every frame on its timestamp.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use crate::frames::{self, fmt_ranges};
+use crate::png;
+use crate::queue::par_each;
+use crate::thumbs::parse_dims;
+use crate::visual::{self, BROWSERS};
+use crate::{default_concurrency, parse_bool, parse_kv, state_dir, targets};
+
+/// The host's own audio, when the run doesn't name one.
+const HOST_AUDIO: &str = "apps/host/public/bad-apple.mp3";
+
+/// Video and audio codec arguments for the container `out` names.
+fn codecs(out: &Path) -> Option<[&'static str; 6]> {
+  match out.extension().and_then(|e| e.to_str()) {
+    Some("mp4") => Some(["-c:v", "libx264", "-pix_fmt", "yuv420p", "-c:a", "aac"]),
+    Some("webm") => Some(["-c:v", "libvpx-vp9", "-pix_fmt", "yuv420p", "-c:a", "libopus"]),
+    _ => None,
+  }
+}
+
+pub fn run(args: &[String]) {
+  let out = PathBuf::from(parse_kv(args, "--out").unwrap_or_else(|| "bad-apple.mp4".to_string()));
+  let fps: f64 = parse_kv(args, "--fps")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(24.0);
+  let offset_ms: f64 = parse_kv(args, "--audio-offset")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(0.0);
+  let keep = parse_kv(args, "--keep-frames").and_then(|v| parse_bool(&v)).unwrap_or(false);
+  let concurrency: usize = parse_kv(args, "--concurrency")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or_else(default_concurrency);
+  let (start, end) = frames::range_args(args);
+  if !(fps.is_finite() && fps > 0.0) {
+    eprintln!("record: --fps must be a positive number");
+    std::process::exit(2);
+  }
+  let Some(codecs) = codecs(&out) else {
+    eprintln!("record: --out must end in .mp4 or .webm");
+    std::process::exit(2);
+  };
+  let audio: Option<PathBuf> = match parse_kv(args, "--audio").as_deref() {
+    Some("" | "none") => None,
+    Some(a) => Some(PathBuf::from(a)),
+    None => Some(PathBuf::from(HOST_AUDIO)).filter(|p| p.is_file()),
+  };
+  if let Some(a) = audio.as_deref().filter(|a| !a.is_file()) {
+    eprintln!("record: audio {} not found", a.display());
+    std::process::exit(2);
+  }
+
+  let Some(browser) = visual::find_browser(parse_kv(args, "--browser")) else {
+    eprintln!("record: no headless Chrome found (tried {}; set --browser or CHROME)", BROWSERS.join(", "));
+    std::process::exit(2);
+  };
+  let ffmpeg_found = Command::new("ffmpeg")
+    .arg("-version")
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .status()
+    .is_ok();
+  if !ffmpeg_found {
+    eprintln!("record: `ffmpeg` not found on PATH");
+    std::process::exit(2);
+  }
+
+  targets::dists("record", args);
+  let built: Vec<usize> = frames::selected(args)
+    .into_iter()
+    .filter(|&n| frames::entry_path(n).is_file())
+    .collect();
+  if built.is_empty() {
+    eprintln!("record: no built frames in range (start={start} end={end})");
+    std::process::exit(1);
+  }
+  // The host's frame size unless asked otherwise; sources are the truth.
+  let dims = parse_kv(args, "--size")
+    .and_then(|v| parse_dims(&v))
+    .or_else(|| {
+      frames::source_image(&frames::sources_dir(), built[0])
+        .and_then(|p| png::read_header(&p).ok())
+        .map(|h| (h.width as usize, h.height as usize))
+    })
+    .unwrap_or((480, 360));
+
+  let dir = state_dir().join("record");
+  let _ = std::fs::remove_dir_all(&dir);
+  if let Err(e) = std::fs::create_dir_all(&dir) {
+    eprintln!("record: {}: {e}", dir.display());
+    std::process::exit(1);
+  }
+  let port = match visual::start_server(dims) {
+    Ok(p) => p,
+    Err(e) => {
+      eprintln!("record: cannot listen: {e}");
+      std::process::exit(1);
+    }
+  };
+  let (first, last) = (built[0], built[built.len() - 1]);
+  eprintln!(
+    "record: frames {first}..{last} ({} built) with {browser} at {}x{} {fps}fps concurrency={concurrency}",
+    built.len(),
+    dims.0,
+    dims.1
+  );
+
+  let capture = |n: usize| dir.join(format!("frame-{n:04}.png"));
+  let failed: Mutex<Vec<(usize, String)>> = Mutex::new(Vec::new());
+  par_each(&built, concurrency, |&n| {
+    let url = format!("http://127.0.0.1:{port}/check/{n}");
+    if let Err(e) = visual::capture(&browser, &url, dims, &capture(n)) {
+      failed.lock().unwrap().push((n, e));
+    }
+  });
+  let mut failed = failed.into_inner().unwrap();
+  if !failed.is_empty() {
+    failed.sort();
+    let list: Vec<usize> = failed.iter().map(|(n, _)| *n).collect();
+    eprintln!(
+      "record: {} frame(s) failed to render: {} (frame-{:04}: {})",
+      list.len(),
+      fmt_ranges(&list),
+      failed[0].0,
+      failed[0].1
+    );
+    std::process::exit(1);
+  }
+
+  // One numbered image per frame time; a missing frame repeats the one
+  // before it, as the host holds it on screen.
+  let mut held = Vec::new();
+  let mut prev: Option<PathBuf> = None;
+  for (i, n) in (first..=last).enumerate() {
+    let src = if built.binary_search(&n).is_ok() {
+      Some(capture(n))
+    } else {
+      held.push(n);
+      prev.clone()
+    };
+    let Some(src) = src else {
+      continue;
+    };
+    let seq = dir.join(format!("seq-{i:06}.png"));
+    if let Err(e) = std::fs::hard_link(&src, &seq).or_else(|_| std::fs::copy(&src, &seq).map(|_| ())) {
+      eprintln!("record: {}: {e}", seq.display());
+      std::process::exit(1);
+    }
+    prev = Some(src);
+  }
+  if !held.is_empty() {
+    eprintln!("record: warning: {} frame(s) not built; held over: {}", held.len(), fmt_ranges(&held));
+  }
+
+  let mut cmd = Command::new("ffmpeg");
+  cmd.args(["-y", "-loglevel", "error", "-framerate"])
+    .arg(fps.to_string())
+    .arg("-i")
+    .arg(dir.join("seq-%06d.png"));
+  if let Some(a) = &audio {
+    cmd.arg("-itsoffset").arg(format!("{:.3}", offset_ms / 1000.0)).arg("-i").arg(a);
+    cmd.args(["-map", "0:v", "-map", "1:a", "-shortest"]);
+  }
+  cmd.args(&codecs[..4]);
+  if audio.is_some() {
+    cmd.args(&codecs[4..]);
+  }
+  // Odd sizes don't encode as yuv420p.
+  cmd.args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"]).arg(&out);
+  let status = cmd.stdin(Stdio::null()).status();
+  if !keep {
+    let _ = std::fs::remove_dir_all(&dir);
+  }
+  match status {
+    Ok(s) if s.success() => {}
+    Ok(s) => {
+      eprintln!("record: ffmpeg exited with {s}");
+      std::process::exit(1);
+    }
+    Err(e) => {
+      eprintln!("record: ffmpeg: {e}");
+      std::process::exit(1);
+    }
+  }
+  let secs = (last - first + 1) as f64 / fps;
+  eprintln!(
+    "record: wrote {} ({} frames, {secs:.1}s{})",
+    out.display(),
+    last - first + 1,
+    audio.as_ref().map(|a| format!(", audio {}", a.display())).unwrap_or_default()
+  );
+}
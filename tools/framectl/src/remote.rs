@@ -0,0 +1,495 @@
+//! Coordinator/worker protocol for spreading a build over several machines.
+//!
+//! Line-based over TCP, one connection per build slot:
+//!
+//! ```text
+//! C: HELLO framectl/1
+//! W: SLOTS <n>
+//! C: BUILD <frame> <dry_run 0|1> [<script install|typecheck|build|test>]
+//! W: BUSY                          (every few seconds while building)
+//! W: DONE <frame> <ok 0|1> <len> [<cpu_ms> <max_rss_kb>]
+//! W: <len bytes of stderr tail>
+//! ```
+//!
+//! Workers run builds in their own checkout, so every machine needs the
+//! same workspace with dependencies installed. A multi-stage pipeline is one
+//! BUILD per stage on the same connection.
+//!
+//! There is no authentication: anyone who can connect can start builds. A
+//! worker listens on loopback unless given an address to bind (reach it
+//! through an ssh tunnel, or bind a trusted network's interface), runs at
+//! most its slots' builds at once (BUILDs beyond that wait, with BUSY) and
+//! turns away connections beyond twice its slots with `FULL`.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::build::TaskResult;
+use crate::classify::is_oom;
+use crate::exec::{self, ExecOpts, Network, Script, StageTime};
+use crate::queue::TaskQueue;
+use crate::rusage::Usage;
+use crate::throttle::Throttle;
+
+const HELLO: &str = "HELLO framectl/1";
+const BUSY_EVERY: Duration = Duration::from_secs(5);
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const RECONNECT_TRIES: usize = 3;
+/// Longest protocol line a worker reads.
+const MAX_LINE: u64 = 4096;
+/// Largest stderr tail a coordinator accepts; workers send a few KB.
+const MAX_TAIL: usize = 1 << 20;
+
+/// `:7000` listens on loopback only; bind `0.0.0.0:7000` (or one
+/// interface's address) to take builds from other machines.
+pub fn listen_addr(s: &str) -> String {
+  if s.starts_with(':') {
+    format!("127.0.0.1{s}")
+  } else {
+    s.to_string()
+  }
+}
+
+/// The worker's build slots: BUILDs past `max` wait for one to free up.
+struct Slots {
+  max: usize,
+  busy: Mutex<usize>,
+  cv: Condvar,
+}
+
+/// A slot taken by one build, given back when it ends.
+struct Permit(Arc<Slots>);
+
+impl Drop for Permit {
+  fn drop(&mut self) {
+    *self.0.busy.lock().unwrap() -= 1;
+    self.0.cv.notify_one();
+  }
+}
+
+/// Wait for a free slot, telling the coordinator BUSY meanwhile.
+fn take_slot(slots: &Arc<Slots>, w: &mut TcpStream) -> io::Result<Permit> {
+  let mut busy = slots.busy.lock().unwrap();
+  while *busy >= slots.max {
+    let (b, wait) = slots.cv.wait_timeout(busy, BUSY_EVERY).unwrap();
+    busy = b;
+    if wait.timed_out() {
+      writeln!(w, "BUSY")?;
+    }
+  }
+  *busy += 1;
+  Ok(Permit(Arc::clone(slots)))
+}
+
+pub fn serve(listen: &str, slots: usize, silent: bool, network: Network) -> io::Result<()> {
+  let listener = TcpListener::bind(listen)?;
+  eprintln!("worker: listening on {listen} slots={slots}");
+  if listener.local_addr().is_ok_and(|a| !a.ip().is_loopback()) {
+    eprintln!("worker: warning: builds are taken from anyone who can reach {listen}; use it on a trusted network only");
+  }
+  let slots = Arc::new(Slots {
+    max: slots,
+    busy: Mutex::new(0),
+    cv: Condvar::new(),
+  });
+  let open = Arc::new(AtomicUsize::new(0));
+  let max_conns = slots.max * 2;
+  for stream in listener.incoming() {
+    let mut stream = match stream {
+      Ok(s) => s,
+      Err(e) => {
+        eprintln!("worker: accept failed: {e}");
+        continue;
+      }
+    };
+    let peer = stream
+      .peer_addr()
+      .map(|a| a.to_string())
+      .unwrap_or_else(|_| "?".to_string());
+    if open.fetch_add(1, Ordering::SeqCst) >= max_conns {
+      open.fetch_sub(1, Ordering::SeqCst);
+      eprintln!("worker: turning away {peer}: {max_conns} connections open");
+      let _ = writeln!(stream, "FULL");
+      continue;
+    }
+    let slots = Arc::clone(&slots);
+    let open = Arc::clone(&open);
+    thread::spawn(move || {
+      if let Err(e) = handle_conn(stream, slots, silent, network) {
+        eprintln!("worker: connection {peer} closed: {e}");
+      }
+      open.fetch_sub(1, Ordering::SeqCst);
+    });
+  }
+  Ok(())
+}
+
+fn handle_conn(stream: TcpStream, slots: Arc<Slots>, silent: bool, network: Network) -> io::Result<()> {
+  let mut reader = BufReader::new(stream.try_clone()?);
+  let mut w = stream;
+  let mut line = String::new();
+  loop {
+    line.clear();
+    if reader.by_ref().take(MAX_LINE).read_line(&mut line)? == 0 {
+      return Ok(());
+    }
+    if !line.ends_with('\n') && line.len() as u64 >= MAX_LINE {
+      return Err(bad_data("line too long"));
+    }
+    let mut parts = line.split_whitespace();
+    match parts.next() {
+      Some("HELLO") => writeln!(w, "SLOTS {}", slots.max)?,
+      Some("BUILD") => {
+        let n: usize = parts
+          .next()
+          .and_then(|v| v.parse().ok())
+          .ok_or_else(|| bad_data("BUILD without frame"))?;
+        let dry_run = parts.next() == Some("1");
+        let script = match parts.next() {
+          None => Script::Build,
+          Some(s) => Script::parse(s).ok_or_else(|| bad_data(&format!("unknown script: {s}")))?,
+        };
+        let permit = take_slot(&slots, &mut w)?;
+        let (tx, rx) = mpsc::channel();
+        let opts = ExecOpts {
+          script,
+          silent,
+          dry_run,
+          network,
+          node: None,
+        };
+        // The slot is held until the build ends, even if we hang up first.
+        thread::spawn(move || {
+          let _permit = permit;
+          let _ = tx.send(exec::run_frame(n, opts));
+        });
+        let out = loop {
+          match rx.recv_timeout(BUSY_EVERY) {
+            Ok(out) => break out,
+            Err(RecvTimeoutError::Timeout) => writeln!(w, "BUSY")?,
+            Err(RecvTimeoutError::Disconnected) => return Err(bad_data("build thread died")),
+          }
+        };
+        let tail = out.err_tail.as_bytes();
+        let usage = out
+          .usage
+          .map(|u| format!(" {} {}", u.cpu.as_millis(), u.max_rss_kb))
+          .unwrap_or_default();
+        writeln!(w, "DONE {n} {} {}{usage}", if out.ok { 1 } else { 0 }, tail.len())?;
+        w.write_all(tail)?;
+        w.flush()?;
+        eprintln!(
+          "worker: frame-{n:04} {} {}",
+          script.name(),
+          if out.ok { "ok" } else { "failed" }
+        );
+      }
+      _ => return Err(bad_data(&format!("unexpected line: {}", line.trim()))),
+    }
+  }
+}
+
+struct Conn {
+  reader: BufReader<TcpStream>,
+  w: TcpStream,
+  slots: usize,
+}
+
+fn connect(addr: &str) -> io::Result<Conn> {
+  let sa = addr
+    .to_socket_addrs()?
+    .next()
+    .ok_or_else(|| bad_data("no address"))?;
+  let stream = TcpStream::connect_timeout(&sa, CONNECT_TIMEOUT)?;
+  stream.set_read_timeout(Some(READ_TIMEOUT))?;
+  let mut conn = Conn {
+    reader: BufReader::new(stream.try_clone()?),
+    w: stream,
+    slots: 0,
+  };
+  writeln!(conn.w, "{HELLO}")?;
+  let line = read_line(&mut conn.reader)?;
+  conn.slots = line
+    .strip_prefix("SLOTS ")
+    .and_then(|v| v.trim().parse().ok())
+    .ok_or_else(|| bad_data(&format!("bad handshake: {line}")))?;
+  Ok(conn)
+}
+
+/// Ask a worker how many build slots it offers.
+pub fn probe(addr: &str) -> io::Result<usize> {
+  connect(addr).map(|c| c.slots)
+}
+
+struct Reply {
+  ok: bool,
+  err_tail: String,
+  usage: Option<Usage>,
+}
+
+fn remote_build(conn: &mut Conn, n: usize, opts: ExecOpts) -> io::Result<Reply> {
+  writeln!(
+    conn.w,
+    "BUILD {n} {} {}",
+    if opts.dry_run { 1 } else { 0 },
+    opts.script.name()
+  )?;
+  loop {
+    let line = read_line(&mut conn.reader)?;
+    if line == "BUSY" {
+      continue;
+    }
+    let mut parts = line.split_whitespace();
+    if parts.next() != Some("DONE") || parts.next().and_then(|v| v.parse::<usize>().ok()) != Some(n) {
+      return Err(bad_data(&format!("unexpected reply: {line}")));
+    }
+    let ok = parts.next() == Some("1");
+    let len: usize = parts
+      .next()
+      .and_then(|v| v.parse().ok())
+      .ok_or_else(|| bad_data("DONE without length"))?;
+    if len > MAX_TAIL {
+      return Err(bad_data(&format!("DONE with a {len}-byte tail (at most {MAX_TAIL})")));
+    }
+    let cpu_ms = parts.next().and_then(|v| v.parse::<u64>().ok());
+    let rss = parts.next().and_then(|v| v.parse::<u64>().ok());
+    let usage = cpu_ms.zip(rss).map(|(cpu_ms, max_rss_kb)| Usage {
+      cpu: Duration::from_millis(cpu_ms),
+      max_rss_kb,
+    });
+    let mut buf = vec![0u8; len];
+    conn.reader.read_exact(&mut buf)?;
+    return Ok(Reply {
+      ok,
+      err_tail: String::from_utf8_lossy(&buf).into_owned(),
+      usage,
+    });
+  }
+}
+
+/// `stages` for frame `n` on the worker, stopping at the first failure.
+fn remote_pipeline(conn: &mut Conn, n: usize, opts: ExecOpts, stages: &[Script]) -> io::Result<(Reply, Vec<StageTime>)> {
+  let mut times = Vec::new();
+  let mut usage = None;
+  let mut last = Reply {
+    ok: true,
+    err_tail: String::new(),
+    usage: None,
+  };
+  for &script in stages {
+    let t = Instant::now();
+    last = remote_build(conn, n, ExecOpts { script, ..opts })?;
+    usage = exec::add_usage(usage, last.usage);
+    times.push(StageTime {
+      script,
+      dur: t.elapsed(),
+      ok: last.ok,
+    });
+    if !last.ok {
+      break;
+    }
+  }
+  last.usage = usage;
+  Ok((last, times))
+}
+
+/// One coordinator-side slot: pulls frames and builds them on `addr`.
+/// A frame in flight on a worker that drops off is handed back to the queue.
+pub fn run_slot(
+  addr: &str,
+  slot: usize,
+  queue: &TaskQueue,
+  throttle: &Throttle,
+  res_tx: &Sender<TaskResult>,
+  opts: ExecOpts,
+  stages: &[Script],
+) {
+  let mut tries = 0;
+  loop {
+    let mut conn = match connect(addr) {
+      Ok(c) => {
+        tries = 0;
+        c
+      }
+      Err(e) => {
+        tries += 1;
+        if tries >= RECONNECT_TRIES {
+          eprintln!("worker {addr}: giving up on slot: {e}");
+          return;
+        }
+        thread::sleep(Duration::from_secs(2));
+        continue;
+      }
+    };
+
+    loop {
+      let Some(n) = queue.pop() else {
+        return;
+      };
+      throttle.before_spawn();
+      if queue.is_closed() {
+        queue.requeue(n);
+        return;
+      }
+      let t = Instant::now();
+      match remote_pipeline(&mut conn, n, opts, stages) {
+        Ok((Reply { ok, err_tail, usage }, stages)) => {
+          if ok {
+            queue.finish(n);
+          } else {
+            // The worker keeps the full log; keep the tail here.
+            let script = stages.last().map_or(opts.script, |st| st.script);
+            exec::write_log(n, script, &err_tail);
+            if !is_oom(&err_tail) {
+              queue.fail(n);
+            }
+          }
+          let _ = res_tx.send(TaskResult {
+            n,
+            slot,
+            started: t,
+            ok,
+            err_tail,
+            dur: t.elapsed(),
+            usage,
+            stages,
+          });
+        }
+        Err(e) => {
+          eprintln!("worker {addr}: lost during frame-{n:04} ({e}); requeued");
+          queue.requeue(n);
+          break;
+        }
+      }
+    }
+  }
+}
+
+fn read_line(r: &mut BufReader<TcpStream>) -> io::Result<String> {
+  let mut line = String::new();
+  if r.read_line(&mut line)? == 0 {
+    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed"));
+  }
+  Ok(line.trim_end().to_string())
+}
+
+fn bad_data(msg: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const OPTS: ExecOpts = ExecOpts {
+    script: Script::Build,
+    silent: true,
+    dry_run: true,
+    network: Network::Online,
+    node: None,
+  };
+
+  /// A worker on a loopback port for one connection.
+  fn worker(slots: usize) -> (String, thread::JoinHandle<io::Result<()>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let handle = thread::spawn(move || {
+      let (stream, _) = listener.accept()?;
+      let slots = Arc::new(Slots {
+        max: slots,
+        busy: Mutex::new(0),
+        cv: Condvar::new(),
+      });
+      handle_conn(stream, slots, true, Network::Online)
+    });
+    (addr, handle)
+  }
+
+  /// A fake worker that answers the handshake, then sends `reply` verbatim
+  /// to the first BUILD.
+  fn scripted(reply: &'static [u8]) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+      let (stream, _) = listener.accept().unwrap();
+      let mut reader = BufReader::new(stream.try_clone().unwrap());
+      let mut w = stream;
+      let _ = read_line(&mut reader);
+      let _ = writeln!(w, "SLOTS 1");
+      let _ = read_line(&mut reader);
+      let _ = w.write_all(reply);
+    });
+    addr
+  }
+
+  #[test]
+  fn listen_defaults_to_loopback() {
+    assert_eq!(listen_addr(":7000"), "127.0.0.1:7000");
+    assert_eq!(listen_addr("0.0.0.0:7000"), "0.0.0.0:7000");
+    assert_eq!(listen_addr("10.0.0.5:7000"), "10.0.0.5:7000");
+  }
+
+  #[test]
+  fn handshake_and_pipeline() {
+    let (addr, handle) = worker(3);
+    let mut conn = connect(&addr).unwrap();
+    assert_eq!(conn.slots, 3);
+    let stages = [Script::Install, Script::Build];
+    let (reply, times) = remote_pipeline(&mut conn, 7, OPTS, &stages).unwrap();
+    assert!(reply.ok && reply.err_tail.is_empty());
+    assert!(times.iter().map(|t| t.script).eq(stages));
+    drop(conn);
+    assert!(handle.join().unwrap().is_ok());
+  }
+
+  #[test]
+  fn worker_rejects_bad_lines() {
+    let (addr, handle) = worker(1);
+    let mut s = TcpStream::connect(&addr).unwrap();
+    writeln!(s, "BUILD nope 0").unwrap();
+    let err = handle.join().unwrap().unwrap_err();
+    assert_eq!(err.to_string(), "BUILD without frame");
+
+    let (addr, handle) = worker(1);
+    let mut s = TcpStream::connect(&addr).unwrap();
+    writeln!(s, "BUILD 1 0 deploy").unwrap();
+    assert_eq!(handle.join().unwrap().unwrap_err().to_string(), "unknown script: deploy");
+
+    let (addr, handle) = worker(1);
+    let mut s = TcpStream::connect(&addr).unwrap();
+    s.write_all(&vec![b'x'; MAX_LINE as usize + 10]).unwrap();
+    assert_eq!(handle.join().unwrap().unwrap_err().to_string(), "line too long");
+  }
+
+  #[test]
+  fn reads_done_with_usage_and_tail() {
+    let addr = scripted(b"BUSY\nDONE 4 0 5 1500 2048\noops!");
+    let mut conn = connect(&addr).unwrap();
+    let reply = remote_build(&mut conn, 4, OPTS).unwrap();
+    assert!(!reply.ok);
+    assert_eq!(reply.err_tail, "oops!");
+    let usage = reply.usage.unwrap();
+    assert_eq!((usage.cpu, usage.max_rss_kb), (Duration::from_millis(1500), 2048));
+  }
+
+  #[test]
+  fn rejects_bad_replies() {
+    let addr = scripted(b"DONE 5 1 0\n");
+    let err = remote_build(&mut connect(&addr).unwrap(), 4, OPTS).err().unwrap();
+    assert_eq!(err.to_string(), "unexpected reply: DONE 5 1 0");
+
+    let addr = scripted(b"DONE 4 1 99999999\n");
+    let err = remote_build(&mut connect(&addr).unwrap(), 4, OPTS).err().unwrap();
+    assert_eq!(err.to_string(), format!("DONE with a 99999999-byte tail (at most {MAX_TAIL})"));
+
+    let addr = scripted(b"DONE 4 1\n");
+    let err = remote_build(&mut connect(&addr).unwrap(), 4, OPTS).err().unwrap();
+    assert_eq!(err.to_string(), "DONE without length");
+  }
+}
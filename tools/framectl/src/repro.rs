@@ -0,0 +1,161 @@
+//! `repro`: evidence that frame builds are deterministic, which content-hash
+//! caching (`hash-manifest`) relies on. A sample of frames is built twice
+//! from a clean bundler cache, each dist moved aside under `.framectl/repro`,
+//! and the two compared file by file. The second build goes back to `dist`.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::build::parse_network;
+use crate::compress::walk;
+use crate::exec::{self, ExecOpts, Script};
+use crate::frames::{self, fmt_ranges};
+use crate::queue::par_each;
+use crate::{default_concurrency, lock, parse_kv, state_dir, visual};
+
+/// FNV-1a of each file under `dir`, by `dir`-relative path.
+fn file_hashes(dir: &Path) -> std::io::Result<BTreeMap<PathBuf, u64>> {
+  let mut files = Vec::new();
+  walk(dir, &mut files);
+  let mut out = BTreeMap::new();
+  for p in files {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in std::fs::read(&p)? {
+      h ^= b as u64;
+      h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    out.insert(p.strip_prefix(dir).unwrap_or(&p).to_path_buf(), h);
+  }
+  Ok(out)
+}
+
+enum Verdict {
+  Same(usize),
+  /// Files that differ, and those only one build produced.
+  Differs { changed: Vec<String>, one_sided: Vec<String> },
+  Broken(String),
+}
+
+/// Build frame `n` from a clean cache and move its dist to `to`.
+fn build_into(n: usize, opts: ExecOpts, to: &Path) -> Result<(), String> {
+  let dir = frames::frame_dir(n);
+  let _ = std::fs::remove_dir_all(dir.join("node_modules/.cache"));
+  let _ = std::fs::remove_dir_all(dir.join("dist"));
+  let out = exec::run_frame(n, opts);
+  if !out.ok {
+    let last = out.err_tail.trim_end().lines().last().unwrap_or("").trim().to_string();
+    return Err(format!("build failed: {last}"));
+  }
+  let _ = std::fs::remove_dir_all(to);
+  std::fs::create_dir_all(to.parent().unwrap_or(Path::new("."))).map_err(|e| e.to_string())?;
+  std::fs::rename(dir.join("dist"), to).map_err(|e| format!("moving dist aside: {e}"))
+}
+
+fn check(n: usize, opts: ExecOpts, work: &Path) -> Verdict {
+  let (a, b) = (work.join("a"), work.join("b"));
+  if let Err(e) = build_into(n, opts, &a).and_then(|()| build_into(n, opts, &b)) {
+    return Verdict::Broken(e);
+  }
+  let (ha, hb) = match (file_hashes(&a), file_hashes(&b)) {
+    (Ok(ha), Ok(hb)) => (ha, hb),
+    (Err(e), _) | (_, Err(e)) => return Verdict::Broken(format!("reading dist: {e}")),
+  };
+  // The frame keeps its second build.
+  let _ = std::fs::rename(&b, frames::frame_dir(n).join("dist"));
+  let show = |p: &PathBuf| p.to_string_lossy().replace('\\', "/");
+  let changed: Vec<String> = ha
+    .iter()
+    .filter(|(p, h)| hb.get(*p).is_some_and(|o| o != *h))
+    .map(|(p, _)| show(p))
+    .collect();
+  let one_sided: Vec<String> = ha
+    .keys()
+    .filter(|p| !hb.contains_key(*p))
+    .chain(hb.keys().filter(|p| !ha.contains_key(*p)))
+    .map(show)
+    .collect();
+  if changed.is_empty() && one_sided.is_empty() {
+    let _ = std::fs::remove_dir_all(work);
+    Verdict::Same(ha.len())
+  } else {
+    Verdict::Differs { changed, one_sided }
+  }
+}
+
+fn list(files: &[String]) -> String {
+  let shown: Vec<&str> = files.iter().take(5).map(String::as_str).collect();
+  let more = files.len().saturating_sub(shown.len());
+  let more = if more > 0 { format!(" (+{more} more)") } else { String::new() };
+  format!("{}{more}", shown.join(", "))
+}
+
+pub fn run(args: &[String]) {
+  let count: usize = parse_kv(args, "--sample")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(5);
+  let concurrency: usize = parse_kv(args, "--concurrency")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or_else(default_concurrency);
+  let (start, end) = frames::range_args(args);
+
+  let found: Vec<usize> = frames::selected(args);
+  if found.is_empty() || count == 0 {
+    eprintln!("repro: no frames to check (start={start} end={end} sample={count})");
+    std::process::exit(2);
+  }
+  let picked = visual::sample(&found, count);
+  let _lock = lock::acquire(args, "repro");
+  eprintln!(
+    "repro: building {} frame(s) twice: {} concurrency={concurrency}",
+    picked.len(),
+    fmt_ranges(&picked)
+  );
+
+  let opts = ExecOpts {
+    script: Script::Build,
+    silent: true,
+    dry_run: false,
+    network: parse_network(args),
+    node: None,
+  };
+  let root: PathBuf = state_dir().join("repro");
+  let verdicts: Mutex<BTreeMap<usize, Verdict>> = Mutex::new(BTreeMap::new());
+  par_each(&picked, concurrency, |&n| {
+    let v = check(n, opts, &root.join(format!("frame-{n:04}")));
+    match &v {
+      Verdict::Same(files) => eprintln!("frame-{n:04}: reproducible ({files} file(s))"),
+      Verdict::Differs { changed, one_sided } => {
+        eprintln!("frame-{n:04}: NOT reproducible");
+        if !changed.is_empty() {
+          eprintln!("  differ: {}", list(changed));
+        }
+        if !one_sided.is_empty() {
+          eprintln!("  in one build only: {}", list(one_sided));
+        }
+      }
+      Verdict::Broken(e) => eprintln!("frame-{n:04}: {e}"),
+    }
+    verdicts.lock().unwrap().insert(n, v);
+  });
+  let verdicts = verdicts.into_inner().unwrap();
+
+  let pick = |f: fn(&Verdict) -> bool| -> Vec<usize> { verdicts.iter().filter(|(_, v)| f(v)).map(|(n, _)| *n).collect() };
+  let same = pick(|v| matches!(v, Verdict::Same(_)));
+  let differs = pick(|v| matches!(v, Verdict::Differs { .. }));
+  let broken = pick(|v| matches!(v, Verdict::Broken(_)));
+  eprintln!("repro: {}/{} frame(s) reproducible", same.len(), picked.len());
+  if !differs.is_empty() {
+    eprintln!(
+      "repro: nondeterministic: {} (first builds kept under {})",
+      fmt_ranges(&differs),
+      root.display()
+    );
+  }
+  if !broken.is_empty() {
+    eprintln!("repro: could not check: {}", fmt_ranges(&broken));
+  }
+  if !differs.is_empty() || !broken.is_empty() {
+    std::process::exit(1);
+  }
+}
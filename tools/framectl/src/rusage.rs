@@ -0,0 +1,188 @@
+//! Per-child CPU time and peak RSS. `getrusage(RUSAGE_CHILDREN)` lumps all
+//! concurrent builds together, so each child is reaped with `wait4` instead;
+//! on Windows, each runs in its own job object.
+
+use std::io;
+use std::process::{Child, ExitStatus};
+use std::time::Duration;
+
+#[derive(Clone, Copy)]
+pub struct Usage {
+  pub cpu: Duration,
+  pub max_rss_kb: u64,
+}
+
+#[cfg(unix)]
+mod sys {
+  use std::os::raw::{c_int, c_long};
+
+  #[cfg(target_os = "macos")]
+  type Suseconds = i32;
+  #[cfg(not(target_os = "macos"))]
+  type Suseconds = c_long;
+
+  #[repr(C)]
+  #[derive(Default)]
+  pub struct Timeval {
+    pub tv_sec: c_long,
+    pub tv_usec: Suseconds,
+  }
+
+  #[repr(C)]
+  #[derive(Default)]
+  pub struct Rusage {
+    pub ru_utime: Timeval,
+    pub ru_stime: Timeval,
+    pub ru_maxrss: c_long,
+    pub rest: [c_long; 13],
+  }
+
+  extern "C" {
+    pub fn wait4(pid: c_int, status: *mut c_int, options: c_int, rusage: *mut Rusage) -> c_int;
+  }
+}
+
+/// Reap `child`, returning its exit status and resource usage. The `Child`
+/// must not be waited on afterwards.
+#[cfg(unix)]
+pub fn wait(child: &mut Child) -> io::Result<(ExitStatus, Option<Usage>)> {
+  use std::os::unix::process::ExitStatusExt;
+
+  let pid = child.id() as i32;
+  let mut status = 0;
+  let mut ru = sys::Rusage::default();
+  loop {
+    // SAFETY: `status` and `ru` are valid for writes and `pid` is our child.
+    let r = unsafe { sys::wait4(pid, &mut status, 0, &mut ru) };
+    if r == pid {
+      break;
+    }
+    let err = io::Error::last_os_error();
+    if err.kind() != io::ErrorKind::Interrupted {
+      return Err(err);
+    }
+  }
+
+  let tv = |t: &sys::Timeval| Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64);
+  // Linux reports ru_maxrss in KiB, macOS in bytes.
+  let max_rss_kb = if cfg!(target_os = "macos") {
+    ru.ru_maxrss as u64 / 1024
+  } else {
+    ru.ru_maxrss as u64
+  };
+  let usage = Usage {
+    cpu: tv(&ru.ru_utime) + tv(&ru.ru_stime),
+    max_rss_kb,
+  };
+  Ok((ExitStatus::from_raw(status), Some(usage)))
+}
+
+#[cfg(windows)]
+mod sys {
+  use std::ffi::c_void;
+
+  pub type Handle = *mut c_void;
+
+  pub const BASIC_AND_IO_ACCOUNTING: i32 = 8;
+  pub const EXTENDED_LIMIT: i32 = 9;
+
+  #[repr(C)]
+  #[derive(Default)]
+  pub struct IoCounters {
+    pub counts: [u64; 6],
+  }
+
+  /// `JOBOBJECT_BASIC_AND_IO_ACCOUNTING_INFORMATION`; times in 100ns ticks.
+  #[repr(C)]
+  #[derive(Default)]
+  pub struct Accounting {
+    pub total_user_time: i64,
+    pub total_kernel_time: i64,
+    pub this_period_user_time: i64,
+    pub this_period_kernel_time: i64,
+    pub page_faults: u32,
+    pub total_processes: u32,
+    pub active_processes: u32,
+    pub terminated_processes: u32,
+    pub io: IoCounters,
+  }
+
+  /// `JOBOBJECT_EXTENDED_LIMIT_INFORMATION`.
+  #[repr(C)]
+  #[derive(Default)]
+  pub struct ExtendedLimits {
+    pub per_process_user_time_limit: i64,
+    pub per_job_user_time_limit: i64,
+    pub limit_flags: u32,
+    pub min_working_set: usize,
+    pub max_working_set: usize,
+    pub active_process_limit: u32,
+    pub affinity: usize,
+    pub priority_class: u32,
+    pub scheduling_class: u32,
+    pub io: IoCounters,
+    pub process_memory_limit: usize,
+    pub job_memory_limit: usize,
+    pub peak_process_memory: usize,
+    pub peak_job_memory: usize,
+  }
+
+  extern "system" {
+    pub fn CreateJobObjectW(attributes: *mut c_void, name: *const u16) -> Handle;
+    pub fn AssignProcessToJobObject(job: Handle, process: Handle) -> i32;
+    pub fn QueryInformationJobObject(job: Handle, class: i32, info: *mut c_void, len: u32, ret: *mut u32) -> i32;
+    pub fn CloseHandle(handle: Handle) -> i32;
+  }
+
+  /// The job's `class` information, as `T` (its struct for that class).
+  pub fn query<T: Default>(job: Handle, class: i32) -> Option<T> {
+    let mut info = T::default();
+    let len = std::mem::size_of::<T>() as u32;
+    let ptr = &mut info as *mut T as *mut c_void;
+    // SAFETY: `ptr` points at `len` writable bytes laid out as `class` asks.
+    let ok = unsafe { QueryInformationJobObject(job, class, ptr, len, std::ptr::null_mut()) };
+    (ok != 0).then_some(info)
+  }
+}
+
+/// Windows has no `wait4`: the child goes into a job object, whose
+/// accounting covers it and everything it starts, and cpu time and peak
+/// memory come from there. Processes the child started before `wait` put it
+/// in the job aren't counted; pnpm takes far longer than that to start any.
+/// Peak memory is the largest commit of any one process in the job, the
+/// closest Windows has to a peak RSS. Without a job (creating or joining one
+/// failed), the build still runs, just without cpu/rss numbers.
+#[cfg(windows)]
+pub fn wait(child: &mut Child) -> io::Result<(ExitStatus, Option<Usage>)> {
+  use std::os::windows::io::AsRawHandle;
+
+  // SAFETY: no attributes and no name are both allowed.
+  let job = unsafe { sys::CreateJobObjectW(std::ptr::null_mut(), std::ptr::null()) };
+  // SAFETY: `job` is checked non-null and the child's handle is open.
+  let joined = !job.is_null() && unsafe { sys::AssignProcessToJobObject(job, child.as_raw_handle()) } != 0;
+  let status = child.wait();
+  let usage = if joined { job_usage(job) } else { None };
+  if !job.is_null() {
+    // SAFETY: `job` came from `CreateJobObjectW` and is closed once.
+    unsafe { sys::CloseHandle(job) };
+  }
+  status.map(|s| (s, usage))
+}
+
+#[cfg(windows)]
+fn job_usage(job: sys::Handle) -> Option<Usage> {
+  let acct: sys::Accounting = sys::query(job, sys::BASIC_AND_IO_ACCOUNTING)?;
+  let limits: sys::ExtendedLimits = sys::query(job, sys::EXTENDED_LIMIT)?;
+  let ticks = (acct.total_user_time + acct.total_kernel_time).max(0) as u64;
+  Some(Usage {
+    cpu: Duration::from_nanos(ticks.saturating_mul(100)),
+    max_rss_kb: limits.peak_process_memory as u64 / 1024,
+  })
+}
+
+/// No per-child accounting on this platform; builds still run, just without
+/// cpu/rss numbers.
+#[cfg(not(any(unix, windows)))]
+pub fn wait(child: &mut Child) -> io::Result<(ExitStatus, Option<Usage>)> {
+  child.wait().map(|s| (s, None))
+}
@@ -0,0 +1,169 @@
+//! `gen-server-config`: nginx `location` blocks or Caddy routes serving each
+//! frame's dist at its URL, with the headers federation needs: CORS so the
+//! host may load remotes cross-origin, long-lived caching for content-hashed
+//! chunks but revalidation for the unhashed `remoteEntry.js`, and the `.br`
+//! and `.gz` files `compress` writes served in place of the originals.
+//!
+//! With `--manifest` the frames are served at their hashed `hash-manifest`
+//! paths, where every file, remoteEntry.js included, is immutable.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::frames::{self, ENTRY};
+use crate::{parse_kv, playlist, targets};
+
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const REVALIDATE: &str = "no-cache";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+  Nginx,
+  Caddy,
+}
+
+struct Route {
+  n: usize,
+  /// URL path the frame is served at, with a trailing slash.
+  path: String,
+  /// Whether the path changes with the content (a hash-manifest path).
+  hashed: bool,
+}
+
+/// The path part of a base URL: `https://cdn.example.com/frames` -> `/frames`.
+fn url_path(url: &str) -> String {
+  let rest = match url.split_once("://") {
+    Some((_, rest)) => rest.find('/').map_or("", |i| &rest[i..]),
+    None => url,
+  };
+  format!("/{}", rest.trim_matches('/')).trim_end_matches('/').to_string()
+}
+
+fn render(format: Format, routes: &[Route], root: &Path, origin: &str) -> String {
+  let mut out = String::new();
+  let comment = match format {
+    Format::Nginx => "Include inside a `server` block.",
+    Format::Caddy => "Import inside a site block.",
+  };
+  let _ = writeln!(out, "# Generated by `framectl gen-server-config`. Do not edit by hand.");
+  let _ = writeln!(out, "# {} frame(s). {comment}", routes.len());
+  if format == Format::Nginx {
+    let _ = writeln!(out, "# brotli_static needs the ngx_brotli module; drop it to serve .gz only.");
+  }
+  for r in routes {
+    let dist = root.join(frames::dist_dir(r.n));
+    let dist = dist.display().to_string().replace('\\', "/");
+    let entry_cache = if r.hashed { IMMUTABLE } else { REVALIDATE };
+    let _ = writeln!(out);
+    match format {
+      Format::Nginx => {
+        for (loc, alias, cache) in [
+          (format!("= {}{ENTRY}", r.path), format!("{dist}/{ENTRY}"), entry_cache),
+          (r.path.clone(), format!("{dist}/"), IMMUTABLE),
+        ] {
+          let _ = writeln!(out, "location {loc} {{");
+          let _ = writeln!(out, "  alias {alias};");
+          let _ = writeln!(out, "  gzip_static on;");
+          let _ = writeln!(out, "  brotli_static on;");
+          let _ = writeln!(out, "  add_header Access-Control-Allow-Origin \"{origin}\" always;");
+          let _ = writeln!(out, "  add_header Cache-Control \"{cache}\" always;");
+          let _ = writeln!(out, "}}");
+        }
+      }
+      Format::Caddy => {
+        let _ = writeln!(out, "handle_path {}* {{", r.path);
+        let _ = writeln!(out, "  root * {dist}");
+        let _ = writeln!(out, "  header Access-Control-Allow-Origin \"{origin}\"");
+        if r.hashed {
+          let _ = writeln!(out, "  header Cache-Control \"{IMMUTABLE}\"");
+        } else {
+          // Disjoint matchers: Caddy orders same-name directives itself.
+          let _ = writeln!(out, "  @entry path /{ENTRY}");
+          let _ = writeln!(out, "  @chunks not path /{ENTRY}");
+          let _ = writeln!(out, "  header @entry Cache-Control \"{entry_cache}\"");
+          let _ = writeln!(out, "  header @chunks Cache-Control \"{IMMUTABLE}\"");
+        }
+        let _ = writeln!(out, "  file_server {{");
+        let _ = writeln!(out, "    precompressed br gzip");
+        let _ = writeln!(out, "  }}");
+        let _ = writeln!(out, "}}");
+      }
+    }
+  }
+  out
+}
+
+pub fn run(args: &[String]) {
+  let format = match parse_kv(args, "--format").as_deref() {
+    None | Some("nginx") => Format::Nginx,
+    Some("caddy") => Format::Caddy,
+    Some(other) => {
+      eprintln!("invalid --format={other} (expected nginx or caddy)");
+      std::process::exit(2);
+    }
+  };
+  let out = parse_kv(args, "--out").unwrap_or_else(|| "-".to_string());
+  let origin = parse_kv(args, "--cors-origin").unwrap_or_else(|| "*".to_string());
+  let base = url_path(&parse_kv(args, "--base-url").unwrap_or_default());
+  // Where the workspace (and so apps/frames) sits on the server.
+  let root = match parse_kv(args, "--root") {
+    Some(r) => PathBuf::from(r),
+    None => std::env::current_dir().unwrap_or_else(|e| {
+      eprintln!("gen-server-config: cannot resolve the workspace directory: {e}");
+      std::process::exit(2);
+    }),
+  };
+  let (start, end) = frames::range_args(args);
+
+  targets::dists("gen-server-config", args);
+  let routes: Vec<Route> = match parse_kv(args, "--manifest") {
+    Some(m) => playlist::from_manifest(Path::new(&m))
+      .unwrap_or_else(|e| {
+        eprintln!("gen-server-config: {m}: {e}");
+        std::process::exit(1);
+      })
+      .into_iter()
+      .map(|(n, entry)| Route {
+        n,
+        path: format!("{}/", url_path(entry.strip_suffix(ENTRY).unwrap_or(&entry))),
+        hashed: true,
+      })
+      .collect(),
+    None => frames::discover(&frames::frames_dir())
+      .into_iter()
+      .map(|n| Route {
+        n,
+        path: format!("{base}/frame-{n:04}/"),
+        hashed: false,
+      })
+      .collect(),
+  };
+  let routes: Vec<Route> = routes.into_iter().filter(|r| (start..=end).contains(&r.n)).collect();
+  if routes.is_empty() {
+    eprintln!("gen-server-config: no frames in range (start={start} end={end})");
+    std::process::exit(2);
+  }
+  let unbuilt: Vec<usize> = routes
+    .iter()
+    .map(|r| r.n)
+    .filter(|&n| !frames::entry_path(n).is_file())
+    .collect();
+  if !unbuilt.is_empty() {
+    eprintln!(
+      "gen-server-config: warning: {} frame(s) not built yet: {}",
+      unbuilt.len(),
+      frames::fmt_ranges(&unbuilt)
+    );
+  }
+
+  let text = render(format, &routes, &root, &origin);
+  if out == "-" {
+    print!("{text}");
+    return;
+  }
+  if let Err(e) = std::fs::write(&out, text) {
+    eprintln!("gen-server-config: write {out}: {e}");
+    std::process::exit(1);
+  }
+  eprintln!("gen-server-config: wrote {out} ({} frames)", routes.len());
+}
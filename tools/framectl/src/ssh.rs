@@ -0,0 +1,147 @@
+//! `--ssh-hosts`: frames built on other machines over plain `ssh`, without a
+//! `framectl worker` running there. Each host needs the workspace checked
+//! out at `--ssh-dir` (the local path by default) with dependencies
+//! installed; `--ssh-sync=1` rsyncs each frame's package there before its
+//! build. A built frame's `dist/` is rsynced back, so the rest of the run
+//! (hooks, summaries, `pack`) sees it as if it were built here.
+
+use std::process::{Command, Stdio};
+
+use crate::exec::{self, ExecOpts, Outcome, Script, StageTime};
+use crate::exitcode::{self, Kind};
+use crate::frames::frame_dir;
+use crate::{parse_bool, parse_kv};
+
+pub struct Ssh {
+  pub hosts: Vec<String>,
+  /// Builds at once per host.
+  pub slots: usize,
+  dir: String,
+  sync: bool,
+}
+
+const SSH_OPTS: [&str; 6] = ["-o", "BatchMode=yes", "-o", "ConnectTimeout=10", "-o", "ServerAliveInterval=15"];
+
+/// Single-quoted for a POSIX shell; a leading `~/` stays expandable.
+fn quote(s: &str) -> String {
+  match s.strip_prefix("~/") {
+    Some(rest) => format!("\"$HOME\"/{}", quote(rest)),
+    None => format!("'{}'", s.replace('\'', "'\\''")),
+  }
+}
+
+impl Ssh {
+  pub fn from_args(args: &[String]) -> Option<Ssh> {
+    let hosts: Vec<String> = parse_kv(args, "--ssh-hosts")?
+      .split(',')
+      .map(|h| h.trim().to_string())
+      .filter(|h| !h.is_empty())
+      .collect();
+    if hosts.is_empty() {
+      exitcode::usage("--ssh-hosts needs at least one user@host");
+    }
+    let slots = match parse_kv(args, "--ssh-slots") {
+      None => 1,
+      Some(v) => v.parse().ok().filter(|s| *s > 0).unwrap_or_else(|| {
+        exitcode::usage(format!("invalid --ssh-slots={v} (expected builds per host)"));
+      }),
+    };
+    let dir = parse_kv(args, "--ssh-dir").unwrap_or_else(|| {
+      std::env::current_dir()
+        .map(|d| d.display().to_string())
+        .unwrap_or_else(|_| ".".to_string())
+    });
+    let sync = parse_kv(args, "--ssh-sync").and_then(|v| parse_bool(&v)).unwrap_or(false);
+    for tool in ["ssh", "rsync"] {
+      let found = Command::new(tool)
+        .arg(if tool == "ssh" { "-V" } else { "--version" })
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok();
+      if !found {
+        exitcode::fail(Kind::Environment, format!("--ssh-hosts needs `{tool}` on PATH"));
+      }
+    }
+    Some(Ssh { hosts, slots, dir, sync })
+  }
+
+  fn ssh(&self, host: &str, remote: &str) -> Command {
+    let mut cmd = Command::new("ssh");
+    cmd.args(SSH_OPTS).arg("-T").arg(host).arg(format!("cd {} && {remote}", quote(&self.dir)));
+    cmd
+  }
+
+  /// Whether `host` answers and has pnpm in the workspace.
+  pub fn probe(&self, host: &str) -> Result<(), String> {
+    let out = self
+      .ssh(host, "pnpm --version")
+      .stdin(Stdio::null())
+      .output()
+      .map_err(|e| e.to_string())?;
+    if out.status.success() {
+      return Ok(());
+    }
+    let err = String::from_utf8_lossy(&out.stderr);
+    Err(err.lines().last().unwrap_or("no output").trim().to_string())
+  }
+
+  /// `rsync` a workspace-relative directory between here and `host`;
+  /// `to_host` pushes.
+  fn rsync(&self, host: &str, path: &str, to_host: bool, excludes: &[&str]) -> Result<(), String> {
+    let remote = format!("{host}:{}/{path}", self.dir.trim_end_matches('/'));
+    let mut cmd = Command::new("rsync");
+    cmd.args(["-a", "--delete", "-e"]).arg(format!("ssh {}", SSH_OPTS.join(" ")));
+    for e in excludes {
+      cmd.arg(format!("--exclude={e}"));
+    }
+    if to_host {
+      cmd.arg(path).arg(remote);
+    } else {
+      cmd.arg(remote).arg(path);
+    }
+    let out = cmd.stdin(Stdio::null()).output().map_err(|e| format!("rsync: {e}"))?;
+    if out.status.success() {
+      Ok(())
+    } else {
+      Err(exec::tail(&String::from_utf8_lossy(&out.stderr), 600))
+    }
+  }
+
+  /// `stages` for frame `n` on `host`, with the frame synced there first
+  /// (with `--ssh-sync`) and its dist fetched after a successful build.
+  pub fn run_pipeline(&self, host: &str, n: usize, opts: ExecOpts, stages: &[Script]) -> (Outcome, Vec<StageTime>) {
+    let dir = frame_dir(n).display().to_string();
+    let fail = |script: Script, msg: String| {
+      exec::write_log(n, script, &msg);
+      Outcome {
+        ok: false,
+        err_tail: msg,
+        usage: None,
+      }
+    };
+    if self.sync && !opts.dry_run {
+      if let Err(e) = self.rsync(host, &format!("{dir}/"), true, &["node_modules", "dist"]) {
+        return (fail(stages[0], format!("ssh: syncing frame-{n:04} to {host} failed: {e}")), Vec::new());
+      }
+    }
+    let (mut out, times) = exec::run_pipeline_with(opts, stages, |o| {
+      let env: Vec<String> = exec::env(o).into_iter().map(|(k, v)| quote(&format!("{k}={v}"))).collect();
+      let argv: Vec<String> = exec::argv(n, o).iter().map(|a| quote(a)).collect();
+      let remote = if env.is_empty() {
+        argv.join(" ")
+      } else {
+        format!("env {} {}", env.join(" "), argv.join(" "))
+      };
+      exec::run_command(n, o, self.ssh(host, &remote))
+    });
+    if out.ok && !opts.dry_run && stages.contains(&Script::Build) {
+      if let Err(e) = self.rsync(host, &format!("{dir}/dist/"), false, &[]) {
+        let usage = out.usage;
+        out = fail(Script::Build, format!("ssh: fetching frame-{n:04} dist from {host} failed: {e}"));
+        out.usage = usage;
+      }
+    }
+    (out, times)
+  }
+}
@@ -0,0 +1,76 @@
+//! `--strict-stderr`: a build that exits 0 but warns on stderr (deprecations,
+//! peer dependency mismatches, asset size limits) fails, so one warning
+//! doesn't quietly spread to every frame. `--stderr-patterns` replaces the
+//! default patterns and `--stderr-allow` exempts lines; both are files of one
+//! regex per line, `#` starting a comment.
+
+use std::sync::OnceLock;
+
+use crate::exitcode;
+use crate::pattern::Regex;
+use crate::{parse_bool, parse_kv};
+
+const DEFAULT_PATTERNS: [&str; 4] = [
+  r"(?i)\bwarn(ing)?\b",
+  r"(?i)deprecat",
+  r"(?i)peer dep|unmet peer|incorrect peer",
+  r"(?i)size limit|exceeds? the recommended",
+];
+
+/// Prefix of the line a strict failure appends to the output.
+pub const MARK: &str = "strict-stderr:";
+
+struct Strict {
+  deny: Vec<Regex>,
+  allow: Vec<Regex>,
+}
+
+static STRICT: OnceLock<Strict> = OnceLock::new();
+
+fn compile(src: &str, origin: &str) -> Regex {
+  Regex::new(src).unwrap_or_else(|e| {
+    exitcode::usage(format!("invalid pattern /{src}/ in {origin}: {e}"));
+  })
+}
+
+fn read_patterns(path: &str) -> Vec<Regex> {
+  let text = std::fs::read_to_string(path).unwrap_or_else(|e| {
+    exitcode::usage(format!("cannot read {path}: {e}"));
+  });
+  text
+    .lines()
+    .map(str::trim)
+    .filter(|l| !l.is_empty() && !l.starts_with('#'))
+    .map(|l| compile(l, path))
+    .collect()
+}
+
+/// Turn strict mode on for this process from the flags; `Some((patterns,
+/// allowed))` when it is on.
+pub fn install(args: &[String]) -> Option<(usize, usize)> {
+  if !parse_kv(args, "--strict-stderr").and_then(|v| parse_bool(&v)).unwrap_or(false) {
+    return None;
+  }
+  let deny = match parse_kv(args, "--stderr-patterns") {
+    Some(path) => read_patterns(&path),
+    None => DEFAULT_PATTERNS.iter().map(|p| compile(p, "defaults")).collect(),
+  };
+  let allow = parse_kv(args, "--stderr-allow").map(|p| read_patterns(&p)).unwrap_or_default();
+  let counts = (deny.len(), allow.len());
+  let _ = STRICT.set(Strict { deny, allow });
+  Some(counts)
+}
+
+/// The first offending line of `stderr` and which pattern it hit, with how
+/// many more there are; `None` when strict mode is off or nothing matched.
+pub fn violation(stderr: &str) -> Option<String> {
+  let strict = STRICT.get()?;
+  let mut hits = stderr.lines().filter_map(|line| {
+    let pat = strict.deny.iter().find(|p| p.is_match(line))?;
+    (!strict.allow.iter().any(|a| a.is_match(line))).then(|| (line.trim(), pat.as_str()))
+  });
+  let (line, pat) = hits.next()?;
+  let more = hits.count();
+  let more = if more > 0 { format!(" (+{more} more)") } else { String::new() };
+  Some(format!("{MARK} warning matching /{pat}/: {line}{more}"))
+}
@@ -0,0 +1,440 @@
+//! End-of-run report: the timing lines on stderr and the `--summary-out` JSON.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::exec::{Script, StageTime};
+use crate::{filterexpr, fmt_dur};
+use crate::frames::{self, fmt_ranges};
+use crate::json::Value;
+use crate::rusage::Usage;
+use crate::state_dir;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Status {
+  Ok,
+  Failed,
+  Skipped,
+}
+
+impl Status {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Status::Ok => "ok",
+      Status::Failed => "failed",
+      Status::Skipped => "skipped",
+    }
+  }
+}
+
+pub struct FrameRecord {
+  pub status: Status,
+  pub dur: Duration,
+  pub usage: Option<Usage>,
+  /// Failure class from `classify`, for failed frames.
+  pub error: Option<String>,
+  /// Failing test names, for `framectl test`.
+  pub failed_tests: Vec<String>,
+  /// Stages run, in order; a failed frame's last stage is the one that failed.
+  pub stages: Vec<StageTime>,
+}
+
+impl FrameRecord {
+  pub fn failed_stage(&self) -> Option<Script> {
+    self.stages.last().filter(|st| !st.ok).map(|st| st.script)
+  }
+}
+
+/// Only multi-stage runs report per-stage numbers.
+fn multi_stage(records: &BTreeMap<usize, FrameRecord>) -> bool {
+  records.values().any(|r| r.stages.len() > 1)
+}
+
+fn fmt_rss(kb: u64) -> String {
+  if kb >= 1024 * 1024 {
+    format!("{:.1}GB", kb as f64 / (1024.0 * 1024.0))
+  } else {
+    format!("{}MB", kb / 1024)
+  }
+}
+
+pub fn print_timing(records: &BTreeMap<usize, FrameRecord>) {
+  let built: Vec<(usize, &FrameRecord)> = records
+    .iter()
+    .filter(|(_, r)| r.status != Status::Skipped)
+    .map(|(n, r)| (*n, r))
+    .collect();
+  if built.is_empty() {
+    return;
+  }
+
+  let total: Duration = built.iter().map(|(_, r)| r.dur).sum();
+  let avg = total / built.len() as u32;
+  let mut by_dur = built.clone();
+  by_dur.sort_by_key(|r| Reverse(r.1.dur));
+  let slowest: Vec<String> = by_dur
+    .iter()
+    .take(5)
+    .map(|(n, r)| format!("{} {:.1}s", frames::label(*n), r.dur.as_secs_f64()))
+    .collect();
+  eprintln!("timing: avg={:.1}s slowest: {}", avg.as_secs_f64(), slowest.join(", "));
+  if multi_stage(records) {
+    print_stage_timing(&built);
+  }
+
+  let mut with_usage: Vec<(usize, Usage)> = built
+    .iter()
+    .filter_map(|(n, r)| r.usage.map(|u| (*n, u)))
+    .collect();
+  if with_usage.is_empty() {
+    return;
+  }
+  let cpu: Duration = with_usage.iter().map(|(_, u)| u.cpu).sum();
+  with_usage.sort_by_key(|r| Reverse(r.1.max_rss_kb));
+  let hogs: Vec<String> = with_usage
+    .iter()
+    .take(5)
+    .map(|(n, u)| format!("{} {}", frames::label(*n), fmt_rss(u.max_rss_kb)))
+    .collect();
+  eprintln!("resources: cpu_total={} peak_rss: {}", fmt_dur(cpu), hogs.join(", "));
+}
+
+/// `stages: install avg=4.1s total=2m, typecheck ...`, in pipeline order.
+fn print_stage_timing(built: &[(usize, &FrameRecord)]) {
+  let mut order: Vec<Script> = Vec::new();
+  let mut times: Vec<Vec<Duration>> = Vec::new();
+  for (_, r) in built {
+    for st in &r.stages {
+      let i = match order.iter().position(|s| *s == st.script) {
+        Some(i) => i,
+        None => {
+          order.push(st.script);
+          times.push(Vec::new());
+          order.len() - 1
+        }
+      };
+      times[i].push(st.dur);
+    }
+  }
+  let parts: Vec<String> = order
+    .iter()
+    .zip(&times)
+    .map(|(s, ts)| {
+      let total: Duration = ts.iter().sum();
+      let avg = total / ts.len() as u32;
+      format!("{} avg={:.1}s total={}", s.name(), avg.as_secs_f64(), fmt_dur(total))
+    })
+    .collect();
+  eprintln!("stages: {}", parts.join(", "));
+}
+
+/// `failures: 38 oom, 2 TS2307`, most common class first.
+pub fn print_failures(records: &BTreeMap<usize, FrameRecord>) {
+  let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+  for r in records.values() {
+    if r.status == Status::Failed {
+      *counts.entry(r.error.as_deref().unwrap_or("other")).or_default() += 1;
+    }
+  }
+  if counts.is_empty() {
+    return;
+  }
+  let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+  counts.sort_by_key(|(_, c)| Reverse(*c));
+  let parts: Vec<String> = counts.iter().map(|(k, c)| format!("{c} {k}")).collect();
+  eprintln!("failures: {}", parts.join(", "));
+  if multi_stage(records) {
+    let mut by_stage: Vec<(&str, usize)> = Vec::new();
+    for r in records.values().filter(|r| r.status == Status::Failed) {
+      let name = r.failed_stage().map_or("?", |s| s.name());
+      match by_stage.iter_mut().find(|(k, _)| *k == name) {
+        Some((_, c)) => *c += 1,
+        None => by_stage.push((name, 1)),
+      }
+    }
+    by_stage.sort_by_key(|(_, c)| Reverse(*c));
+    let parts: Vec<String> = by_stage.iter().map(|(k, c)| format!("{c} at {k}")).collect();
+    eprintln!("failed stages: {}", parts.join(", "));
+  }
+
+  let tests = failing_tests(records);
+  if tests.is_empty() {
+    return;
+  }
+  eprintln!("failing tests:");
+  for (name, frames) in tests.iter().take(10) {
+    eprintln!("  {name} ({}: {})", frames.len(), fmt_ranges(frames));
+  }
+  if tests.len() > 10 {
+    eprintln!("  ... and {} more", tests.len() - 10);
+  }
+}
+
+/// Each failing test name with the frames it failed in, most widespread first.
+fn failing_tests(records: &BTreeMap<usize, FrameRecord>) -> Vec<(String, Vec<usize>)> {
+  let mut by_name: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+  for (n, r) in records {
+    if r.status == Status::Failed {
+      for t in &r.failed_tests {
+        by_name.entry(t.as_str()).or_default().push(*n);
+      }
+    }
+  }
+  let mut out: Vec<(String, Vec<usize>)> = by_name.into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+  out.sort_by_key(|(_, v)| Reverse(v.len()));
+  out
+}
+
+pub struct Totals {
+  pub total: usize,
+  pub elapsed: Duration,
+}
+
+pub fn to_json(records: &BTreeMap<usize, FrameRecord>, totals: &Totals) -> Value {
+  let count = |s: Status| records.values().filter(|r| r.status == s).count();
+  let multi = multi_stage(records);
+  let frames = records
+    .iter()
+    .map(|(n, r)| {
+      let mut fields = vec![
+        ("frame", Value::from(*n)),
+        ("status", Value::from(r.status.as_str())),
+        ("duration_secs", Value::from(r.dur.as_secs_f64())),
+      ];
+      if let Some(expr) = filterexpr::get(*n) {
+        fields.push(("filter_expr", Value::from(expr)));
+      }
+      if let Some(e) = &r.error {
+        fields.push(("error_class", Value::from(e.as_str())));
+      }
+      if multi {
+        if let Some(s) = r.failed_stage() {
+          fields.push(("failed_stage", Value::from(s.name())));
+        }
+        let stages = r
+          .stages
+          .iter()
+          .map(|st| {
+            Value::obj([
+              ("stage", Value::from(st.script.name())),
+              ("duration_secs", Value::from(st.dur.as_secs_f64())),
+              ("ok", Value::Bool(st.ok)),
+            ])
+          })
+          .collect();
+        fields.push(("stages", Value::Arr(stages)));
+      }
+      if !r.failed_tests.is_empty() {
+        let names = r.failed_tests.iter().map(|t| Value::from(t.as_str())).collect();
+        fields.push(("failed_tests", Value::Arr(names)));
+      }
+      if let Some(u) = r.usage {
+        fields.push(("cpu_secs", Value::from(u.cpu.as_secs_f64())));
+        fields.push(("max_rss_kb", Value::from(u.max_rss_kb as f64)));
+      }
+      Value::obj(fields)
+    })
+    .collect();
+  let tests = failing_tests(records)
+    .into_iter()
+    .map(|(name, frames)| {
+      Value::obj([
+        ("name", Value::from(name)),
+        ("frames", Value::Arr(frames.into_iter().map(Value::from).collect())),
+      ])
+    })
+    .collect();
+  Value::obj([
+    ("total", Value::from(totals.total)),
+    ("ok", Value::from(count(Status::Ok))),
+    ("failed", Value::from(count(Status::Failed))),
+    ("skipped", Value::from(count(Status::Skipped))),
+    ("elapsed_secs", Value::from(totals.elapsed.as_secs_f64())),
+    ("frames", Value::Arr(frames)),
+    ("failing_tests", Value::Arr(tests)),
+  ])
+}
+
+pub fn write(path: impl AsRef<Path>, records: &BTreeMap<usize, FrameRecord>, totals: &Totals) {
+  let path = path.as_ref();
+  if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+    let _ = std::fs::create_dir_all(dir);
+  }
+  if let Err(e) = std::fs::write(path, to_json(records, totals).pretty()) {
+    eprintln!("warning: could not write summary {}: {e}", path.display());
+  }
+}
+
+/// Every real run also leaves its summary here, for `--order=failed-first`.
+pub fn last_path(script: Script) -> PathBuf {
+  match script {
+    Script::Build => state_dir().join("summary.json"),
+    other => state_dir().join(format!("summary-{}.json", other.name())),
+  }
+}
+
+/// Frames that failed in the last run of `script`.
+pub fn last_failed(script: Script) -> BTreeSet<usize> {
+  let Ok(doc) = crate::json::read(&last_path(script)) else {
+    return BTreeSet::new();
+  };
+  let Some(Value::Arr(frames)) = doc.get("frames") else {
+    return BTreeSet::new();
+  };
+  frames
+    .iter()
+    .filter(|f| f.get("status").and_then(Value::as_str) == Some("failed"))
+    .filter_map(frame_of)
+    .collect()
+}
+
+/// A summary entry's frame number; a `--filter-expr` task's is this run's
+/// number for its expression, as summaries from other runs number them
+/// differently.
+pub fn frame_of(f: &Value) -> Option<usize> {
+  if let Some(expr) = f.get("filter_expr").and_then(Value::as_str) {
+    return Some(filterexpr::id(expr));
+  }
+  match f.get("frame") {
+    Some(Value::Num(n)) => Some(*n as usize),
+    _ => None,
+  }
+}
+
+/// A `--summary-out` file back as records, with its elapsed time, for
+/// `--retry-failed` to merge into.
+pub fn read(path: &Path) -> Result<(BTreeMap<usize, FrameRecord>, Duration), String> {
+  let doc = crate::json::read(path)?;
+  let Some(Value::Arr(frames)) = doc.get("frames") else {
+    return Err(format!("{}: no frames list", path.display()));
+  };
+  let num = |v: &Value, key: &str| match v.get(key) {
+    Some(Value::Num(n)) => Some(*n),
+    _ => None,
+  };
+  let secs = |s: Option<f64>| Duration::from_secs_f64(s.unwrap_or(0.0).max(0.0));
+  let mut records = BTreeMap::new();
+  for f in frames {
+    let Some(n) = frame_of(f) else {
+      return Err(format!("{}: frame entry without a number", path.display()));
+    };
+    let status = match f.get("status").and_then(Value::as_str) {
+      Some("ok") => Status::Ok,
+      Some("skipped") => Status::Skipped,
+      _ => Status::Failed,
+    };
+    let stages = match f.get("stages") {
+      Some(Value::Arr(st)) => st
+        .iter()
+        .filter_map(|st| {
+          Some(StageTime {
+            script: Script::parse(st.get("stage")?.as_str()?)?,
+            dur: secs(num(st, "duration_secs")),
+            ok: st.get("ok") == Some(&Value::Bool(true)),
+          })
+        })
+        .collect(),
+      _ => Vec::new(),
+    };
+    let failed_tests = match f.get("failed_tests") {
+      Some(Value::Arr(t)) => t.iter().filter_map(|t| t.as_str().map(str::to_string)).collect(),
+      _ => Vec::new(),
+    };
+    let usage = num(f, "cpu_secs").map(|cpu| Usage {
+      cpu: secs(Some(cpu)),
+      max_rss_kb: num(f, "max_rss_kb").unwrap_or(0.0) as u64,
+    });
+    let record = FrameRecord {
+      status,
+      dur: secs(num(f, "duration_secs")),
+      usage,
+      error: f.get("error_class").and_then(Value::as_str).map(str::to_string),
+      failed_tests,
+      stages,
+    };
+    records.insert(n, record);
+  }
+  Ok((records, secs(num(&doc, "elapsed_secs"))))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn record(status: Status, secs: u64) -> FrameRecord {
+    FrameRecord {
+      status,
+      dur: Duration::from_secs(secs),
+      usage: None,
+      error: None,
+      failed_tests: Vec::new(),
+      stages: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn round_trip() {
+    let task = filterexpr::id("@summary-test/pkg...");
+    let mut records = BTreeMap::new();
+    let mut built = record(Status::Ok, 12);
+    built.usage = Some(Usage {
+      cpu: Duration::from_secs(30),
+      max_rss_kb: 2048,
+    });
+    built.stages = vec![
+      StageTime {
+        script: Script::Typecheck,
+        dur: Duration::from_secs(4),
+        ok: true,
+      },
+      StageTime {
+        script: Script::Build,
+        dur: Duration::from_secs(8),
+        ok: true,
+      },
+    ];
+    records.insert(3, built);
+    let mut failed = record(Status::Failed, 5);
+    failed.error = Some("oom".to_string());
+    failed.failed_tests = vec!["renders".to_string()];
+    records.insert(5, failed);
+    records.insert(8, record(Status::Skipped, 0));
+    records.insert(task, record(Status::Failed, 2));
+    let totals = Totals {
+      total: 4,
+      elapsed: Duration::from_secs(20),
+    };
+
+    let path = std::env::temp_dir().join(format!("framectl-summary-{}.json", std::process::id()));
+    write(&path, &records, &totals);
+    let read_back = read(&path);
+    let _ = std::fs::remove_file(&path);
+    let (back, elapsed) = read_back.unwrap();
+
+    assert_eq!(elapsed, totals.elapsed);
+    assert_eq!(back.keys().collect::<Vec<_>>(), records.keys().collect::<Vec<_>>());
+    for (n, want) in &records {
+      let got = &back[n];
+      assert!(got.status == want.status, "status of {n}");
+      assert_eq!(got.dur, want.dur);
+      assert_eq!(got.error, want.error);
+      assert_eq!(got.failed_tests, want.failed_tests);
+      assert_eq!(got.usage.map(|u| (u.cpu, u.max_rss_kb)), want.usage.map(|u| (u.cpu, u.max_rss_kb)));
+      let stages = |r: &FrameRecord| -> Vec<_> { r.stages.iter().map(|st| (st.script.name(), st.dur, st.ok)).collect() };
+      assert_eq!(stages(got), stages(want));
+    }
+  }
+
+  #[test]
+  fn task_entries_by_expression() {
+    let doc = Value::obj([
+      ("frame", Value::from(999_999_999usize)),
+      ("filter_expr", Value::from("@summary-test/other")),
+    ]);
+    assert_eq!(frame_of(&doc), filterexpr::find("@summary-test/other"));
+    assert_eq!(frame_of(&Value::obj([("frame", Value::from(4usize))])), Some(4));
+    assert_eq!(frame_of(&Value::obj([("status", Value::from("ok"))])), None);
+  }
+}
@@ -0,0 +1,237 @@
+//! Build targets: `--target=NAME` builds every frame for one output format,
+//! `--targets=esm,system` once per target (each a child `framectl` run with
+//! `--target`, like `--node-versions`), then reports and writes a summary
+//! with each frame's status per target.
+//!
+//! A target reaches the frame's build two ways: `FRAMECTL_TARGET=NAME` in its
+//! environment (the frame template picks the container format and builds into
+//! `dist/NAME` from it), and the `build:NAME` script instead of `build` when
+//! the frame's package.json has one.
+
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::sync::OnceLock;
+
+use crate::exec::Script;
+use crate::exitcode::{self, Kind};
+use crate::frames::{self, fmt_ranges};
+use crate::json::{self, Value};
+use crate::{filterexpr, parse_kv, state_dir, summary};
+
+/// The variable frame builds see the target in.
+pub const ENV: &str = "FRAMECTL_TARGET";
+
+/// Flags the matrix sets for its children itself.
+const OWN: [&str; 5] = ["--targets", "--target", "--summary-out", "--keep-going", "--interactive"];
+
+static TARGET: OnceLock<String> = OnceLock::new();
+
+fn valid(name: &str) -> bool {
+  !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Build for `--target=NAME` in this process.
+pub fn install(args: &[String]) -> Option<&'static str> {
+  let name = parse_kv(args, "--target")?;
+  if !valid(&name) {
+    exitcode::usage(format!("invalid --target={name} (expected a name like esm or system)"));
+  }
+  Some(TARGET.get_or_init(|| name).as_str())
+}
+
+pub fn active() -> Option<&'static str> {
+  TARGET.get().map(String::as_str)
+}
+
+/// For a command reading frame dists: `--target=NAME` reads `dist/NAME`.
+/// Without it, frames built only per target are an error rather than read
+/// as one flat dist holding every target.
+pub fn dists(cmd: &str, args: &[String]) {
+  if install(args).is_some() {
+    return;
+  }
+  let per_target: Vec<usize> = frames::selected(args)
+    .into_iter()
+    .filter(|&n| !frames::entry_path(n).is_file() && !frames::built_targets(n).is_empty())
+    .collect();
+  if let Some(&n) = per_target.first() {
+    exitcode::usage(format!(
+      "{cmd}: frame(s) {} are built per target ({}); pass --target=NAME",
+      fmt_ranges(&per_target),
+      frames::built_targets(n).join(", ")
+    ));
+  }
+}
+
+/// The pnpm script frame `n` runs for `name` (the script `script` resolved
+/// to): `name:TARGET` for the build if the frame defines it.
+pub fn script_name(n: usize, script: Script, name: &str) -> String {
+  let Some(target) = active().filter(|_| script == Script::Build && !filterexpr::is_task(n)) else {
+    return name.to_string();
+  };
+  let specific = format!("{name}:{target}");
+  let pkg = json::read(&frames::frame_dir(n).join("package.json"));
+  match pkg.ok().as_ref().and_then(|p| p.get("scripts")).and_then(|s| s.get(&specific)) {
+    Some(_) => specific,
+    None => name.to_string(),
+  }
+}
+
+/// One target's frames, from its child's summary.
+fn outcome(path: &std::path::Path) -> Option<BTreeMap<usize, Value>> {
+  let doc = json::read(path).ok()?;
+  let Value::Obj(fields) = doc else {
+    return None;
+  };
+  let frames = fields.into_iter().find(|(k, _)| k == "frames").map(|(_, v)| v)?;
+  let Value::Arr(frames) = frames else {
+    return None;
+  };
+  let mut out = BTreeMap::new();
+  for f in frames {
+    if let Some(n) = summary::frame_of(&f) {
+      out.insert(n, f);
+    }
+  }
+  Some(out)
+}
+
+pub fn run(args: &[String], script: Script) {
+  let spec = parse_kv(args, "--targets").unwrap_or_default();
+  let mut targets: Vec<String> = Vec::new();
+  for t in spec.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+    if !valid(t) {
+      exitcode::usage(format!("invalid --targets={spec} (expected names like esm,system)"));
+    }
+    if !targets.iter().any(|have| have == t) {
+      targets.push(t.to_string());
+    }
+  }
+  if targets.is_empty() {
+    exitcode::usage("--targets needs at least one target");
+  }
+  let clash = ["--target", "--node-versions", "--workers", "--ssh-hosts", "--retry-failed"];
+  if let Some(flag) = clash.iter().find(|k| args.iter().any(|a| a == *k || a.starts_with(&format!("{k}=")))) {
+    exitcode::usage(format!("--targets and {flag} don't mix"));
+  }
+
+  let child_args: Vec<String> = args
+    .iter()
+    .filter(|a| !OWN.iter().any(|k| a.starts_with(&format!("{k}="))))
+    .cloned()
+    .collect();
+  let exe = std::env::current_exe().unwrap_or_else(|_| "framectl".into());
+  let dry_run = parse_kv(args, "--dry-run").and_then(|v| crate::parse_bool(&v)).unwrap_or(false);
+
+  let mut results: Vec<(&str, Option<BTreeMap<usize, Value>>)> = Vec::new();
+  for t in &targets {
+    eprintln!("targets: {} for {t} (dist/{t})", script.name());
+    let out = state_dir().join(format!("target-{t}.json"));
+    let _ = std::fs::remove_file(&out);
+    let status = Command::new(&exe)
+      .arg(script.name())
+      .args(&child_args)
+      .arg(format!("--target={t}"))
+      .arg("--keep-going=1")
+      .arg("--interactive=0")
+      .arg(format!("--summary-out={}", out.display()))
+      .status();
+    if let Err(e) = status {
+      eprintln!("targets: {t}: {e}");
+    }
+    results.push((t, outcome(&out)));
+  }
+
+  // Per frame, each target's record; a frame is ok only if every target is.
+  let mut by_frame: BTreeMap<usize, Vec<(&str, Value)>> = BTreeMap::new();
+  let mut bad = false;
+  eprintln!("targets: {}", script.name());
+  for (t, r) in &mut results {
+    let Some(r) = r.take() else {
+      bad = true;
+      eprintln!("  {t}: did not run (no summary)");
+      continue;
+    };
+    let status = |f: &Value| f.get("status").and_then(Value::as_str).unwrap_or("failed").to_string();
+    let failed: Vec<usize> = r.iter().filter(|(_, f)| status(f) == "failed").map(|(n, _)| *n).collect();
+    let ok = r.values().filter(|f| status(f) == "ok").count();
+    if failed.is_empty() {
+      eprintln!("  {t}: {ok} ok");
+    } else {
+      bad = true;
+      eprintln!("  {t}: {ok} ok, {} failed: {}", failed.len(), fmt_ranges(&failed));
+    }
+    for (n, mut f) in r {
+      if let Value::Obj(fields) = &mut f {
+        fields.retain(|(k, _)| k != "frame" && k != "filter_expr");
+      }
+      by_frame.entry(n).or_default().push((t, f));
+    }
+  }
+  let ran = results.len();
+  let partial: Vec<String> = by_frame
+    .iter()
+    .filter_map(|(n, ts)| {
+      let failed: Vec<&str> = ts.iter().filter(|(_, f)| f.get("status").and_then(Value::as_str) == Some("failed")).map(|(t, _)| *t).collect();
+      (!failed.is_empty() && failed.len() < ran).then(|| format!("{} ({})", fmt_ranges(&[*n]), failed.join(",")))
+    })
+    .collect();
+  if !partial.is_empty() {
+    eprintln!("targets: fail only on some targets: {}", partial.join(", "));
+  }
+
+  let doc = summary_json(&targets, by_frame);
+  if !dry_run {
+    write(&summary::last_path(script), &doc);
+  }
+  if let Some(path) = parse_kv(args, "--summary-out") {
+    write(std::path::Path::new(&path), &doc);
+  }
+  if bad {
+    exitcode::exit(Kind::Build, "targets: failed on at least one target", Vec::new());
+  }
+}
+
+/// The combined summary: `status` per frame across targets (so
+/// `--order=failed-first` and `--retry-failed` read it like any other), and
+/// each target's own record under `targets`.
+fn summary_json(targets: &[String], by_frame: BTreeMap<usize, Vec<(&str, Value)>>) -> Value {
+  let mut ok = 0;
+  let mut failed = 0;
+  let frames = by_frame
+    .into_iter()
+    .map(|(n, ts)| {
+      let status = if ts.len() < targets.len() || ts.iter().any(|(_, f)| f.get("status").and_then(Value::as_str) == Some("failed")) {
+        failed += 1;
+        "failed"
+      } else if ts.iter().all(|(_, f)| f.get("status").and_then(Value::as_str) == Some("ok")) {
+        ok += 1;
+        "ok"
+      } else {
+        "skipped"
+      };
+      let mut fields = vec![("frame", Value::from(n)), ("status", Value::from(status))];
+      if let Some(expr) = filterexpr::get(n) {
+        fields.push(("filter_expr", Value::from(expr)));
+      }
+      fields.push(("targets", Value::obj(ts)));
+      Value::obj(fields)
+    })
+    .collect::<Vec<_>>();
+  Value::obj([
+    ("targets", Value::Arr(targets.iter().map(|t| Value::from(t.as_str())).collect())),
+    ("total", Value::from(frames.len())),
+    ("ok", Value::from(ok)),
+    ("failed", Value::from(failed)),
+    ("frames", Value::Arr(frames)),
+  ])
+}
+
+fn write(path: &std::path::Path, doc: &Value) {
+  if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+    let _ = std::fs::create_dir_all(dir);
+  }
+  if let Err(e) = std::fs::write(path, doc.pretty()) {
+    eprintln!("warning: could not write summary {}: {e}", path.display());
+  }
+}
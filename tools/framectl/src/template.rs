@@ -0,0 +1,148 @@
+//! `sync-template`: copy the shared frame files from a template dir into
+//! every frame package, filling in the per-frame bits.
+//!
+//! Template files may use `{{id}}` (`0042`), `{{n}}` (`42`), `{{package}}`,
+//! `{{scope}}` (`frame_0042`), `{{port}}` and `{{assetPrefix}}`. Port and asset
+//! prefix are read back from the frame's current rsbuild.config.mjs, so a
+//! patched CDN prefix survives a sync.
+
+use std::path::{Path, PathBuf};
+
+use crate::frames::{self, fmt_ranges};
+use crate::{parse_bool, parse_kv};
+
+/// Generated from the frame's source image; never overwritten by a template.
+const PER_FRAME: [&str; 2] = ["src/frame.js", "src/frame.css"];
+
+fn walk(dir: &Path, rel: &Path, out: &mut Vec<PathBuf>) {
+  let Ok(rd) = std::fs::read_dir(dir) else {
+    return;
+  };
+  for ent in rd.flatten() {
+    let rel = rel.join(ent.file_name());
+    match ent.file_type() {
+      Ok(t) if t.is_dir() => walk(&ent.path(), &rel, out),
+      Ok(t) if t.is_file() => out.push(rel),
+      _ => {}
+    }
+  }
+}
+
+/// The value after `key:` in a JS config, quoted or bare (`'./'`, `4101`,
+/// `{{port}}`).
+pub fn extract(src: &str, key: &str) -> Option<String> {
+  let pat = format!("{key}:");
+  let at = src.match_indices(&pat).map(|(i, _)| i).find(|&i| {
+    !src[..i].ends_with(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+  })?;
+  let rest = src[at + pat.len()..].trim_start();
+  match rest.chars().next()? {
+    q @ ('\'' | '"' | '`') => rest[1..].find(q).map(|end| rest[1..1 + end].to_string()),
+    // An unfilled placeholder, when reading a template itself.
+    '{' if rest.starts_with("{{") => rest.find("}}").map(|end| rest[..end + 2].to_string()),
+    _ => {
+      let end = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+      (end > 0).then(|| rest[..end].to_string())
+    }
+  }
+}
+
+fn render(text: &str, n: usize, current_config: Option<&str>) -> String {
+  let asset_prefix = current_config
+    .and_then(|c| extract(c, "assetPrefix"))
+    .unwrap_or_else(|| "./".to_string());
+  // Same default as the generator (--port=4100 plus the frame number).
+  let port = current_config
+    .and_then(|c| extract(c, "port"))
+    .unwrap_or_else(|| (4100 + n).to_string());
+  text
+    .replace("{{id}}", &format!("{n:04}"))
+    .replace("{{n}}", &n.to_string())
+    .replace("{{package}}", &frames::frame_pkg(n))
+    .replace("{{scope}}", &format!("frame_{n:04}"))
+    .replace("{{port}}", &port)
+    .replace("{{assetPrefix}}", &asset_prefix)
+}
+
+pub fn run(args: &[String]) {
+  let template = PathBuf::from(parse_kv(args, "--template").unwrap_or_else(|| "templates/frame".to_string()));
+  let dry_run: bool = parse_kv(args, "--dry-run")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(false);
+
+  let mut files = Vec::new();
+  walk(&template, Path::new(""), &mut files);
+  files.sort();
+  files.retain(|f| {
+    let per_frame = PER_FRAME.iter().any(|p| Path::new(p) == f);
+    if per_frame {
+      eprintln!("sync-template: skipping {} (generated per frame)", f.display());
+    }
+    !per_frame
+  });
+  if files.is_empty() {
+    eprintln!("sync-template: no files in {}", template.display());
+    std::process::exit(2);
+  }
+  let sources: Vec<(PathBuf, String)> = files
+    .into_iter()
+    .map(|f| match std::fs::read_to_string(template.join(&f)) {
+      Ok(text) => (f, text),
+      Err(e) => {
+        eprintln!("sync-template: {}: {e}", template.join(&f).display());
+        std::process::exit(1);
+      }
+    })
+    .collect();
+
+  let mut modified: Vec<usize> = Vec::new();
+  let mut per_file: Vec<Vec<usize>> = sources.iter().map(|_| Vec::new()).collect();
+  let mut failed = 0usize;
+  for n in frames::selected(args) {
+    let dir = frames::frame_dir(n);
+    let current_config = std::fs::read_to_string(dir.join("rsbuild.config.mjs")).ok();
+    let mut changed = false;
+    for (i, (rel, text)) in sources.iter().enumerate() {
+      let want = render(text, n, current_config.as_deref());
+      let dst = dir.join(rel);
+      if std::fs::read_to_string(&dst).ok().as_deref() == Some(want.as_str()) {
+        continue;
+      }
+      changed = true;
+      per_file[i].push(n);
+      if dry_run {
+        continue;
+      }
+      let res = dst
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(&dst, &want));
+      if let Err(e) = res {
+        eprintln!("sync-template: {}: {e}", dst.display());
+        failed += 1;
+      }
+    }
+    if changed {
+      modified.push(n);
+    }
+  }
+
+  for ((rel, _), ns) in sources.iter().zip(&per_file) {
+    if !ns.is_empty() {
+      eprintln!("  {}: {}", rel.display(), fmt_ranges(ns));
+    }
+  }
+  eprintln!(
+    "sync-template: {} {} frame(s){}",
+    if dry_run { "would modify" } else { "modified" },
+    modified.len(),
+    if modified.is_empty() {
+      String::new()
+    } else {
+      format!(": {}", fmt_ranges(&modified))
+    }
+  );
+  if failed > 0 {
+    std::process::exit(1);
+  }
+}
@@ -0,0 +1,98 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Spreads out build spawns: a ramp-up delay per slot before its first task,
+/// an optional cap on new spawns per second for the whole run, and an
+/// optional load-average ceiling above which no new build starts.
+pub struct Throttle {
+  stagger: Duration,
+  interval: Option<Duration>,
+  next: Mutex<Instant>,
+  max_load: Option<f64>,
+  paused: AtomicBool,
+}
+
+impl Throttle {
+  pub fn new(stagger: Duration, spawns_per_sec: Option<f64>) -> Self {
+    Throttle {
+      stagger,
+      interval: spawns_per_sec
+        .filter(|r| *r > 0.0)
+        .map(|r| Duration::from_secs_f64(1.0 / r)),
+      next: Mutex::new(Instant::now()),
+      max_load: None,
+      paused: AtomicBool::new(false),
+    }
+  }
+
+  /// Hold new spawns while the 1-minute load average is above `max`.
+  pub fn with_max_load(mut self, max: Option<f64>) -> Self {
+    if max.is_some() && load_avg().is_none() {
+      eprintln!("warning: load average unavailable on this platform; ignoring --max-load");
+      return self;
+    }
+    self.max_load = max;
+    self
+  }
+
+  /// Called once by slot `i` before it takes its first frame.
+  pub fn ramp_up(&self, i: usize) {
+    if !self.stagger.is_zero() && i > 0 {
+      thread::sleep(self.stagger * i as u32);
+    }
+  }
+
+  /// Blocks until the rate limit allows another spawn.
+  pub fn before_spawn(&self) {
+    let Some(interval) = self.interval else {
+      return;
+    };
+    let at = {
+      let mut next = self.next.lock().unwrap();
+      let at = (*next).max(Instant::now());
+      *next = at + interval;
+      at
+    };
+    let now = Instant::now();
+    if at > now {
+      thread::sleep(at - now);
+    }
+  }
+
+  /// Whether the load average is above `--max-load`, saying so when that
+  /// changes. The build loop pauses the queue meanwhile, so nothing is handed
+  /// out (and shown in flight) while it waits.
+  pub fn load_high(&self) -> bool {
+    let Some(max) = self.max_load else {
+      return false;
+    };
+    let load = load_avg().unwrap_or(0.0);
+    let high = load > max;
+    if self.paused.swap(high, Ordering::Relaxed) != high {
+      if high {
+        eprintln!("load: {load:.2} > {max}; pausing new builds");
+      } else {
+        eprintln!("load: {load:.2} <= {max}; resuming");
+      }
+    }
+    high
+  }
+}
+
+#[cfg(unix)]
+fn load_avg() -> Option<f64> {
+  extern "C" {
+    fn getloadavg(loadavg: *mut f64, nelem: std::os::raw::c_int) -> std::os::raw::c_int;
+  }
+  let mut avg = [0f64; 1];
+  // SAFETY: `avg` has room for the one sample asked for.
+  let n = unsafe { getloadavg(avg.as_mut_ptr(), 1) };
+  (n == 1).then_some(avg[0])
+}
+
+#[cfg(not(unix))]
+fn load_avg() -> Option<f64> {
+  None
+}
@@ -0,0 +1,148 @@
+//! `thumbs`: contact sheets of the source frames, for eyeballing gaps, blank
+//! frames and ordering mistakes.
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::frames;
+use crate::png::{self, Image};
+use crate::queue::par_each;
+use crate::{default_concurrency, parse_kv};
+
+// Cells for frames without a usable source image.
+const MISSING: [u8; 3] = [255, 0, 255];
+const UNREADABLE: [u8; 3] = [255, 0, 0];
+const GAP: [u8; 3] = [40, 40, 40];
+
+pub fn parse_dims(s: &str) -> Option<(usize, usize)> {
+  let (a, b) = s.split_once('x')?;
+  let (a, b) = (a.parse().ok()?, b.parse().ok()?);
+  (a > 0 && b > 0).then_some((a, b))
+}
+
+pub fn run(args: &[String]) {
+  let src_dir = parse_kv(args, "--frames-dir")
+    .map(PathBuf::from)
+    .unwrap_or_else(frames::sources_dir);
+  let out_dir = PathBuf::from(parse_kv(args, "--out").unwrap_or_else(|| "thumbs".to_string()));
+  let (cols, rows) = parse_kv(args, "--grid").and_then(|v| parse_dims(&v)).unwrap_or((30, 20));
+  let (cw, ch) = parse_kv(args, "--cell").and_then(|v| parse_dims(&v)).unwrap_or((64, 48));
+  let concurrency: usize = parse_kv(args, "--concurrency")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or_else(default_concurrency)
+    .max(1);
+
+  let start: usize = parse_kv(args, "--start")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(1);
+  let end: usize = match parse_kv(args, "--end").and_then(|v| v.parse().ok()) {
+    Some(v) => v,
+    None => frames::infer_end(&frames::frames_dir())
+      .or_else(|| frames::discover_sources(&src_dir).last().copied())
+      .unwrap_or(0),
+  };
+  if end < start || end == 0 {
+    eprintln!("invalid frame range: start={start} end={end}");
+    std::process::exit(2);
+  }
+
+  if let Err(e) = std::fs::create_dir_all(&out_dir) {
+    eprintln!("thumbs: {}: {e}", out_dir.display());
+    std::process::exit(1);
+  }
+
+  let per_sheet = cols * rows;
+  let sheets = (end - start + 1).div_ceil(per_sheet);
+  eprintln!(
+    "thumbs: frames {start}..{end} -> {sheets} sheet(s) of {cols}x{rows} in {}",
+    out_dir.display()
+  );
+
+  let missing = Mutex::new(Vec::new());
+  let unreadable = Mutex::new(Vec::new());
+  let sheet_ids: Vec<usize> = (0..sheets).collect();
+  par_each(&sheet_ids, concurrency, |&i| {
+    let first = start + i * per_sheet;
+    let last = (first + per_sheet - 1).min(end);
+    let (width, height) = (cols * cw, rows * ch);
+    let mut sheet = Image {
+      width,
+      height,
+      rgb: GAP.repeat(width * height),
+    };
+    for n in first..=last {
+      let k = n - first;
+      let (x0, y0) = ((k % cols) * cw, (k / cols) * ch);
+      match frames::source_image(&src_dir, n) {
+        None => {
+          fill(&mut sheet, x0, y0, cw, ch, MISSING);
+          missing.lock().unwrap().push(n);
+        }
+        Some(p) => match png::decode_file(&p) {
+          Ok(img) => blit_scaled(&mut sheet, &img, x0, y0, cw, ch),
+          Err(e) => {
+            fill(&mut sheet, x0, y0, cw, ch, UNREADABLE);
+            unreadable.lock().unwrap().push((n, e));
+          }
+        },
+      }
+    }
+    let path = out_dir.join(format!("sheet-{first:04}-{last:04}.png"));
+    match std::fs::write(&path, png::encode_rgb(&sheet)) {
+      Ok(()) => eprintln!("thumbs: wrote {}", path.display()),
+      Err(e) => eprintln!("thumbs: write {}: {e}", path.display()),
+    }
+  });
+
+  let mut missing = missing.into_inner().unwrap();
+  missing.sort_unstable();
+  if !missing.is_empty() {
+    eprintln!(
+      "missing ({}, magenta): {}",
+      missing.len(),
+      frames::fmt_ranges(&missing)
+    );
+  }
+  let mut unreadable = unreadable.into_inner().unwrap();
+  unreadable.sort_by_key(|(n, _)| *n);
+  for (n, e) in &unreadable {
+    eprintln!("unreadable (red): frame {n}: {e}");
+  }
+}
+
+fn fill(img: &mut Image, x0: usize, y0: usize, w: usize, h: usize, c: [u8; 3]) {
+  for y in y0..y0 + h.saturating_sub(1) {
+    for x in x0..x0 + w.saturating_sub(1) {
+      let i = (y * img.width + x) * 3;
+      img.rgb[i..i + 3].copy_from_slice(&c);
+    }
+  }
+}
+
+/// Box-filter `src` down into a `w`x`h` cell, leaving a 1px gap.
+pub fn blit_scaled(dst: &mut Image, src: &Image, x0: usize, y0: usize, w: usize, h: usize) {
+  let (w, h) = (w.saturating_sub(1).max(1), h.saturating_sub(1).max(1));
+  for cy in 0..h {
+    let sy0 = cy * src.height / h;
+    let sy1 = ((cy + 1) * src.height / h).max(sy0 + 1).min(src.height);
+    for cx in 0..w {
+      let sx0 = cx * src.width / w;
+      let sx1 = ((cx + 1) * src.width / w).max(sx0 + 1).min(src.width);
+      let mut acc = [0u32; 3];
+      let mut count = 0u32;
+      for sy in sy0..sy1 {
+        for sx in sx0..sx1 {
+          let i = (sy * src.width + sx) * 3;
+          for (c, a) in acc.iter_mut().enumerate() {
+            *a += src.rgb[i + c] as u32;
+          }
+          count += 1;
+        }
+      }
+      let i = ((y0 + cy) * dst.width + x0 + cx) * 3;
+      for (c, a) in acc.iter().enumerate() {
+        dst.rgb[i + c] = (a / count.max(1)) as u8;
+      }
+    }
+  }
+}
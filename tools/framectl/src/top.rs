@@ -0,0 +1,258 @@
+//! `top`: live view of the frames a running local build has in flight,
+//! through its control socket (see `control`): frame, child pid, elapsed
+//! time, and CPU% and RSS of the child's whole process tree (Linux only).
+//! Keys: up/down select a frame, `k` kills it, `-`/`+` change the run's
+//! concurrency, `q` quits.
+
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::control;
+use crate::procinfo::{self, Proc};
+use crate::{fmt_dur, parse_bool, parse_kv};
+
+const REFRESH: Duration = Duration::from_secs(1);
+
+struct Ps {
+  limit: usize,
+  max: usize,
+  done: usize,
+  total: usize,
+  /// (frame, pid, elapsed), longest-running first.
+  frames: Vec<(usize, u32, Duration)>,
+}
+
+fn fetch() -> Result<Ps, String> {
+  let reply = control::request("ps")?;
+  let mut lines = reply.lines();
+  let head: Vec<usize> = lines
+    .next()
+    .unwrap_or("")
+    .split_whitespace()
+    .filter_map(|w| w.parse().ok())
+    .collect();
+  let [limit, max, done, total] = head[..] else {
+    return Err(format!("unexpected reply: {reply:?}"));
+  };
+  let mut frames: Vec<(usize, u32, Duration)> = lines
+    .filter_map(|l| {
+      let mut w = l.split_whitespace().map(|v| v.parse::<u64>().ok());
+      Some((w.next()?? as usize, w.next()?? as u32, Duration::from_millis(w.next()??)))
+    })
+    .collect();
+  frames.sort_by_key(|f| std::cmp::Reverse(f.2));
+  Ok(Ps {
+    limit,
+    max,
+    done,
+    total,
+    frames,
+  })
+}
+
+/// CPU% of each pid's tree since the previous sample.
+struct Sampler {
+  prev: BTreeMap<u32, (Duration, Instant)>,
+}
+
+impl Sampler {
+  fn sample(&mut self, pids: &[u32]) -> BTreeMap<u32, (Option<f64>, u64)> {
+    let procs: BTreeMap<u32, Proc> = procinfo::snapshot();
+    let now = Instant::now();
+    let mut out = BTreeMap::new();
+    let mut next = BTreeMap::new();
+    for &pid in pids {
+      let Some((cpu, rss)) = procinfo::tree_usage(&procs, pid) else {
+        continue;
+      };
+      let pct = self.prev.get(&pid).map(|(was, at)| {
+        let wall = now.duration_since(*at).as_secs_f64().max(0.001);
+        cpu.saturating_sub(*was).as_secs_f64() / wall * 100.0
+      });
+      out.insert(pid, (pct, rss));
+      next.insert(pid, (cpu, now));
+    }
+    self.prev = next;
+    out
+  }
+}
+
+fn render(ps: &Ps, usage: &BTreeMap<u32, (Option<f64>, u64)>, selected: Option<usize>, rows: usize) -> String {
+  let mut out = format!(
+    "framectl top: done={}/{} in flight={} concurrency={}/{}\n\n",
+    ps.done,
+    ps.total,
+    ps.frames.len(),
+    ps.limit,
+    ps.max
+  );
+  out.push_str("FRAME          PID   ELAPSED    CPU%      RSS\n");
+  for (i, &(n, pid, elapsed)) in ps.frames.iter().take(rows).enumerate() {
+    let (cpu, rss) = match usage.get(&pid) {
+      Some((pct, rss)) => (
+        pct.map(|p| format!("{p:.0}")).unwrap_or_else(|| "-".to_string()),
+        format!("{}M", rss / 1024),
+      ),
+      None => ("-".to_string(), "-".to_string()),
+    };
+    let pid = if pid == 0 { "-".to_string() } else { pid.to_string() };
+    let row = format!("frame-{n:04} {pid:>8} {:>9} {cpu:>7} {rss:>8}", fmt_dur(elapsed));
+    if selected == Some(i) {
+      out.push_str(&format!("\x1b[7m{row}\x1b[0m\n"));
+    } else {
+      out.push_str(&format!("{row}\n"));
+    }
+  }
+  if ps.frames.len() > rows {
+    out.push_str(&format!("... {} more\n", ps.frames.len() - rows));
+  }
+  out
+}
+
+/// `stty` on our terminal; `None` if it failed or isn't there.
+fn stty(args: &[&str]) -> Option<String> {
+  let out = Command::new("stty").args(args).stdin(Stdio::inherit()).stderr(Stdio::null()).output().ok()?;
+  out.status.success().then(|| String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// Keys without Enter and without echo until dropped.
+struct RawMode {
+  saved: Option<String>,
+}
+
+impl RawMode {
+  fn enter() -> RawMode {
+    let saved = stty(&["-g"]);
+    if saved.is_some() {
+      stty(&["-icanon", "-echo", "-isig", "min", "1"]);
+    }
+    print!("\x1b[?25l");
+    RawMode { saved }
+  }
+}
+
+impl Drop for RawMode {
+  fn drop(&mut self) {
+    if let Some(s) = &self.saved {
+      stty(&[s]);
+    }
+    print!("\x1b[?25h");
+    let _ = std::io::stdout().flush();
+  }
+}
+
+fn terminal_rows() -> usize {
+  stty(&["size"])
+    .and_then(|s| s.split_whitespace().next()?.parse::<usize>().ok())
+    .filter(|&r| r > 0)
+    .unwrap_or(24)
+}
+
+fn one_shot(command: &str) {
+  match control::request(command) {
+    Ok(reply) => eprint!("top: {}", reply.trim_start_matches("ok: ")),
+    Err(e) => {
+      eprintln!("top: {e}");
+      std::process::exit(1);
+    }
+  }
+}
+
+pub fn run(args: &[String]) {
+  if let Some(n) = parse_kv(args, "--kill") {
+    return one_shot(&format!("kill {n}"));
+  }
+  if let Some(c) = parse_kv(args, "--concurrency") {
+    return one_shot(&format!("concurrency {c}"));
+  }
+  let once = parse_kv(args, "--once")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or_else(|| !(std::io::stdin().is_terminal() && std::io::stdout().is_terminal()));
+
+  let mut sampler = Sampler { prev: BTreeMap::new() };
+  let mut ps = fetch().unwrap_or_else(|e| {
+    eprintln!("top: {e}");
+    std::process::exit(1);
+  });
+  if once {
+    // Two samples for a CPU%.
+    sampler.sample(&ps.frames.iter().map(|f| f.1).collect::<Vec<_>>());
+    std::thread::sleep(Duration::from_millis(500));
+    let usage = sampler.sample(&ps.frames.iter().map(|f| f.1).collect::<Vec<_>>());
+    print!("{}", render(&ps, &usage, None, usize::MAX));
+    return;
+  }
+
+  let _raw = RawMode::enter();
+  let (tx, keys) = mpsc::channel::<Vec<u8>>();
+  std::thread::spawn(move || {
+    let mut buf = [0u8; 16];
+    while let Ok(k) = std::io::stdin().read(&mut buf) {
+      if k == 0 || tx.send(buf[..k].to_vec()).is_err() {
+        break;
+      }
+    }
+  });
+
+  let mut selected = 0usize;
+  let mut message = String::new();
+  let mut confirm: Option<usize> = None;
+  let mut usage = BTreeMap::new();
+  let mut fetched = Instant::now();
+  loop {
+    let rows = terminal_rows().saturating_sub(7).max(1);
+    selected = selected.min(ps.frames.len().min(rows).saturating_sub(1));
+    let footer = match confirm {
+      Some(n) => format!("kill frame-{n:04}? [y/N]"),
+      None => "up/down select  k kill  -/+ concurrency  q quit".to_string(),
+    };
+    print!(
+      "\x1b[H\x1b[2J{}\n{message}\n{footer}",
+      render(&ps, &usage, (!ps.frames.is_empty()).then_some(selected), rows)
+    );
+    let _ = std::io::stdout().flush();
+
+    match keys.recv_timeout(REFRESH.saturating_sub(fetched.elapsed())) {
+      Ok(k) => {
+        let frame = ps.frames.get(selected).map(|f| f.0);
+        if let Some(n) = confirm.take() {
+          if k == b"y" || k == b"Y" {
+            message = control::request(&format!("kill {n}")).map(|r| r.trim().to_string()).unwrap_or_else(|e| e);
+          }
+          continue;
+        }
+        match &k[..] {
+          b"q" | b"\x03" | b"\x1b" => break,
+          b"\x1b[A" | b"\x1bOA" => selected = selected.saturating_sub(1),
+          b"\x1b[B" | b"\x1bOB" => selected += 1,
+          b"k" => confirm = frame,
+          b"-" | b"+" | b"=" => {
+            let want = if k == b"-" { ps.limit.saturating_sub(1).max(1) } else { (ps.limit + 1).min(ps.max) };
+            message = control::request(&format!("concurrency {want}")).map(|r| r.trim().to_string()).unwrap_or_else(|e| e);
+            ps.limit = want;
+          }
+          _ => {}
+        }
+        continue;
+      }
+      Err(RecvTimeoutError::Timeout) => {}
+      Err(RecvTimeoutError::Disconnected) => break,
+    }
+    fetched = Instant::now();
+    match fetch() {
+      Ok(next) => {
+        usage = sampler.sample(&next.frames.iter().map(|f| f.1).collect::<Vec<_>>());
+        ps = next;
+      }
+      Err(_) => {
+        print!("\x1b[H\x1b[2J");
+        println!("top: the build has finished");
+        return;
+      }
+    }
+  }
+  print!("\x1b[H\x1b[2J");
+}
@@ -0,0 +1,75 @@
+//! `--profile`: the run in Chrome trace-event format, for chrome://tracing or
+//! Perfetto. Each build slot is a track; frames are complete (`X`) events with
+//! their pipeline stages nested underneath.
+
+use std::time::{Duration, Instant};
+
+use crate::exec::StageTime;
+use crate::frames;
+use crate::json::Value;
+
+pub struct Trace {
+  t0: Instant,
+  events: Vec<Value>,
+}
+
+fn micros(d: Duration) -> Value {
+  Value::from(d.as_micros() as f64)
+}
+
+impl Trace {
+  /// `tracks` names each slot, in slot order.
+  pub fn new(t0: Instant, tracks: &[String]) -> Self {
+    let events = tracks
+      .iter()
+      .enumerate()
+      .map(|(tid, name)| {
+        Value::obj([
+          ("name", Value::from("thread_name")),
+          ("ph", Value::from("M")),
+          ("pid", Value::from(1usize)),
+          ("tid", Value::from(tid)),
+          ("args", Value::obj([("name", Value::from(name.as_str()))])),
+        ])
+      })
+      .collect();
+    Trace { t0, events }
+  }
+
+  fn complete(&mut self, name: String, cat: &str, slot: usize, at: Instant, dur: Duration, ok: bool) {
+    self.events.push(Value::obj([
+      ("name", Value::from(name)),
+      ("cat", Value::from(cat)),
+      ("ph", Value::from("X")),
+      ("ts", micros(at.saturating_duration_since(self.t0))),
+      ("dur", micros(dur)),
+      ("pid", Value::from(1usize)),
+      ("tid", Value::from(slot)),
+      ("args", Value::obj([("ok", Value::from(ok))])),
+    ]));
+  }
+
+  /// One attempt at frame `n` on `slot`. Stages ran back to back from `started`.
+  pub fn frame(&mut self, slot: usize, n: usize, started: Instant, dur: Duration, ok: bool, stages: &[StageTime]) {
+    self.complete(frames::label(n), "frame", slot, started, dur, ok);
+    if stages.len() > 1 {
+      let mut at = started;
+      for st in stages {
+        self.complete(st.script.name().to_string(), "stage", slot, at, st.dur, st.ok);
+        at += st.dur;
+      }
+    }
+  }
+
+  pub fn write(self, path: &str) {
+    let count = self.events.len();
+    let doc = Value::obj([
+      ("traceEvents", Value::Arr(self.events)),
+      ("displayTimeUnit", Value::from("ms")),
+    ]);
+    match std::fs::write(path, doc.compact()) {
+      Ok(()) => eprintln!("profile: wrote {path} ({count} events)"),
+      Err(e) => eprintln!("warning: could not write profile {path}: {e}"),
+    }
+  }
+}
@@ -0,0 +1,228 @@
+//! `typereport`: TypeScript diagnostics from every frame, merged by error
+//! code and file. A template mistake repeats in all frames; it comes out as
+//! one line with the frames it affects rather than thousands of identical
+//! errors. Runs `tsc --noEmit` per frame, or with `--from-logs=1` reads the
+//! logs failed builds and typecheck stages left in `.framectl/logs`.
+
+use std::collections::BTreeMap;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use crate::drift::normalize;
+use crate::exec::{self, Script};
+use crate::frames::{self, fmt_ranges};
+use crate::queue::par_each;
+use crate::{default_concurrency, parse_bool, parse_kv};
+
+/// One diagnostic, frame number normalized out.
+pub struct Diag {
+  pub code: String,
+  pub file: String,
+  /// `line:col`.
+  pub at: String,
+  pub message: String,
+}
+
+/// `file(12,5): error TS2322: msg` (`--pretty false`) or
+/// `file:12:5 - error TS2322: msg` (pretty, colors stripped).
+pub fn parse_line(line: &str) -> Option<Diag> {
+  let line = strip_ansi(line);
+  let (head, rest) = line.split_once(": error TS").or_else(|| line.split_once(" - error TS"))?;
+  let (code, message) = rest.split_once(':')?;
+  if code.is_empty() || !code.bytes().all(|b| b.is_ascii_digit()) {
+    return None;
+  }
+  let (file, at) = match head.strip_suffix(')').and_then(|h| h.rsplit_once('(')) {
+    Some((file, pos)) => (file, pos.replace(',', ":")),
+    None => {
+      let mut parts = head.rsplitn(3, ':');
+      match (parts.next(), parts.next(), parts.next()) {
+        (Some(col), Some(ln), Some(file)) if col.parse::<u32>().is_ok() && ln.parse::<u32>().is_ok() => (file, format!("{ln}:{col}")),
+        _ => (head, String::new()),
+      }
+    }
+  };
+  Some(Diag {
+    code: format!("TS{code}"),
+    file: file.trim().to_string(),
+    at,
+    message: message.trim().to_string(),
+  })
+}
+
+pub fn strip_ansi(s: &str) -> String {
+  let mut out = String::with_capacity(s.len());
+  let mut chars = s.chars();
+  while let Some(c) = chars.next() {
+    if c == '\x1b' {
+      // CSI: ESC [ params final-byte.
+      for c in chars.by_ref() {
+        if c.is_ascii_alphabetic() {
+          break;
+        }
+      }
+      continue;
+    }
+    out.push(c);
+  }
+  out
+}
+
+fn parse(text: &str, n: usize) -> Vec<Diag> {
+  text
+    .lines()
+    .filter_map(parse_line)
+    .map(|d| Diag {
+      file: normalize(&d.file, n),
+      message: normalize(&d.message, n),
+      ..d
+    })
+    .collect()
+}
+
+/// `tsc --noEmit` in frame `n`; its diagnostics, or why there are none.
+fn check_frame(n: usize) -> Result<Vec<Diag>, String> {
+  let out = Command::new("pnpm")
+    .args(["exec", "tsc", "--noEmit", "--pretty", "false"])
+    .current_dir(frames::frame_dir(n))
+    .stdin(Stdio::null())
+    .output()
+    .map_err(|e| format!("spawn failed: {e}"))?;
+  let text = String::from_utf8_lossy(&out.stdout).into_owned() + &String::from_utf8_lossy(&out.stderr);
+  let diags = parse(&text, n);
+  if !out.status.success() && diags.is_empty() {
+    let tail = exec::tail(&text, 300);
+    return Err(tail.lines().last().unwrap_or("tsc failed without diagnostics").trim().to_string());
+  }
+  Ok(diags)
+}
+
+/// What frame `n`'s build and typecheck logs say, if any are there.
+fn from_logs(n: usize) -> Option<Vec<Diag>> {
+  let texts: Vec<String> = [Script::Typecheck, Script::Build]
+    .iter()
+    .filter_map(|&s| std::fs::read_to_string(exec::log_path(n, s)).ok())
+    .collect();
+  (!texts.is_empty()).then(|| texts.iter().flat_map(|t| parse(t, n)).collect())
+}
+
+#[derive(Default)]
+struct Group {
+  frames: Vec<usize>,
+  /// Distinct message -> how many frames have it.
+  messages: BTreeMap<String, usize>,
+  at: String,
+}
+
+pub fn run(args: &[String]) {
+  let logs = parse_kv(args, "--from-logs").and_then(|v| parse_bool(&v)).unwrap_or(false);
+  let concurrency: usize = parse_kv(args, "--concurrency")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or_else(default_concurrency);
+  let found: Vec<usize> = frames::selected(args);
+  if found.is_empty() {
+    eprintln!("typereport: no frame-XXXX dirs found under apps/frames");
+    std::process::exit(2);
+  }
+  eprintln!(
+    "typereport: {} over {} frame(s){}",
+    if logs { "build logs" } else { "tsc --noEmit" },
+    found.len(),
+    if logs { String::new() } else { format!(" concurrency={concurrency}") }
+  );
+
+  let groups: Mutex<BTreeMap<(String, String), Group>> = Mutex::new(BTreeMap::new());
+  let broken: Mutex<Vec<(usize, String)>> = Mutex::new(Vec::new());
+  let mut read = 0usize;
+  let add = |n: usize, diags: Vec<Diag>| {
+    let mut groups = groups.lock().unwrap();
+    for d in diags {
+      let g = groups.entry((d.code, d.file)).or_default();
+      if g.frames.last() != Some(&n) {
+        g.frames.push(n);
+        *g.messages.entry(d.message).or_default() += 1;
+      }
+      if g.at.is_empty() {
+        g.at = d.at;
+      }
+    }
+  };
+  if logs {
+    for &n in &found {
+      if let Some(diags) = from_logs(n) {
+        read += 1;
+        add(n, diags);
+      }
+    }
+  } else {
+    par_each(&found, concurrency, |&n| match check_frame(n) {
+      Ok(diags) => add(n, diags),
+      Err(e) => broken.lock().unwrap().push((n, e)),
+    });
+    read = found.len();
+  }
+  let groups = groups.into_inner().unwrap();
+  let mut broken = broken.into_inner().unwrap();
+
+  // The most widespread first.
+  let mut merged: Vec<((String, String), Group)> = groups.into_iter().collect();
+  for (_, g) in &mut merged {
+    g.frames.sort_unstable();
+    g.frames.dedup();
+  }
+  merged.sort_by(|a, b| b.1.frames.len().cmp(&a.1.frames.len()).then_with(|| a.0.cmp(&b.0)));
+  for ((code, file), g) in &merged {
+    let (message, _) = g.messages.iter().max_by_key(|(_, c)| **c).map(|(m, c)| (m.as_str(), *c)).unwrap_or(("", 0));
+    let variants = if g.messages.len() > 1 { format!(" [+{} variant(s)]", g.messages.len() - 1) } else { String::new() };
+    let at = if g.at.is_empty() { String::new() } else { format!(":{}", g.at) };
+    eprintln!(
+      "  {code}  {file}{at}  {message}{variants}  ({} frame(s): {})",
+      g.frames.len(),
+      fmt_ranges(&g.frames)
+    );
+  }
+
+  let mut by_code: BTreeMap<&str, (usize, Vec<usize>)> = BTreeMap::new();
+  for ((code, _), g) in &merged {
+    let e = by_code.entry(code).or_default();
+    e.0 += 1;
+    e.1.extend(&g.frames);
+  }
+  let mut affected: Vec<usize> = merged.iter().flat_map(|(_, g)| g.frames.iter().copied()).collect();
+  affected.sort_unstable();
+  affected.dedup();
+  if !by_code.is_empty() {
+    let codes: Vec<String> = by_code
+      .iter_mut()
+      .map(|(code, (files, frames))| {
+        frames.sort_unstable();
+        frames.dedup();
+        format!("{code} {} frame(s) in {files} file(s)", frames.len())
+      })
+      .collect();
+    eprintln!("by code: {}", codes.join(", "));
+  }
+  if !broken.is_empty() {
+    broken.sort();
+    let frames: Vec<usize> = broken.iter().map(|(n, _)| *n).collect();
+    eprintln!(
+      "typereport: {} frame(s) could not be checked: {} (frame-{:04}: {})",
+      frames.len(),
+      fmt_ranges(&frames),
+      broken[0].0,
+      broken[0].1
+    );
+  }
+  if logs && read < found.len() {
+    eprintln!("typereport: {} of {} frame(s) had logs", read, found.len());
+  }
+  let hits: usize = merged.iter().map(|(_, g)| g.frames.len()).sum();
+  eprintln!(
+    "typereport: {} unique diagnostic(s), {hits} hit(s) across {} frame(s)",
+    merged.len(),
+    affected.len()
+  );
+  if !merged.is_empty() || !broken.is_empty() {
+    std::process::exit(1);
+  }
+}
@@ -0,0 +1,211 @@
+//! `upgrade-deps`: rewrite one or more dependency ranges in every frame's
+//! package.json, and point out manifests that no longer look like the rest.
+
+use std::collections::BTreeMap;
+use std::process::Command;
+
+use crate::drift::{self, Template};
+use crate::frames::{self, fmt_ranges};
+use crate::json::{self, Value};
+use crate::{parse_bool, parse_kv};
+
+const SECTIONS: [&str; 4] = [
+  "dependencies",
+  "devDependencies",
+  "peerDependencies",
+  "optionalDependencies",
+];
+
+struct Spec {
+  name: String,
+  range: String,
+}
+
+/// `react@^18.3.0`, `@rsbuild/core@2.0.0`.
+fn parse_spec(s: &str) -> Option<Spec> {
+  let at = s.get(1..)?.rfind('@')? + 1;
+  let (name, range) = (&s[..at], &s[at + 1..]);
+  (!name.is_empty() && !range.is_empty()).then(|| Spec {
+    name: name.to_string(),
+    range: range.to_string(),
+  })
+}
+
+/// Set `spec` wherever the package already lists it. Returns (present, changed).
+fn apply(doc: &mut Value, spec: &Spec) -> (bool, bool) {
+  let (mut present, mut changed) = (false, false);
+  for section in SECTIONS {
+    let Some(Value::Obj(deps)) = doc.get_mut(section) else {
+      continue;
+    };
+    if let Some((_, v)) = deps.iter_mut().find(|(k, _)| *k == spec.name) {
+      present = true;
+      if v.as_str() != Some(spec.range.as_str()) {
+        *v = Value::from(spec.range.as_str());
+        changed = true;
+      }
+    }
+  }
+  (present, changed)
+}
+
+pub fn run(args: &[String]) {
+  let specs: Vec<Spec> = args
+    .iter()
+    .filter(|a| !a.starts_with("--"))
+    .map(|a| {
+      parse_spec(a).unwrap_or_else(|| {
+        eprintln!("upgrade-deps: expected NAME@RANGE, got {a:?}");
+        std::process::exit(2);
+      })
+    })
+    .collect();
+  if specs.is_empty() {
+    eprintln!("usage: framectl upgrade-deps NAME@RANGE... [--range=A-B] [--install=0|1] [--dry-run=0|1]");
+    std::process::exit(2);
+  }
+  let dry_run: bool = parse_kv(args, "--dry-run")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(false);
+  let install: bool = parse_kv(args, "--install")
+    .and_then(|v| parse_bool(&v))
+    .unwrap_or(false);
+  let (start, end) = match parse_kv(args, "--range") {
+    Some(r) => frames::parse_range(&r).unwrap_or_else(|| {
+      eprintln!("upgrade-deps: bad --range {r:?} (expected A-B)");
+      std::process::exit(2);
+    }),
+    None => (
+      parse_kv(args, "--start").and_then(|v| v.parse().ok()).unwrap_or(1),
+      parse_kv(args, "--end").and_then(|v| v.parse().ok()).unwrap_or(usize::MAX),
+    ),
+  };
+
+  let mut docs: BTreeMap<usize, Value> = BTreeMap::new();
+  let mut unreadable = 0usize;
+  let mut updated: Vec<Vec<usize>> = specs.iter().map(|_| Vec::new()).collect();
+  let mut absent: Vec<Vec<usize>> = specs.iter().map(|_| Vec::new()).collect();
+  let mut written = 0usize;
+  for n in frames::discover(&frames::frames_dir()) {
+    if !(start..=end).contains(&n) {
+      continue;
+    }
+    let path = frames::frame_dir(n).join("package.json");
+    let mut doc = match json::read(&path) {
+      Ok(d) => d,
+      Err(e) => {
+        eprintln!("upgrade-deps: {e}");
+        unreadable += 1;
+        continue;
+      }
+    };
+    let mut changed = false;
+    for (i, spec) in specs.iter().enumerate() {
+      match apply(&mut doc, spec) {
+        (false, _) => absent[i].push(n),
+        (true, true) => {
+          updated[i].push(n);
+          changed = true;
+        }
+        (true, false) => {}
+      }
+    }
+    if changed && !dry_run {
+      let tmp = path.with_extension("json.tmp");
+      let res = std::fs::write(&tmp, doc.pretty()).and_then(|_| std::fs::rename(&tmp, &path));
+      if let Err(e) = res {
+        eprintln!("upgrade-deps: {}: {e}", path.display());
+        unreadable += 1;
+        continue;
+      }
+      written += 1;
+    }
+    docs.insert(n, doc);
+  }
+  if docs.is_empty() {
+    eprintln!("upgrade-deps: no frame manifests in range {start}-{end}");
+    std::process::exit(if unreadable > 0 { 1 } else { 2 });
+  }
+
+  for (i, spec) in specs.iter().enumerate() {
+    let mut line = format!(
+      "upgrade-deps: {}@{}: {} {}",
+      spec.name,
+      spec.range,
+      if dry_run { "would update" } else { "updated" },
+      updated[i].len()
+    );
+    if !absent[i].is_empty() {
+      line.push_str(&format!(", not a dependency of {}: {}", absent[i].len(), fmt_ranges(&absent[i])));
+    }
+    eprintln!("{line}");
+  }
+  if !dry_run {
+    eprintln!("upgrade-deps: wrote {written} manifest(s)");
+  }
+
+  // Compared against --template (with the same bumps applied) where given,
+  // field by field against the majority otherwise.
+  let mut template = Template::default();
+  if let Some(p) = parse_kv(args, "--template") {
+    match json::read(p.as_ref()) {
+      Ok(mut t) => {
+        for spec in &specs {
+          apply(&mut t, spec);
+        }
+        template.fields = drift::manifest_fields(&t, 0);
+        template.files.push("package.json");
+        template.per_frame.push("package.json:name".to_string());
+      }
+      Err(e) => {
+        eprintln!("upgrade-deps: template {e}");
+        std::process::exit(2);
+      }
+    }
+  }
+  let fields = docs
+    .iter()
+    .map(|(n, d)| (*n, drift::manifest_fields(d, *n)))
+    .collect();
+  drift::report(&fields, &template);
+
+  if install && !dry_run && written > 0 {
+    eprintln!("upgrade-deps: pnpm install");
+    match Command::new("pnpm").arg("install").status() {
+      Ok(s) if s.success() => {}
+      Ok(s) => {
+        eprintln!("upgrade-deps: pnpm install failed ({s})");
+        std::process::exit(1);
+      }
+      Err(e) => {
+        eprintln!("upgrade-deps: pnpm install: {e}");
+        std::process::exit(1);
+      }
+    }
+  }
+  if unreadable > 0 {
+    std::process::exit(1);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn spec(s: &str) -> Option<(String, String)> {
+    parse_spec(s).map(|s| (s.name, s.range))
+  }
+
+  #[test]
+  fn specs() {
+    assert_eq!(spec("react@^18.3.0"), Some(("react".into(), "^18.3.0".into())));
+    assert_eq!(spec("@rsbuild/core@2.0.0"), Some(("@rsbuild/core".into(), "2.0.0".into())));
+  }
+
+  #[test]
+  fn bad_specs() {
+    for s in ["", "react", "react@", "@rsbuild/core", "@scope@", "@1.0.0"] {
+      assert_eq!(spec(s), None, "{s}");
+    }
+  }
+}
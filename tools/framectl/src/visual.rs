@@ -0,0 +1,297 @@
+//! `visual-check`: renders a sample of built frames in headless Chrome and
+//! compares each screenshot with its source image, to catch frames that
+//! built fine but render blank or wrong.
+//!
+//! Frame dists are served over a throwaway local HTTP server; each frame is
+//! loaded the way the host does it (remote entry, container, `./Frame`).
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::frames;
+use crate::png::{self, Image};
+use crate::{parse_kv, state_dir, targets};
+
+pub const BROWSERS: [&str; 4] = ["chromium", "chromium-browser", "google-chrome", "google-chrome-stable"];
+/// Source and render are both thresholded at mid-gray before comparing.
+const MID: u32 = 128;
+
+fn content_type(path: &Path) -> &'static str {
+  match path.extension().and_then(|e| e.to_str()).unwrap_or("") {
+    "html" => "text/html",
+    "js" | "mjs" => "text/javascript",
+    "css" => "text/css",
+    "json" | "map" => "application/json",
+    "png" => "image/png",
+    "svg" => "image/svg+xml",
+    _ => "application/octet-stream",
+  }
+}
+
+/// Page that mounts frame `n` into a `w`x`h` viewport.
+fn harness(n: usize, w: usize, h: usize) -> String {
+  format!(
+    r#"<!doctype html>
+<html><head><style>
+  html, body {{ margin: 0; background: #fff; overflow: hidden; }}
+  :root {{ --frame-width: {w}px; --frame-height: {h}px; }}
+</style></head>
+<body><div id="root"></div>
+<script>
+  const s = document.createElement('script');
+  s.src = '/frame-{n:04}/{entry}';
+  s.onload = async () => {{
+    const c = window['frame_{n:04}'];
+    const init = globalThis.__webpack_init_sharing__ ?? globalThis.__rspack_init_sharing__;
+    const scopes = globalThis.__webpack_share_scopes__ ?? globalThis.__rspack_share_scopes__;
+    if (typeof init === 'function' && scopes?.default) {{
+      await init('default');
+      await c.init(scopes.default);
+    }}
+    const mod = (await c.get('./Frame'))();
+    mod.mount(document.getElementById('root'));
+  }};
+  document.head.appendChild(s);
+</script></body></html>
+"#,
+    entry = frames::ENTRY
+  )
+}
+
+/// GET `/check/N` is the harness; `/frame-XXXX/...` maps into that frame's dist.
+fn serve(stream: TcpStream, dims: (usize, usize)) -> std::io::Result<()> {
+  let mut reader = BufReader::new(stream.try_clone()?);
+  let mut line = String::new();
+  reader.read_line(&mut line)?;
+  let path = line.split_whitespace().nth(1).unwrap_or("/").split('?').next().unwrap_or("/").to_string();
+  let (status, ctype, body) = if let Some(n) = path.strip_prefix("/check/").and_then(|v| v.parse().ok()) {
+    ("200 OK", "text/html", harness(n, dims.0, dims.1).into_bytes())
+  } else {
+    let rel = path.trim_start_matches('/');
+    let mut parts = rel.splitn(2, '/');
+    let file = match (parts.next(), parts.next()) {
+      (Some(dir), Some(rest)) if !rest.contains("..") => dir
+        .strip_prefix("frame-")
+        .and_then(|id| id.parse::<usize>().ok())
+        .map(|n| frames::dist_dir(n).join(rest)),
+      _ => None,
+    };
+    match file.and_then(|f| std::fs::read(&f).ok().map(|b| (f, b))) {
+      Some((f, b)) => ("200 OK", content_type(&f), b),
+      None => ("404 Not Found", "text/plain", b"not found".to_vec()),
+    }
+  };
+  let mut w = stream;
+  write!(
+    w,
+    "HTTP/1.0 {status}\r\nContent-Type: {ctype}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+    body.len()
+  )?;
+  w.write_all(&body)
+}
+
+pub fn find_browser(explicit: Option<String>) -> Option<String> {
+  let candidates: Vec<String> = match explicit.or_else(|| std::env::var("CHROME").ok()) {
+    Some(b) => vec![b],
+    None => BROWSERS.iter().map(|b| b.to_string()).collect(),
+  };
+  candidates.into_iter().find(|b| {
+    Command::new(b)
+      .arg("--version")
+      .stdout(Stdio::null())
+      .stderr(Stdio::null())
+      .status()
+      .is_ok()
+  })
+}
+
+/// Serve the harness and frame dists on a loopback port for the rest of the
+/// process; returns the port.
+pub fn start_server(dims: (usize, usize)) -> std::io::Result<u16> {
+  let listener = TcpListener::bind("127.0.0.1:0")?;
+  let port = listener.local_addr()?.port();
+  thread::spawn(move || {
+    for stream in listener.incoming().flatten() {
+      thread::spawn(move || {
+        let _ = serve(stream, dims);
+      });
+    }
+  });
+  Ok(port)
+}
+
+/// Render `url` at `dims` into the PNG at `out`.
+pub fn capture(browser: &str, url: &str, dims: (usize, usize), out: &Path) -> Result<(), String> {
+  let res = Command::new(browser)
+    .args(["--headless=new", "--disable-gpu", "--hide-scrollbars", "--no-first-run"])
+    .arg("--force-device-scale-factor=1")
+    .arg(format!("--window-size={},{}", dims.0, dims.1))
+    .arg("--virtual-time-budget=5000")
+    .arg(format!("--screenshot={}", out.display()))
+    .arg(url)
+    .stdin(Stdio::null())
+    .stdout(Stdio::null())
+    .stderr(Stdio::piped())
+    .output()
+    .map_err(|e| format!("{browser}: {e}"))?;
+  if !out.is_file() {
+    let err = String::from_utf8_lossy(&res.stderr);
+    return Err(format!("no screenshot ({})", err.lines().last().unwrap_or("no output").trim()));
+  }
+  Ok(())
+}
+
+fn screenshot(browser: &str, url: &str, dims: (usize, usize), out: &Path) -> Result<Image, String> {
+  capture(browser, url, dims, out)?;
+  png::decode_file(out)
+}
+
+fn dark(img: &Image, x: usize, y: usize) -> bool {
+  let p = (y * img.width + x) * 3;
+  let luma = (img.rgb[p] as u32 * 299 + img.rgb[p + 1] as u32 * 587 + img.rgb[p + 2] as u32 * 114) / 1000;
+  luma < MID
+}
+
+/// Fraction of source pixels whose thresholded value differs in the render,
+/// sampling the render at the same relative position.
+fn mismatch(source: &Image, render: &Image) -> f64 {
+  let total = source.width * source.height;
+  if total == 0 || render.width == 0 || render.height == 0 {
+    return 1.0;
+  }
+  let mut differ = 0usize;
+  for y in 0..source.height {
+    let ry = y * render.height / source.height;
+    for x in 0..source.width {
+      let rx = x * render.width / source.width;
+      if dark(source, x, y) != dark(render, rx, ry) {
+        differ += 1;
+      }
+    }
+  }
+  differ as f64 / total as f64
+}
+
+fn uniform(img: &Image) -> bool {
+  img.rgb.chunks_exact(3).all(|p| p == &img.rgb[..3])
+}
+
+/// `count` frames spread evenly over `built`, first and last included.
+pub fn sample(built: &[usize], count: usize) -> Vec<usize> {
+  if count >= built.len() {
+    return built.to_vec();
+  }
+  if count <= 1 {
+    return built[..count].to_vec();
+  }
+  let mut out: Vec<usize> = (0..count).map(|i| built[i * (built.len() - 1) / (count - 1)]).collect();
+  out.dedup();
+  out
+}
+
+pub fn run(args: &[String]) {
+  let src_dir = parse_kv(args, "--frames-dir")
+    .map(PathBuf::from)
+    .unwrap_or_else(frames::sources_dir);
+  let count: usize = parse_kv(args, "--sample")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(20);
+  let threshold: f64 = parse_kv(args, "--threshold")
+    .and_then(|v| v.parse().ok())
+    .unwrap_or(0.02);
+  let out_dir = parse_kv(args, "--out")
+    .map(PathBuf::from)
+    .unwrap_or_else(|| state_dir().join("visual"));
+  let (start, end) = frames::range_args(args);
+
+  let Some(browser) = find_browser(parse_kv(args, "--browser")) else {
+    eprintln!("visual-check: no headless Chrome found (tried {}; set --browser or CHROME)", BROWSERS.join(", "));
+    std::process::exit(2);
+  };
+
+  targets::dists("visual-check", args);
+  let built: Vec<usize> = frames::selected(args)
+    .into_iter()
+    .filter(|&n| frames::entry_path(n).is_file())
+    .filter(|&n| frames::source_image(&src_dir, n).is_some())
+    .collect();
+  if built.is_empty() {
+    eprintln!(
+      "visual-check: no built frames with a source image in {} (start={start} end={end})",
+      src_dir.display()
+    );
+    std::process::exit(2);
+  }
+  let picked = sample(&built, count);
+
+  let first = frames::source_image(&src_dir, picked[0]).and_then(|p| png::decode_file(&p).ok());
+  let Some(dims) = first.map(|img| (img.width, img.height)) else {
+    eprintln!("visual-check: cannot decode the source image of frame {}", picked[0]);
+    std::process::exit(1);
+  };
+  if let Err(e) = std::fs::create_dir_all(&out_dir) {
+    eprintln!("visual-check: {}: {e}", out_dir.display());
+    std::process::exit(1);
+  }
+
+  let port = match start_server(dims) {
+    Ok(p) => p,
+    Err(e) => {
+      eprintln!("visual-check: cannot listen: {e}");
+      std::process::exit(1);
+    }
+  };
+  eprintln!(
+    "visual-check: {} of {} frame(s) with {browser} at {}x{} threshold={threshold}",
+    picked.len(),
+    built.len(),
+    dims.0,
+    dims.1
+  );
+
+  let mut bad: Vec<usize> = Vec::new();
+  for &n in &picked {
+    let shot = out_dir.join(format!("frame-{n:04}.png"));
+    let _ = std::fs::remove_file(&shot);
+    let url = format!("http://127.0.0.1:{port}/check/{n}");
+    let verdict = frames::source_image(&src_dir, n)
+      .ok_or_else(|| "no source image".to_string())
+      .and_then(|p| png::decode_file(&p))
+      .and_then(|source| screenshot(&browser, &url, dims, &shot).map(|render| (source, render)))
+      .and_then(|(source, render)| {
+        if uniform(&render) && !uniform(&source) {
+          return Err("renders blank".to_string());
+        }
+        let diff = mismatch(&source, &render);
+        if diff > threshold {
+          Err(format!("{:.1}% of pixels differ", diff * 100.0))
+        } else {
+          Ok(diff)
+        }
+      });
+    match verdict {
+      Ok(_) => {
+        let _ = std::fs::remove_file(&shot);
+      }
+      Err(e) => {
+        eprintln!("  frame-{n:04}: {e} (screenshot: {})", shot.display());
+        bad.push(n);
+      }
+    }
+  }
+
+  if bad.is_empty() {
+    eprintln!("visual-check: {} frame(s) match their source", picked.len());
+    return;
+  }
+  eprintln!(
+    "visual-check: {} of {} frame(s) mismatch: {}",
+    bad.len(),
+    picked.len(),
+    frames::fmt_ranges(&bad)
+  );
+  std::process::exit(1);
+}
@@ -0,0 +1,179 @@
+//! Warning counts for a run: every build's captured output (stdout and
+//! stderr, also with `--silent`) is scanned for warning lines, bucketed by
+//! category, and totalled at the end so warning creep across the fleet shows
+//! up before `--strict-stderr` is worth turning on. `--warnings-out` keeps
+//! the counts as JSON for tracking them from run to run.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use crate::exec::Script;
+use crate::frames::fmt_ranges;
+use crate::json::Value;
+use crate::pattern::Regex;
+
+/// Checked in order; a line counts once, for the first that matches.
+const CATEGORIES: [(&str, &str); 5] = [
+  ("performance", r"(?i)size limit|exceeds? the recommended|performance recommendations"),
+  ("deprecation", r"(?i)deprecat"),
+  ("peer-dependency", r"(?i)peer dep|unmet peer|incorrect peer|missing peer"),
+  ("module", r"(?i)critical dependency|export [^\n]{1,200} was not found|module warning"),
+  ("other", r"(?i)\bwarn(ing)?\b"),
+];
+
+/// `webpack compiled with 3 warnings` and the like count nothing new.
+const TALLY: &str = r"(?i)\b\d+ warnings?\b";
+
+/// Characters of a line looked at: warnings say what they are up front,
+/// and minified bundles or code frames can put a megabyte on one line.
+const MAX_LINE: usize = 2000;
+
+/// A category's count in one frame's stage, and its first line.
+type Hits = BTreeMap<&'static str, (usize, String)>;
+
+/// Per frame and stage, so a retried build replaces its earlier count.
+static SEEN: Mutex<BTreeMap<(usize, &'static str), Hits>> = Mutex::new(BTreeMap::new());
+
+fn patterns() -> &'static (Vec<(&'static str, Regex)>, Regex) {
+  static PATTERNS: OnceLock<(Vec<(&'static str, Regex)>, Regex)> = OnceLock::new();
+  PATTERNS.get_or_init(|| {
+    let cats = CATEGORIES
+      .iter()
+      .map(|&(name, src)| (name, Regex::new(src).expect("built-in warning pattern")))
+      .collect();
+    (cats, Regex::new(TALLY).expect("built-in warning pattern"))
+  })
+}
+
+fn head(line: &str) -> &str {
+  line.char_indices().nth(MAX_LINE).map_or(line, |(i, _)| &line[..i])
+}
+
+/// Count the warnings in frame `n`'s `script` output.
+pub fn scan(n: usize, script: Script, output: &str) {
+  let (cats, tally) = patterns();
+  let mut hits = Hits::new();
+  for line in output.lines().map(head).filter(|l| !tally.is_match(l)) {
+    if let Some((name, _)) = cats.iter().find(|(_, p)| p.is_match(line)) {
+      let e = hits.entry(name).or_insert_with(|| (0, line.trim().to_string()));
+      e.0 += 1;
+    }
+  }
+  let mut seen = SEEN.lock().unwrap();
+  if hits.is_empty() {
+    seen.remove(&(n, script.name()));
+  } else {
+    seen.insert((n, script.name()), hits);
+  }
+}
+
+pub struct Category {
+  pub count: usize,
+  pub frames: Vec<usize>,
+  /// The first line counted.
+  pub sample: String,
+}
+
+/// The run's counts so far, by category.
+pub fn totals() -> BTreeMap<&'static str, Category> {
+  let mut out: BTreeMap<&'static str, Category> = BTreeMap::new();
+  for (&(n, _), hits) in SEEN.lock().unwrap().iter() {
+    for (&name, (count, sample)) in hits {
+      let c = out.entry(name).or_insert_with(|| Category {
+        count: 0,
+        frames: Vec::new(),
+        sample: sample.clone(),
+      });
+      c.count += count;
+      if c.frames.last() != Some(&n) {
+        c.frames.push(n);
+      }
+    }
+  }
+  out
+}
+
+/// The `warnings:` lines at the end of a run, and `--warnings-out`.
+pub fn report(out: Option<&str>) {
+  let cats = totals();
+  let total: usize = cats.values().map(|c| c.count).sum();
+  let mut frames: Vec<usize> = cats.values().flat_map(|c| c.frames.iter().copied()).collect();
+  frames.sort_unstable();
+  frames.dedup();
+  if total > 0 {
+    let mut by_count: Vec<(&&str, &Category)> = cats.iter().collect();
+    by_count.sort_by_key(|c| std::cmp::Reverse(c.1.count));
+    let parts: Vec<String> = by_count
+      .iter()
+      .map(|(name, c)| format!("{name} {} in {} frame(s)", c.count, c.frames.len()))
+      .collect();
+    eprintln!("warnings: {total} in {} frame(s): {}", frames.len(), parts.join(", "));
+    for (name, c) in &by_count {
+      eprintln!("  {name}: {} (frames {})", c.sample, fmt_ranges(&c.frames));
+    }
+  }
+  let Some(path) = out else {
+    return;
+  };
+  let doc = Value::obj([
+    ("total", Value::Num(total as f64)),
+    ("frames", Value::Num(frames.len() as f64)),
+    (
+      "categories",
+      Value::obj(CATEGORIES.iter().map(|&(name, _)| {
+        let c = cats.get(name);
+        let fields = [
+          ("count", Value::Num(c.map_or(0, |c| c.count) as f64)),
+          ("frames", Value::Num(c.map_or(0, |c| c.frames.len()) as f64)),
+          ("ranges", Value::Str(c.map(|c| fmt_ranges(&c.frames)).unwrap_or_default())),
+          ("sample", c.map_or(Value::Null, |c| Value::Str(c.sample.clone()))),
+        ];
+        (name, Value::obj(fields))
+      })),
+    ),
+  ]);
+  let path = Path::new(path);
+  if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+    let _ = std::fs::create_dir_all(dir);
+  }
+  if let Err(e) = std::fs::write(path, doc.pretty()) {
+    eprintln!("warning: could not write {}: {e}", path.display());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn counts(n: usize, output: &str) -> Vec<(&'static str, usize)> {
+    scan(n, Script::Build, output);
+    let seen = SEEN.lock().unwrap();
+    seen.get(&(n, Script::Build.name())).map(|h| h.iter().map(|(&k, v)| (k, v.0)).collect()).unwrap_or_default()
+  }
+
+  #[test]
+  fn categories() {
+    let output = "\
+WARNING in ./src/index.ts
+export 'Frame' (imported as 'Frame') was not found in './frame'
+(node:12) DeprecationWarning: Buffer() is deprecated
+ WARN  Issues with peer dependencies found
+warning: something else
+webpack compiled with 3 warnings
+";
+    assert_eq!(counts(9001, output), [("deprecation", 1), ("module", 1), ("other", 2), ("peer-dependency", 1)]);
+    assert!(counts(9001, "all good\n").is_empty());
+    assert!(!SEEN.lock().unwrap().contains_key(&(9001, Script::Build.name())));
+  }
+
+  #[test]
+  fn long_lines() {
+    let minified = format!("export {} was not found", "x".repeat(1_000_000));
+    assert_eq!(counts(9002, &minified), []);
+    let code_frame = format!("warning: {}", "y".repeat(1_000_000));
+    assert_eq!(counts(9003, &code_frame), [("other", 1)]);
+    let sample = &SEEN.lock().unwrap()[&(9003, Script::Build.name())]["other"].1;
+    assert_eq!(sample.chars().count(), MAX_LINE);
+  }
+}
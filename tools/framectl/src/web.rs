@@ -0,0 +1,189 @@
+//! `--web`: a small dashboard served from the running build, fed by a
+//! server-sent event stream.
+//!
+//! ```text
+//! GET /         the dashboard page
+//! GET /status   latest progress event, as JSON
+//! GET /events   text/event-stream: the latest progress, then every event
+//! ```
+//!
+//! Events are JSON objects with a `type` of `progress`, `failure` or `done`.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::json::Value;
+
+const EVERY: Duration = Duration::from_millis(500);
+/// Comment line sent to idle streams so proxies keep them open.
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Default)]
+struct Shared {
+  latest: String,
+  clients: Vec<Sender<String>>,
+}
+
+pub struct Web {
+  shared: Arc<Mutex<Shared>>,
+  last: Option<Instant>,
+}
+
+impl Web {
+  pub fn start(listen: &str) -> std::io::Result<Web> {
+    let listener = TcpListener::bind(listen)?;
+    eprintln!("web: dashboard on http://{}/", listener.local_addr()?);
+    let shared = Arc::new(Mutex::new(Shared::default()));
+    let for_server = Arc::clone(&shared);
+    thread::spawn(move || {
+      for stream in listener.incoming().flatten() {
+        let shared = Arc::clone(&for_server);
+        thread::spawn(move || {
+          let _ = handle(stream, &shared);
+        });
+      }
+    });
+    Ok(Web { shared, last: None })
+  }
+
+  /// Progress is sent at most every half second.
+  pub fn due(&self) -> bool {
+    self.last.is_none_or(|t| t.elapsed() >= EVERY)
+  }
+
+  pub fn progress(&mut self, fields: Vec<(&'static str, Value)>) {
+    self.last = Some(Instant::now());
+    self.latest("progress", fields);
+  }
+
+  /// Final progress; it stays the latest event for /status.
+  pub fn finish(&mut self, fields: Vec<(&'static str, Value)>) {
+    self.latest("done", fields);
+  }
+
+  fn latest(&self, kind: &str, fields: Vec<(&'static str, Value)>) {
+    let doc = with_type(kind, fields).compact();
+    let mut sh = self.shared.lock().unwrap();
+    sh.latest = doc.clone();
+    sh.clients.retain(|c| c.send(doc.clone()).is_ok());
+  }
+
+  pub fn event(&self, kind: &str, fields: Vec<(&'static str, Value)>) {
+    let doc = with_type(kind, fields).compact();
+    let mut sh = self.shared.lock().unwrap();
+    sh.clients.retain(|c| c.send(doc.clone()).is_ok());
+  }
+}
+
+fn with_type(kind: &str, fields: Vec<(&'static str, Value)>) -> Value {
+  let mut all = vec![("type", Value::from(kind))];
+  all.extend(fields);
+  Value::obj(all)
+}
+
+fn handle(stream: TcpStream, shared: &Mutex<Shared>) -> std::io::Result<()> {
+  let mut reader = BufReader::new(stream.try_clone()?);
+  let mut line = String::new();
+  reader.read_line(&mut line)?;
+  let path = line.split_whitespace().nth(1).unwrap_or("/");
+  let mut w = stream;
+  match path {
+    "/" | "/index.html" => respond(&mut w, "200 OK", "text/html; charset=utf-8", PAGE),
+    "/status" => {
+      let latest = shared.lock().unwrap().latest.clone();
+      respond(&mut w, "200 OK", "application/json", if latest.is_empty() { "{}" } else { &latest })
+    }
+    "/events" => {
+      let (tx, rx) = mpsc::channel();
+      let latest = {
+        let mut sh = shared.lock().unwrap();
+        sh.clients.push(tx);
+        sh.latest.clone()
+      };
+      write!(
+        w,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n"
+      )?;
+      if !latest.is_empty() {
+        write!(w, "data: {latest}\n\n")?;
+      }
+      w.flush()?;
+      stream_events(&mut w, rx)
+    }
+    _ => respond(&mut w, "404 Not Found", "text/plain", "not found"),
+  }
+}
+
+fn stream_events(w: &mut TcpStream, rx: Receiver<String>) -> std::io::Result<()> {
+  loop {
+    match rx.recv_timeout(KEEPALIVE) {
+      Ok(doc) => write!(w, "data: {doc}\n\n")?,
+      Err(mpsc::RecvTimeoutError::Timeout) => write!(w, ": keepalive\n\n")?,
+      Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+    }
+    w.flush()?;
+  }
+}
+
+fn respond(w: &mut TcpStream, status: &str, ctype: &str, body: &str) -> std::io::Result<()> {
+  write!(
+    w,
+    "HTTP/1.1 {status}\r\nContent-Type: {ctype}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+    body.len()
+  )
+}
+
+const PAGE: &str = r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>framectl</title>
+<style>
+  body { font: 14px/1.4 system-ui, sans-serif; margin: 2rem; color: #222; }
+  h1 { font-size: 1.2rem; }
+  .bar { height: 1rem; background: #eee; border-radius: 4px; overflow: hidden; max-width: 40rem; }
+  .bar > div { height: 100%; background: #3a7; width: 0; }
+  table { border-collapse: collapse; margin-top: 1rem; }
+  td, th { padding: 2px 12px 2px 0; text-align: left; font-variant-numeric: tabular-nums; }
+  .failed { color: #b33; }
+  #failures { font-family: ui-monospace, monospace; white-space: pre; }
+</style>
+</head>
+<body>
+<h1>framectl <span id="state">connecting</span></h1>
+<div class="bar"><div id="bar"></div></div>
+<p id="summary"></p>
+<table>
+  <thead><tr><th>slot</th><th>done</th><th>failed</th><th>last frame</th></tr></thead>
+  <tbody id="workers"></tbody>
+</table>
+<p>in flight: <span id="inflight"></span></p>
+<h2>Failures</h2>
+<div id="failures"></div>
+<script>
+  const $ = (id) => document.getElementById(id);
+  const fmt = (s) => s == null ? '?' : s >= 3600 ? `${(s / 3600).toFixed(1)}h` : s >= 60 ? `${Math.round(s / 60)}m` : `${Math.round(s)}s`;
+  const es = new EventSource('/events');
+  es.onmessage = (m) => {
+    const e = JSON.parse(m.data);
+    if (e.type === 'progress' || e.type === 'done') {
+      $('state').textContent = e.state;
+      $('bar').style.width = `${(100 * e.done / Math.max(e.total, 1)).toFixed(1)}%`;
+      $('summary').textContent =
+        `${e.done}/${e.total} done, ${e.ok} ok, ${e.failed} failed, ${e.rate.toFixed(2)}/s, elapsed ${fmt(e.elapsed_secs)}, eta ${fmt(e.eta_secs)}`;
+      $('inflight').textContent = (e.in_flight || []).join(', ') || 'none';
+      $('workers').innerHTML = (e.workers || []).map((w) =>
+        `<tr><td>${w.name}</td><td>${w.done}</td><td class="${w.failed ? 'failed' : ''}">${w.failed}</td><td>${w.last ?? ''}</td></tr>`).join('');
+    } else if (e.type === 'failure') {
+      $('failures').textContent += `frame-${String(e.frame).padStart(4, '0')} ${e.class}${e.stage ? ` at ${e.stage}` : ''}\n`;
+    }
+  };
+  es.onerror = () => { $('state').textContent = 'disconnected'; };
+</script>
+</body>
+</html>
+"#;
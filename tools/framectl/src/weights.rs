@@ -0,0 +1,124 @@
+//! `--weights`: how expensive each frame is to build relative to a typical
+//! one, for frames a run has no duration history for yet (high-motion frames
+//! bundle far more path data than a still one). One `FRAMES WEIGHT` pair per
+//! line, FRAMES a number or `A-B` range, `#` starting a comment; later lines
+//! win and unlisted frames weigh 1.
+//!
+//! ```text
+//! 1200-1450 3
+//! 1337 5
+//! ```
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::exitcode;
+use crate::frames;
+use crate::parse_kv;
+
+#[derive(Clone)]
+pub struct Weights {
+  ranges: Vec<(usize, usize, f64)>,
+}
+
+impl Weights {
+  pub fn parse(text: &str) -> Result<Weights, String> {
+    let mut ranges = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+      let line = line.split('#').next().unwrap_or("").trim();
+      if line.is_empty() {
+        continue;
+      }
+      let bad = || format!("line {}: expected `FRAMES WEIGHT`, got `{line}`", i + 1);
+      let mut parts = line.split_whitespace();
+      let (Some(spec), Some(w), None) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(bad());
+      };
+      let w: f64 = w.parse().ok().filter(|w: &f64| w.is_finite() && *w > 0.0).ok_or_else(bad)?;
+      let spans = frames::parse_ranges(spec).ok_or_else(bad)?;
+      ranges.extend(spans.into_iter().map(|(a, b)| (a, b, w)));
+    }
+    Ok(Weights { ranges })
+  }
+
+  /// `--weights=FILE`, exiting on an unreadable or malformed file.
+  pub fn from_args(args: &[String]) -> Option<Weights> {
+    let path = parse_kv(args, "--weights")?;
+    let parsed = std::fs::read_to_string(&path)
+      .map_err(|e| e.to_string())
+      .and_then(|text| Weights::parse(&text));
+    match parsed {
+      Ok(w) => Some(w),
+      Err(e) => {
+        exitcode::usage(format!("--weights={path}: {e}"));
+      }
+    }
+  }
+
+  pub fn get(&self, n: usize) -> f64 {
+    self
+      .ranges
+      .iter()
+      .rev()
+      .find(|&&(a, b, _)| (a..=b).contains(&n))
+      .map(|r| r.2)
+      .unwrap_or(1.0)
+  }
+
+  /// How many of `plan`'s frames have a weight other than 1.
+  pub fn listed(&self, plan: &[usize]) -> usize {
+    plan.iter().filter(|&&n| self.get(n) != 1.0).count()
+  }
+}
+
+/// Expected seconds for each frame of `plan`: its recorded duration, else
+/// its weight times the median seconds per unit of weight among the frames
+/// that have one (1s per unit without any history).
+pub fn costs(plan: &[usize], hist: &BTreeMap<usize, Duration>, weights: Option<&Weights>) -> Vec<f64> {
+  let weight = |n: usize| weights.map(|w| w.get(n)).unwrap_or(1.0);
+  let mut per_unit: Vec<f64> = plan
+    .iter()
+    .filter_map(|&n| hist.get(&n).map(|d| d.as_secs_f64() / weight(n)))
+    .collect();
+  per_unit.sort_by(f64::total_cmp);
+  let unit = per_unit.get(per_unit.len() / 2).copied().unwrap_or(1.0);
+  plan
+    .iter()
+    .map(|&n| hist.get(&n).map(Duration::as_secs_f64).unwrap_or_else(|| weight(n) * unit))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_ranges_and_comments() {
+    let w = Weights::parse("# motion\n1200-1450 3\n\n1337 5   # the blur\n10,20-21 0.5\n").unwrap();
+    assert_eq!(w.get(1199), 1.0);
+    assert_eq!(w.get(1200), 3.0);
+    assert_eq!(w.get(1450), 3.0);
+    // Later lines win.
+    assert_eq!(w.get(1337), 5.0);
+    assert_eq!((w.get(10), w.get(21), w.get(15)), (0.5, 0.5, 1.0));
+    assert_eq!(w.listed(&[1, 10, 1300, 1337]), 3);
+  }
+
+  #[test]
+  fn rejects_bad_lines() {
+    for (text, line) in [("5", 1), ("1 2 3", 1), ("\n1 x", 2), ("1 0", 1), ("1 -2", 1), ("1 inf", 1), ("5-1 2", 1)] {
+      let err = Weights::parse(text).err().unwrap();
+      assert!(err.starts_with(&format!("line {line}: expected `FRAMES WEIGHT`")), "{text:?}: {err}");
+    }
+  }
+
+  #[test]
+  fn costs_from_history_and_weights() {
+    let w = Weights::parse("3 4").unwrap();
+    let hist: BTreeMap<usize, Duration> = [(1, Duration::from_secs(2)), (3, Duration::from_secs(40))].into_iter().collect();
+    // Per unit: frame 1 2s, frame 3 10s; median (upper) 10s.
+    assert_eq!(costs(&[1, 2, 3], &hist, Some(&w)), [2.0, 10.0, 40.0]);
+    assert_eq!(costs(&[2, 3], &BTreeMap::new(), Some(&w)), [1.0, 4.0]);
+    assert_eq!(costs(&[2, 3], &BTreeMap::new(), None), [1.0, 1.0]);
+  }
+}